@@ -0,0 +1,186 @@
+/*! Dispatch path benchmarks for async orchestrator
+ * Measures submissions/sec, dispatch latency, and log-write throughput
+ * against an in-memory pool running no-op (echo / zero-length sleep) jobs
+ *
+ * Run with: cargo bench --bench dispatch > /dev/null
+ * NOTE: plain timing loops (harness = false); results go to stderr so
+ * the pool's stdout logging can be discarded
+ */
+use async_job_orchestrator::access::Admins;
+use async_job_orchestrator::cache::CacheConfig;
+use async_job_orchestrator::clock::SystemClock;
+use async_job_orchestrator::config::{OverflowPolicy, PoolConfig};
+use async_job_orchestrator::defaults::JobDefaults;
+use async_job_orchestrator::egress::EgressPolicy;
+use async_job_orchestrator::estimate::AdmissionConfig;
+use async_job_orchestrator::handlers::Handlers;
+use async_job_orchestrator::health::HealthConfig;
+use async_job_orchestrator::history::RetentionConfig;
+use async_job_orchestrator::hooks::HookList;
+use async_job_orchestrator::ids::UlidIds;
+use async_job_orchestrator::jobs::{JobPool, JobSubmission, JobView};
+use async_job_orchestrator::logs::{LogBuffer, LogLevel};
+use async_job_orchestrator::notify::NotifyConfig;
+use async_job_orchestrator::profiles::EnvProfiles;
+use async_job_orchestrator::queues::{DEFAULT_QUEUE, QueueConfig};
+use async_job_orchestrator::ratelimit::RateLimitList;
+use async_job_orchestrator::tenants::TenantPolicies;
+use async_job_orchestrator::usage::CostModel;
+use async_job_orchestrator::webhooks::WebhookConfig;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const SUBMISSIONS: usize = 10_000;
+const LATENCY_SAMPLES: usize = 200;
+const LOG_LINES: usize = 1_000_000;
+// lines per buffer, kept well under the 64 KB cap so nothing truncates
+const LOG_LINES_PER_BUFFER: usize = 1_000;
+
+fn main() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(bench_submissions());
+    rt.block_on(bench_dispatch_latency());
+    bench_log_writes();
+}
+
+fn pool_config() -> PoolConfig {
+    PoolConfig {
+        max_jobs: 4,
+        submission_queue_size: 32,
+        // backpressure instead of rejection: the loop's pace is what we measure
+        overflow_policy: OverflowPolicy::BlockWithDeadline(Duration::from_secs(10)),
+        sample_interval: Duration::from_secs(1),
+        autoscale: None,
+        load_shed: None,
+        queues: vec![QueueConfig::new(DEFAULT_QUEUE)],
+        webhooks: WebhookConfig {
+            max_attempts: 1,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(100),
+            timeout: Duration::from_secs(1),
+            queue_size: 16,
+            concurrency: 1,
+        },
+        notify: NotifyConfig::default(),
+        clock: Arc::new(SystemClock),
+        executor: Arc::new(Handlers::builtin()),
+        ids: Arc::new(UlidIds),
+        hooks: HookList::default(),
+        event_log: None,
+        cost: CostModel::default(),
+        pause_state: None,
+        cache: CacheConfig::default(),
+        defaults: JobDefaults::default(),
+        tenants: TenantPolicies::default(),
+        admission: AdmissionConfig {
+            deadlines: false,
+            quota_window: Duration::from_secs(86_400),
+        },
+        scratch: None,
+        job_timeout: None,
+        history_budget: 0,
+        retention: RetentionConfig::default(),
+        dedup_window: Duration::ZERO,
+        pin_quota: 0,
+        admins: Admins::default(),
+        stuck: None,
+        breaker: None,
+        shortest_first: None,
+        earliest_deadline_first: false,
+        env_profiles: EnvProfiles::default(),
+        egress: EgressPolicy::default(),
+        health: HealthConfig::default(),
+        rate_limits: RateLimitList::default(),
+        snapshot_interval: None,
+        log_rate_limit: None,
+        schedule_purge_after: None,
+    }
+}
+
+fn echo() -> JobSubmission {
+    serde_json::from_str(r#"{"type":"echo","payload":{"message":"bench"}}"#).unwrap()
+}
+
+fn sleep(ms: u32) -> JobSubmission {
+    let json = format!(r#"{{"type":"sleep","payload":{{"milliseconds":{ms}}}}}"#);
+    serde_json::from_str(&json).unwrap()
+}
+
+// Submissions/sec through the bounded channel into the run loop
+async fn bench_submissions() {
+    let pool = JobPool::start(&pool_config());
+    let start = Instant::now();
+    for _ in 0..SUBMISSIONS {
+        pool.submit(echo()).await.unwrap();
+    }
+    let elapsed = start.elapsed();
+    eprintln!(
+        "submissions: {} in {:?} ({:.0}/s)",
+        SUBMISSIONS,
+        elapsed,
+        SUBMISSIONS as f64 / elapsed.as_secs_f64()
+    );
+}
+
+// Time from job creation to RUNNING, one job at a time on an idle pool
+async fn bench_dispatch_latency() {
+    let pool = JobPool::start(&pool_config());
+    let mut samples = Vec::with_capacity(LATENCY_SAMPLES);
+    for _ in 0..LATENCY_SAMPLES {
+        wait_idle(&pool).await;
+        // long enough to observe the job while it is active
+        pool.submit(sleep(20)).await.unwrap();
+        samples.push(wait_started(&pool).await);
+    }
+    samples.sort();
+    let pct = |p: usize| samples[(samples.len() - 1) * p / 100];
+    eprintln!(
+        "dispatch latency: p50 {:?} p95 {:?} max {:?} ({} samples)",
+        pct(50),
+        pct(95),
+        pct(100),
+        samples.len()
+    );
+}
+
+// Log lines/sec and bytes/sec into per-job log buffers
+fn bench_log_writes() {
+    let line = "job heartbeat: step 42 of 100 complete";
+    let mut log = LogBuffer::new();
+    let start = Instant::now();
+    for i in 0..LOG_LINES {
+        if i % LOG_LINES_PER_BUFFER == 0 {
+            log = LogBuffer::new();
+        }
+        log.logf(LogLevel::INFO, format_args!("{}", line));
+    }
+    let elapsed = start.elapsed();
+    // "[INFO] " + line + "\n"
+    let bytes = LOG_LINES * (line.len() + 8);
+    eprintln!(
+        "log writes: {} lines in {:?} ({:.0} lines/s, {:.1} MB/s)",
+        LOG_LINES,
+        elapsed,
+        LOG_LINES as f64 / elapsed.as_secs_f64(),
+        bytes as f64 / elapsed.as_secs_f64() / 1e6
+    );
+}
+
+async fn wait_idle(pool: &Arc<JobPool>) {
+    let active = |jobs: Vec<JobView>| jobs.iter().any(|job| !job.state.is_terminal());
+    while active(pool.get_jobs(None).await.unwrap()) {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+}
+
+// Poll until the (only) active job has started; return its queue->run latency
+async fn wait_started(pool: &Arc<JobPool>) -> Duration {
+    loop {
+        for job in pool.get_jobs(None).await.unwrap() {
+            if let (false, Some(started)) = (job.state.is_terminal(), job.started_at) {
+                return (started - job.created_at).to_std().unwrap_or_default();
+            }
+        }
+        tokio::task::yield_now().await;
+    }
+}
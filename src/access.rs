@@ -0,0 +1,92 @@
+/*! Access module for async orchestrator
+ * Who makes a request, and whether they may change a schedule or workflow
+ *
+ * A request names its principal in the X-Principal header; requests without
+ * one act as "anonymous". Schedules and workflows record who created them
+ * (their owner) and who last changed them, and only the owner or an admin
+ * (ADMINS, comma separated) may replace or delete them. Only an admin may
+ * restore a deleted schedule.
+ * NOTE: the header is trusted as sent; put the orchestrator behind a proxy
+ * that authenticates callers and sets it
+ */
+use crate::api_error::ApiError;
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+pub const PRINCIPAL_HEADER: &str = "X-Principal";
+pub const ANONYMOUS: &str = "anonymous";
+
+/**
+ * Admins
+ * Principals allowed to change anything, as read from config
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Admins(pub BTreeSet<String>);
+
+impl FromStr for Admins {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Admins(
+            s.split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect(),
+        ))
+    }
+}
+
+impl Admins {
+    /**
+     * principal: who a request acts as, from its header value if any
+     */
+    pub fn principal(&self, name: Option<&str>) -> Principal {
+        let name = name
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .unwrap_or(ANONYMOUS);
+        Principal {
+            name: name.to_string(),
+            admin: self.0.contains(name),
+        }
+    }
+}
+
+/**
+ * Principal
+ * Who a request acts as
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Principal {
+    pub name: String,
+    pub admin: bool,
+}
+
+impl Principal {
+    /**
+     * check_owner: Forbidden unless this is the owner of what, or an admin
+     */
+    pub fn check_owner(&self, what: &str, owner: &str) -> Result<(), ApiError> {
+        if self.admin || self.name == owner {
+            return Ok(());
+        }
+        Err(ApiError::Forbidden(format!(
+            "{what} is owned by '{owner}'; '{}' may not change it",
+            self.name
+        )))
+    }
+
+    /**
+     * check_admin: Forbidden unless this is an admin
+     */
+    pub fn check_admin(&self, what: &str) -> Result<(), ApiError> {
+        if self.admin {
+            return Ok(());
+        }
+        Err(ApiError::Forbidden(format!(
+            "{what} is for admins; '{}' is not one",
+            self.name
+        )))
+    }
+}
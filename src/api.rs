@@ -1,11 +1,59 @@
 /*! API module for async job orchestrator */
 use axum::{
-    Json, Router, extract::State as AxumState, http::StatusCode, routing::get, routing::post,
+    Json, Router,
+    body::Body,
+    extract::{Path, Query, State as AxumState},
+    http::{HeaderMap, StatusCode, header},
+    response::sse::{self, KeepAlive, Sse},
+    response::{IntoResponse, Response},
+    routing::get,
+    routing::post,
 };
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
 
+use crate::access::{PRINCIPAL_HEADER, Principal};
 use crate::api_error::ApiError;
-use crate::jobs::{JobPool, Job, JobSubmission};
+use crate::breaker::CircuitStatus;
+use crate::cache::CachedJob;
+use crate::children::JobTree;
+use crate::client::{IDEMPOTENCY_KEY_HEADER, REQUEST_DEADLINE_HEADER};
+use crate::dedup::DedupReport;
+use crate::executor::ExecutorInfo;
+use crate::fields::FieldSelection;
+use crate::health::GateStatus;
+use crate::ids::JobId;
+use crate::jobs::{
+    BulkCancelResult, CancelFilter, GroupCancelResult, GroupStatus, Job, JobAccepted, JobPool,
+    JobSubmission, JobView, MapAccepted, MapStatus, MapSubmission, QueuedJob, Transition,
+    WaitStatus,
+};
+use crate::labels::LabelSelector;
+use crate::listing::{JobListing, JobSearch, JobSort, ListFilter, NEXT_CURSOR_HEADER, Page};
+use crate::logs::LogOrder;
+use crate::pause::PauseState;
+use crate::profiles::ProfileView;
+use crate::resources::ResourceStatus;
+use crate::schedules::{
+    Schedule, ScheduleChange, ScheduleDefinition, SchedulePreview, ScheduleRun,
+};
+use crate::snapshot::SNAPSHOT_AT_HEADER;
+use crate::unique::DuplicatePolicy;
+use crate::usage::UsageReport;
+use crate::workflows::{
+    DagSubmission, RunRequest, WorkflowDefinition, WorkflowRun, WorkflowVersion,
+};
+
+// POST /jobs/wait timeout when none is given, and the most allowed
+const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_WAIT_TIMEOUT: Duration = Duration::from_secs(300);
 
 /**
 Creates the main application router and wires up all the handlers.
@@ -16,36 +64,1147 @@ pub fn create_router(pool: Arc<JobPool>) -> Router {
     // We are encapsulating the routing logic here.
     Router::new()
         .route("/jobs", post(post_jobs).get(get_jobs))
+        .route("/jobs/search", post(post_jobs_search))
+        .route("/jobs/wait", post(post_jobs_wait))
+        .route("/jobs/cancel", post(post_jobs_cancel))
+        .route("/jobs/{id}", get(get_job))
+        .route("/jobs/{id}/events", get(get_job_events))
+        .route("/jobs/{id}/children", get(get_job_children))
+        .route("/jobs/{id}/cancel", post(post_job_cancel))
+        .route("/jobs/{id}/clone", post(post_job_clone))
+        .route("/jobs/{id}/rerun", post(post_job_rerun))
+        .route("/jobs/{id}/pin", post(post_job_pin).delete(delete_job_pin))
+        .route("/maps", post(post_maps))
+        .route("/maps/{id}", get(get_map))
+        .route("/workflows", get(get_workflows).post(post_workflows))
+        .route("/workflows/{name}", get(get_workflow).put(put_workflow))
+        .route("/workflows/{name}/versions", get(get_workflow_versions))
+        .route("/workflows/{name}/run", post(post_workflow_run))
+        .route("/workflow-runs/{id}", get(get_workflow_run))
+        .route("/schedules", post(post_schedule).get(get_schedules))
+        .route(
+            "/schedules/{id}",
+            get(get_schedule).put(put_schedule).delete(delete_schedule),
+        )
+        .route("/schedules/{id}/runs", get(get_schedule_runs))
+        .route("/schedules/{id}/next", get(get_schedule_next))
+        .route("/schedules/{id}/history", get(get_schedule_history))
+        .route("/groups/{id}", get(get_group))
+        .route("/groups/{id}/logs", get(get_group_logs))
+        .route("/groups/{id}/cancel", post(post_group_cancel))
+        .route("/usage", get(get_usage))
+        .route("/admin/pauses", get(get_pauses))
+        .route("/admin/dedup", get(get_dedup))
+        .route("/admin/env-profiles", get(get_env_profiles))
+        .route("/admin/health-gates", get(get_health_gates))
+        .route("/admin/circuit-breakers", get(get_circuit_breakers))
+        .route("/admin/resources", get(get_resources))
+        .route("/admin/unique-keys", get(get_unique_keys))
+        .route("/admin/schedules/deleted", get(get_deleted_schedules))
+        .route("/admin/schedules/{id}/restore", post(post_schedule_restore))
+        .route("/admin/schemas", get(get_schemas))
+        .route(
+            "/admin/schemas/{type}",
+            get(get_schema).put(put_schema).delete(delete_schema),
+        )
+        .route("/admin/pool/pause", post(post_pool_pause))
+        .route("/admin/pool/resume", post(post_pool_resume))
+        .route("/admin/queues/{name}/pause", post(post_queue_pause))
+        .route("/admin/queues/{name}/resume", post(post_queue_resume))
         .route("/metrics", get(get_metrics))
+        .route("/executors", get(get_executors))
+        .route("/pool", get(get_pool))
+        .route("/queue", get(get_queue))
+        .route("/events", get(get_events))
         .with_state(pool)
 }
 
+/**
+Query parameters for submitting a job
+*/
+#[derive(Deserialize)]
+struct SubmitQuery {
+    #[serde(default)]
+    dry_run: bool,
+    // hold the response until the job finishes
+    #[serde(default)]
+    wait: bool,
+}
+
 /**
 Submit a new job for immediate execution
+Answers 201 with the job's id, and its url in Location
+With dry_run, only validate it and report where it would go, how long
+it would likely wait, and its estimated duration and cost.
+With wait, answer once the job has finished (200, as /jobs/wait reports
+it), or with the job as it stands (202) when the wait ends first: after
+the default wait, at the X-Request-Deadline, or as soon as the job is
+expected to finish after that deadline.
+A cacheable job type with a recent successful run of
+the same payload gets that job back (200, "cached": true) without running.
+An Idempotency-Key already used within the dedup window gets the job first
+submitted with it back (200) instead of a new one.
+A unique_key held by an unfinished job is a conflict (409), or with
+on_duplicate "coalesce" gets that job back (200).
+Fields left out are filled from the tenant's and then the job type's defaults, if
+they have any; a priority above the tenant's max_priority is forbidden (403)
+With admission control, a job estimated to complete after its deadline is
+refused (400), and one estimated to cost more than its tenant has left of
+its quota is forbidden (403)
+A critical job with preempt set that finds the pool full stops the lowest
+priority running job, which is requeued, and takes its slot once that
+job's execution returns (only if the executor stops cancelled jobs early)
+Its on_success or on_failure submission, checked along with it, is
+submitted once it has finished that way
 */
 async fn post_jobs(
     AxumState(pool): AxumState<Arc<JobPool>>,
-    Json(req): Json<JobSubmission>,
-) -> Result<StatusCode, ApiError> {
+    Query(query): Query<SubmitQuery>,
+    headers: HeaderMap,
+    Json(raw): Json<Value>,
+) -> Result<Response, ApiError> {
+    let req: JobSubmission = pool.with_defaults(raw)?;
+    let respond_by = request_deadline(&headers)?;
+    if query.dry_run {
+        println!("[api] Job dry run: {:?}", req);
+        return Ok(Json(pool.dry_run(&req).await?).into_response());
+    }
+    pool.check_submission(&req)?;
+    let key = headers
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok());
+    // the key is claimed now, so a concurrent submission with it waits on
+    // this one's job rather than creating another
+    let claimed = match key {
+        Some(key) => match pool.claim_key(key, &req).await {
+            Ok(id) => Some((key, id)),
+            Err(id) => {
+                println!("[api] Duplicate submission of job {id} (key {key})");
+                if query.wait {
+                    return Ok(wait_response(&pool, id, respond_by).await);
+                }
+                return Ok((
+                    [(header::LOCATION, format!("/jobs/{id}"))],
+                    Json(JobAccepted { id }),
+                )
+                    .into_response());
+            }
+        },
+        None => None,
+    };
+    if let Some(unique_key) = &req.unique_key
+        && req.on_duplicate == Some(DuplicatePolicy::Coalesce)
+        && let Some(id) = pool.unique_holder(unique_key)
+    {
+        println!("[api] Submission coalesced into job {id} (unique key {unique_key})");
+        if let Some((key, claim)) = claimed {
+            pool.release_key(key, claim).await;
+        }
+        if query.wait {
+            return Ok(wait_response(&pool, id, respond_by).await);
+        }
+        return Ok((
+            [(header::LOCATION, format!("/jobs/{id}"))],
+            Json(JobAccepted { id }),
+        )
+            .into_response());
+    }
+    if let Some(job) = pool.cached(&req).await {
+        println!("[api] Job answered from cache: {}", job.id());
+        if let Some((key, claim)) = claimed {
+            pool.release_key(key, claim).await;
+        }
+        return Ok(Json(CachedJob { cached: true, job }).into_response());
+    }
     println!("[api] Job submitted: {:?}", req);
-    pool.submit(req).await?;
-    Ok(StatusCode::ACCEPTED)
+    let id = match claimed {
+        Some((key, claim)) => pool.submit_claimed(key, claim, req).await?,
+        None => pool.submit(req).await?,
+    };
+    if query.wait {
+        return Ok(wait_response(&pool, id, respond_by).await);
+    }
+    Ok((
+        StatusCode::CREATED,
+        [(header::LOCATION, format!("/jobs/{id}"))],
+        Json(JobAccepted { id }),
+    )
+        .into_response())
+}
+
+// Wait on a submitted job, answering with it finished (200), or as it
+// stands (202) if the wait ends first
+async fn wait_response(pool: &JobPool, id: JobId, respond_by: Option<DateTime<Utc>>) -> Response {
+    let mut waited = pool.wait_for(&[id], DEFAULT_WAIT_TIMEOUT, respond_by).await;
+    let status = if waited.timed_out {
+        StatusCode::ACCEPTED
+    } else {
+        StatusCode::OK
+    };
+    let job = waited.jobs.pop().unwrap_or(WaitStatus { id, job: None });
+    (
+        status,
+        [(header::LOCATION, format!("/jobs/{id}"))],
+        Json(job),
+    )
+        .into_response()
+}
+
+// The request deadline a client set, if any
+fn request_deadline(headers: &HeaderMap) -> Result<Option<DateTime<Utc>>, ApiError> {
+    let Some(value) = headers.get(REQUEST_DEADLINE_HEADER) else {
+        return Ok(None);
+    };
+    value
+        .to_str()
+        .ok()
+        .and_then(|v| DateTime::parse_from_rfc3339(v.trim()).ok())
+        .map(|at| Some(at.with_timezone(&Utc)))
+        .ok_or_else(|| {
+            ApiError::BadRequest(format!(
+                "{REQUEST_DEADLINE_HEADER}: expected an RFC 3339 time"
+            ))
+        })
+}
+
+/**
+Query parameters for listing jobs
+*/
+#[derive(Deserialize)]
+struct JobsQuery {
+    queue: Option<String>,
+    // comma separated states
+    state: Option<String>,
+    // comma separated job types
+    #[serde(rename = "type")]
+    job_type: Option<String>,
+    created_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+    // a sort key, descending with a leading '-'
+    sort: Option<String>,
+    // page size, and where the page starts (a previous page's next cursor)
+    limit: Option<usize>,
+    cursor: Option<String>,
+    // comma separated key:value (or key) labels the jobs must carry
+    label: Option<String>,
+    // comma separated: only these fields of each job
+    fields: Option<String>,
+    // comma separated: heavy fields to add (logs, result, events)
+    include: Option<String>,
+}
+
+/**
+Query parameters for getting one job
+*/
+#[derive(Deserialize)]
+struct JobQuery {
+    fields: Option<String>,
+    include: Option<String>,
 }
 
 /**
-Get the active jobs
+List every job the pool holds, optionally only those on one queue, in
+some states (?state=running,queued), of some types (?type=), created in a
+time range (?created_after=, ?created_before=, RFC 3339), or carrying
+labels (?label=team:payments; several, comma separated, must all match)
+?sort=created_at orders them by a key (created_at, started_at,
+finished_at, priority or type), descending with a leading '-'
+With snapshots on, the jobs are as of the latest snapshot (its time in
+X-Snapshot-At)
+?limit= pages the listing in id order; a page with more after it carries
+the next page's cursor in X-Next-Cursor, to pass back as ?cursor=
+?fields= answers with only those fields of each job; ?include= adds its
+logs, full result or state changes (events)
 */
 async fn get_jobs(
     AxumState(pool): AxumState<Arc<JobPool>>,
-) -> Result<(StatusCode, Json<Vec<Job>>), ApiError> {
-    let jobs = pool.get_jobs().await?;
-    Ok((StatusCode::OK, Json(jobs)))
+    Query(query): Query<JobsQuery>,
+) -> Result<Response, ApiError> {
+    let selection = FieldSelection::parse(query.fields.as_deref(), query.include.as_deref())?;
+    let labels: LabelSelector = query
+        .label
+        .as_deref()
+        .unwrap_or_default()
+        .parse()
+        .map_err(ApiError::BadRequest)?;
+    let list = ListFilter {
+        queue: query.queue,
+        created_after: query.created_after,
+        created_before: query.created_before,
+        labels,
+        ..ListFilter::default()
+    }
+    .with_states(query.state.as_deref().unwrap_or_default())?
+    .with_types(
+        query.job_type.as_deref().unwrap_or_default(),
+        pool.job_types(),
+    )?;
+    let sort: Option<JobSort> = query
+        .sort
+        .as_deref()
+        .map(str::parse)
+        .transpose()
+        .map_err(ApiError::BadRequest)?;
+    let filter = |job: &Job| list.matches(job);
+    if let Some(page) = Page::parse(query.limit, query.cursor.as_deref())? {
+        if sort.is_some() {
+            return Err(ApiError::BadRequest(
+                "a paged listing is in id order; it can't be sorted".to_string(),
+            ));
+        }
+        let listed = pool
+            .get_jobs_page(filter, &page, |job| selection.listed(job))
+            .await?;
+        return Ok(listing_response(listed));
+    }
+    if selection.is_default() {
+        let listed = pool
+            .get_jobs_as(filter, sort.as_ref(), |job| JobView::from(job))
+            .await?;
+        return Ok(listing_response(listed));
+    }
+    let listed = pool
+        .get_jobs_as(filter, sort.as_ref(), |job| selection.listed(job))
+        .await?;
+    Ok(listing_response(listed))
+}
+
+/**
+Search jobs with a JSON query: any of some states, of some types, in a
+queue, carrying labels, created, started or finished in time ranges, or
+whose result contains some text (ignoring case)
+{"states": ["failed"], "labels": ["team:payments"], "finished": {"after":
+"2024-05-01T00:00:00Z"}, "result_contains": "timeout", "sort": "-finished_at",
+"limit": 50}
+Answers as GET /jobs does; a search may be both sorted and paged, its
+cursor in X-Next-Cursor going back as "cursor"
+*/
+async fn post_jobs_search(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Json(search): Json<JobSearch>,
+) -> Result<Response, ApiError> {
+    let fields = (!search.fields.is_empty()).then(|| search.fields.join(","));
+    let include = (!search.include.is_empty()).then(|| search.include.join(","));
+    let selection = FieldSelection::parse(fields.as_deref(), include.as_deref())?;
+    let list = search.filter(pool.job_types())?;
+    let sort: Option<JobSort> = search
+        .sort
+        .as_deref()
+        .map(str::parse)
+        .transpose()
+        .map_err(ApiError::BadRequest)?;
+    let filter = |job: &Job| list.matches(job);
+    let listed = match (Page::parse(search.limit, search.cursor.as_deref())?, sort) {
+        (Some(page), Some(sort)) => {
+            pool.get_jobs_sorted_page(filter, &sort, &page, |job| selection.listed(job))
+                .await?
+        }
+        (Some(page), None) => {
+            pool.get_jobs_page(filter, &page, |job| selection.listed(job))
+                .await?
+        }
+        (None, sort) => {
+            pool.get_jobs_as(filter, sort.as_ref(), |job| selection.listed(job))
+                .await?
+        }
+    };
+    Ok(listing_response(listed))
+}
+
+// Listed jobs, with the next page's cursor and the snapshot's time, if any
+fn listing_response<T: Serialize>(listed: JobListing<T>) -> Response {
+    let mut response = match listed.as_of {
+        Some(as_of) => snapshot_response(listed.jobs, as_of),
+        None => Json(listed.jobs).into_response(),
+    };
+    if let Some(next) = listed.next
+        && let Ok(value) = next.as_str().parse()
+    {
+        response.headers_mut().insert(NEXT_CURSOR_HEADER, value);
+    }
+    response
+}
+
+// A body read from the snapshot taken at as_of
+fn snapshot_response<T: Serialize>(body: T, as_of: DateTime<Utc>) -> Response {
+    let as_of = as_of.to_rfc3339_opts(SecondsFormat::Millis, true);
+    ([(SNAPSHOT_AT_HEADER, as_of)], Json(body)).into_response()
+}
+
+/**
+Get one job, with its revision as the ETag
+Answers If-None-Match with 304 while the job is unchanged
+?fields= answers with only those fields; ?include=logs adds its log
+*/
+async fn get_job(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(id): Path<JobId>,
+    Query(query): Query<JobQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let selection = FieldSelection::parse(query.fields.as_deref(), query.include.as_deref())?;
+    let job = pool
+        .job(id)
+        .await
+        .ok_or_else(|| ApiError::NotFound(format!("job {id}")))?;
+    let etag = job_etag(&job);
+    if if_none_match(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+    if selection.is_default() {
+        return Ok(([(header::ETAG, etag)], Json(job)).into_response());
+    }
+    Ok(([(header::ETAG, etag)], Json(selection.detail(&job))).into_response())
+}
+
+/**
+A job's state changes, oldest first: each with when and why it happened,
+and how long the job had spent in the state it left
+*/
+async fn get_job_events(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(id): Path<JobId>,
+) -> Result<Json<Vec<Transition>>, ApiError> {
+    let job = pool
+        .job(id)
+        .await
+        .ok_or_else(|| ApiError::NotFound(format!("job {id}")))?;
+    Ok(Json(job.transitions().to_vec()))
+}
+
+/**
+The jobs a job spawned, oldest first, each with the jobs it spawned
+*/
+async fn get_job_children(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(id): Path<JobId>,
+) -> Result<Json<Vec<JobTree>>, ApiError> {
+    Ok(Json(pool.job_children(id).await?))
+}
+
+/**
+Cancel a queued or running job, returning it as it is after the cancel
+A running job is marked cancelling and its execution asked to stop; it ends
+cancelled once it returns. A job already finished is a conflict
+*/
+async fn post_job_cancel(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(id): Path<JobId>,
+) -> Result<Json<Job>, ApiError> {
+    println!("[api] Cancel job: {}", id);
+    Ok(Json(pool.cancel_job(id).await?))
+}
+
+/**
+Resubmit a job with a JSON merge patch applied to its submission, e.g.
+{"payload": {"message": "fixed"}} to change one payload field; null removes
+a field. The copy is a new job (201, Location) whose cloned_from names the
+original
+*/
+async fn post_job_clone(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(id): Path<JobId>,
+    Json(patch): Json<Value>,
+) -> Result<Response, ApiError> {
+    println!("[api] Clone job: {}", id);
+    let clone_id = pool.clone_job(id, &patch).await?;
+    Ok((
+        StatusCode::CREATED,
+        [(header::LOCATION, format!("/jobs/{clone_id}"))],
+        Json(JobAccepted { id: clone_id }),
+    )
+        .into_response())
+}
+
+/**
+Run a finished job again with the same submission. The rerun is a new job
+(201, Location) whose rerun_of names the original; a job not yet finished
+is a conflict
+*/
+async fn post_job_rerun(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(id): Path<JobId>,
+) -> Result<Response, ApiError> {
+    println!("[api] Rerun job: {}", id);
+    let rerun_id = pool.rerun_job(id).await?;
+    Ok((
+        StatusCode::CREATED,
+        [(header::LOCATION, format!("/jobs/{rerun_id}"))],
+        Json(JobAccepted { id: rerun_id }),
+    )
+        .into_response())
+}
+
+/**
+Pin a finished job: it stays in history, however full, until unpinned.
+Each tenant may pin up to PIN_QUOTA jobs; past that, or for a job not yet
+finished, it is a conflict
+*/
+async fn post_job_pin(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(id): Path<JobId>,
+) -> Result<Json<Job>, ApiError> {
+    println!("[api] Pin job: {}", id);
+    Ok(Json(pool.pin_job(id, true).await?))
+}
+
+/**
+Unpin a job, leaving it to be evicted like any other
+*/
+async fn delete_job_pin(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(id): Path<JobId>,
+) -> Result<Json<Job>, ApiError> {
+    println!("[api] Unpin job: {}", id);
+    Ok(Json(pool.pin_job(id, false).await?))
+}
+
+// Strong ETag for a job's current revision
+fn job_etag(job: &Job) -> String {
+    format!("\"{}\"", job.revision())
+}
+
+// Whether If-None-Match names the ETag (or is "*")
+// NOTE: weak comparison, as RFC 9110 asks for If-None-Match
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/**
+Request body for waiting on jobs
+*/
+#[derive(Deserialize)]
+struct WaitRequest {
+    ids: Vec<JobId>,
+    timeout_ms: Option<u64>,
+}
+
+/**
+Wait for jobs to finish, returning each job's final status
+Returns early with timed_out set if the timeout passes first
+With an X-Request-Deadline, the wait also ends by then, or as soon as a job
+is expected to finish after it (listing it as late); unfinished jobs are
+then answered as they stand, with 202. Executions see the deadline as
+their budget, and may wind up early to meet it
+*/
+async fn post_jobs_wait(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    headers: HeaderMap,
+    Json(req): Json<WaitRequest>,
+) -> Result<Response, ApiError> {
+    if req.ids.is_empty() {
+        return Err(ApiError::BadRequest("no job ids to wait for".to_string()));
+    }
+    let respond_by = request_deadline(&headers)?;
+    let timeout = req
+        .timeout_ms
+        .map_or(DEFAULT_WAIT_TIMEOUT, Duration::from_millis)
+        .min(MAX_WAIT_TIMEOUT);
+    let waited = pool.wait_for(&req.ids, timeout, respond_by).await;
+    let status = if waited.timed_out && respond_by.is_some() {
+        StatusCode::ACCEPTED
+    } else {
+        StatusCode::OK
+    };
+    Ok((status, Json(waited)).into_response())
+}
+
+/**
+Cancel every queued or running job matching a filter, returning the ids
+affected; running jobs are marked cancelling and end cancelled once they
+return
+*/
+async fn post_jobs_cancel(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Json(filter): Json<CancelFilter>,
+) -> Result<Json<BulkCancelResult>, ApiError> {
+    println!("[api] Bulk cancel: {:?}", filter);
+    Ok(Json(pool.cancel_jobs(&filter).await?))
+}
+
+/**
+Submit a map: one child job per input, tracked together, and with "reduce", a
+job run on their results once they have all succeeded
+Fields left out are filled from the tenant's and then the job type's defaults, if
+they have any
+*/
+async fn post_maps(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Json(raw): Json<Value>,
+) -> Result<(StatusCode, Json<MapAccepted>), ApiError> {
+    let req: MapSubmission = pool.with_defaults(raw)?;
+    println!(
+        "[api] Map submitted: {} x {} on '{}'",
+        req.inputs.len(),
+        req.job_type,
+        req.queue
+    );
+    let accepted = pool.submit_map(req).await?;
+    Ok((StatusCode::ACCEPTED, Json(accepted)))
+}
+
+/**
+Get a map's progress and the results of its finished children, and of its
+reducer
+*/
+async fn get_map(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(id): Path<String>,
+) -> Result<Json<MapStatus>, ApiError> {
+    Ok(Json(pool.map_status(&id).await?))
+}
+
+/**
+List workflows (latest version of each)
+*/
+async fn get_workflows(AxumState(pool): AxumState<Arc<JobPool>>) -> Json<Vec<WorkflowVersion>> {
+    Json(pool.workflows().list())
+}
+
+/**
+Submit a DAG of job nodes and edges: stored as the next version of its
+workflow and run at once, each node once its upstream nodes succeed.
+Follow the run at /workflow-runs/{id}
+A workflow that exists already may only be changed by its owner or an admin
+*/
+async fn post_workflows(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    headers: HeaderMap,
+    Json(req): Json<DagSubmission>,
+) -> Result<Response, ApiError> {
+    println!(
+        "[api] Workflow DAG submitted: {} ({} nodes, {} edges)",
+        req.name,
+        req.nodes.len(),
+        req.edges.len()
+    );
+    let run = pool
+        .workflows()
+        .submit_dag(req, &principal(&pool, &headers))?;
+    Ok((
+        StatusCode::ACCEPTED,
+        [(header::LOCATION, format!("/workflow-runs/{}", run.id))],
+        Json(run),
+    )
+        .into_response())
+}
+
+/**
+Query parameters for getting a workflow
+*/
+#[derive(Deserialize)]
+struct WorkflowQuery {
+    version: Option<u32>,
+}
+
+/**
+Get a workflow definition, the latest version unless one is asked for
+*/
+async fn get_workflow(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(name): Path<String>,
+    Query(query): Query<WorkflowQuery>,
+) -> Result<Json<WorkflowVersion>, ApiError> {
+    Ok(Json(pool.workflows().get(&name, query.version)?))
+}
+
+/**
+Get every stored version of a workflow, newest first, with who uploaded each
+*/
+async fn get_workflow_versions(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(name): Path<String>,
+) -> Result<Json<Vec<WorkflowVersion>>, ApiError> {
+    Ok(Json(pool.workflows().versions(&name)?))
+}
+
+/**
+Upload a workflow definition as its next version
+The uploader of the first version owns the workflow; later versions may only
+be uploaded by the owner or an admin
+*/
+async fn put_workflow(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    Json(req): Json<WorkflowDefinition>,
+) -> Result<(StatusCode, Json<WorkflowVersion>), ApiError> {
+    println!("[api] Workflow uploaded: {}", name);
+    let stored = pool
+        .workflows()
+        .upload(&name, req, &principal(&pool, &headers))?;
+    Ok((StatusCode::CREATED, Json(stored)))
+}
+
+/**
+Start a run of a workflow
+*/
+async fn post_workflow_run(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(name): Path<String>,
+    Json(req): Json<RunRequest>,
+) -> Result<(StatusCode, Json<WorkflowRun>), ApiError> {
+    println!("[api] Workflow run: {}", name);
+    let run = pool.workflows().start_run(&name, req)?;
+    Ok((StatusCode::ACCEPTED, Json(run)))
+}
+
+/**
+Get a workflow run's progress
+*/
+async fn get_workflow_run(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(id): Path<String>,
+) -> Result<Json<WorkflowRun>, ApiError> {
+    Ok(Json(pool.workflows().get_run(&id)?))
+}
+
+/**
+Create a cron schedule, owned by the requesting principal
+*/
+async fn post_schedule(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    headers: HeaderMap,
+    Json(req): Json<ScheduleDefinition>,
+) -> Result<(StatusCode, Json<Schedule>), ApiError> {
+    println!("[api] Schedule created: '{}'", req.cron);
+    pool.check_submission(&req.job)?;
+    let schedule = pool.schedules().create(req, &principal(&pool, &headers))?;
+    Ok((StatusCode::CREATED, Json(schedule)))
+}
+
+/**
+List schedules
+*/
+async fn get_schedules(AxumState(pool): AxumState<Arc<JobPool>>) -> Json<Vec<Schedule>> {
+    Json(pool.schedules().list())
+}
+
+/**
+Get a schedule
+*/
+async fn get_schedule(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(id): Path<String>,
+) -> Result<Json<Schedule>, ApiError> {
+    Ok(Json(pool.schedules().get(&id)?))
+}
+
+/**
+Replace a schedule's definition; its run history is kept, and the definition
+replaced is added to its change history
+Only the schedule's owner or an admin may replace it
+*/
+async fn put_schedule(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(req): Json<ScheduleDefinition>,
+) -> Result<Json<Schedule>, ApiError> {
+    println!("[api] Schedule replaced: {}", id);
+    pool.check_submission(&req.job)?;
+    let by = principal(&pool, &headers);
+    Ok(Json(pool.schedules().replace(&id, req, &by)?))
+}
+
+/**
+Get a schedule's change history, newest first: who replaced it, when, and
+the definition they replaced
+*/
+async fn get_schedule_history(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<ScheduleChange>>, ApiError> {
+    Ok(Json(pool.schedules().changes(&id)?))
+}
+
+/**
+List the registered payload schemas, by job type
+*/
+async fn get_schemas(AxumState(pool): AxumState<Arc<JobPool>>) -> Json<BTreeMap<String, Value>> {
+    Json(pool.schemas().list())
+}
+
+/**
+Get a job type's payload schema
+*/
+async fn get_schema(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(job_type): Path<String>,
+) -> Result<Json<Value>, ApiError> {
+    pool.schemas()
+        .get(&job_type)
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound(format!("schema for '{job_type}'")))
+}
+
+/**
+Register (or replace) a job type's payload schema; submissions of the type
+whose payload doesn't match are rejected from then on
+*/
+async fn put_schema(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(job_type): Path<String>,
+    Json(schema): Json<Value>,
+) -> Result<StatusCode, ApiError> {
+    println!("[api] Schema registered: {}", job_type);
+    pool.schemas().put(&job_type, schema)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/**
+Remove a job type's payload schema
+*/
+async fn delete_schema(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(job_type): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    println!("[api] Schema removed: {}", job_type);
+    pool.schemas().remove(&job_type)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/**
+Delete a schedule; only its owner or an admin may
+It stops firing and is no longer listed, but can still be fetched, and an
+admin may restore it until it is purged
+*/
+async fn delete_schedule(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode, ApiError> {
+    println!("[api] Schedule deleted: {}", id);
+    pool.schedules().delete(&id, &principal(&pool, &headers))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/**
+List deleted schedules not yet purged, most recently deleted first
+*/
+async fn get_deleted_schedules(AxumState(pool): AxumState<Arc<JobPool>>) -> Json<Vec<Schedule>> {
+    Json(pool.schedules().deleted())
+}
+
+/**
+Restore a deleted schedule; only an admin may
+*/
+async fn post_schedule_restore(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<Schedule>, ApiError> {
+    println!("[api] Schedule restored: {}", id);
+    let by = principal(&pool, &headers);
+    Ok(Json(pool.schedules().restore(&id, &by)?))
+}
+
+/**
+Get a schedule's recent runs, newest first: when each fired, the job it
+spawned, and how that job turned out
+*/
+async fn get_schedule_runs(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<ScheduleRun>>, ApiError> {
+    Ok(Json(pool.schedules().runs(&id, &pool).await?))
+}
+
+/**
+Query parameters for previewing a schedule
+*/
+#[derive(Deserialize)]
+struct PreviewQuery {
+    #[serde(default = "default_preview_count")]
+    count: usize,
+}
+
+fn default_preview_count() -> usize {
+    10
+}
+
+/**
+Get a schedule's next fire times, enabled or not
+*/
+async fn get_schedule_next(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(id): Path<String>,
+    Query(query): Query<PreviewQuery>,
+) -> Result<Json<SchedulePreview>, ApiError> {
+    Ok(Json(pool.schedules().preview(&id, query.count)?))
+}
+
+/**
+Get a job group's aggregate status
+*/
+async fn get_group(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(id): Path<String>,
+) -> Result<Json<GroupStatus>, ApiError> {
+    Ok(Json(pool.group_status(&id).await?))
+}
+
+/**
+Query parameters for a group's logs
+*/
+#[derive(Deserialize)]
+struct GroupLogsQuery {
+    // interleaved (by time, the default) or segmented (job by job)
+    order: Option<String>,
+}
+
+/**
+Download the logs of all of a job group's jobs as NDJSON, one
+{"job_id", "at", "line"} per log line, interleaved by time or (with
+?order=segmented) job by job
+*/
+async fn get_group_logs(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(id): Path<String>,
+    Query(query): Query<GroupLogsQuery>,
+) -> Result<Response, ApiError> {
+    let order = query
+        .order
+        .as_deref()
+        .map(str::parse::<LogOrder>)
+        .transpose()
+        .map_err(ApiError::BadRequest)?
+        .unwrap_or_default();
+    let lines = pool.group_logs(&id, order).await?;
+    let body = stream::iter(lines).map(|line| {
+        let mut json = serde_json::to_string(&line).unwrap_or_default();
+        json.push('\n');
+        Ok::<_, Infallible>(json)
+    });
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"group-logs.ndjson\"",
+            ),
+        ],
+        Body::from_stream(body),
+    )
+        .into_response())
+}
+
+/**
+Cancel a job group's jobs; running jobs are marked cancelling and end
+cancelled once they return
+*/
+async fn post_group_cancel(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(id): Path<String>,
+) -> Result<Json<GroupCancelResult>, ApiError> {
+    println!("[api] Cancel group: {}", id);
+    Ok(Json(pool.cancel_group(&id).await?))
+}
+
+/**
+Request body for pausing; the reason is shown with the pause
+*/
+#[derive(Deserialize)]
+struct PauseRequest {
+    reason: Option<String>,
+}
+
+// The reason given, if a body was sent
+fn pause_reason(req: Option<Json<PauseRequest>>) -> String {
+    req.and_then(|Json(req)| req.reason)
+        .unwrap_or_else(|| "no reason given".to_string())
+}
+
+/**
+Get the current pauses and their reasons
+*/
+async fn get_pauses(AxumState(pool): AxumState<Arc<JobPool>>) -> Json<PauseState> {
+    Json(pool.pauses().await)
+}
+
+/**
+Get the duplicate submissions suppressed: counts since startup and the
+latest, by idempotency key or cached payload hash
+*/
+async fn get_dedup(AxumState(pool): AxumState<Arc<JobPool>>) -> Json<DedupReport> {
+    Json(pool.dedup_report().await)
+}
+
+/**
+List the environment profiles jobs can run with: literal values, and the
+orchestrator variable each secret is read from (never its value)
+*/
+async fn get_env_profiles(AxumState(pool): AxumState<Arc<JobPool>>) -> Json<Vec<ProfileView>> {
+    Json(pool.env_profiles().list())
+}
+
+/**
+List the health-gated job types and each one's downstream health as last
+checked; jobs of an unhealthy type are held pending
+*/
+async fn get_health_gates(AxumState(pool): AxumState<Arc<JobPool>>) -> Json<Vec<GateStatus>> {
+    Json(pool.health_gates())
+}
+
+/**
+List each job type's circuit breaker; jobs of a type whose circuit is open
+fail fast instead of running
+*/
+async fn get_circuit_breakers(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+) -> Json<Vec<CircuitStatus>> {
+    Json(pool.circuit_breakers())
+}
+
+/**
+List the shared resources the executor registered, and whether each has
+been made yet
+*/
+async fn get_resources(AxumState(pool): AxumState<Arc<JobPool>>) -> Json<Vec<ResourceStatus>> {
+    Json(pool.resources())
+}
+
+/**
+List the unique keys held by unfinished jobs, each with the job holding it
+*/
+async fn get_unique_keys(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+) -> Json<BTreeMap<String, JobId>> {
+    Json(pool.unique_keys())
+}
+
+/**
+Pause dispatching on every queue; submissions are held, running jobs finish
+*/
+async fn post_pool_pause(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    req: Option<Json<PauseRequest>>,
+) -> Result<Json<PauseState>, ApiError> {
+    Ok(Json(pool.pause(None, &pause_reason(req)).await?))
+}
+
+/**
+Lift the pool pause
+*/
+async fn post_pool_resume(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+) -> Result<Json<PauseState>, ApiError> {
+    Ok(Json(pool.resume(None).await?))
+}
+
+/**
+Pause dispatching on one queue
+*/
+async fn post_queue_pause(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(name): Path<String>,
+    req: Option<Json<PauseRequest>>,
+) -> Result<Json<PauseState>, ApiError> {
+    Ok(Json(pool.pause(Some(&name), &pause_reason(req)).await?))
+}
+
+/**
+Lift a queue's pause
+*/
+async fn post_queue_resume(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Path(name): Path<String>,
+) -> Result<Json<PauseState>, ApiError> {
+    Ok(Json(pool.resume(Some(&name)).await?))
+}
+
+/**
+Query parameters for usage reports
+*/
+#[derive(Deserialize)]
+struct UsageQuery {
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+}
+
+/**
+Get the cost of jobs that finished in [from, to), by type, queue and tenant
+*/
+async fn get_usage(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+    Query(query): Query<UsageQuery>,
+) -> Json<UsageReport> {
+    Json(pool.usage(query.from, query.to).await)
 }
 
 /**
 Get job orchestrator metrics
+With snapshots on, as of the latest snapshot (its time in X-Snapshot-At)
 */
-async fn get_metrics() -> &'static str {
-    println!("GET metrics");
-    return "get /metrics";
+async fn get_metrics(AxumState(pool): AxumState<Arc<JobPool>>) -> Response {
+    match pool.snapshot() {
+        Some(snapshot) => snapshot_response(&snapshot.metrics, snapshot.taken_at),
+        None => Json(pool.metrics().await).into_response(),
+    }
+}
+
+/**
+List the executors jobs run on, with their versions and capabilities
+*/
+async fn get_executors(AxumState(pool): AxumState<Arc<JobPool>>) -> Json<Vec<ExecutorInfo>> {
+    Json(pool.executors().await)
+}
+
+/**
+Get current pool occupancy
+With snapshots on, as of the latest snapshot (its time in X-Snapshot-At)
+*/
+async fn get_pool(AxumState(pool): AxumState<Arc<JobPool>>) -> Response {
+    match pool.snapshot() {
+        Some(snapshot) => snapshot_response(&snapshot.status, snapshot.taken_at),
+        None => Json(pool.status().await).into_response(),
+    }
+}
+
+/**
+List pending jobs in the order they will start, with estimated start times
+*/
+async fn get_queue(AxumState(pool): AxumState<Arc<JobPool>>) -> Json<Vec<QueuedJob>> {
+    Json(pool.queue().await)
+}
+
+/**
+Stream pool events as server-sent events (one JSON event per message)
+NOTE: a subscriber that falls too far behind skips the events it missed
+*/
+async fn get_events(
+    AxumState(pool): AxumState<Arc<JobPool>>,
+) -> Sse<impl Stream<Item = Result<sse::Event, Infallible>>> {
+    let events = stream::unfold(pool.subscribe(), |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => {
+                    let message = sse::Event::default()
+                        .json_data(&event)
+                        .unwrap_or_else(|_| sse::Event::default().comment("unserializable event"));
+                    return Some((Ok(message), rx));
+                }
+                Err(RecvError::Lagged(n)) => {
+                    println!("[api] event subscriber lagged, skipped {} events", n);
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+// Who a request acts as, from its principal header
+fn principal(pool: &JobPool, headers: &HeaderMap) -> Principal {
+    pool.principal(
+        headers
+            .get(PRINCIPAL_HEADER)
+            .and_then(|value| value.to_str().ok()),
+    )
 }
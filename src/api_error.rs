@@ -5,18 +5,44 @@ use axum::{
 
 #[derive(Debug)]
 pub enum ApiError {
+    BadRequest(String),
+    NotFound(String),
+    Forbidden(String),
+    Conflict(String),
     JobQueueClosed,
+    QueueFull,
+    Overloaded,
     InternalError(String),
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         match self {
+            ApiError::BadRequest(msg) => {
+                (StatusCode::BAD_REQUEST, format!("bad request: {msg}")).into_response()
+            }
+            ApiError::NotFound(what) => {
+                (StatusCode::NOT_FOUND, format!("not found: {what}")).into_response()
+            }
+            ApiError::Forbidden(msg) => {
+                (StatusCode::FORBIDDEN, format!("forbidden: {msg}")).into_response()
+            }
+            ApiError::Conflict(msg) => {
+                (StatusCode::CONFLICT, format!("conflict: {msg}")).into_response()
+            }
             ApiError::JobQueueClosed => (
                 StatusCode::SERVICE_UNAVAILABLE,
                 "job queue closed or unavailable",
             )
                 .into_response(),
+            ApiError::QueueFull => {
+                (StatusCode::TOO_MANY_REQUESTS, "job submission queue full").into_response()
+            }
+            ApiError::Overloaded => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "overloaded: shedding low-priority submissions",
+            )
+                .into_response(),
             ApiError::InternalError(msg) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 format!("internal error: {msg}"),
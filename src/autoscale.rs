@@ -0,0 +1,71 @@
+/*! Autoscale module for async orchestrator
+ * Grows and shrinks the pool's max_jobs based on queue depth
+ */
+
+/**
+ * AutoscaleConfig
+ * Bounds and hysteresis for the autoscaler
+ */
+#[derive(Debug, Clone)]
+pub struct AutoscaleConfig {
+    // never shrink below this
+    pub min_jobs: usize,
+    // never grow above this
+    pub max_jobs: usize,
+    // how many slots to add/remove per adjustment
+    pub step: usize,
+    // depth at or above which the queue counts as deep
+    pub grow_depth: usize,
+    // consecutive deep samples before growing
+    pub grow_after: u32,
+    // consecutive idle samples before shrinking
+    pub shrink_after: u32,
+}
+
+/**
+ * Autoscaler
+ * Hysteresis controller: a direction must hold for several consecutive
+ * samples before max_jobs changes, and any change resets both streaks
+ */
+pub struct Autoscaler {
+    config: AutoscaleConfig,
+    deep_streak: u32,
+    idle_streak: u32,
+}
+
+impl Autoscaler {
+    pub fn new(config: AutoscaleConfig) -> Self {
+        debug_assert!(config.min_jobs > 0 && config.min_jobs <= config.max_jobs);
+        Self {
+            config,
+            deep_streak: 0,
+            idle_streak: 0,
+        }
+    }
+
+    // Take one sample of the pool
+    // depth: work waiting for a slot; busy: occupied slots
+    // Returns the new max_jobs if it should change
+    pub fn observe(&mut self, depth: usize, busy: usize, max_jobs: usize) -> Option<usize> {
+        let deep = depth >= self.config.grow_depth;
+        // idle: nothing waiting and less than half the slots in use
+        let idle = depth == 0 && busy * 2 < max_jobs;
+
+        self.deep_streak = if deep { self.deep_streak + 1 } else { 0 };
+        self.idle_streak = if idle { self.idle_streak + 1 } else { 0 };
+
+        let target = if self.deep_streak >= self.config.grow_after {
+            (max_jobs + self.config.step).min(self.config.max_jobs)
+        } else if self.idle_streak >= self.config.shrink_after {
+            max_jobs
+                .saturating_sub(self.config.step)
+                .max(self.config.min_jobs)
+        } else {
+            return None;
+        };
+
+        self.deep_streak = 0;
+        self.idle_streak = 0;
+        (target != max_jobs).then_some(target)
+    }
+}
@@ -0,0 +1,327 @@
+/*! orchctl: command-line client for the async job orchestrator
+ *
+ * Usage: orchctl [--server URL] <command> [args]
+ *   submit <file.json>                     submit the job in a JSON file ("-" for stdin)
+ *   list [--queue NAME] [--state STATE]    list jobs
+ *   stats                                  show orchestrator metrics
+ *   top                                    live dashboard (Ctrl-C to quit)
+ *   replay <log> [--until TIME] [--steps]  rebuild pool state from an event log
+ *
+ * The server defaults to $ORCH_URL, then http://localhost:3000
+ */
+use async_job_orchestrator::client::Client;
+use async_job_orchestrator::events::{Event, EventKind};
+use async_job_orchestrator::jobs::{JobSubmission, State};
+use async_job_orchestrator::replay::{self, Replay};
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+use std::env;
+use std::io::{BufReader, Read, Write};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const USAGE: &str = "usage: orchctl [--server URL] <submit FILE | list [--queue NAME] [--state STATE] | stats | top | replay LOG [--until TIME] [--steps]>";
+
+// transitions kept on the top screen
+const TOP_RECENT_EVENTS: usize = 20;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let server = take_flag(&mut args, "--server")
+        .or_else(|| env::var("ORCH_URL").ok())
+        .unwrap_or_else(|| "http://localhost:3000".to_string());
+    let client = Client::new(&server);
+
+    let mut rest = args.split_off(args.len().min(1));
+    let result = match args.first().map(String::as_str) {
+        Some("submit") => submit(&client, &rest).await,
+        Some("list") => list(&client, &mut rest).await,
+        Some("stats") => stats(&client).await,
+        Some("top") => top(&client, &server).await,
+        Some("replay") => replay(&mut rest),
+        _ => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("orchctl: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Remove "--name value" from args, returning the value
+fn take_flag(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let i = args.iter().position(|a| a == name)?;
+    if i + 1 >= args.len() {
+        return None;
+    }
+    let value = args.remove(i + 1);
+    args.remove(i);
+    Some(value)
+}
+
+async fn submit(client: &Client, args: &[String]) -> Result<(), String> {
+    let [path] = args else {
+        return Err(USAGE.to_string());
+    };
+    let mut raw = String::new();
+    if path == "-" {
+        std::io::stdin()
+            .read_to_string(&mut raw)
+            .map_err(|e| format!("stdin: {e}"))?;
+    } else {
+        raw = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    }
+    let job: JobSubmission = serde_json::from_str(&raw).map_err(|e| format!("{path}: {e}"))?;
+    let id = client.submit(&job).await.map_err(|e| e.to_string())?;
+    println!("submitted {id}");
+    Ok(())
+}
+
+async fn list(client: &Client, args: &mut Vec<String>) -> Result<(), String> {
+    let queue = take_flag(args, "--queue");
+    let state = take_flag(args, "--state")
+        .map(|s| s.parse::<State>())
+        .transpose()?;
+    if !args.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    let jobs = client
+        .list_jobs(queue.as_deref())
+        .await
+        .map_err(|e| e.to_string())?;
+    println!(
+        "{:<26}  {:<10}  {:<10}  {:<8}  CREATED",
+        "ID", "STATE", "QUEUE", "PRIORITY"
+    );
+    for job in jobs {
+        if state.as_ref().is_some_and(|s| *s != job.state) {
+            continue;
+        }
+        println!(
+            "{:<26}  {:<10}  {:<10}  {:<8}  {}",
+            job.id,
+            job.state.to_string(),
+            job.queue,
+            format!("{:?}", job.priority).to_lowercase(),
+            job.created_at.format("%Y-%m-%d %H:%M:%S")
+        );
+    }
+    Ok(())
+}
+
+async fn stats(client: &Client) -> Result<(), String> {
+    let metrics = client.metrics().await.map_err(|e| e.to_string())?;
+    println!("submitted  {}", metrics.total_submitted);
+    println!("running    {}", metrics.running);
+    println!("stuck      {}", metrics.stuck);
+    println!("queued     {}", metrics.queued);
+    println!("succeeded  {}", metrics.succeeded);
+    println!("failed     {}", metrics.failed);
+    println!("avg run    {}ms", metrics.avg_duration_ms);
+    if !metrics.failures.is_empty() {
+        let failures: Vec<String> = metrics
+            .failures
+            .iter()
+            .map(|(class, count)| format!("{class} {count}"))
+            .collect();
+        println!("failures   {}", failures.join(", "));
+    }
+    if metrics.reclaimed_slots > 0 {
+        println!("reclaimed  {} slots", metrics.reclaimed_slots);
+    }
+    for (job_type, bucket) in &metrics.rate_limits {
+        println!(
+            "rate limit {job_type}: {:.2} of {} tokens per {}ms",
+            bucket.tokens, bucket.capacity, bucket.period_ms
+        );
+    }
+    for (job_type, d) in &metrics.durations {
+        println!(
+            "run time   {job_type}: p50 {}ms, p90 {}ms, p99 {}ms ({} runs)",
+            d.p50_ms, d.p90_ms, d.p99_ms, d.samples
+        );
+    }
+    let history = &metrics.history;
+    let budget = match history.budget_bytes {
+        0 => "unlimited".to_string(),
+        bytes => format!("{bytes} bytes"),
+    };
+    println!(
+        "history    {} jobs, {} bytes of {} ({} evicted, {} pinned)",
+        history.jobs, history.bytes, budget, history.evicted, history.pinned
+    );
+    if history.evicted > 0 {
+        let by = &history.evicted_by;
+        println!(
+            "evicted    {} over budget, {} over count, {} past age ({} archived)",
+            by.budget, by.count, by.age, by.archived
+        );
+    }
+    Ok(())
+}
+
+// Live dashboard: pool occupancy, queue depth, and recent job transitions
+// NOTE: redraws the whole screen each second with plain ANSI escapes
+async fn top(client: &Client, server: &str) -> Result<(), String> {
+    let recent: Arc<Mutex<VecDeque<Event>>> = Arc::default();
+
+    // follow the event stream in the background, reconnecting as needed
+    let events_client = client.clone();
+    let events_recent = recent.clone();
+    tokio::spawn(async move {
+        loop {
+            if let Ok(mut events) = events_client.events().await {
+                while let Ok(Some(event)) = events.next().await {
+                    let mut recent = events_recent.lock().unwrap();
+                    recent.push_front(event);
+                    recent.truncate(TOP_RECENT_EVENTS);
+                }
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    });
+
+    loop {
+        let status = client.pool_status().await;
+        let mut screen = String::from("\x1b[2J\x1b[H");
+        screen.push_str(&format!(
+            "orchctl top  {}  {}\n\n",
+            server,
+            chrono::Local::now().format("%H:%M:%S")
+        ));
+        match status {
+            Err(e) => screen.push_str(&format!("unavailable: {e}\n")),
+            Ok(status) => {
+                let width = status.max_jobs.max(status.busy);
+                screen.push_str(&format!(
+                    "pool   [{}{}] {}/{} busy{}{}\n\n",
+                    "#".repeat(status.busy),
+                    ".".repeat(width - status.busy),
+                    status.busy,
+                    status.max_jobs,
+                    if status.shedding { "  SHEDDING" } else { "" },
+                    if status.paused { "  PAUSED" } else { "" }
+                ));
+                screen.push_str(&format!(
+                    "{:<12} {:>8} {:>8} {:>8} {:>8}\n",
+                    "QUEUE", "RUNNING", "CAP", "PENDING", "LIMIT"
+                ));
+                for q in status.queues {
+                    let cap = q.max_concurrency.map_or("-".to_string(), |c| c.to_string());
+                    screen.push_str(&format!(
+                        "{:<12} {:>8} {:>8} {:>8} {:>8}{}\n",
+                        q.name,
+                        q.running,
+                        cap,
+                        q.pending,
+                        q.pending_limit,
+                        if q.paused { "  paused" } else { "" }
+                    ));
+                }
+            }
+        }
+
+        screen.push_str("\nRECENT EVENTS\n");
+        for event in recent.lock().unwrap().iter() {
+            let what = match &event.kind {
+                EventKind::JobStateChanged { job_id, state, .. } => format!("{job_id}  {state}"),
+                other => format!("{other:?}"),
+            };
+            screen.push_str(&format!(
+                "{}  {}\n",
+                event
+                    .at
+                    .with_timezone(&chrono::Local)
+                    .format("%H:%M:%S%.3f"),
+                what
+            ));
+        }
+
+        let mut stdout = std::io::stdout();
+        stdout
+            .write_all(screen.as_bytes())
+            .and_then(|_| stdout.flush())
+            .map_err(|e| e.to_string())?;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+// Rebuild pool state from an event log, optionally up to a time, printing
+// each event as it is applied with --steps
+fn replay(args: &mut Vec<String>) -> Result<(), String> {
+    let until = take_flag(args, "--until")
+        .map(|t| {
+            t.parse::<DateTime<Utc>>()
+                .map_err(|e| format!("--until {t}: {e} (expected RFC 3339)"))
+        })
+        .transpose()?;
+    let steps = match args.iter().position(|a| a == "--steps") {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    };
+    let [path] = args.as_slice() else {
+        return Err(USAGE.to_string());
+    };
+    let file = std::fs::File::open(path).map_err(|e| format!("{path}: {e}"))?;
+    let events = replay::read_log(BufReader::new(file)).map_err(|e| format!("{path}: {e}"))?;
+
+    let mut replay = Replay::new(events);
+    if let Some(until) = until {
+        replay = replay.until(until);
+    }
+    while let Some(event) = replay.step() {
+        if steps {
+            let what = match &event.kind {
+                EventKind::JobStateChanged { job_id, state, .. } => format!("{job_id}  {state}"),
+                other => format!("{other:?}"),
+            };
+            println!("{}  {}", event.at.format("%Y-%m-%d %H:%M:%S%.3f"), what);
+        }
+    }
+
+    let projection = replay.projection();
+    if steps {
+        println!();
+    }
+    println!(
+        "{} events applied, {} not{}",
+        projection.events_applied,
+        replay.remaining(),
+        until.map_or(String::new(), |t| format!(" (after {t})"))
+    );
+    if let Some(at) = projection.at {
+        println!("state as of {}", at.format("%Y-%m-%d %H:%M:%S%.3f"));
+    }
+    for (state, count) in projection.counts() {
+        println!("  {:<10} {}", state, count);
+    }
+    if let Some(shedding) = &projection.shedding {
+        println!(
+            "shedding since {} (depth {}, latency {}ms)",
+            shedding.since.format("%H:%M:%S%.3f"),
+            shedding.depth,
+            shedding.latency_ms
+        );
+    }
+    let unfinished: Vec<_> = projection.unfinished().collect();
+    if !unfinished.is_empty() {
+        println!("\n{:<26}  {:<10}  SINCE", "UNFINISHED", "STATE");
+        for (id, job) in unfinished {
+            let since = job.started_at.unwrap_or(job.first_seen);
+            println!(
+                "{:<26}  {:<10}  {}",
+                id,
+                job.state.to_string(),
+                since.format("%Y-%m-%d %H:%M:%S%.3f")
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,205 @@
+/*! Breaker module for async orchestrator
+ * Per job type circuit breakers: a type that keeps failing fails fast
+ *
+ * With CIRCUIT_BREAKER_THRESHOLD set, each job type's consecutive failures
+ * (executor failures, timeouts and stuck jobs) are counted; a success
+ * resets the count. When the count reaches the threshold the type's
+ * circuit opens: for CIRCUIT_BREAKER_COOLDOWN_MS its jobs fail as they come
+ * to dispatch, with class circuit_open, instead of taking a slot. Once the
+ * cool-down has passed the circuit is half open: the next job runs as a
+ * trial while the rest still fail fast, and the circuit closes if the
+ * trial succeeds or opens again if it fails. GET /admin/circuit-breakers
+ * shows each type's circuit.
+ * NOTE: jobs failed fast are retryable, and don't count as failures
+ */
+use crate::clock::Clock;
+use crate::failure::{FailureClass, FailureInfo};
+use crate::ids::JobId;
+use crate::jobs::{Job, State};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/**
+ * BreakerConfig
+ * When a job type's circuit opens, and for how long
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct BreakerConfig {
+    // consecutive failures that open the circuit
+    pub threshold: u32,
+    // how long an open circuit fails jobs fast before a trial runs
+    pub cool_down: Duration,
+}
+
+/**
+ * CircuitState
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    // jobs run
+    Closed,
+    // jobs fail fast until the cool-down has passed
+    Open,
+    // one trial job runs; the rest fail fast
+    HalfOpen,
+}
+
+/**
+ * CircuitStatus
+ * One job type's circuit
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CircuitStatus {
+    pub job_type: String,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    // when it last opened, while not closed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opened_at: Option<DateTime<Utc>>,
+    // when an open circuit goes half open
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub half_open_at: Option<DateTime<Utc>>,
+    // the half open circuit's trial job, while it runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trial: Option<JobId>,
+    // times it opened since startup
+    pub opened: u64,
+}
+
+// One job type's circuit, as tracked
+#[derive(Debug, Default)]
+struct Circuit {
+    failures: u32,
+    // None: closed
+    opened_at: Option<DateTime<Utc>>,
+    trial: Option<JobId>,
+    opened: u64,
+}
+
+/**
+ * CircuitBreakers
+ * Every job type's circuit; cheap to clone
+ */
+#[derive(Clone)]
+pub struct CircuitBreakers {
+    // None: circuits never open
+    config: Option<BreakerConfig>,
+    circuits: Arc<Mutex<BTreeMap<String, Circuit>>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl CircuitBreakers {
+    pub fn new(config: Option<BreakerConfig>, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            config,
+            circuits: Arc::new(Mutex::new(BTreeMap::new())),
+            clock,
+        }
+    }
+
+    /**
+     * admit: whether job_id, of job_type, may run now; Err: why it fails
+     * fast instead
+     * A job admitted while the circuit is half open is its trial
+     */
+    pub fn admit(&self, job_type: &str, job_id: JobId) -> Result<(), FailureInfo> {
+        let Some(config) = self.config else {
+            return Ok(());
+        };
+        let mut circuits = self.circuits.lock().unwrap();
+        let Some(circuit) = circuits.get_mut(job_type) else {
+            return Ok(());
+        };
+        let Some(opened_at) = circuit.opened_at else {
+            return Ok(());
+        };
+        let half_open_at = opened_at + config.cool_down;
+        let message = if self.clock.now() < half_open_at {
+            format!(
+                "circuit open for {job_type} after {} consecutive failures, until {half_open_at}",
+                circuit.failures
+            )
+        } else if let Some(trial) = circuit.trial {
+            format!("circuit half open for {job_type}: job {trial} running as its trial")
+        } else {
+            println!("[CircuitBreakers]: {job_type} half open, job {job_id} on trial");
+            circuit.trial = Some(job_id);
+            return Ok(());
+        };
+        Err(FailureInfo::pool(FailureClass::CircuitOpen, &message))
+    }
+
+    /**
+     * record: count a finished job's outcome toward its type's circuit
+     */
+    pub fn record(&self, job: &Job) {
+        let Some(config) = self.config else {
+            return;
+        };
+        let failed = job.started_at().is_some()
+            && job.failure().is_some_and(|f| {
+                matches!(
+                    f.class,
+                    FailureClass::Executor | FailureClass::Timeout | FailureClass::Stuck
+                )
+            });
+        let succeeded = *job.state() == State::SUCCEEDED;
+        let job_type = job.submission().kind.name();
+        let now = self.clock.now();
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(job_type.to_string()).or_default();
+        let trial = circuit.trial == Some(job.id());
+        if trial {
+            circuit.trial = None;
+        }
+        if succeeded {
+            if circuit.opened_at.take().is_some() {
+                println!("[CircuitBreakers]: {job_type} closed");
+            }
+            circuit.failures = 0;
+        } else if failed {
+            circuit.failures += 1;
+            let reopen = trial && circuit.opened_at.is_some();
+            let open = circuit.opened_at.is_none() && circuit.failures >= config.threshold;
+            if reopen || open {
+                println!(
+                    "[CircuitBreakers]: {job_type} open after {} consecutive failures",
+                    circuit.failures
+                );
+                circuit.opened_at = Some(now);
+                circuit.opened += 1;
+            }
+        }
+    }
+
+    pub fn list(&self) -> Vec<CircuitStatus> {
+        let cool_down = self.config.map(|c| c.cool_down).unwrap_or_default();
+        let now = self.clock.now();
+        self.circuits
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(job_type, circuit)| {
+                let half_open_at = circuit.opened_at.map(|at| at + cool_down);
+                let state = match half_open_at {
+                    None => CircuitState::Closed,
+                    Some(at) if now < at => CircuitState::Open,
+                    Some(_) => CircuitState::HalfOpen,
+                };
+                CircuitStatus {
+                    job_type: job_type.clone(),
+                    state,
+                    consecutive_failures: circuit.failures,
+                    opened_at: circuit.opened_at,
+                    half_open_at,
+                    trial: circuit.trial,
+                    opened: circuit.opened,
+                }
+            })
+            .collect()
+    }
+}
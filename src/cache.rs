@@ -0,0 +1,128 @@
+/*! Cache module for async orchestrator
+ * Result caching for job types that are pure functions of their payload
+ *
+ * Types are made cacheable, each with a TTL, by RESULT_CACHE_TTLS, e.g.
+ * "report=3600,echo=60" (seconds). When a job of a cacheable type succeeds
+ * its record is kept under a hash of its type and payload; a submission with
+ * the same hash within the TTL gets that record back instead of running.
+ * NOTE: a cache hit runs nothing, so it sends no callback or notifications
+ */
+use crate::jobs::{Job, JobSubmission, State};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::str::FromStr;
+use std::time::Duration;
+
+/**
+ * CacheConfig
+ * TTL per cacheable job type
+ */
+#[derive(Debug, Clone, Default)]
+pub struct CacheConfig {
+    pub ttls: HashMap<String, Duration>,
+}
+
+// "type=seconds,..."
+impl FromStr for CacheConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut ttls = HashMap::new();
+        for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (job_type, secs) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("'{entry}': expected type=seconds"))?;
+            let secs: u64 = secs.trim().parse().map_err(|e| format!("'{entry}': {e}"))?;
+            ttls.insert(job_type.trim().to_string(), Duration::from_secs(secs));
+        }
+        Ok(Self { ttls })
+    }
+}
+
+/**
+ * CachedJob
+ * A submission answered from the cache: the earlier job's record
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CachedJob {
+    // always true; marks the response as a cache hit
+    pub cached: bool,
+    #[serde(flatten)]
+    pub job: Job,
+}
+
+/**
+ * ResultCache
+ * Recent successful jobs of cacheable types, by payload hash
+ */
+#[derive(Debug, Default)]
+pub struct ResultCache {
+    config: CacheConfig,
+    entries: HashMap<u64, Job>,
+}
+
+impl ResultCache {
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            entries: HashMap::new(),
+        }
+    }
+
+    /**
+     * insert: remember a finished job, if it succeeded and its type is
+     * cacheable; expired entries are dropped along the way
+     */
+    pub fn insert(&mut self, job: &Job, now: DateTime<Utc>) {
+        let Some(ttl) = self.ttl(job.submission()) else {
+            return;
+        };
+        if *job.state() != State::SUCCEEDED || ttl.is_zero() {
+            return;
+        }
+        let config = &self.config;
+        self.entries
+            .retain(|_, cached| !expired(config, cached, now));
+        self.entries
+            .insert(payload_hash(job.submission()), job.clone());
+    }
+
+    /**
+     * get: a live cached job for the same type and payload
+     */
+    pub fn get(&self, submission: &JobSubmission, now: DateTime<Utc>) -> Option<&Job> {
+        self.ttl(submission)?;
+        self.entries
+            .get(&payload_hash(submission))
+            .filter(|cached| !expired(&self.config, cached, now))
+    }
+
+    fn ttl(&self, submission: &JobSubmission) -> Option<Duration> {
+        self.config.ttls.get(submission.kind.name()).copied()
+    }
+}
+
+// Past its type's TTL, counted from when it finished
+fn expired(config: &CacheConfig, job: &Job, now: DateTime<Utc>) -> bool {
+    let ttl = config
+        .ttls
+        .get(job.submission().kind.name())
+        .copied()
+        .unwrap_or_default();
+    job.finished_at()
+        .and_then(|finished| (now - finished).to_std().ok())
+        .is_none_or(|age| age >= ttl)
+}
+
+// Hash of the job type and payload, as serialized
+// NOTE: JSON objects serialize with sorted keys, so key order in the
+// submission doesn't matter
+pub fn payload_hash(submission: &JobSubmission) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(&submission.kind)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    hasher.finish()
+}
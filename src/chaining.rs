@@ -0,0 +1,86 @@
+/*! Chaining module for async orchestrator
+ * Follow-up jobs: submitted when a job finishes, by how it ended
+ *
+ * A submission may carry an on_success and an on_failure submission. When
+ * the job succeeds, the pool submits its on_success; when it fails, times
+ * out or misses its deadline, its on_failure. Follow-ups are checked along
+ * with the job when it is submitted, are submitted as any job is (and may
+ * be refused as any is, e.g. by a full queue), carry the job's id as
+ * follows, and may have follow-ups of their own: a simple pipeline,
+ * without a workflow.
+ * NOTE: a job cancelled or skipped has neither follow-up submitted
+ */
+use crate::ids::JobId;
+use crate::jobs::{Job, JobPool, JobSubmission, State};
+use std::sync::Weak;
+use tokio::sync::mpsc;
+
+/**
+ * FollowUp
+ * A finished job's follow-up, to be submitted
+ */
+#[derive(Debug)]
+pub struct FollowUp {
+    after: JobId,
+    submission: JobSubmission,
+}
+
+impl FollowUp {
+    /**
+     * of: the follow-up a finished job's outcome calls for, if it has one
+     */
+    pub fn of(job: &Job) -> Option<Self> {
+        let submission = job.submission();
+        let next = match job.state() {
+            State::SUCCEEDED => submission.on_success.as_deref(),
+            State::FAILED | State::TIMED_OUT | State::DEADLINE_MISSED => {
+                submission.on_failure.as_deref()
+            }
+            _ => None,
+        }?;
+        Some(Self {
+            after: job.id(),
+            submission: next.clone(),
+        })
+    }
+}
+
+/**
+ * FollowUps
+ * Follow-ups waiting to be submitted
+ */
+#[derive(Debug)]
+pub struct FollowUps {
+    requests: mpsc::UnboundedReceiver<FollowUp>,
+}
+
+/**
+ * channel: a follow-up sender for the pool, and its receiver
+ */
+pub fn channel() -> (mpsc::UnboundedSender<FollowUp>, FollowUps) {
+    let (tx, requests) = mpsc::unbounded_channel();
+    (tx, FollowUps { requests })
+}
+
+impl FollowUps {
+    /**
+     * drive: submit follow-ups as jobs finish, until the pool is gone
+     */
+    pub async fn drive(mut self, pool: Weak<JobPool>) {
+        while let Some(follow_up) = self.requests.recv().await {
+            let Some(pool) = pool.upgrade() else {
+                return;
+            };
+            match pool
+                .submit_follow_up(follow_up.after, follow_up.submission)
+                .await
+            {
+                Ok(id) => println!("[Chaining]: job {}: follow-up {}", follow_up.after, id),
+                Err(e) => println!(
+                    "[Chaining]: job {}: follow-up rejected: {:?}",
+                    follow_up.after, e
+                ),
+            }
+        }
+    }
+}
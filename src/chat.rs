@@ -0,0 +1,77 @@
+/*! Chat module for async orchestrator
+ * Slack / Discord incoming-webhook notification channels
+ * NOTE: the built-in client speaks plain http:// only; Slack and Discord
+ * hooks are https, so point channels at a TLS-terminating egress proxy
+ */
+use crate::jobs::Job;
+use crate::notify::{Notification, NotificationChannel, NotifyEvent};
+use crate::webhooks::{Delivery, Webhooks};
+use serde_json::json;
+
+/**
+ * ChatService
+ * Decides the message payload format
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChatService {
+    Slack,
+    Discord,
+}
+
+/**
+ * ChatChannel
+ * One incoming webhook; posts go through the webhook worker's retries
+ */
+pub struct ChatChannel {
+    service: ChatService,
+    url: String,
+    webhooks: Webhooks,
+}
+
+impl ChatChannel {
+    pub fn new(service: ChatService, url: &str, webhooks: Webhooks) -> Self {
+        Self {
+            service,
+            url: url.to_string(),
+            webhooks,
+        }
+    }
+}
+
+impl NotificationChannel for ChatChannel {
+    fn send(&self, notification: &Notification) -> Result<(), String> {
+        let body = format_message(self.service, notification.event, notification.job);
+        self.webhooks
+            .enqueue(Delivery::untracked(notification.job.id(), &self.url, body))
+    }
+}
+
+// Render a job event as the service's webhook payload
+fn format_message(service: ChatService, event: NotifyEvent, job: &Job) -> Vec<u8> {
+    let submission = job.submission();
+    let icon = match event {
+        NotifyEvent::Failed => "\u{274c}",
+        NotifyEvent::Succeeded => "\u{2705}",
+    };
+    let payload = match service {
+        ChatService::Slack => json!({
+            "text": format!(
+                "{icon} Job `{}` ({}) on queue *{}* {event}\n```{}```",
+                job.id(),
+                submission.kind.name(),
+                submission.queue,
+                job.result()
+            )
+        }),
+        ChatService::Discord => json!({
+            "content": format!(
+                "{icon} Job `{}` ({}) on queue **{}** {event}\n```\n{}\n```",
+                job.id(),
+                submission.kind.name(),
+                submission.queue,
+                job.result()
+            )
+        }),
+    };
+    payload.to_string().into_bytes()
+}
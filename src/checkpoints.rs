@@ -0,0 +1,118 @@
+/*! Checkpoints module for async orchestrator
+ * Progress a long multi-step job saves, so a retry can resume from it
+ *
+ * An execution saves named checkpoints (small JSON values) through
+ * ExecContext::checkpoints as it goes; they are kept on the job once its
+ * execution returns, and shown on GET /jobs/{id}. A submission whose
+ * resume_from names an earlier job starts with that job's checkpoints,
+ * and ExecContext::resume holds the last one saved, so the executor can
+ * skip the steps already done. Workflow step retries, and reruns of jobs
+ * that didn't succeed, resume from the attempt before, and a job preempted
+ * for a critical one from where it was stopped.
+ * NOTE: a job resumed from one that has left history starts afresh
+ */
+use crate::clock::{Clock, SystemClock};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+
+// largest checkpoint, serialized
+pub const CHECKPOINT_MAX_BYTES: usize = 64 * 1024;
+// most checkpoint names a job may save
+pub const CHECKPOINT_MAX_NAMES: usize = 32;
+
+/**
+ * Checkpoint
+ * One named checkpoint, as last saved
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    pub name: String,
+    pub data: Value,
+    pub saved_at: DateTime<Utc>,
+}
+
+/**
+ * Checkpoints
+ * An execution's checkpoints, oldest saved first; cheap to clone, clones
+ * share them
+ */
+#[derive(Debug, Clone)]
+pub struct Checkpoints {
+    saved: Arc<Mutex<Vec<Checkpoint>>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for Checkpoints {
+    fn default() -> Self {
+        Self::new(Vec::new(), Arc::new(SystemClock))
+    }
+}
+
+impl Checkpoints {
+    /**
+     * new: checkpoints starting from those of an earlier attempt
+     */
+    pub fn new(saved: Vec<Checkpoint>, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            saved: Arc::new(Mutex::new(saved)),
+            clock,
+        }
+    }
+
+    /**
+     * save: checkpoint name as data, replacing (and moving last) any
+     * earlier one of the name
+     * Fails for data over CHECKPOINT_MAX_BYTES, or a new name past
+     * CHECKPOINT_MAX_NAMES
+     */
+    pub fn save(&self, name: &str, data: Value) -> Result<(), String> {
+        let size = serde_json::to_vec(&data).map_or(0, |s| s.len());
+        if size > CHECKPOINT_MAX_BYTES {
+            return Err(format!(
+                "checkpoint '{name}': {size} bytes, over the {CHECKPOINT_MAX_BYTES} byte limit"
+            ));
+        }
+        let mut saved = self.saved.lock().unwrap();
+        match saved.iter().position(|c| c.name == name) {
+            Some(at) => {
+                saved.remove(at);
+            }
+            None if saved.len() >= CHECKPOINT_MAX_NAMES => {
+                return Err(format!(
+                    "checkpoint '{name}': over the {CHECKPOINT_MAX_NAMES} checkpoint limit"
+                ));
+            }
+            None => {}
+        }
+        saved.push(Checkpoint {
+            name: name.to_string(),
+            data,
+            saved_at: self.clock.now(),
+        });
+        Ok(())
+    }
+
+    /**
+     * get: the checkpoint saved as name, if any
+     */
+    pub fn get(&self, name: &str) -> Option<Value> {
+        let saved = self.saved.lock().unwrap();
+        saved
+            .iter()
+            .find(|c| c.name == name)
+            .map(|c| c.data.clone())
+    }
+
+    /**
+     * last: the checkpoint saved most recently, if any
+     */
+    pub fn last(&self) -> Option<Checkpoint> {
+        self.saved.lock().unwrap().last().cloned()
+    }
+
+    pub fn list(&self) -> Vec<Checkpoint> {
+        self.saved.lock().unwrap().clone()
+    }
+}
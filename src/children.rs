@@ -0,0 +1,148 @@
+/*! Children module for async orchestrator
+ * Sub-jobs: jobs a running job's execution spawns
+ *
+ * An execution may spawn child jobs through ExecContext::children. Each is
+ * submitted as any job is (and may be refused as any is), with its
+ * parent's id as parent_id, and is listed in the parent's children once
+ * the parent's execution returns. GET /jobs/{id}/children gives the tree
+ * of a job's children, and theirs.
+ * A parent submitted with wait_for_children stays RUNNING after its
+ * execution returns (its slot freed) until every child has finished: it
+ * then succeeds if they all did, and fails otherwise.
+ * NOTE: a parent waiting for its children isn't timed out, and cancelling
+ * a parent doesn't cancel its children
+ */
+use crate::ids::JobId;
+use crate::jobs::{JobPool, JobSubmission, JobView};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak, mpsc as std_mpsc};
+use tokio::sync::mpsc;
+
+/**
+ * SpawnRequest
+ * A child an execution asks the pool to submit, and where to answer
+ */
+#[derive(Debug)]
+pub struct SpawnRequest {
+    parent: JobId,
+    submission: JobSubmission,
+    reply: std_mpsc::Sender<Result<JobId, String>>,
+}
+
+/**
+ * Spawner
+ * Spawns a running job's children; the default spawns none
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Spawner {
+    // the job spawning, and where its requests go; None: can't spawn
+    target: Option<(JobId, mpsc::UnboundedSender<SpawnRequest>)>,
+    // ids of the children spawned so far
+    spawned: Arc<Mutex<Vec<JobId>>>,
+}
+
+impl Spawner {
+    pub fn new(parent: JobId, requests: mpsc::UnboundedSender<SpawnRequest>) -> Self {
+        Self {
+            target: Some((parent, requests)),
+            spawned: Arc::default(),
+        }
+    }
+
+    /**
+     * spawn: submit a child job, returning its id once accepted
+     * NOTE: blocks until the pool answers; call it from the execution's
+     * own thread
+     */
+    pub fn spawn(&self, submission: JobSubmission) -> Result<JobId, String> {
+        let Some((parent, requests)) = &self.target else {
+            return Err("this execution can't spawn jobs".to_string());
+        };
+        let (reply, answer) = std_mpsc::channel();
+        requests
+            .send(SpawnRequest {
+                parent: *parent,
+                submission,
+                reply,
+            })
+            .map_err(|_| "pool is gone".to_string())?;
+        let id = answer.recv().map_err(|_| "pool is gone".to_string())??;
+        self.spawned.lock().unwrap().push(id);
+        Ok(id)
+    }
+
+    /**
+     * spawned: the children spawned so far, oldest first
+     */
+    pub fn spawned(&self) -> Vec<JobId> {
+        self.spawned.lock().unwrap().clone()
+    }
+}
+
+/**
+ * SpawnRequests
+ * Children waiting to be submitted
+ */
+#[derive(Debug)]
+pub struct SpawnRequests {
+    requests: mpsc::UnboundedReceiver<SpawnRequest>,
+}
+
+/**
+ * channel: a spawn request sender for executions, and its receiver
+ */
+pub fn channel() -> (mpsc::UnboundedSender<SpawnRequest>, SpawnRequests) {
+    let (tx, requests) = mpsc::unbounded_channel();
+    (tx, SpawnRequests { requests })
+}
+
+impl SpawnRequests {
+    /**
+     * drive: submit children as executions ask, until the pool is gone
+     */
+    pub async fn drive(mut self, pool: Weak<JobPool>) {
+        while let Some(request) = self.requests.recv().await {
+            let Some(pool) = pool.upgrade() else {
+                return;
+            };
+            let result = pool
+                .submit_child(request.parent, request.submission)
+                .await
+                .map_err(|e| format!("{e:?}"));
+            if let Err(e) = &result {
+                println!("[Children]: job {}: child rejected: {}", request.parent, e);
+            }
+            // the execution may have given up waiting
+            let _ = request.reply.send(result);
+        }
+    }
+}
+
+/**
+ * JobTree
+ * A job, and the tree of the children it spawned
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JobTree {
+    #[serde(flatten)]
+    pub job: JobView,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<JobTree>,
+}
+
+/**
+ * tree: the children of parent, oldest first, each with its own; taken
+ * from the jobs listed by their parent's id
+ */
+pub fn tree(parent: JobId, by_parent: &mut HashMap<JobId, Vec<JobView>>) -> Vec<JobTree> {
+    let mut children = by_parent.remove(&parent).unwrap_or_default();
+    children.sort_by_key(|child| (child.created_at, child.id));
+    children
+        .into_iter()
+        .map(|job| {
+            let children = tree(job.id, by_parent);
+            JobTree { job, children }
+        })
+        .collect()
+}
@@ -0,0 +1,918 @@
+/*! Client module for async orchestrator
+ * Typed async client for the orchestrator's HTTP API
+ */
+use crate::access::PRINCIPAL_HEADER;
+use crate::breaker::CircuitStatus;
+use crate::children::JobTree;
+use crate::events::Event;
+use crate::executor::ExecutorInfo;
+use crate::health::GateStatus;
+use crate::http_client::{self, HttpError, HttpResponse, LineStream};
+use crate::ids::JobId;
+use crate::jobs::{
+    BulkCancelResult, CancelFilter, DryRunResult, GroupCancelResult, GroupStatus, Job, JobAccepted,
+    JobSubmission, JobView, MapAccepted, MapStatus, MapSubmission, PoolMetrics, PoolStatus,
+    QueuedJob, Transition, WaitResult,
+};
+use crate::listing::{JobSearch, NEXT_CURSOR_HEADER};
+use crate::logs::{GroupLogLine, LogOrder};
+use crate::pause::PauseState;
+use crate::resources::ResourceStatus;
+use crate::schedules::{
+    Schedule, ScheduleChange, ScheduleDefinition, SchedulePreview, ScheduleRun,
+};
+use crate::usage::UsageReport;
+use crate::workflows::{
+    DagSubmission, RunRequest, WorkflowDefinition, WorkflowRun, WorkflowVersion,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+use ulid::Ulid;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+// the time a client stops waiting for a response, in RFC 3339
+pub const REQUEST_DEADLINE_HEADER: &str = "X-Request-Deadline";
+
+/**
+ * ClientError
+ */
+#[derive(Debug)]
+pub enum ClientError {
+    // no usable response
+    Http(HttpError),
+    // non-2xx response
+    Status { status: u16, body: String },
+    // 2xx response that didn't decode
+    Decode(String),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Http(e) => write!(f, "{e}"),
+            ClientError::Status { status, body } => write!(f, "http {status}: {body}"),
+            ClientError::Decode(msg) => write!(f, "decode error: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+/**
+ * RetryPolicy
+ * Exponential backoff between attempts
+ */
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    // total attempts, including the first
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+/**
+ * Client
+ * NOTE: retries only when the request cannot have been acted on: the
+ * connection failed, or the server answered 429/503 (rejected before
+ * enqueueing). Submissions carry an idempotency key, reused across
+ * retries of the same submission. With a principal set, every request
+ * names it in the X-Principal header.
+ */
+#[derive(Debug, Clone)]
+pub struct Client {
+    // e.g. "http://localhost:3000", no trailing slash
+    base_url: String,
+    retry: RetryPolicy,
+    timeout: Duration,
+    // sent as the principal header; None: requests act as anonymous
+    principal: Option<String>,
+}
+
+impl Client {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            retry: RetryPolicy::default(),
+            timeout: Duration::from_secs(10),
+            principal: None,
+        }
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /**
+     * with_principal: act as name, e.g. to change schedules it owns
+     */
+    pub fn with_principal(mut self, name: &str) -> Self {
+        self.principal = Some(name.to_string());
+        self
+    }
+
+    /**
+     * submit: submit a job, returning its id
+     * Uses a fresh idempotency key
+     */
+    pub async fn submit(&self, job: &JobSubmission) -> Result<JobId, ClientError> {
+        self.submit_with_key(job, &Ulid::new().to_string()).await
+    }
+
+    /**
+     * submit_with_key: submit a job with a caller-chosen idempotency key
+     */
+    pub async fn submit_with_key(
+        &self,
+        job: &JobSubmission,
+        idempotency_key: &str,
+    ) -> Result<JobId, ClientError> {
+        let body = serde_json::to_vec(job).map_err(|e| ClientError::Decode(e.to_string()))?;
+        let response = self
+            .send(
+                "POST",
+                "/jobs",
+                &[
+                    ("Content-Type", "application/json"),
+                    (IDEMPOTENCY_KEY_HEADER, idempotency_key),
+                ],
+                Some(&body),
+            )
+            .await?;
+        // a cache hit answers with the cached job, which has an id too
+        let accepted: JobAccepted = serde_json::from_slice(&response.body)
+            .map_err(|e| ClientError::Decode(e.to_string()))?;
+        Ok(accepted.id)
+    }
+
+    /**
+     * dry_run: validate a job and see where it would go, without
+     * submitting it
+     */
+    pub async fn dry_run(&self, job: &JobSubmission) -> Result<DryRunResult, ClientError> {
+        let body = serde_json::to_vec(job).map_err(|e| ClientError::Decode(e.to_string()))?;
+        let response = self
+            .send(
+                "POST",
+                "/jobs?dry_run=true",
+                &[("Content-Type", "application/json")],
+                Some(&body),
+            )
+            .await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * job: one job's full record
+     */
+    pub async fn job(&self, id: JobId) -> Result<Job, ClientError> {
+        let response = self.send("GET", &format!("/jobs/{id}"), &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * cancel_job: cancel a queued or running job
+     */
+    pub async fn cancel_job(&self, id: JobId) -> Result<Job, ClientError> {
+        let response = self
+            .send("POST", &format!("/jobs/{id}/cancel"), &[], None)
+            .await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * job_events: a job's state changes, oldest first
+     */
+    pub async fn job_events(&self, id: JobId) -> Result<Vec<Transition>, ClientError> {
+        let response = self
+            .send("GET", &format!("/jobs/{id}/events"), &[], None)
+            .await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * job_children: the tree of the jobs a job spawned
+     */
+    pub async fn job_children(&self, id: JobId) -> Result<Vec<JobTree>, ClientError> {
+        let response = self
+            .send("GET", &format!("/jobs/{id}/children"), &[], None)
+            .await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * clone_job: resubmit a job with a JSON merge patch applied to its
+     * submission, returning the copy's id
+     */
+    pub async fn clone_job(&self, id: JobId, patch: &Value) -> Result<JobId, ClientError> {
+        let body = serde_json::to_vec(patch).map_err(|e| ClientError::Decode(e.to_string()))?;
+        let response = self
+            .send(
+                "POST",
+                &format!("/jobs/{id}/clone"),
+                &[("Content-Type", "application/json")],
+                Some(&body),
+            )
+            .await?;
+        let accepted: JobAccepted = serde_json::from_slice(&response.body)
+            .map_err(|e| ClientError::Decode(e.to_string()))?;
+        Ok(accepted.id)
+    }
+
+    /**
+     * rerun_job: run a finished job again, returning the rerun's id
+     */
+    pub async fn rerun_job(&self, id: JobId) -> Result<JobId, ClientError> {
+        let response = self
+            .send("POST", &format!("/jobs/{id}/rerun"), &[], None)
+            .await?;
+        let accepted: JobAccepted = serde_json::from_slice(&response.body)
+            .map_err(|e| ClientError::Decode(e.to_string()))?;
+        Ok(accepted.id)
+    }
+
+    /**
+     * job_if_changed: a job's record, unless it is still at revision
+     * Returns None while unchanged; the server sends no body then
+     */
+    pub async fn job_if_changed(
+        &self,
+        id: JobId,
+        revision: u64,
+    ) -> Result<Option<Job>, ClientError> {
+        let etag = format!("\"{revision}\"");
+        let path = format!("/jobs/{id}");
+        match self
+            .send("GET", &path, &[("If-None-Match", &etag)], None)
+            .await
+        {
+            Err(ClientError::Status { status: 304, .. }) => Ok(None),
+            Err(e) => Err(e),
+            Ok(response) => serde_json::from_slice(&response.body)
+                .map(Some)
+                .map_err(|e| ClientError::Decode(e.to_string())),
+        }
+    }
+
+    /**
+     * list_jobs: list jobs, optionally only those on one queue
+     */
+    pub async fn list_jobs(&self, queue: Option<&str>) -> Result<Vec<JobView>, ClientError> {
+        let path = match queue {
+            Some(queue) => format!("/jobs?queue={}", http_client::encode(queue)),
+            None => "/jobs".to_string(),
+        };
+        let response = self.send("GET", &path, &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * list_jobs_page: up to limit jobs in id order, after cursor (None:
+     * from the first); and the next page's cursor, if more follow
+     */
+    pub async fn list_jobs_page(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> Result<(Vec<JobView>, Option<String>), ClientError> {
+        let mut path = format!("/jobs?limit={limit}");
+        if let Some(cursor) = cursor {
+            path.push_str(&format!("&cursor={}", http_client::encode(cursor)));
+        }
+        let response = self.send("GET", &path, &[], None).await?;
+        let jobs = serde_json::from_slice(&response.body)
+            .map_err(|e| ClientError::Decode(e.to_string()))?;
+        let next = response.header(NEXT_CURSOR_HEADER).map(str::to_string);
+        Ok((jobs, next))
+    }
+
+    /**
+     * search_jobs: the jobs a search (without fields or include) finds;
+     * and the next page's cursor, if it is paged and more follow
+     */
+    pub async fn search_jobs(
+        &self,
+        search: &JobSearch,
+    ) -> Result<(Vec<JobView>, Option<String>), ClientError> {
+        let body = serde_json::to_vec(search).map_err(|e| ClientError::Decode(e.to_string()))?;
+        let response = self
+            .send(
+                "POST",
+                "/jobs/search",
+                &[("Content-Type", "application/json")],
+                Some(&body),
+            )
+            .await?;
+        let jobs = serde_json::from_slice(&response.body)
+            .map_err(|e| ClientError::Decode(e.to_string()))?;
+        let next = response.header(NEXT_CURSOR_HEADER).map(str::to_string);
+        Ok((jobs, next))
+    }
+
+    /**
+     * wait_for: wait until the jobs finish, or the server-side timeout passes
+     * NOTE: the server caps the timeout
+     */
+    pub async fn wait_for(
+        &self,
+        ids: &[JobId],
+        timeout: Duration,
+    ) -> Result<WaitResult, ClientError> {
+        let body = serde_json::json!({ "ids": ids, "timeout_ms": timeout.as_millis() as u64 });
+        // the request itself outlives the wait
+        let waiting = self.clone().with_timeout(self.timeout + timeout);
+        let response = waiting
+            .send(
+                "POST",
+                "/jobs/wait",
+                &[("Content-Type", "application/json")],
+                Some(body.to_string().as_bytes()),
+            )
+            .await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * wait_until: wait until the jobs finish, or the deadline passes; the
+     * wait ends early if a job is expected to finish after the deadline
+     */
+    pub async fn wait_until(
+        &self,
+        ids: &[JobId],
+        deadline: DateTime<Utc>,
+    ) -> Result<WaitResult, ClientError> {
+        let timeout = (deadline - Utc::now()).to_std().unwrap_or_default();
+        let body = serde_json::json!({ "ids": ids, "timeout_ms": timeout.as_millis() as u64 });
+        let deadline = deadline.to_rfc3339_opts(SecondsFormat::Millis, true);
+        let waiting = self.clone().with_timeout(self.timeout + timeout);
+        let response = waiting
+            .send(
+                "POST",
+                "/jobs/wait",
+                &[
+                    ("Content-Type", "application/json"),
+                    (REQUEST_DEADLINE_HEADER, &deadline),
+                ],
+                Some(body.to_string().as_bytes()),
+            )
+            .await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * submit_map: submit one job per input from a template
+     */
+    pub async fn submit_map(&self, map: &MapSubmission) -> Result<MapAccepted, ClientError> {
+        let body = serde_json::to_vec(map).map_err(|e| ClientError::Decode(e.to_string()))?;
+        let response = self
+            .send(
+                "POST",
+                "/maps",
+                &[("Content-Type", "application/json")],
+                Some(&body),
+            )
+            .await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * map_status: a map's progress and per-item results
+     */
+    pub async fn map_status(&self, map_id: &str) -> Result<MapStatus, ClientError> {
+        let path = format!("/maps/{}", http_client::encode(map_id));
+        let response = self.send("GET", &path, &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * upload_workflow: store a workflow definition as its next version
+     */
+    pub async fn upload_workflow(
+        &self,
+        name: &str,
+        definition: &WorkflowDefinition,
+    ) -> Result<WorkflowVersion, ClientError> {
+        let path = format!("/workflows/{}", http_client::encode(name));
+        let body =
+            serde_json::to_vec(definition).map_err(|e| ClientError::Decode(e.to_string()))?;
+        let response = self
+            .send(
+                "PUT",
+                &path,
+                &[("Content-Type", "application/json")],
+                Some(&body),
+            )
+            .await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * workflow_versions: every stored version of a workflow, newest first
+     */
+    pub async fn workflow_versions(&self, name: &str) -> Result<Vec<WorkflowVersion>, ClientError> {
+        let path = format!("/workflows/{}/versions", http_client::encode(name));
+        let response = self.send("GET", &path, &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * submit_dag: store a DAG of jobs as a workflow version and run it
+     */
+    pub async fn submit_dag(&self, dag: &DagSubmission) -> Result<WorkflowRun, ClientError> {
+        let body = serde_json::to_vec(dag).map_err(|e| ClientError::Decode(e.to_string()))?;
+        let response = self
+            .send(
+                "POST",
+                "/workflows",
+                &[("Content-Type", "application/json")],
+                Some(&body),
+            )
+            .await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * run_workflow: start a run of a stored workflow
+     */
+    pub async fn run_workflow(
+        &self,
+        name: &str,
+        request: &RunRequest,
+    ) -> Result<WorkflowRun, ClientError> {
+        let path = format!("/workflows/{}/run", http_client::encode(name));
+        let body = serde_json::to_vec(request).map_err(|e| ClientError::Decode(e.to_string()))?;
+        let response = self
+            .send(
+                "POST",
+                &path,
+                &[("Content-Type", "application/json")],
+                Some(&body),
+            )
+            .await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * workflow_run: a workflow run's progress
+     */
+    pub async fn workflow_run(&self, run_id: &str) -> Result<WorkflowRun, ClientError> {
+        let path = format!("/workflow-runs/{}", http_client::encode(run_id));
+        let response = self.send("GET", &path, &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * create_schedule: add a cron schedule
+     */
+    pub async fn create_schedule(
+        &self,
+        definition: &ScheduleDefinition,
+    ) -> Result<Schedule, ClientError> {
+        let body =
+            serde_json::to_vec(definition).map_err(|e| ClientError::Decode(e.to_string()))?;
+        let response = self
+            .send(
+                "POST",
+                "/schedules",
+                &[("Content-Type", "application/json")],
+                Some(&body),
+            )
+            .await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * list_schedules: every schedule
+     */
+    pub async fn list_schedules(&self) -> Result<Vec<Schedule>, ClientError> {
+        let response = self.send("GET", "/schedules", &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * schedule: one schedule
+     */
+    pub async fn schedule(&self, id: &str) -> Result<Schedule, ClientError> {
+        let path = format!("/schedules/{}", http_client::encode(id));
+        let response = self.send("GET", &path, &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * replace_schedule: update a schedule, keeping its run history
+     */
+    pub async fn replace_schedule(
+        &self,
+        id: &str,
+        definition: &ScheduleDefinition,
+    ) -> Result<Schedule, ClientError> {
+        let path = format!("/schedules/{}", http_client::encode(id));
+        let body =
+            serde_json::to_vec(definition).map_err(|e| ClientError::Decode(e.to_string()))?;
+        let response = self
+            .send(
+                "PUT",
+                &path,
+                &[("Content-Type", "application/json")],
+                Some(&body),
+            )
+            .await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * delete_schedule: delete a schedule; an admin may restore it until it
+     * is purged
+     */
+    pub async fn delete_schedule(&self, id: &str) -> Result<(), ClientError> {
+        let path = format!("/schedules/{}", http_client::encode(id));
+        self.send("DELETE", &path, &[], None).await?;
+        Ok(())
+    }
+
+    /**
+     * deleted_schedules: deleted schedules not yet purged
+     */
+    pub async fn deleted_schedules(&self) -> Result<Vec<Schedule>, ClientError> {
+        let response = self
+            .send("GET", "/admin/schedules/deleted", &[], None)
+            .await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * restore_schedule: bring back a deleted schedule (as an admin)
+     */
+    pub async fn restore_schedule(&self, id: &str) -> Result<Schedule, ClientError> {
+        let path = format!("/admin/schedules/{}/restore", http_client::encode(id));
+        let response = self.send("POST", &path, &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * schedule_runs: a schedule's recent runs, newest first
+     */
+    pub async fn schedule_runs(&self, id: &str) -> Result<Vec<ScheduleRun>, ClientError> {
+        let path = format!("/schedules/{}/runs", http_client::encode(id));
+        let response = self.send("GET", &path, &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * schedule_history: a schedule's replaced definitions, newest first
+     */
+    pub async fn schedule_history(&self, id: &str) -> Result<Vec<ScheduleChange>, ClientError> {
+        let path = format!("/schedules/{}/history", http_client::encode(id));
+        let response = self.send("GET", &path, &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * schedule_preview: a schedule's next count fire times
+     */
+    pub async fn schedule_preview(
+        &self,
+        id: &str,
+        count: usize,
+    ) -> Result<SchedulePreview, ClientError> {
+        let path = format!("/schedules/{}/next?count={count}", http_client::encode(id));
+        let response = self.send("GET", &path, &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * group_status: aggregate status of a job group
+     */
+    pub async fn group_status(&self, group_id: &str) -> Result<GroupStatus, ClientError> {
+        let path = format!("/groups/{}", http_client::encode(group_id));
+        let response = self.send("GET", &path, &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * group_logs: the log lines of all of a job group's jobs
+     */
+    pub async fn group_logs(
+        &self,
+        group_id: &str,
+        order: LogOrder,
+    ) -> Result<Vec<GroupLogLine>, ClientError> {
+        let order = match order {
+            LogOrder::Interleaved => "interleaved",
+            LogOrder::Segmented => "segmented",
+        };
+        let path = format!(
+            "/groups/{}/logs?order={order}",
+            http_client::encode(group_id)
+        );
+        let response = self.send("GET", &path, &[], None).await?;
+        response
+            .text()
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_str(line).map_err(|e| ClientError::Decode(e.to_string())))
+            .collect()
+    }
+
+    /**
+     * cancel_group: cancel a job group's jobs that haven't started
+     */
+    pub async fn cancel_group(&self, group_id: &str) -> Result<GroupCancelResult, ClientError> {
+        let path = format!("/groups/{}/cancel", http_client::encode(group_id));
+        let response = self.send("POST", &path, &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * cancel_jobs: cancel every queued or running job matching a filter
+     */
+    pub async fn cancel_jobs(
+        &self,
+        filter: &CancelFilter,
+    ) -> Result<BulkCancelResult, ClientError> {
+        let body = serde_json::to_vec(filter).map_err(|e| ClientError::Decode(e.to_string()))?;
+        let response = self
+            .send(
+                "POST",
+                "/jobs/cancel",
+                &[("Content-Type", "application/json")],
+                Some(&body),
+            )
+            .await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * schemas: the registered payload schemas, by job type
+     */
+    pub async fn schemas(&self) -> Result<BTreeMap<String, Value>, ClientError> {
+        let response = self.send("GET", "/admin/schemas", &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * put_schema: register (or replace) a job type's payload schema
+     */
+    pub async fn put_schema(&self, job_type: &str, schema: &Value) -> Result<(), ClientError> {
+        let path = format!("/admin/schemas/{}", http_client::encode(job_type));
+        self.send(
+            "PUT",
+            &path,
+            &[("Content-Type", "application/json")],
+            Some(schema.to_string().as_bytes()),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /**
+     * delete_schema: remove a job type's payload schema
+     */
+    pub async fn delete_schema(&self, job_type: &str) -> Result<(), ClientError> {
+        let path = format!("/admin/schemas/{}", http_client::encode(job_type));
+        self.send("DELETE", &path, &[], None).await?;
+        Ok(())
+    }
+
+    /**
+     * health_gates: each health-gated job type's downstream health
+     */
+    pub async fn health_gates(&self) -> Result<Vec<GateStatus>, ClientError> {
+        let response = self.send("GET", "/admin/health-gates", &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * resources: the shared resources the executor registered
+     */
+    pub async fn resources(&self) -> Result<Vec<ResourceStatus>, ClientError> {
+        let response = self.send("GET", "/admin/resources", &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * circuit_breakers: each job type's circuit breaker
+     */
+    pub async fn circuit_breakers(&self) -> Result<Vec<CircuitStatus>, ClientError> {
+        let response = self
+            .send("GET", "/admin/circuit-breakers", &[], None)
+            .await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * unique_keys: held unique keys, each with the job holding it
+     */
+    pub async fn unique_keys(&self) -> Result<BTreeMap<String, JobId>, ClientError> {
+        let response = self.send("GET", "/admin/unique-keys", &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * pauses: what is paused, and why
+     */
+    pub async fn pauses(&self) -> Result<PauseState, ClientError> {
+        let response = self.send("GET", "/admin/pauses", &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * pause: pause the pool (queue None) or one queue
+     */
+    pub async fn pause(
+        &self,
+        queue: Option<&str>,
+        reason: &str,
+    ) -> Result<PauseState, ClientError> {
+        let body = serde_json::json!({ "reason": reason }).to_string();
+        let response = self
+            .send(
+                "POST",
+                &pause_path(queue, "pause"),
+                &[("Content-Type", "application/json")],
+                Some(body.as_bytes()),
+            )
+            .await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * resume: lift the pool's (queue None) or one queue's pause
+     */
+    pub async fn resume(&self, queue: Option<&str>) -> Result<PauseState, ClientError> {
+        let response = self
+            .send("POST", &pause_path(queue, "resume"), &[], None)
+            .await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * usage: cost of the jobs that finished in [from, to), either bound
+     * optional
+     */
+    pub async fn usage(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<UsageReport, ClientError> {
+        let bounds: Vec<String> = [("from", from), ("to", to)]
+            .into_iter()
+            .filter_map(|(name, t)| {
+                let t = t?.to_rfc3339_opts(SecondsFormat::AutoSi, true);
+                Some(format!("{name}={}", http_client::encode(&t)))
+            })
+            .collect();
+        let path = if bounds.is_empty() {
+            "/usage".to_string()
+        } else {
+            format!("/usage?{}", bounds.join("&"))
+        };
+        let response = self.send("GET", &path, &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * metrics: job counts and run times since startup, with history usage
+     */
+    pub async fn metrics(&self) -> Result<PoolMetrics, ClientError> {
+        let response = self.send("GET", "/metrics", &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * executors: the server's executors, with versions and capabilities
+     */
+    pub async fn executors(&self) -> Result<Vec<ExecutorInfo>, ClientError> {
+        let response = self.send("GET", "/executors", &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * pool_status: current pool occupancy
+     */
+    pub async fn pool_status(&self) -> Result<PoolStatus, ClientError> {
+        let response = self.send("GET", "/pool", &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * queue: pending jobs in the order they will start
+     */
+    pub async fn queue(&self) -> Result<Vec<QueuedJob>, ClientError> {
+        let response = self.send("GET", "/queue", &[], None).await?;
+        serde_json::from_slice(&response.body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /**
+     * events: subscribe to the live pool event stream
+     * NOTE: not retried; reconnect by calling again
+     */
+    pub async fn events(&self) -> Result<EventStream, ClientError> {
+        let url = format!("{}/events", self.base_url);
+        let lines = http_client::stream(&url, &[("Accept", "text/event-stream")], self.timeout)
+            .await
+            .map_err(ClientError::Http)?;
+        if !(200..300).contains(&lines.status) {
+            return Err(ClientError::Status {
+                status: lines.status,
+                body: String::new(),
+            });
+        }
+        Ok(EventStream { lines })
+    }
+
+    // Send a request, retrying per the retry policy
+    async fn send(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Option<&[u8]>,
+    ) -> Result<HttpResponse, ClientError> {
+        let url = format!("{}{}", self.base_url, path);
+        let mut headers = headers.to_vec();
+        if let Some(principal) = &self.principal {
+            headers.push((PRINCIPAL_HEADER, principal));
+        }
+        let mut backoff = self.retry.initial_backoff;
+        let mut attempt = 1;
+        loop {
+            let result = http_client::request(method, &url, &headers, body, self.timeout).await;
+            let retryable = match &result {
+                Err(HttpError::Io(e)) => e.kind() == std::io::ErrorKind::ConnectionRefused,
+                Ok(response) => response.status == 429 || response.status == 503,
+                Err(_) => false,
+            };
+            if !retryable || attempt >= self.retry.max_attempts {
+                let response = result.map_err(ClientError::Http)?;
+                if !response.is_success() {
+                    return Err(ClientError::Status {
+                        status: response.status,
+                        body: response.text(),
+                    });
+                }
+                return Ok(response);
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(self.retry.max_backoff);
+            attempt += 1;
+        }
+    }
+}
+
+// Admin path pausing or resuming the pool, or one queue
+fn pause_path(queue: Option<&str>, action: &str) -> String {
+    match queue {
+        None => format!("/admin/pool/{action}"),
+        Some(name) => format!("/admin/queues/{}/{action}", http_client::encode(name)),
+    }
+}
+
+/**
+ * EventStream
+ * Pool events decoded from the server-sent event stream
+ */
+pub struct EventStream {
+    lines: LineStream,
+}
+
+impl EventStream {
+    /**
+     * next: the next event; None when the server ends the stream
+     */
+    pub async fn next(&mut self) -> Result<Option<Event>, ClientError> {
+        let mut data = String::new();
+        loop {
+            let Some(line) = self.lines.next_line().await.map_err(ClientError::Http)? else {
+                return Ok(None);
+            };
+            if let Some(value) = line.strip_prefix("data:") {
+                data.push_str(value.strip_prefix(' ').unwrap_or(value));
+            } else if line.is_empty() && !data.is_empty() {
+                // end of message
+                return serde_json::from_str(&data)
+                    .map(Some)
+                    .map_err(|e| ClientError::Decode(e.to_string()));
+            }
+            // comments (keep-alives) and other fields are ignored
+        }
+    }
+}
@@ -0,0 +1,26 @@
+/*! Clock module for async orchestrator
+ * Where the pool reads the current time
+ */
+use chrono::{DateTime, Utc};
+use std::fmt;
+
+/**
+ * Clock
+ * Source of wall-clock time for timestamps and schedules
+ */
+pub trait Clock: Send + Sync + fmt::Debug {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/**
+ * SystemClock
+ * The real time
+ */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
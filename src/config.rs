@@ -0,0 +1,453 @@
+/*! Config module for async orchestrator
+ * Runtime parameters, read from env vars
+ */
+use crate::access::Admins;
+use crate::autoscale::AutoscaleConfig;
+use crate::breaker::BreakerConfig;
+use crate::cache::CacheConfig;
+use crate::clock::{Clock, SystemClock};
+use crate::defaults::JobDefaults;
+use crate::durations::ShortestFirstConfig;
+use crate::egress::EgressPolicy;
+use crate::email::{self, EmailConfig};
+use crate::estimate::AdmissionConfig;
+use crate::executor::Executor;
+use crate::handlers::Handlers;
+use crate::health::{GateList, HealthConfig};
+use crate::history::RetentionConfig;
+use crate::hooks::HookList;
+use crate::ids::{IdFormat, IdGenerator, PrefixedIds};
+use crate::jobs::Priority;
+use crate::notify::{ChannelList, NotifyConfig, RuleList};
+use crate::overload::LoadShedConfig;
+use crate::profiles::EnvProfiles;
+use crate::queues::{DEFAULT_QUEUE, PendingOverflow, QueueConfig, QueueList};
+use crate::ratelimit::RateLimitList;
+use crate::scratch::ScratchConfig;
+use crate::tenants::TenantPolicies;
+use crate::usage::CostModel;
+use crate::webhooks::WebhookConfig;
+use std::env;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/**
+ * OverflowPolicy
+ * What a submission does when the submission queue is full
+ */
+#[derive(Debug, Clone, Copy)]
+pub enum OverflowPolicy {
+    // fail immediately
+    Reject,
+    // wait for room, but give up after the deadline
+    BlockWithDeadline(Duration),
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+
+    // "reject" or "block:<ms>"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "reject" => Ok(OverflowPolicy::Reject),
+            Some(("block", ms)) => ms
+                .parse::<u64>()
+                .map(|ms| OverflowPolicy::BlockWithDeadline(Duration::from_millis(ms)))
+                .map_err(|_| format!("invalid block deadline '{ms}'")),
+            _ => Err(format!("unknown overflow policy '{s}'")),
+        }
+    }
+}
+
+/**
+ * StuckAction
+ * What the watchdog does with a running job whose heartbeat went quiet
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StuckAction {
+    // mark it STUCK, back to RUNNING if it beats again
+    Flag,
+    // fail it and ask its execution to stop; its slot stays busy until
+    // the execution returns
+    Fail,
+}
+
+impl FromStr for StuckAction {
+    type Err = String;
+
+    // "flag" or "fail"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flag" => Ok(StuckAction::Flag),
+            "fail" => Ok(StuckAction::Fail),
+            _ => Err(format!("unknown stuck action '{s}'")),
+        }
+    }
+}
+
+/**
+ * StuckConfig
+ * When a running job counts as stuck, and what happens to it
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct StuckConfig {
+    // longest a running job may go without a heartbeat (its start counts
+    // as one)
+    pub after: Duration,
+    pub action: StuckAction,
+}
+
+/**
+ * PoolConfig
+ * Job pool parameters
+ */
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    pub max_jobs: usize,
+    pub submission_queue_size: usize,
+    pub overflow_policy: OverflowPolicy,
+    // how often the run loop samples load for the controllers below
+    pub sample_interval: Duration,
+    // None: max_jobs stays fixed
+    pub autoscale: Option<AutoscaleConfig>,
+    // None: never shed load
+    pub load_shed: Option<LoadShedConfig>,
+    // named queues, in dispatch order; always includes the default queue
+    pub queues: Vec<QueueConfig>,
+    // completion callback delivery
+    pub webhooks: WebhookConfig,
+    // notification channels and routing
+    pub notify: NotifyConfig,
+    // time source for timestamps and schedules
+    pub clock: Arc<dyn Clock>,
+    // runs the jobs
+    pub executor: Arc<dyn Executor>,
+    // makes new jobs' ids
+    pub ids: Arc<dyn IdGenerator>,
+    // middleware around the executor, outermost first
+    pub hooks: HookList,
+    // append pool events here, for replay; None: not persisted
+    pub event_log: Option<PathBuf>,
+    // cost weight per job type, for usage reports
+    pub cost: CostModel,
+    // save pauses here, restoring them at startup; None: not persisted
+    pub pause_state: Option<PathBuf>,
+    // cacheable job types and how long their results are reused
+    pub cache: CacheConfig,
+    // per job type defaults for fields a submission omits
+    pub defaults: JobDefaults,
+    // per tenant default queue, highest priority allowed and cost quota
+    pub tenants: TenantPolicies,
+    // which estimates submissions must pass to be admitted
+    pub admission: AdmissionConfig,
+    // None: jobs get no scratch directory
+    pub scratch: Option<ScratchConfig>,
+    // timeout of jobs that don't set timeout_ms; None: unlimited
+    pub job_timeout: Option<Duration>,
+    // bytes finished jobs may take in memory; 0: unlimited
+    pub history_budget: usize,
+    // how many finished jobs are kept, for how long, and where evicted
+    // ones are archived
+    pub retention: RetentionConfig,
+    // how long idempotency keys are remembered; zero: keys are ignored
+    pub dedup_window: Duration,
+    // finished jobs each tenant may pin (jobs without a tenant share one
+    // quota)
+    pub pin_quota: usize,
+    // principals who may change any schedule or workflow
+    pub admins: Admins,
+    // None: running jobs are never considered stuck
+    pub stuck: Option<StuckConfig>,
+    // None: job types' circuits never open
+    pub breaker: Option<BreakerConfig>,
+    // None: each queue dispatches in priority order, oldest first
+    pub shortest_first: Option<ShortestFirstConfig>,
+    // dispatch jobs with nearer deadlines first among those of equal
+    // priority, ahead of shortest-first
+    pub earliest_deadline_first: bool,
+    // environment variable sets submissions run with, by name
+    pub env_profiles: EnvProfiles,
+    // where jobs may connect out to, and through which proxy
+    pub egress: EgressPolicy,
+    // downstream health checks gating job types; no gates: nothing is held
+    pub health: HealthConfig,
+    // token bucket rate limits by job type; none: no type is limited
+    pub rate_limits: RateLimitList,
+    // how often list and stats endpoints' read snapshot is retaken
+    // None: they read the live pool
+    pub snapshot_interval: Option<Duration>,
+    // bytes per second each job's execution may log; None: unlimited
+    pub log_rate_limit: Option<u64>,
+    // how long deleted schedules can be restored before they are purged
+    // None: until restart
+    pub schedule_purge_after: Option<Duration>,
+}
+
+/**
+ * Config
+ * Top level application config
+ */
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub port: u16,
+    pub pool: PoolConfig,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        let max_jobs = env_or("MAX_CONCURRENCY", 4);
+        Self {
+            port: env_or("PORT", 3000),
+            pool: PoolConfig {
+                max_jobs,
+                submission_queue_size: env_or("SUBMISSION_QUEUE_SIZE", 32),
+                overflow_policy: env_or("OVERFLOW_POLICY", OverflowPolicy::Reject),
+                sample_interval: Duration::from_millis(env_or("SAMPLE_INTERVAL_MS", 1000)),
+                autoscale: autoscale_from_env(max_jobs),
+                load_shed: load_shed_from_env(),
+                queues: queues_from_env(),
+                webhooks: WebhookConfig {
+                    max_attempts: env_or("WEBHOOK_MAX_ATTEMPTS", 5),
+                    initial_backoff: Duration::from_millis(env_or("WEBHOOK_BACKOFF_MS", 500)),
+                    max_backoff: Duration::from_millis(env_or("WEBHOOK_MAX_BACKOFF_MS", 60_000)),
+                    timeout: Duration::from_millis(env_or("WEBHOOK_TIMEOUT_MS", 10_000)),
+                    queue_size: env_or("WEBHOOK_QUEUE_SIZE", 1024),
+                    concurrency: env_or("WEBHOOK_CONCURRENCY", 4),
+                },
+                notify: notify_from_env(),
+                clock: Arc::new(SystemClock),
+                executor: Arc::new(Handlers::builtin()),
+                ids: ids_from_env(),
+                hooks: env_or("EXECUTOR_HOOKS", HookList::default()),
+                event_log: env::var("EVENT_LOG").ok().map(PathBuf::from),
+                cost: env_or("COST_WEIGHTS", CostModel::default()),
+                pause_state: env::var("PAUSE_STATE").ok().map(PathBuf::from),
+                cache: env_or("RESULT_CACHE_TTLS", CacheConfig::default()),
+                defaults: env_or("JOB_DEFAULTS", JobDefaults::default()),
+                tenants: env_or("TENANT_POLICIES", TenantPolicies::default()),
+                admission: AdmissionConfig {
+                    deadlines: env_or("ADMIT_BY_DEADLINE", false),
+                    quota_window: Duration::from_secs(env_or("TENANT_QUOTA_WINDOW_SECS", 86_400)),
+                },
+                scratch: env::var("SCRATCH_DIR").ok().map(|root| ScratchConfig {
+                    root: PathBuf::from(root),
+                    retain_failed: env_or("SCRATCH_RETAIN_FAILED", false),
+                }),
+                job_timeout: match env_or("JOB_TIMEOUT_MS", 0) {
+                    0 => None,
+                    ms => Some(Duration::from_millis(ms)),
+                },
+                history_budget: env_or("HISTORY_BUDGET_BYTES", 256 * 1024 * 1024),
+                retention: RetentionConfig {
+                    max_jobs: env_or("HISTORY_MAX_JOBS", 0),
+                    max_age: match env_or("HISTORY_MAX_AGE_SECS", 0) {
+                        0 => None,
+                        secs => Some(Duration::from_secs(secs)),
+                    },
+                    gc_interval: Duration::from_secs(env_or("HISTORY_GC_INTERVAL_SECS", 60).max(1)),
+                    archive: env::var("HISTORY_ARCHIVE").ok().map(PathBuf::from),
+                },
+                dedup_window: Duration::from_secs(env_or("DEDUP_WINDOW_SECS", 3600)),
+                pin_quota: env_or("PIN_QUOTA", 100),
+                admins: env_or("ADMINS", Admins::default()),
+                stuck: stuck_from_env(),
+                breaker: breaker_from_env(),
+                shortest_first: shortest_first_from_env(),
+                earliest_deadline_first: env_or("EARLIEST_DEADLINE_FIRST", false),
+                env_profiles: env_or("ENV_PROFILES", EnvProfiles::default()),
+                egress: egress_from_env(),
+                health: HealthConfig {
+                    gates: env_or("HEALTH_GATES", GateList::default()),
+                    interval: Duration::from_millis(env_or("HEALTH_CHECK_INTERVAL_MS", 5000)),
+                    max_hold: Duration::from_millis(env_or("HEALTH_MAX_HOLD_MS", 300_000)),
+                },
+                rate_limits: env_or("RATE_LIMITS", RateLimitList::default()),
+                snapshot_interval: match env_or("SNAPSHOT_INTERVAL_MS", 0) {
+                    0 => None,
+                    ms => Some(Duration::from_millis(ms)),
+                },
+                log_rate_limit: match env_or("LOG_RATE_LIMIT_BYTES", 0) {
+                    0 => None,
+                    bytes => Some(bytes),
+                },
+                schedule_purge_after: match env_or("SCHEDULE_PURGE_AFTER_SECS", 7 * 24 * 3600) {
+                    0 => None,
+                    secs => Some(Duration::from_secs(secs)),
+                },
+            },
+        }
+    }
+}
+
+// Autoscaling is enabled by setting a ceiling (AUTOSCALE_MAX_JOBS)
+// NOTE: the configured concurrency is the floor unless overridden
+fn autoscale_from_env(max_jobs: usize) -> Option<AutoscaleConfig> {
+    let ceiling: usize = env_or("AUTOSCALE_MAX_JOBS", 0);
+    if ceiling == 0 {
+        return None;
+    }
+    let min_jobs = env_or("AUTOSCALE_MIN_JOBS", max_jobs).clamp(1, ceiling);
+    Some(AutoscaleConfig {
+        min_jobs,
+        max_jobs: ceiling,
+        step: env_or("AUTOSCALE_STEP", 1),
+        grow_depth: env_or("AUTOSCALE_GROW_DEPTH", 1),
+        grow_after: env_or("AUTOSCALE_GROW_AFTER", 3),
+        shrink_after: env_or("AUTOSCALE_SHRINK_AFTER", 30),
+    })
+}
+
+// Stuck job detection is enabled by setting a heartbeat threshold
+// (STUCK_AFTER_MS); executors that don't beat their heartbeat need one
+// longer than their longest job
+fn stuck_from_env() -> Option<StuckConfig> {
+    match env_or("STUCK_AFTER_MS", 0) {
+        0 => None,
+        ms => Some(StuckConfig {
+            after: Duration::from_millis(ms),
+            action: env_or("STUCK_ACTION", StuckAction::Flag),
+        }),
+    }
+}
+
+// Circuit breakers are enabled by setting the consecutive failures that
+// open a job type's circuit (CIRCUIT_BREAKER_THRESHOLD)
+fn breaker_from_env() -> Option<BreakerConfig> {
+    match env_or("CIRCUIT_BREAKER_THRESHOLD", 0) {
+        0 => None,
+        threshold => Some(BreakerConfig {
+            threshold,
+            cool_down: Duration::from_millis(env_or("CIRCUIT_BREAKER_COOLDOWN_MS", 30_000)),
+        }),
+    }
+}
+
+// Job ids are ULIDs unless ID_FORMAT names another built-in format,
+// after ID_PREFIX if set
+fn ids_from_env() -> Arc<dyn IdGenerator> {
+    let format: IdFormat = env_or("ID_FORMAT", IdFormat::default());
+    let ids = format.generator(env_or("SNOWFLAKE_WORKER_ID", 0));
+    match env::var("ID_PREFIX") {
+        Err(_) => ids,
+        Ok(prefix) => match PrefixedIds::new(&prefix, ids.clone()) {
+            Ok(prefixed) => Arc::new(prefixed),
+            Err(e) => {
+                println!("[config] ignoring ID_PREFIX={prefix}: {e}");
+                ids
+            }
+        },
+    }
+}
+
+// Shortest-first dispatch is enabled by setting the queue depth it starts
+// at (SHORTEST_FIRST_DEPTH)
+fn shortest_first_from_env() -> Option<ShortestFirstConfig> {
+    match env_or("SHORTEST_FIRST_DEPTH", 0) {
+        0 => None,
+        depth => Some(ShortestFirstConfig {
+            depth,
+            percentile: env_or("SHORTEST_FIRST_PERCENTILE", 50u8).clamp(1, 100),
+        }),
+    }
+}
+
+// Load shedding is enabled by setting a depth and/or latency threshold
+fn load_shed_from_env() -> Option<LoadShedConfig> {
+    let depth: usize = env_or("SHED_DEPTH", 0);
+    let latency_ms: u64 = env_or("SHED_LATENCY_MS", 0);
+    if depth == 0 && latency_ms == 0 {
+        return None;
+    }
+    Some(LoadShedConfig {
+        depth: (depth > 0).then_some(depth),
+        latency: (latency_ms > 0).then(|| Duration::from_millis(latency_ms)),
+        enter_after: env_or("SHED_ENTER_AFTER", 3),
+        exit_after: env_or("SHED_EXIT_AFTER", 10),
+        shed_below: env_or("SHED_BELOW", Priority::High),
+    })
+}
+
+// Notifications: named channels (NOTIFY_CHANNELS) and the rules routing
+// job events to them (NOTIFY_RULES), e.g.
+//   NOTIFY_CHANNELS="ops=slack:http://proxy/slack/T0/B0/x,oncall=email:a@example.com|b@example.com"
+//   NOTIFY_RULES="failed->ops; failed,queue=critical->oncall"
+fn notify_from_env() -> NotifyConfig {
+    let ChannelList(channels) = env_or("NOTIFY_CHANNELS", ChannelList::default());
+    let RuleList(rules) = env_or("NOTIFY_RULES", RuleList::default());
+    NotifyConfig {
+        channels,
+        rules,
+        email: email_from_env(),
+    }
+}
+
+// Email channels need a relay (SMTP_SERVER, host:port)
+fn email_from_env() -> Option<EmailConfig> {
+    let server = env::var("SMTP_SERVER").ok()?;
+    let server = if server.contains(':') {
+        server
+    } else {
+        format!("{server}:25")
+    };
+    Some(EmailConfig {
+        server,
+        from: env_or("SMTP_FROM", "orchestrator@localhost".to_string()),
+        subject: env_or("SMTP_SUBJECT", email::DEFAULT_SUBJECT.to_string()),
+        body: env_or("SMTP_BODY", email::DEFAULT_BODY.to_string()),
+        timeout: Duration::from_millis(env_or("SMTP_TIMEOUT_MS", 30_000)),
+        queue_size: env_or("SMTP_QUEUE_SIZE", 256),
+    })
+}
+
+// Job egress, e.g. EGRESS_ALLOW="api.example.com,*.internal",
+// EGRESS_PROXY="http://proxy.internal:3128", EGRESS_DENY_BY_DEFAULT=true
+fn egress_from_env() -> EgressPolicy {
+    EgressPolicy {
+        allow: env::var("EGRESS_ALLOW")
+            .unwrap_or_default()
+            .split(',')
+            .map(|pattern| pattern.trim().to_string())
+            .filter(|pattern| !pattern.is_empty())
+            .collect(),
+        proxy: env::var("EGRESS_PROXY")
+            .ok()
+            .filter(|proxy| !proxy.is_empty()),
+        deny_by_default: env_or("EGRESS_DENY_BY_DEFAULT", false),
+    }
+}
+
+// Named queues come from QUEUES, e.g. "critical:2:10,default,bulk:1:1000",
+// or "bulk:1:1000:reject:2" for a bulk queue stealing with weight 2
+// NOTE: the default queue is added (uncapped, no pending room) if missing
+fn queues_from_env() -> Vec<QueueConfig> {
+    let QueueList(mut queues) = env_or("QUEUES", QueueList(Vec::new()));
+    if !queues.iter().any(|q| q.name == DEFAULT_QUEUE) {
+        queues.push(QueueConfig {
+            pending_limit: env_or("PENDING_LIMIT", 64),
+            overflow: env_or("PENDING_OVERFLOW", PendingOverflow::Reject),
+            ..QueueConfig::new(DEFAULT_QUEUE)
+        });
+    }
+    queues
+}
+
+// Read and parse an env var, falling back to a default
+// NOTE: an unparseable value is reported and ignored
+fn env_or<T>(name: &str, default: T) -> T
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    match env::var(name) {
+        Err(_) => default,
+        Ok(raw) => match raw.parse::<T>() {
+            Ok(v) => v,
+            Err(e) => {
+                println!("[config] ignoring {name}={raw}: {e}");
+                default
+            }
+        },
+    }
+}
@@ -0,0 +1,211 @@
+/*! Cron module for async orchestrator
+ * Five-field cron expressions: minute hour day-of-month month day-of-week
+ *
+ * Each field is "*", a value, a range "a-b", or a list of these, each
+ * optionally stepped ("*\/15", "1-30/2"). Months and weekdays also accept
+ * three-letter names (JAN, MON); Sunday is 0 or 7. As in classic cron,
+ * when both day fields are restricted a day matching either one fires.
+ */
+use chrono::{DateTime, Datelike, Duration, FixedOffset, TimeZone, Timelike, Utc};
+use std::fmt;
+use std::str::FromStr;
+
+// give up looking for a fire time this far ahead (e.g. "0 0 30 2 *")
+const SEARCH_YEARS: i32 = 5;
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/**
+ * Field
+ * The values one cron field matches, as a bit set
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Field {
+    bits: u64,
+    // "*" (possibly stepped): doesn't restrict the day
+    any: bool,
+}
+
+impl Field {
+    fn contains(&self, v: u32) -> bool {
+        self.bits & (1 << v) != 0
+    }
+
+    // Parse one field over [min, max]; names map to min, min+1, ...
+    fn parse(s: &str, min: u32, max: u32, names: &[&str]) -> Result<Self, String> {
+        let value = |v: &str| -> Result<u32, String> {
+            let lower = v.to_ascii_lowercase();
+            let n = match names.iter().position(|n| *n == lower) {
+                Some(i) => i as u32 + min,
+                None => v.parse().map_err(|_| format!("invalid value '{v}'"))?,
+            };
+            if n < min || n > max {
+                return Err(format!("value {n} out of range {min}-{max}"));
+            }
+            Ok(n)
+        };
+
+        let mut field = Field {
+            bits: 0,
+            any: false,
+        };
+        for part in s.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (
+                    range,
+                    step.parse::<u32>()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or_else(|| format!("invalid step '{step}'"))?,
+                ),
+                None => (part, 1),
+            };
+            let (lo, hi) = if range == "*" {
+                field.any = true;
+                (min, max)
+            } else if let Some((lo, hi)) = range.split_once('-') {
+                (value(lo)?, value(hi)?)
+            } else {
+                let v = value(range)?;
+                // "5/15" means from 5 to the end, every 15
+                (v, if step > 1 { max } else { v })
+            };
+            if lo > hi {
+                return Err(format!("empty range '{range}'"));
+            }
+            for v in (lo..=hi).step_by(step as usize) {
+                field.bits |= 1 << v;
+            }
+        }
+        Ok(field)
+    }
+}
+
+/**
+ * Cron
+ * A parsed cron expression
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cron {
+    source: String,
+    minutes: Field,
+    hours: Field,
+    days: Field,
+    months: Field,
+    weekdays: Field,
+}
+
+impl FromStr for Cron {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("cron expression '{s}' needs 5 fields"));
+        };
+        let field = |name: &str, s: &str, min, max, names| {
+            Field::parse(s, min, max, names).map_err(|e| format!("{name}: {e}"))
+        };
+        let mut weekdays = field("day of week", weekday, 0, 7, &WEEKDAYS)?;
+        // 7 is another Sunday
+        if weekdays.contains(7) {
+            weekdays.bits |= 1;
+        }
+        Ok(Self {
+            source: fields.join(" "),
+            minutes: field("minute", minute, 0, 59, &[])?,
+            hours: field("hour", hour, 0, 23, &[])?,
+            days: field("day of month", day, 1, 31, &[])?,
+            months: field("month", month, 1, 12, &MONTHS)?,
+            weekdays,
+        })
+    }
+}
+
+impl fmt::Display for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Cron {
+    // Day fields combine with OR when both are restricted, else AND
+    fn day_matches(&self, t: &DateTime<FixedOffset>) -> bool {
+        let dom = self.days.contains(t.day());
+        let dow = self.weekdays.contains(t.weekday().num_days_from_sunday());
+        match (self.days.any, self.weekdays.any) {
+            (false, false) => dom || dow,
+            _ => dom && dow,
+        }
+    }
+
+    /**
+     * next_after: the first fire time strictly after `after`, with fields
+     * read as wall-clock time at the given offset
+     * Returns None if nothing matches within a few years
+     */
+    pub fn next_after(&self, after: DateTime<Utc>, tz: FixedOffset) -> Option<DateTime<Utc>> {
+        // start at the next whole minute
+        let mut t = after
+            .with_timezone(&tz)
+            .with_second(0)?
+            .with_nanosecond(0)?
+            + Duration::minutes(1);
+        let limit = t.year() + SEARCH_YEARS;
+        while t.year() <= limit {
+            if !self.months.contains(t.month()) {
+                // first minute of next month
+                let (y, m) = if t.month() == 12 {
+                    (t.year() + 1, 1)
+                } else {
+                    (t.year(), t.month() + 1)
+                };
+                t = tz.with_ymd_and_hms(y, m, 1, 0, 0, 0).single()?;
+                continue;
+            }
+            if !self.day_matches(&t) {
+                t = (t + Duration::days(1)).with_hour(0)?.with_minute(0)?;
+                continue;
+            }
+            if !self.hours.contains(t.hour()) {
+                t = (t + Duration::hours(1)).with_minute(0)?;
+                continue;
+            }
+            if !self.minutes.contains(t.minute()) {
+                t += Duration::minutes(1);
+                continue;
+            }
+            return Some(t.with_timezone(&Utc));
+        }
+        None
+    }
+}
+
+/**
+ * parse_timezone: "UTC", or a fixed offset such as "+02:00" / "-0530"
+ * NOTE: no named zones (and so no DST); use the offset in effect
+ */
+pub fn parse_timezone(s: &str) -> Result<FixedOffset, String> {
+    if s.eq_ignore_ascii_case("utc") || s == "Z" {
+        return Ok(FixedOffset::east_opt(0).unwrap());
+    }
+    let invalid = || format!("invalid timezone '{s}': use UTC or an offset like +02:00");
+    let (sign, rest) = match s.as_bytes().first() {
+        Some(b'+') => (1, &s[1..]),
+        Some(b'-') => (-1, &s[1..]),
+        _ => return Err(invalid()),
+    };
+    let digits: String = rest.chars().filter(|c| *c != ':').collect();
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let hours: i32 = digits[..2].parse().map_err(|_| invalid())?;
+    let minutes: i32 = digits[2..].parse().map_err(|_| invalid())?;
+    if minutes >= 60 {
+        return Err(invalid());
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).ok_or_else(invalid)
+}
@@ -0,0 +1,213 @@
+/*! Dedup module for async orchestrator
+ * Duplicate submission detection, and what it suppressed
+ *
+ * A submission carrying an Idempotency-Key header that was already used
+ * within DEDUP_WINDOW_SECS gets the job first submitted under that key back
+ * (200) instead of creating another. Together with result cache hits (see
+ * the cache module), these suppressions are counted and the most recent
+ * listed on GET /admin/dedup, so callers can see how much duplicate traffic
+ * they send.
+ * A key is claimed for its first submission's job before that job is
+ * submitted, so of two concurrent first submissions of one key only one
+ * creates a job; the key is let go if the submission is then refused.
+ * NOTE: a submission racing the first one may be answered with its job
+ * before the first has been accepted
+ */
+use crate::ids::JobId;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+// suppressions listed on the report
+const RECENT_LIMIT: usize = 100;
+
+/**
+ * SuppressionKind
+ * How a duplicate was recognised
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SuppressionKind {
+    // a reused Idempotency-Key
+    IdempotencyKey,
+    // the type and payload of a cached result
+    PayloadHash,
+}
+
+/**
+ * Suppression
+ * One submission answered with an existing job instead of a new one
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Suppression {
+    pub at: DateTime<Utc>,
+    pub kind: SuppressionKind,
+    // the idempotency key, or the payload hash in hex
+    pub key: String,
+    pub job_type: String,
+    // the job answered with
+    pub job_id: JobId,
+    // suppressions of this key within the window, this one included
+    pub hits: u64,
+}
+
+/**
+ * DedupReport
+ * Suppression counts since startup and the latest suppressions
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DedupReport {
+    pub window_secs: u64,
+    // idempotency keys currently remembered
+    pub tracked_keys: usize,
+    pub key_hits: u64,
+    pub cache_hits: u64,
+    // newest first
+    pub recent: Vec<Suppression>,
+}
+
+/**
+ * KeyEntry
+ * The job first submitted under an idempotency key
+ */
+#[derive(Debug, Clone)]
+struct KeyEntry {
+    job_id: JobId,
+    job_type: String,
+    first_seen: DateTime<Utc>,
+    hits: u64,
+}
+
+/**
+ * Dedup
+ * Remembered idempotency keys and recent suppressions
+ */
+#[derive(Debug, Default)]
+pub struct Dedup {
+    // Duration::ZERO: idempotency keys are ignored
+    window: Duration,
+    keys: HashMap<String, KeyEntry>,
+    // payload hash -> suppressions within the window
+    hashes: HashMap<u64, (DateTime<Utc>, u64)>,
+    recent: VecDeque<Suppression>,
+    key_hits: u64,
+    cache_hits: u64,
+}
+
+impl Dedup {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            ..Self::default()
+        }
+    }
+
+    /**
+     * claim: the job first submitted under key within the window, if any,
+     * counting the suppression; None: key is claimed for job_id (unless
+     * keys are ignored), to be released if its submission fails
+     */
+    pub fn claim(
+        &mut self,
+        key: &str,
+        job_id: JobId,
+        job_type: &str,
+        now: DateTime<Utc>,
+    ) -> Option<JobId> {
+        self.expire(now);
+        let Some(entry) = self.keys.get_mut(key) else {
+            self.remember(key, job_id, job_type, now);
+            return None;
+        };
+        entry.hits += 1;
+        let suppression = Suppression {
+            at: now,
+            kind: SuppressionKind::IdempotencyKey,
+            key: key.to_string(),
+            job_type: entry.job_type.clone(),
+            job_id: entry.job_id,
+            hits: entry.hits,
+        };
+        let job_id = entry.job_id;
+        self.key_hits += 1;
+        self.record(suppression);
+        Some(job_id)
+    }
+
+    // Remember the job submitted under key, for the length of the window
+    fn remember(&mut self, key: &str, job_id: JobId, job_type: &str, now: DateTime<Utc>) {
+        if self.window.is_zero() {
+            return;
+        }
+        self.keys.entry(key.to_string()).or_insert(KeyEntry {
+            job_id,
+            job_type: job_type.to_string(),
+            first_seen: now,
+            hits: 0,
+        });
+    }
+
+    /**
+     * release: let go of key, if it is still claimed for job_id
+     */
+    pub fn release(&mut self, key: &str, job_id: JobId) {
+        if self
+            .keys
+            .get(key)
+            .is_some_and(|entry| entry.job_id == job_id)
+        {
+            self.keys.remove(key);
+        }
+    }
+
+    /**
+     * cache_hit: count a submission answered from the result cache
+     */
+    pub fn cache_hit(&mut self, hash: u64, job_id: JobId, job_type: &str, now: DateTime<Utc>) {
+        self.expire(now);
+        let (_, hits) = self.hashes.entry(hash).or_insert((now, 0));
+        *hits += 1;
+        let suppression = Suppression {
+            at: now,
+            kind: SuppressionKind::PayloadHash,
+            key: format!("{hash:016x}"),
+            job_type: job_type.to_string(),
+            job_id,
+            hits: *hits,
+        };
+        self.cache_hits += 1;
+        self.record(suppression);
+    }
+
+    pub fn report(&self) -> DedupReport {
+        DedupReport {
+            window_secs: self.window.as_secs(),
+            tracked_keys: self.keys.len(),
+            key_hits: self.key_hits,
+            cache_hits: self.cache_hits,
+            recent: self.recent.iter().rev().cloned().collect(),
+        }
+    }
+
+    // Keep a suppression, dropping the oldest past the limit
+    fn record(&mut self, suppression: Suppression) {
+        println!(
+            "[Dedup]: suppressed {} submission ({}), answered with job {}",
+            suppression.job_type, suppression.key, suppression.job_id
+        );
+        if self.recent.len() == RECENT_LIMIT {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(suppression);
+    }
+
+    // Forget keys and hash counts first seen before the window
+    fn expire(&mut self, now: DateTime<Utc>) {
+        let window = self.window;
+        let live =
+            |first_seen: DateTime<Utc>| (now - first_seen).to_std().is_ok_and(|age| age < window);
+        self.keys.retain(|_, entry| live(entry.first_seen));
+        self.hashes.retain(|_, (first_seen, _)| live(*first_seen));
+    }
+}
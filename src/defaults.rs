@@ -0,0 +1,109 @@
+/*! Defaults module for async orchestrator
+ * Per job type defaults for submission fields, managed centrally
+ *
+ * Set by JOB_DEFAULTS as "type.field=value" entries, e.g.
+ * "sleep.priority=low,sleep.queue=batch,sleep.timeout_ms=60000". A default
+ * fills a field only when the submission (or map) omits it; what a client
+ * sends always wins, and types without defaults fall back to the usual ones.
+ * NOTE: defaults apply to submissions over the API as JSON, before they are
+ * parsed; submissions built in code are taken as they are
+ */
+use crate::api_error::ApiError;
+use crate::jobs::{JOB_TYPES, Priority};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/**
+ * TypeDefaults
+ * Defaults for one job type; None leaves the field's usual default
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TypeDefaults {
+    pub priority: Option<Priority>,
+    pub queue: Option<String>,
+    pub timeout_ms: Option<u64>,
+}
+
+impl TypeDefaults {
+    // The defaults as submission fields
+    fn fields(&self) -> Vec<(&'static str, Value)> {
+        let mut fields = Vec::new();
+        if let Some(priority) = self.priority {
+            fields.push((
+                "priority",
+                serde_json::to_value(priority).unwrap_or_default(),
+            ));
+        }
+        if let Some(queue) = &self.queue {
+            fields.push(("queue", Value::String(queue.clone())));
+        }
+        if let Some(timeout_ms) = self.timeout_ms {
+            fields.push(("timeout_ms", Value::from(timeout_ms)));
+        }
+        fields
+    }
+}
+
+/**
+ * JobDefaults
+ * Defaults for each job type that has any
+ */
+#[derive(Debug, Clone, Default)]
+pub struct JobDefaults {
+    pub by_type: HashMap<String, TypeDefaults>,
+}
+
+impl JobDefaults {
+    /**
+     * apply: fill the fields a raw submission omits from its type's
+     * defaults, then parse it
+     * Works for anything naming its job type in "type": jobs and maps
+     */
+    pub fn apply<T: DeserializeOwned>(&self, mut raw: Value) -> Result<T, ApiError> {
+        let defaults = raw
+            .get("type")
+            .and_then(Value::as_str)
+            .and_then(|job_type| self.by_type.get(job_type));
+        if let (Some(defaults), Some(fields)) = (defaults, raw.as_object_mut()) {
+            for (name, value) in defaults.fields() {
+                fields.entry(name).or_insert(value);
+            }
+        }
+        serde_json::from_value(raw).map_err(|e| ApiError::BadRequest(e.to_string()))
+    }
+}
+
+// "type.field=value,..."
+impl FromStr for JobDefaults {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut by_type: HashMap<String, TypeDefaults> = HashMap::new();
+        for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (key, value) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("'{entry}': expected type.field=value"))?;
+            let (job_type, field) = key
+                .trim()
+                .split_once('.')
+                .ok_or_else(|| format!("'{entry}': expected type.field=value"))?;
+            if !JOB_TYPES.contains(&job_type) {
+                return Err(format!("'{entry}': unknown job type '{job_type}'"));
+            }
+            let value = value.trim();
+            let defaults = by_type.entry(job_type.to_string()).or_default();
+            match field {
+                "priority" => defaults.priority = Some(value.parse()?),
+                "queue" => defaults.queue = Some(value.to_string()),
+                "timeout_ms" => {
+                    defaults.timeout_ms =
+                        Some(value.parse().map_err(|e| format!("'{entry}': {e}"))?)
+                }
+                _ => return Err(format!("'{entry}': unknown field '{field}'")),
+            }
+        }
+        Ok(Self { by_type })
+    }
+}
@@ -0,0 +1,98 @@
+/*! Durations module for async orchestrator
+ * How long each job type runs, as percentiles over its recent runs
+ *
+ * Every finished job that ran adds its run time to its type's sample;
+ * GET /metrics reports the percentiles per type. With shortest-first
+ * dispatch enabled (SHORTEST_FIRST_DEPTH), a queue that deep starts, among
+ * its highest priority pending jobs, the one whose type is expected to be
+ * shortest, so many small jobs don't wait behind the occasional long one.
+ * NOTE: a long job may wait for as long as its queue stays deep
+ */
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::Duration;
+
+// recent run times kept per job type
+const SAMPLE_LIMIT: usize = 200;
+
+/**
+ * ShortestFirstConfig
+ * When dispatch prefers the jobs expected to be shortest
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct ShortestFirstConfig {
+    // pending jobs on a queue at or above which it dispatches shortest first
+    pub depth: usize,
+    // percentile of a type's run times taken as its expected run time
+    pub percentile: u8,
+}
+
+/**
+ * DurationPercentiles
+ * Run time percentiles of one job type
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DurationPercentiles {
+    // recent runs the percentiles are over
+    pub samples: usize,
+    pub p50_ms: u64,
+    pub p90_ms: u64,
+    pub p99_ms: u64,
+}
+
+/**
+ * DurationStats
+ * Recent run times per job type
+ */
+#[derive(Debug, Default)]
+pub struct DurationStats {
+    // newest last
+    by_type: HashMap<String, VecDeque<Duration>>,
+}
+
+impl DurationStats {
+    /**
+     * record: a run of job_type that took ran
+     */
+    pub fn record(&mut self, job_type: &str, ran: Duration) {
+        let sample = self.by_type.entry(job_type.to_string()).or_default();
+        if sample.len() == SAMPLE_LIMIT {
+            sample.pop_front();
+        }
+        sample.push_back(ran);
+    }
+
+    /**
+     * percentile: the p-th percentile (nearest rank) of job_type's recent
+     * run times; None: it hasn't run yet
+     */
+    pub fn percentile(&self, job_type: &str, p: u8) -> Option<Duration> {
+        let mut sorted: Vec<Duration> = self.by_type.get(job_type)?.iter().copied().collect();
+        sorted.sort_unstable();
+        Some(nearest_rank(&sorted, p))
+    }
+
+    pub fn report(&self) -> BTreeMap<String, DurationPercentiles> {
+        self.by_type
+            .iter()
+            .map(|(job_type, sample)| {
+                let mut sorted: Vec<Duration> = sample.iter().copied().collect();
+                sorted.sort_unstable();
+                let ms = |p| nearest_rank(&sorted, p).as_millis() as u64;
+                let percentiles = DurationPercentiles {
+                    samples: sorted.len(),
+                    p50_ms: ms(50),
+                    p90_ms: ms(90),
+                    p99_ms: ms(99),
+                };
+                (job_type.clone(), percentiles)
+            })
+            .collect()
+    }
+}
+
+// The p-th percentile of a sorted, non-empty sample
+fn nearest_rank(sorted: &[Duration], p: u8) -> Duration {
+    let rank = (usize::from(p.clamp(1, 100)) * sorted.len()).div_ceil(100);
+    sorted[rank.max(1) - 1]
+}
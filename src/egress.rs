@@ -0,0 +1,92 @@
+/*! Egress module for async orchestrator
+ * Where jobs may make outbound connections, and through which proxy
+ *
+ * Set by EGRESS_ALLOW (host patterns, e.g. "api.example.com,*.internal"),
+ * EGRESS_PROXY (e.g. "http://proxy.internal:3128") and
+ * EGRESS_DENY_BY_DEFAULT. A pattern is a host name, "*.domain" for any host
+ * under domain, or "*" for any host. With deny-by-default set, only hosts
+ * matching a pattern may be reached; without it, any may.
+ * The pool hands the policy to every execution as ExecContext::egress.
+ * Handlers that make network connections (or run commands that do) are
+ * expected to check each target with it before connecting, and to connect
+ * through its proxy when one is set; a handler that doesn't honour it
+ * shouldn't be registered where the policy matters.
+ * NOTE: the policy is only as good as the handlers honouring it; it does
+ * not filter the orchestrator's own traffic (webhooks, notifications)
+ */
+
+/**
+ * EgressPolicy
+ * Allowed host patterns, proxy, and whether unlisted hosts are refused
+ * The default allows every host, directly
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EgressPolicy {
+    // host patterns allowed under deny-by-default
+    pub allow: Vec<String>,
+    // outbound connections go through this proxy; None: directly
+    pub proxy: Option<String>,
+    // refuse hosts no pattern matches
+    pub deny_by_default: bool,
+}
+
+impl EgressPolicy {
+    /**
+     * allows: whether a job may connect to host
+     */
+    pub fn allows(&self, host: &str) -> bool {
+        if !self.deny_by_default {
+            return true;
+        }
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        self.allow
+            .iter()
+            .any(|pattern| matches(&pattern.to_ascii_lowercase(), &host))
+    }
+
+    /**
+     * check: whether a job may connect to target, a url or host[:port]
+     * Err: why not, to fail the job with
+     */
+    pub fn check(&self, target: &str) -> Result<(), String> {
+        let Some(host) = host_of(target) else {
+            return Err(format!("egress: no host in '{target}'"));
+        };
+        if self.allows(host) {
+            Ok(())
+        } else {
+            println!("[Egress]: denied connection to {}", host);
+            Err(format!("egress: {host} is not an allowed host"))
+        }
+    }
+}
+
+// Whether a host matches an allow pattern, both lowercase
+// "*.domain" matches hosts under domain, not domain itself
+fn matches(pattern: &str, host: &str) -> bool {
+    if pattern == "*" {
+        return true;
+    }
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+        None => pattern == host,
+    }
+}
+
+// The host of a url ("scheme://user@host:port/path") or "host:port"
+// None: there isn't one
+fn host_of(target: &str) -> Option<&str> {
+    let rest = target.split_once("://").map_or(target, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let authority = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, authority)| authority);
+    let host = match authority.strip_prefix('[') {
+        // an IPv6 literal, e.g. [::1]:8080
+        Some(bracketed) => bracketed.split_once(']')?.0,
+        None => authority.split(':').next()?,
+    };
+    (!host.is_empty()).then_some(host)
+}
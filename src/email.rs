@@ -0,0 +1,251 @@
+/*! Email module for async orchestrator
+ * Email notification channel, sent over SMTP
+ * NOTE: plain SMTP only (no STARTTLS, no AUTH); point it at a local relay
+ */
+use crate::jobs::Job;
+use crate::notify::{Notification, NotificationChannel};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+
+pub const DEFAULT_SUBJECT: &str = "[orchestrator] job {id} {state}";
+pub const DEFAULT_BODY: &str =
+    "Job {id} ({type}) on queue '{queue}' {state} at {finished_at}.\n\nResult:\n{result}\n";
+
+/**
+ * EmailConfig
+ * SMTP relay and message templates
+ * Templates may use {id}, {type}, {queue}, {priority}, {state}, {result},
+ * {created_at} and {finished_at}
+ */
+#[derive(Debug, Clone)]
+pub struct EmailConfig {
+    // host:port of the SMTP relay
+    pub server: String,
+    pub from: String,
+    pub subject: String,
+    pub body: String,
+    // per-message timeout, covering the whole SMTP conversation
+    pub timeout: Duration,
+    // messages waiting to be sent
+    pub queue_size: usize,
+}
+
+/**
+ * parse_addresses: split and check recipient addresses
+ * NOTE: SMTP needs no CR/LF or angle brackets in them
+ */
+pub fn parse_addresses(s: &str, separator: char) -> Result<Vec<String>, String> {
+    s.split(separator)
+        .map(str::trim)
+        .filter(|a| !a.is_empty())
+        .map(|a| {
+            if a.contains('@') && !a.contains(['<', '>', '\r', '\n', ' ']) {
+                Ok(a.to_string())
+            } else {
+                Err(format!("invalid address '{a}'"))
+            }
+        })
+        .collect()
+}
+
+/**
+ * Message
+ * One rendered notification
+ */
+#[derive(Debug, Clone)]
+struct Message {
+    to: Vec<String>,
+    subject: String,
+    body: String,
+}
+
+/**
+ * Mailer
+ * Handle to the sending worker; cheap to clone
+ */
+#[derive(Clone)]
+pub struct Mailer {
+    config: EmailConfig,
+    tx: mpsc::Sender<Message>,
+}
+
+impl Mailer {
+    pub fn start(config: EmailConfig) -> Self {
+        let (tx, rx) = mpsc::channel(config.queue_size);
+        let worker_config = config.clone();
+        tokio::spawn(async move {
+            Mailer::run(worker_config, rx).await;
+        });
+        Self { config, tx }
+    }
+
+    /**
+     * send: queue a message about a job without waiting
+     * Fails if the send queue is full
+     */
+    pub fn send(&self, job: &Job, to: &[String]) -> Result<(), String> {
+        let message = Message {
+            to: to.to_vec(),
+            // a header can't span lines
+            subject: render(&self.config.subject, job).replace(['\r', '\n'], " "),
+            body: render(&self.config.body, job),
+        };
+        self.tx
+            .try_send(message)
+            .map_err(|_| "email queue full".to_string())
+    }
+
+    async fn run(config: EmailConfig, mut rx: mpsc::Receiver<Message>) {
+        println!("[Mailer]: [run]: starting, relay {}", config.server);
+        // one message at a time; notifications are rare
+        while let Some(message) = rx.recv().await {
+            let result = tokio::time::timeout(config.timeout, send(&config, &message))
+                .await
+                .unwrap_or_else(|_| Err("timed out".to_string()));
+            match result {
+                Ok(()) => println!(
+                    "[Mailer]: sent '{}' to {}",
+                    message.subject,
+                    message.to.join(", ")
+                ),
+                Err(e) => println!("[Mailer]: failed to send '{}': {}", message.subject, e),
+            }
+        }
+    }
+}
+
+/**
+ * EmailChannel
+ * Recipients for notifications, sent through a shared Mailer
+ */
+pub struct EmailChannel {
+    mailer: Mailer,
+    to: Vec<String>,
+}
+
+impl EmailChannel {
+    pub fn new(mailer: Mailer, to: Vec<String>) -> Self {
+        Self { mailer, to }
+    }
+}
+
+impl NotificationChannel for EmailChannel {
+    fn send(&self, notification: &Notification) -> Result<(), String> {
+        self.mailer.send(notification.job, &self.to)
+    }
+}
+
+// Fill in a template's placeholders from a job
+fn render(template: &str, job: &Job) -> String {
+    let submission = job.submission();
+    let finished_at = job
+        .finished_at()
+        .map_or("-".to_string(), |t| t.to_rfc3339());
+    template
+        .replace("{id}", &job.id().to_string())
+        .replace("{type}", submission.kind.name())
+        .replace("{queue}", &submission.queue)
+        .replace(
+            "{priority}",
+            &format!("{:?}", submission.priority).to_lowercase(),
+        )
+        .replace("{state}", &job.state().to_string())
+        .replace("{created_at}", &job.created_at().to_rfc3339())
+        .replace("{finished_at}", &finished_at)
+        .replace("{result}", job.result())
+}
+
+// Deliver one message to the relay
+async fn send(config: &EmailConfig, message: &Message) -> Result<(), String> {
+    let stream = TcpStream::connect(&config.server)
+        .await
+        .map_err(|e| format!("connect: {e}"))?;
+    let mut smtp = Smtp {
+        stream: BufReader::new(stream),
+    };
+
+    smtp.expect(220).await?;
+    smtp.command("EHLO orchestrator", 250).await?;
+    smtp.command(&format!("MAIL FROM:<{}>", config.from), 250)
+        .await?;
+    for to in &message.to {
+        smtp.command(&format!("RCPT TO:<{to}>"), 250).await?;
+    }
+    smtp.command("DATA", 354).await?;
+
+    let mut data = format!(
+        "From: <{}>\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n",
+        config.from,
+        message
+            .to
+            .iter()
+            .map(|to| format!("<{to}>"))
+            .collect::<Vec<_>>()
+            .join(", "),
+        message.subject,
+        chrono::Utc::now().to_rfc2822()
+    );
+    for line in message.body.lines() {
+        // dot-stuffing, so a lone "." in the body doesn't end the message
+        if line.starts_with('.') {
+            data.push('.');
+        }
+        data.push_str(line);
+        data.push_str("\r\n");
+    }
+    data.push_str(".\r\n");
+    smtp.stream
+        .write_all(data.as_bytes())
+        .await
+        .map_err(|e| format!("write: {e}"))?;
+    smtp.expect(250).await?;
+
+    // the message is accepted; a failed QUIT doesn't matter
+    let _ = smtp.command("QUIT", 221).await;
+    Ok(())
+}
+
+/**
+ * Smtp
+ * One SMTP conversation
+ */
+struct Smtp {
+    stream: BufReader<TcpStream>,
+}
+
+impl Smtp {
+    // Send a command and check the reply code
+    async fn command(&mut self, command: &str, code: u16) -> Result<(), String> {
+        self.stream
+            .write_all(format!("{command}\r\n").as_bytes())
+            .await
+            .map_err(|e| format!("write: {e}"))?;
+        self.expect(code).await
+    }
+
+    // Read a (possibly multi-line) reply and check its code
+    async fn expect(&mut self, code: u16) -> Result<(), String> {
+        loop {
+            let mut line = String::new();
+            let n = self
+                .stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| format!("read: {e}"))?;
+            if n == 0 {
+                return Err("connection closed".to_string());
+            }
+            // "250-..." continues, "250 ..." ends the reply
+            if line.as_bytes().get(3) == Some(&b'-') {
+                continue;
+            }
+            let line = line.trim_end();
+            return match line.get(..3).and_then(|c| c.parse::<u16>().ok()) {
+                Some(got) if got == code => Ok(()),
+                _ => Err(format!("expected {code}, got '{line}'")),
+            };
+        }
+    }
+}
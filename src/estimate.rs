@@ -0,0 +1,92 @@
+/*! Estimate module for async orchestrator
+ * Submission-time estimates of a job's duration and cost, and admission
+ * control on them
+ *
+ * A job's duration is estimated by its executor when it can say
+ * (Executor::estimate; the built-in sleep runs as long as its payload
+ * asks), else as the median of its type's recent run times. Its cost is
+ * that duration times its type's cost weight, as usage bills it. A dry run
+ * reports the estimate, and when the job would complete after its
+ * estimated wait.
+ * Admission control is off unless configured:
+ *   ADMIT_BY_DEADLINE=true   a submission estimated to complete after its
+ *                            deadline is refused (400)
+ *   tenant quotas            TENANT_POLICIES "acme.quota=500" caps what a
+ *                            tenant's jobs may cost over the last
+ *                            TENANT_QUOTA_WINDOW_SECS (default a day); a
+ *                            submission whose estimated cost is more than
+ *                            what remains is refused (403)
+ * NOTE: a job with no estimate (a type that hasn't run yet) is admitted,
+ * and only finished jobs count against a quota
+ */
+use crate::api_error::ApiError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/**
+ * EstimateSource
+ * Where a duration estimate came from
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EstimateSource {
+    // the job type's executor
+    Executor,
+    // the median of the type's recent run times
+    History,
+}
+
+/**
+ * Estimate
+ * How long a submission is expected to run, and what it would cost
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Estimate {
+    pub duration_ms: u64,
+    pub cost: f64,
+    pub source: EstimateSource,
+    // after its estimated wait; None: the wait can't be estimated, or
+    // the time is too far off to represent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completes_at: Option<DateTime<Utc>>,
+}
+
+/**
+ * AdmissionConfig
+ * Which estimates a submission must pass to be admitted
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct AdmissionConfig {
+    // refuse submissions estimated to complete after their deadline
+    pub deadlines: bool,
+    // how far back a tenant's usage counts against its quota
+    pub quota_window: Duration,
+}
+
+/**
+ * Refusal
+ * Why admission control turns a submission away
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum Refusal {
+    Deadline(String),
+    Quota(String),
+}
+
+impl Refusal {
+    pub fn message(&self) -> &str {
+        match self {
+            Refusal::Deadline(message) | Refusal::Quota(message) => message,
+        }
+    }
+}
+
+impl From<Refusal> for ApiError {
+    fn from(refusal: Refusal) -> Self {
+        match refusal {
+            Refusal::Deadline(message) => ApiError::BadRequest(message),
+            Refusal::Quota(message) => ApiError::Forbidden(message),
+        }
+    }
+}
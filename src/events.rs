@@ -0,0 +1,159 @@
+/*! Events module for async orchestrator
+ * Pool events, broadcast to any interested subscribers
+ */
+use crate::clock::{Clock, SystemClock};
+use crate::ids::JobId;
+use crate::jobs::State;
+use crate::labels::Labels;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+// events buffered per subscriber before slow subscribers start lagging
+const EVENT_CAPACITY: usize = 1024;
+
+/**
+ * EventKind
+ * What happened
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventKind {
+    // a job moved to a new state
+    JobStateChanged {
+        job_id: JobId,
+        state: State,
+        // the job's submission metadata
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        metadata: Option<Map<String, Value>>,
+        // the job's labels
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        labels: Labels,
+    },
+    // sustained overload: low-priority submissions are being rejected
+    ShedModeEntered {
+        depth: usize,
+        latency_ms: u64,
+    },
+    // load back under thresholds: all submissions accepted again
+    ShedModeExited,
+    // a slot was taken back from an execution that vanished without
+    // reporting its completion; its job was settled as lost
+    SlotReclaimed {
+        job_id: JobId,
+        slot: usize,
+        message: String,
+    },
+    // the pool lost track of something it can't recover on its own
+    InternalError {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        job_id: Option<JobId>,
+        message: String,
+    },
+}
+
+/**
+ * Event
+ * Timestamped event envelope
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+/**
+ * EventBus
+ * Fan-out of pool events; cheap to clone
+ * NOTE: emitting never blocks and never fails, with or without subscribers
+ */
+#[derive(Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<Event>,
+    // stamps events
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(Arc::new(SystemClock))
+    }
+}
+
+impl EventBus {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        let (tx, _) = broadcast::channel(EVENT_CAPACITY);
+        Self { tx, clock }
+    }
+
+    pub fn emit(&self, kind: EventKind) {
+        let event = Event {
+            at: self.clock.now(),
+            kind,
+        };
+        println!("[EventBus]: {:?}", event);
+        // Err: no subscribers, which is fine
+        let _ = self.tx.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.tx.subscribe()
+    }
+}
+
+/**
+ * EventLog
+ * Appends every pool event to a file, one JSON object per line, for
+ * replay (see the replay module)
+ * NOTE: a writer that falls behind the bus loses events; the gap is
+ * reported in the server log
+ */
+pub struct EventLog;
+
+impl EventLog {
+    /**
+     * start: open (or create) the log and follow the bus into it
+     */
+    pub fn start(path: &Path, events: &EventBus) -> Result<(), String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+        let rx = events.subscribe();
+        let path = path.display().to_string();
+        println!("[EventLog]: appending events to {}", path);
+        tokio::spawn(async move {
+            EventLog::run(path, BufWriter::new(file), rx).await;
+        });
+        Ok(())
+    }
+
+    async fn run(path: String, mut out: BufWriter<File>, mut rx: broadcast::Receiver<Event>) {
+        loop {
+            let event = match rx.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(n)) => {
+                    println!("[EventLog]: {}: fell behind, {} events not logged", path, n);
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+            // flush each line, so a crash loses at most the event in hand
+            let written = serde_json::to_writer(&mut out, &event)
+                .map_err(std::io::Error::from)
+                .and_then(|_| out.write_all(b"\n"))
+                .and_then(|_| out.flush());
+            if let Err(e) = written {
+                println!("[EventLog]: {}: write failed: {}", path, e);
+            }
+        }
+    }
+}
@@ -1,3 +1,285 @@
-/*! Excmodule for async orchestrator
- * Defines job structures
+/*! Executor module for async orchestrator
+ * Runs the work for each job type
+ *
+ * Each executor reports a name, version and what it can do (GET
+ * /executors); every job that runs is stamped with the executor version it
+ * ran under, so a change of implementation can be traced to the jobs it
+ * touched.
+ * Long-running work should beat its ExecContext heartbeat as it goes: with
+ * STUCK_AFTER_MS set, a job whose heartbeat goes quiet for longer is
+ * flagged stuck, or failed (see the config module). Multi-step work can
+ * save checkpoints as it goes, and pick up from ExecContext::resume when
+ * retried (see the checkpoints module). Clients and connection pools its
+ * job types share are registered once and taken from
+ * ExecContext::resources (see the resources module). Work that connects
+ * out checks each target with ExecContext::egress first, and goes through
+ * its proxy when one is set (see the egress module).
+ * The pool's default executor runs each job type with a handler
+ * registered for it (see the handlers module).
  */
+use crate::checkpoints::{Checkpoint, Checkpoints};
+use crate::children::Spawner;
+use crate::egress::EgressPolicy;
+use crate::failure::FailureInfo;
+use crate::jobs::{JOB_TYPES, JobSubmission};
+use crate::logs::JobLog;
+use crate::resources::Resources;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/**
+ * CancelToken
+ * Set when a running job is cancelled; long-running work polls it and
+ * stops early. Cheap to clone; clones share the flag
+ */
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/**
+ * Heartbeat
+ * Beaten by a running execution to show it is making progress; the pool's
+ * watchdog notes when the count last moved. Cheap to clone; clones share
+ * the count
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Heartbeat(Arc<AtomicU64>);
+
+impl Heartbeat {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn beat(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Beats so far
+    pub fn beats(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/**
+ * Budget
+ * When the client waiting on a job with a request deadline stops waiting,
+ * if one does; work that can wind up early (returning what it has so far)
+ * may do so by then. Cheap to clone; clones share the time, which a later
+ * waiter may push back
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Budget(Arc<Mutex<Option<DateTime<Utc>>>>);
+
+impl Budget {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+     * extend_to: have the budget last at least until then
+     */
+    pub fn extend_to(&self, until: DateTime<Utc>) {
+        let mut current = self.0.lock().unwrap();
+        if current.is_none_or(|current| current < until) {
+            *current = Some(until);
+        }
+    }
+
+    pub fn until(&self) -> Option<DateTime<Utc>> {
+        *self.0.lock().unwrap()
+    }
+
+    /**
+     * remaining: the time left of the budget at now (zero once spent);
+     * None: no one waits with a deadline
+     */
+    pub fn remaining(&self, now: DateTime<Utc>) -> Option<Duration> {
+        self.until()
+            .map(|until| (until - now).to_std().unwrap_or_default())
+    }
+}
+
+/**
+ * ExecContext
+ * What the pool gives an execution besides its submission
+ */
+#[derive(Debug, Clone, Default)]
+pub struct ExecContext {
+    // set when the job is cancelled; long-running work should stop
+    pub cancel: CancelToken,
+    // beat while working, more often than STUCK_AFTER_MS
+    pub heartbeat: Heartbeat,
+    // the variables of the job's env_profile; empty if it names none
+    pub env: BTreeMap<String, String>,
+    // where the job may connect out to, and through which proxy; check
+    // each target before connecting
+    pub egress: EgressPolicy,
+    // save progress here as the work goes
+    pub checkpoints: Checkpoints,
+    // the last checkpoint of the job this one resumes from
+    // None: starting afresh
+    pub resume: Option<Checkpoint>,
+    // the job's own empty directory, removed once it finishes
+    // None: scratch space is not configured
+    pub scratch: Option<PathBuf>,
+    // how long the execution may run before the job is timed out (and
+    // cancel set); None: no limit
+    pub timeout: Option<Duration>,
+    // when the job must have finished by, however long it has run
+    // None: no deadline
+    pub deadline: Option<DateTime<Utc>>,
+    // when the client waiting on the job stops waiting
+    pub budget: Budget,
+    // spawns child jobs of this one
+    pub children: Spawner,
+    // the shared resources the executor registered
+    pub resources: Resources,
+    // writes the job's log, within the log rate limit
+    pub log: JobLog,
+    // when the job is a map's reducer, the results of the map's children
+    // in input order; empty otherwise
+    pub map_results: Vec<String>,
+}
+
+/**
+ * ExecutorInfo
+ * What an executor is and can do
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExecutorInfo {
+    pub name: String,
+    pub version: String,
+    // job types it runs
+    pub job_types: Vec<String>,
+    // optional behaviour it supports, e.g. "cancel": stops early when a
+    // running job is cancelled
+    pub capabilities: Vec<String>,
+    // job type -> what jobs of that type support, besides capabilities
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub type_capabilities: BTreeMap<String, Vec<String>>,
+}
+
+impl ExecutorInfo {
+    pub fn new(name: &str, version: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            version: version.to_string(),
+            job_types: JOB_TYPES.iter().map(|t| t.to_string()).collect(),
+            capabilities: Vec::new(),
+            type_capabilities: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_job_types(mut self, job_types: &[String]) -> Self {
+        self.job_types = job_types.to_vec();
+        self
+    }
+
+    pub fn with_capabilities(mut self, capabilities: &[&str]) -> Self {
+        self.capabilities = capabilities.iter().map(|c| c.to_string()).collect();
+        self
+    }
+
+    pub fn with_type_capabilities(mut self, job_type: &str, capabilities: &[&str]) -> Self {
+        self.type_capabilities.insert(
+            job_type.to_string(),
+            capabilities.iter().map(|c| c.to_string()).collect(),
+        );
+        self
+    }
+
+    /**
+     * supports: whether jobs of a type have a capability, executor-wide or
+     * their own
+     */
+    pub fn supports(&self, job_type: &str, capability: &str) -> bool {
+        self.capabilities
+            .iter()
+            .chain(self.type_capabilities.get(job_type).into_iter().flatten())
+            .any(|c| c == capability)
+    }
+
+    /**
+     * stamp: "name@version", as recorded on the jobs it runs
+     */
+    pub fn stamp(&self) -> String {
+        format!("{}@{}", self.name, self.version)
+    }
+}
+
+/**
+ * Executor
+ * Runs job submissions; the pool calls it from its execution threads
+ */
+pub trait Executor: Send + Sync + fmt::Debug {
+    fn execute(&self, submission: &JobSubmission) -> Result<String, String>;
+
+    /**
+     * info: name, version, job types and capabilities, as reported on GET
+     * /executors; the pool runs (and accepts) only the job types given
+     * Defaults to an unversioned executor of the built-in job types
+     */
+    fn info(&self) -> ExecutorInfo {
+        ExecutorInfo::new("custom", "unversioned")
+    }
+
+    /**
+     * execute_in: run a submission with its context, stopping early (with
+     * an error) once its cancel token is set
+     * Defaults to execute, ignoring the context
+     */
+    fn execute_in(&self, submission: &JobSubmission, _ctx: &ExecContext) -> Result<String, String> {
+        self.execute(submission)
+    }
+
+    /**
+     * execute_classified: execute_in, with a failure saying what kind it
+     * is and whether a retry could help; this is what the pool calls
+     * Defaults to execute_in's error as a retryable executor failure
+     */
+    fn execute_classified(
+        &self,
+        submission: &JobSubmission,
+        ctx: &ExecContext,
+    ) -> Result<String, FailureInfo> {
+        self.execute_in(submission, ctx)
+            .map_err(FailureInfo::executor)
+    }
+
+    /**
+     * estimate: how long a submission can be expected to run, if the
+     * executor can tell from the submission alone
+     * Defaults to None: the pool estimates from the type's recent run times
+     */
+    fn estimate(&self, _submission: &JobSubmission) -> Option<Duration> {
+        None
+    }
+
+    /**
+     * register_resources: register the resources executions share, once,
+     * as the pool starts
+     * Defaults to none
+     */
+    fn register_resources(&self, _resources: &Resources) -> Result<(), String> {
+        Ok(())
+    }
+}
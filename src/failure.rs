@@ -0,0 +1,145 @@
+/*! Failure module for async orchestrator
+ * Why a job didn't succeed, classified
+ *
+ * A job that ends failed, timed out, cancelled or skipped carries a
+ * FailureInfo next to its result text: a class telling "pool full" from a
+ * timeout from an executor's own error, whether trying again could help,
+ * and whether the pool or the executor decided. Workflow steps are only
+ * retried on retryable failures, and GET /metrics counts failures by class.
+ * Executors classify their errors with Executor::execute_classified; those
+ * that don't report plain, retryable executor failures.
+ */
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/**
+ * FailureClass
+ * What kind of failure ended a job
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureClass {
+    // no slot or pending room for it
+    PoolFull,
+    // ran longer than its timeout
+    Timeout,
+    // its deadline passed before it finished
+    Deadline,
+    // its heartbeat went quiet for longer than STUCK_AFTER_MS
+    Stuck,
+    // held for an unhealthy downstream for longer than HEALTH_MAX_HOLD_MS
+    Unavailable,
+    // its job type's circuit breaker was open
+    CircuitOpen,
+    Cancelled,
+    // a job it depends on didn't succeed
+    Dependency,
+    // the submission can't run as given (unknown queue, unknown
+    // dependency, ...)
+    Invalid,
+    // the executor's own error
+    Executor,
+    // the pool failed it (lost completion, no scratch space, ...)
+    Internal,
+}
+
+impl FailureClass {
+    // Whether trying the same submission again could succeed
+    fn retryable(self) -> bool {
+        matches!(
+            self,
+            FailureClass::PoolFull
+                | FailureClass::Timeout
+                | FailureClass::Stuck
+                | FailureClass::Unavailable
+                | FailureClass::CircuitOpen
+                | FailureClass::Executor
+                | FailureClass::Internal
+        )
+    }
+}
+
+impl fmt::Display for FailureClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            FailureClass::PoolFull => "pool_full",
+            FailureClass::Timeout => "timeout",
+            FailureClass::Deadline => "deadline",
+            FailureClass::Stuck => "stuck",
+            FailureClass::Unavailable => "unavailable",
+            FailureClass::CircuitOpen => "circuit_open",
+            FailureClass::Cancelled => "cancelled",
+            FailureClass::Dependency => "dependency",
+            FailureClass::Invalid => "invalid",
+            FailureClass::Executor => "executor",
+            FailureClass::Internal => "internal",
+        };
+        f.write_str(s)
+    }
+}
+
+/**
+ * FailureSource
+ * Who decided the job failed
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FailureSource {
+    Pool,
+    Executor,
+}
+
+/**
+ * FailureInfo
+ * A classified failure
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FailureInfo {
+    pub class: FailureClass,
+    pub message: String,
+    // whether resubmitting as is could succeed
+    pub retryable: bool,
+    pub source: FailureSource,
+}
+
+impl FailureInfo {
+    /**
+     * pool: a failure the pool decided, retryable as its class is
+     */
+    pub fn pool(class: FailureClass, message: &str) -> Self {
+        Self {
+            class,
+            message: message.to_string(),
+            retryable: class.retryable(),
+            source: FailureSource::Pool,
+        }
+    }
+
+    /**
+     * executor: an executor's error, retryable unless said otherwise
+     */
+    pub fn executor(message: impl Into<String>) -> Self {
+        Self {
+            class: FailureClass::Executor,
+            message: message.into(),
+            retryable: true,
+            source: FailureSource::Executor,
+        }
+    }
+
+    pub fn with_class(mut self, class: FailureClass) -> Self {
+        self.class = class;
+        self
+    }
+
+    pub fn with_retryable(mut self, retryable: bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
+}
+
+impl fmt::Display for FailureInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.class, self.message)
+    }
+}
@@ -0,0 +1,140 @@
+/*! Fields module for async orchestrator
+ * Response field selection on job queries
+ *
+ * GET /jobs and GET /jobs/{id} take ?fields=id,state,finished_at to answer
+ * with only those fields of each job, so a poller can fetch tiny
+ * responses, and ?include=logs,result,events to add heavy fields only when
+ * asked for:
+ *   logs    the job's log, as "logs"
+ *   result  its full result, as "result" (listings carry a summary)
+ *   events  its state changes, as "transitions"
+ * A listing carries none of them unless included; a job's detail carries
+ * its result and state changes as it always has, and its log if included.
+ * Included fields are kept whatever fields selects.
+ * NOTE: a field name no job has is ignored, not refused: optional fields
+ * are left out of jobs that don't have them
+ */
+use crate::api_error::ApiError;
+use crate::jobs::{Job, JobView};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+/**
+ * Include
+ * A heavy field added to jobs only on request
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Include {
+    Logs,
+    Result,
+    Events,
+}
+
+impl Include {
+    // The job field it adds
+    fn key(self) -> &'static str {
+        match self {
+            Include::Logs => "logs",
+            Include::Result => "result",
+            Include::Events => "transitions",
+        }
+    }
+}
+
+impl FromStr for Include {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "logs" => Ok(Include::Logs),
+            "result" => Ok(Include::Result),
+            "events" => Ok(Include::Events),
+            other => Err(format!(
+                "unknown include: {other} (expected logs, result or events)"
+            )),
+        }
+    }
+}
+
+/**
+ * FieldSelection
+ * Which fields of each job a query answers with
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FieldSelection {
+    // None: every field
+    pub fields: Option<BTreeSet<String>>,
+    pub include: BTreeSet<Include>,
+}
+
+impl FieldSelection {
+    /**
+     * parse: a selection from the fields and include query parameters,
+     * each comma separated
+     */
+    pub fn parse(fields: Option<&str>, include: Option<&str>) -> Result<Self, ApiError> {
+        let names = |list: &str| -> Vec<String> {
+            list.split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        let include = include
+            .map(names)
+            .unwrap_or_default()
+            .iter()
+            .map(|name| name.parse())
+            .collect::<Result<_, String>>()
+            .map_err(ApiError::BadRequest)?;
+        Ok(Self {
+            fields: fields.map(|list| names(list).into_iter().collect()),
+            include,
+        })
+    }
+
+    /**
+     * is_default: nothing selected or included; jobs are answered as usual
+     */
+    pub fn is_default(&self) -> bool {
+        self.fields.is_none() && self.include.is_empty()
+    }
+
+    /**
+     * listed: a job as GET /jobs lists it, with this selection
+     */
+    pub fn listed(&self, job: &Job) -> Value {
+        self.select(job, &JobView::from(job))
+    }
+
+    /**
+     * detail: a job as GET /jobs/{id} answers it, with this selection
+     */
+    pub fn detail(&self, job: &Job) -> Value {
+        self.select(job, job)
+    }
+
+    // base as JSON, with the included fields added and only the selected
+    // ones kept
+    fn select(&self, job: &Job, base: &impl Serialize) -> Value {
+        let Ok(Value::Object(mut fields)) = serde_json::to_value(base) else {
+            return Value::Null;
+        };
+        for &include in &self.include {
+            let value = match include {
+                Include::Logs => Value::String(job.log().to_string()),
+                Include::Result => Value::String(job.result().to_string()),
+                Include::Events => serde_json::to_value(job.transitions()).unwrap_or_default(),
+            };
+            fields.insert(include.key().to_string(), value);
+        }
+        if let Some(selected) = &self.fields {
+            fields.retain(|key, _| {
+                selected.contains(key) || self.include.iter().any(|i| i.key() == key)
+            });
+        }
+        Value::Object(fields)
+    }
+}
@@ -0,0 +1,317 @@
+/*! Handlers module for async orchestrator
+ * Job handlers: what runs each job type, registered by type name
+ *
+ * A JobHandler runs jobs of one type, given the job's payload (as JSON)
+ * and its ExecContext. Handlers holds them by type name, and is the
+ * executor the pool runs on by default: Handlers::builtin() has echo and
+ * sleep, and an embedder registers its own types alongside (or in place
+ * of) those, then hands the registry to the pool as PoolConfig::executor.
+ * The pool takes the types its executor reports (GET /executors) as the
+ * ones it runs: a submission of any other type is refused, and so are
+ * schemas, workflow steps and filters naming one.
+ * A handler's run is async: the pool drives it to its outcome on the
+ * job's own execution thread, within the pool's runtime, so it may await
+ * timers and I/O, and blocking in it holds up only its own job.
+ * A handler that connects out (or runs a command that does) checks each
+ * target with ctx.egress first, and connects through its proxy if set.
+ * NOTE: a handler should poll ctx.cancel as it goes; one that doesn't
+ * shouldn't claim "cancel". Config read from the environment at startup
+ * (health gates, rate limits, defaults) only knows the built-in types
+ */
+use crate::executor::{ExecContext, Executor, ExecutorInfo};
+use crate::failure::{FailureClass, FailureInfo};
+use crate::jobs::{JobSubmission, SleepPayload};
+use crate::resources::Resources;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::{Builder, Handle};
+use tokio::time::Instant;
+
+// how often a cancellable sleep checks its token
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(10);
+// how often a sleep checkpoints how long it has slept
+const SLEEP_CHECKPOINT_INTERVAL: Duration = Duration::from_millis(250);
+
+/**
+ * JobOutcome
+ * A handler's answer: the job result (stringified JSON), or why it failed
+ */
+pub type JobOutcome = Result<String, FailureInfo>;
+
+/**
+ * HandlerFuture
+ * A handler's run of one job, yielding its outcome
+ */
+pub type HandlerFuture<'a> = Pin<Box<dyn Future<Output = JobOutcome> + Send + 'a>>;
+
+/**
+ * JobHandler
+ * Runs jobs of one type; the pool calls it from its execution threads
+ */
+pub trait JobHandler: Send + Sync + fmt::Debug {
+    /**
+     * run: run a job to completion, stopping early (with an error) once
+     * its cancel token is set
+     * e.g. Box::pin(async move { ... })
+     */
+    fn run<'a>(&'a self, ctx: &'a ExecContext, payload: &'a Value) -> HandlerFuture<'a>;
+
+    /**
+     * estimate: how long a job with this payload can be expected to run
+     * Defaults to None: the pool estimates from the type's recent run times
+     */
+    fn estimate(&self, _payload: &Value) -> Option<Duration> {
+        None
+    }
+
+    /**
+     * capabilities: optional behaviour it supports (see ExecutorInfo)
+     * Defaults to none
+     */
+    fn capabilities(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /**
+     * register_resources: register the resources its jobs share, once, as
+     * the pool starts
+     * Defaults to none
+     */
+    fn register_resources(&self, _resources: &Resources) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/**
+ * EchoHandler
+ * echo: the payload as the result; reducing a map, its children's results
+ */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EchoHandler;
+
+impl JobHandler for EchoHandler {
+    // a payload that can't be echoed isn't helped by a retry
+    fn run<'a>(&'a self, ctx: &'a ExecContext, payload: &'a Value) -> HandlerFuture<'a> {
+        Box::pin(async move {
+            let echoed = if ctx.map_results.is_empty() {
+                serde_json::to_string(payload)
+            } else {
+                // each child's result as JSON if it is, else as a string
+                let results: Vec<Value> = ctx
+                    .map_results
+                    .iter()
+                    .map(|result| {
+                        serde_json::from_str(result)
+                            .unwrap_or_else(|_| Value::from(result.as_str()))
+                    })
+                    .collect();
+                serde_json::to_string(&results)
+            };
+            echoed.map_err(|e| FailureInfo::executor(format!("echo: {e}")).with_retryable(false))
+        })
+    }
+}
+
+/**
+ * SleepHandler
+ * sleep: sleep for the given milliseconds, then answer "ok"
+ */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SleepHandler;
+
+impl JobHandler for SleepHandler {
+    // in short steps, so a cancel is noticed promptly, beating on each, and
+    // checkpointing the time slept so a retry only sleeps the rest
+    fn run<'a>(&'a self, ctx: &'a ExecContext, payload: &'a Value) -> HandlerFuture<'a> {
+        Box::pin(async move {
+            let payload: SleepPayload = serde_json::from_value(payload.clone()).map_err(|e| {
+                FailureInfo::executor(format!("sleep: {e}"))
+                    .with_class(FailureClass::Invalid)
+                    .with_retryable(false)
+            })?;
+            let slept = ctx
+                .resume
+                .as_ref()
+                .filter(|c| c.name == "slept")
+                .and_then(|c| c.data["milliseconds"].as_u64())
+                .map_or(Duration::ZERO, Duration::from_millis);
+            let total = Duration::from_millis(payload.milliseconds.into());
+            let started = Instant::now();
+            let elapsed = || slept + started.elapsed();
+            let mut checkpointed = Instant::now();
+            while elapsed() < total {
+                if ctx.cancel.is_cancelled() {
+                    return Err(
+                        FailureInfo::executor(format!("cancelled after {:?}", elapsed()))
+                            .with_class(FailureClass::Cancelled)
+                            .with_retryable(false),
+                    );
+                }
+                ctx.heartbeat.beat();
+                if checkpointed.elapsed() >= SLEEP_CHECKPOINT_INTERVAL {
+                    let milliseconds = elapsed().as_millis() as u64;
+                    ctx.checkpoints
+                        .save("slept", json!({ "milliseconds": milliseconds }))
+                        .map_err(|e| FailureInfo::executor(e).with_retryable(false))?;
+                    checkpointed = Instant::now();
+                }
+                tokio::time::sleep(CANCEL_POLL_INTERVAL.min(total.saturating_sub(elapsed()))).await;
+            }
+            Ok("ok".to_string())
+        })
+    }
+
+    // as long as it asks (less on a resumed retry)
+    fn estimate(&self, payload: &Value) -> Option<Duration> {
+        payload["milliseconds"].as_u64().map(Duration::from_millis)
+    }
+
+    fn capabilities(&self) -> &'static [&'static str] {
+        &["cancel", "heartbeat", "checkpoint"]
+    }
+}
+
+/**
+ * Handlers
+ * Job handlers by type name; an executor running each job with its type's
+ * handler. Cheap to clone; clones share the handlers
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Handlers {
+    registry: BTreeMap<String, Arc<dyn JobHandler>>,
+}
+
+impl Handlers {
+    /**
+     * builtin: the built-in job types, echo and sleep
+     */
+    pub fn builtin() -> Self {
+        let mut handlers = Self::default();
+        handlers
+            .registry
+            .insert("echo".to_string(), Arc::new(EchoHandler));
+        handlers
+            .registry
+            .insert("sleep".to_string(), Arc::new(SleepHandler));
+        handlers
+    }
+
+    /**
+     * register: run jobs of job_type with handler, in place of any handler
+     * it had
+     * Err: the name isn't a usable job type
+     */
+    pub fn register(
+        &mut self,
+        job_type: &str,
+        handler: impl JobHandler + 'static,
+    ) -> Result<(), String> {
+        let usable = !job_type.is_empty()
+            && job_type
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+        if !usable {
+            return Err(format!(
+                "job type '{job_type}': use letters, digits, '_', '-' and '.'"
+            ));
+        }
+        if self
+            .registry
+            .insert(job_type.to_string(), Arc::new(handler))
+            .is_some()
+        {
+            println!("[Handlers]: {} handler replaced", job_type);
+        }
+        Ok(())
+    }
+
+    /**
+     * get: the handler for a job type
+     */
+    pub fn get(&self, job_type: &str) -> Option<Arc<dyn JobHandler>> {
+        self.registry.get(job_type).cloned()
+    }
+
+    /**
+     * job_types: the registered job types, in name order
+     */
+    pub fn job_types(&self) -> Vec<String> {
+        self.registry.keys().cloned().collect()
+    }
+}
+
+impl Executor for Handlers {
+    fn execute(&self, submission: &JobSubmission) -> Result<String, String> {
+        self.execute_classified(submission, &ExecContext::default())
+            .map_err(|failure| failure.message)
+    }
+
+    // reports each type's capabilities; none are executor-wide
+    fn info(&self) -> ExecutorInfo {
+        self.registry.iter().fold(
+            ExecutorInfo::new("builtin", env!("CARGO_PKG_VERSION"))
+                .with_job_types(&self.job_types()),
+            |info, (job_type, handler)| {
+                info.with_type_capabilities(job_type, handler.capabilities())
+            },
+        )
+    }
+
+    fn execute_in(&self, submission: &JobSubmission, ctx: &ExecContext) -> Result<String, String> {
+        self.execute_classified(submission, ctx)
+            .map_err(|failure| failure.message)
+    }
+
+    // a handler failing once the job is cancelled was stopped by it
+    fn execute_classified(&self, submission: &JobSubmission, ctx: &ExecContext) -> JobOutcome {
+        let job_type = submission.kind.name();
+        let Some(handler) = self.get(job_type) else {
+            return Err(
+                FailureInfo::executor(format!("no handler for job type '{job_type}'"))
+                    .with_class(FailureClass::Invalid)
+                    .with_retryable(false),
+            );
+        };
+        let payload = submission.kind.payload();
+        drive(handler.run(ctx, &payload)).map_err(|failure| {
+            if ctx.cancel.is_cancelled() {
+                failure
+                    .with_class(FailureClass::Cancelled)
+                    .with_retryable(false)
+            } else {
+                failure
+            }
+        })
+    }
+
+    fn estimate(&self, submission: &JobSubmission) -> Option<Duration> {
+        self.get(submission.kind.name())?
+            .estimate(&submission.kind.payload())
+    }
+
+    fn register_resources(&self, resources: &Resources) -> Result<(), String> {
+        self.registry
+            .values()
+            .try_for_each(|handler| handler.register_resources(resources))
+    }
+}
+
+// Run a handler's future to its outcome on this thread: within the pool's
+// runtime when called from one of its execution threads, else (execute,
+// outside the pool) on a runtime of its own
+// NOTE: not to be called from an async task; it blocks the thread
+fn drive(run: HandlerFuture<'_>) -> JobOutcome {
+    match Handle::try_current() {
+        Ok(runtime) => runtime.block_on(run),
+        Err(_) => Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .map_err(|e| FailureInfo::executor(format!("runtime: {e}")))?
+            .block_on(run),
+    }
+}
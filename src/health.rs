@@ -0,0 +1,218 @@
+/*! Health module for async orchestrator
+ * Health gates: job types held back while a downstream they need is down
+ *
+ * HEALTH_GATES gives job types a health-check URL ("type=url,..."). The
+ * checker GETs each URL every HEALTH_CHECK_INTERVAL_MS; anything but a 2xx
+ * answer marks the gate unhealthy until a check passes again. While a
+ * type's gate is unhealthy its jobs are held pending (even past their
+ * queue's pending limit) instead of dispatched, and one held longer than
+ * HEALTH_MAX_HOLD_MS fails, retryable, rather than waiting on. GET
+ * /admin/health-gates shows each gate.
+ * NOTE: a gate counts as healthy until a check fails
+ */
+use crate::clock::Clock;
+use crate::http_client;
+use crate::jobs::{JOB_TYPES, JobPool};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+// longest a health check may take
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/**
+ * GateList
+ * Job type -> health-check URL, as read from config
+ */
+#[derive(Debug, Clone, Default)]
+pub struct GateList(pub BTreeMap<String, String>);
+
+// "type=url,..."
+impl FromStr for GateList {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut gates = BTreeMap::new();
+        for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (job_type, url) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("'{entry}': expected type=url"))?;
+            let job_type = job_type.trim();
+            if !JOB_TYPES.contains(&job_type) {
+                return Err(format!("'{entry}': unknown job type '{job_type}'"));
+            }
+            gates.insert(job_type.to_string(), url.trim().to_string());
+        }
+        Ok(GateList(gates))
+    }
+}
+
+/**
+ * HealthConfig
+ * Health gates and how they are checked
+ */
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    // empty: no job type is gated
+    pub gates: GateList,
+    pub interval: Duration,
+    // longest a job is held for an unhealthy gate before it fails
+    pub max_hold: Duration,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            gates: GateList::default(),
+            interval: Duration::from_secs(5),
+            max_hold: Duration::from_secs(300),
+        }
+    }
+}
+
+/**
+ * GateStatus
+ * One gate as last checked
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GateStatus {
+    pub job_type: String,
+    pub url: String,
+    pub healthy: bool,
+    // None: not checked yet
+    pub checked_at: Option<DateTime<Utc>>,
+    // when the current unhealthy spell began
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unhealthy_since: Option<DateTime<Utc>>,
+    // why the last failed check failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/**
+ * HealthGates
+ * Every gate's health; cheap to clone
+ */
+#[derive(Clone)]
+pub struct HealthGates {
+    gates: Arc<Mutex<BTreeMap<String, GateStatus>>>,
+    interval: Duration,
+    max_hold: Duration,
+    clock: Arc<dyn Clock>,
+}
+
+impl HealthGates {
+    pub fn new(config: &HealthConfig, clock: Arc<dyn Clock>) -> Self {
+        let gates = config
+            .gates
+            .0
+            .iter()
+            .map(|(job_type, url)| {
+                let status = GateStatus {
+                    job_type: job_type.clone(),
+                    url: url.clone(),
+                    healthy: true,
+                    checked_at: None,
+                    unhealthy_since: None,
+                    last_error: None,
+                };
+                (job_type.clone(), status)
+            })
+            .collect();
+        Self {
+            gates: Arc::new(Mutex::new(gates)),
+            interval: config.interval,
+            max_hold: config.max_hold,
+            clock,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.gates.lock().unwrap().is_empty()
+    }
+
+    pub fn max_hold(&self) -> Duration {
+        self.max_hold
+    }
+
+    /**
+     * unhealthy: the job types whose gate is unhealthy, each with when it
+     * went unhealthy
+     */
+    pub fn unhealthy(&self) -> BTreeMap<String, DateTime<Utc>> {
+        self.gates
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(|g| Some((g.job_type.clone(), g.unhealthy_since?)))
+            .collect()
+    }
+
+    pub fn list(&self) -> Vec<GateStatus> {
+        self.gates.lock().unwrap().values().cloned().collect()
+    }
+
+    /**
+     * drive: check every gate each interval, releasing held jobs when a
+     * gate recovers
+     * NOTE: holds the pool weakly; returns once the pool is gone
+     */
+    pub async fn drive(&self, pool: Weak<JobPool>) {
+        loop {
+            tokio::time::sleep(self.interval).await;
+            let recovered = self.check_all().await;
+            let Some(pool) = pool.upgrade() else {
+                return;
+            };
+            if !recovered.is_empty() {
+                pool.dispatch_held().await;
+            }
+        }
+    }
+
+    // Check every gate, returning those that turned healthy
+    async fn check_all(&self) -> HashSet<String> {
+        let targets: Vec<(String, String)> = self
+            .gates
+            .lock()
+            .unwrap()
+            .values()
+            .map(|g| (g.job_type.clone(), g.url.clone()))
+            .collect();
+        let mut recovered = HashSet::new();
+        for (job_type, url) in targets {
+            let error = match http_client::request("GET", &url, &[], None, CHECK_TIMEOUT).await {
+                Ok(response) if response.is_success() => None,
+                Ok(response) => Some(format!("status {}", response.status)),
+                Err(e) => Some(e.to_string()),
+            };
+            let now = self.clock.now();
+            let mut gates = self.gates.lock().unwrap();
+            let Some(gate) = gates.get_mut(&job_type) else {
+                continue;
+            };
+            gate.checked_at = Some(now);
+            match error {
+                None if !gate.healthy => {
+                    println!("[HealthGates]: {} healthy again", job_type);
+                    gate.healthy = true;
+                    gate.unhealthy_since = None;
+                    recovered.insert(job_type);
+                }
+                None => {}
+                Some(error) => {
+                    if gate.healthy {
+                        println!("[HealthGates]: {} unhealthy: {}", job_type, error);
+                        gate.healthy = false;
+                        gate.unhealthy_since = Some(now);
+                    }
+                    gate.last_error = Some(error);
+                }
+            }
+        }
+        recovered
+    }
+}
@@ -0,0 +1,254 @@
+/*! History module for async orchestrator
+ * Finished jobs kept in memory, within a byte budget and retention limits
+ *
+ * Every finished job's record and log stay in memory so they can be looked
+ * up; HISTORY_BUDGET_BYTES caps what they may take in all. When a finished
+ * job takes history over the budget, the oldest finished jobs are evicted
+ * until it fits again. A periodic GC (every HISTORY_GC_INTERVAL_SECS) also
+ * evicts jobs finished longer than HISTORY_MAX_AGE_SECS ago, then the
+ * oldest beyond HISTORY_MAX_JOBS. Pinned jobs (POST /jobs/{id}/pin) are
+ * never evicted; each tenant may pin up to PIN_QUOTA jobs. Usage, and
+ * evictions by cause, are reported on GET /metrics.
+ * With HISTORY_ARCHIVE set, evicted job records are appended to that file
+ * (one JSON record per line) before they are dropped.
+ * NOTE: sizes are estimates (record, log buffer, result and submission);
+ * evicted jobs are 404 from then on, and archived records leave out logs
+ */
+use crate::jobs::Job;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::collections::vec_deque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/**
+ * RetentionConfig
+ * How long finished jobs are kept, beyond the byte budget
+ */
+#[derive(Debug, Clone)]
+pub struct RetentionConfig {
+    // most finished jobs kept; 0: unlimited
+    pub max_jobs: usize,
+    // longest a finished job is kept; None: unlimited
+    pub max_age: Option<Duration>,
+    // how often the GC applies the limits above
+    pub gc_interval: Duration,
+    // append evicted job records here; None: dropped
+    pub archive: Option<PathBuf>,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            max_jobs: 0,
+            max_age: None,
+            gc_interval: Duration::from_secs(60),
+            archive: None,
+        }
+    }
+}
+
+impl RetentionConfig {
+    // Whether the GC has anything to do
+    pub fn enabled(&self) -> bool {
+        self.max_jobs > 0 || self.max_age.is_some()
+    }
+}
+
+/**
+ * Evictions
+ * Jobs evicted since startup, by cause
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Evictions {
+    // history over its byte budget
+    pub budget: u64,
+    // over HISTORY_MAX_JOBS
+    pub count: u64,
+    // older than HISTORY_MAX_AGE_SECS
+    pub age: u64,
+    // of those, appended to the archive
+    pub archived: u64,
+}
+
+/**
+ * HistoryUsage
+ * What history holds, against its budget
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct HistoryUsage {
+    pub jobs: usize,
+    pub bytes: usize,
+    // 0: unlimited
+    pub budget_bytes: usize,
+    // jobs evicted since startup
+    pub evicted: u64,
+    #[serde(default)]
+    pub evicted_by: Evictions,
+    // jobs exempt from eviction
+    pub pinned: usize,
+}
+
+/**
+ * History
+ * Finished jobs, oldest first
+ */
+#[derive(Debug, Default)]
+pub struct History {
+    jobs: VecDeque<Job>,
+    // estimated size of each job, in step with jobs
+    sizes: VecDeque<usize>,
+    bytes: usize,
+    // 0: unlimited
+    budget: usize,
+    retention: RetentionConfig,
+    // None: evicted jobs are dropped
+    archive: Option<BufWriter<File>>,
+    evicted: Evictions,
+}
+
+impl History {
+    pub fn new(budget: usize, retention: RetentionConfig) -> Self {
+        let archive = retention.archive.as_ref().and_then(|path| {
+            match OpenOptions::new().create(true).append(true).open(path) {
+                Ok(file) => {
+                    println!("[History]: archiving evicted jobs to {}", path.display());
+                    Some(BufWriter::new(file))
+                }
+                Err(e) => {
+                    println!("[History]: archive disabled: {}: {}", path.display(), e);
+                    None
+                }
+            }
+        });
+        Self {
+            budget,
+            retention,
+            archive,
+            ..Self::default()
+        }
+    }
+
+    /**
+     * push: keep a finished job, evicting the oldest unpinned jobs while
+     * history is over its budget
+     */
+    pub fn push(&mut self, job: Job) {
+        let size = job.footprint();
+        self.jobs.push_back(job);
+        self.sizes.push_back(size);
+        self.bytes += size;
+        if self.budget == 0 || self.bytes <= self.budget {
+            return;
+        }
+        let (mut count, mut freed) = (0, 0);
+        while self.bytes > self.budget {
+            let Some(at) = self.jobs.iter().position(|job| !job.pinned()) else {
+                println!("[History]: over budget, but every job left is pinned");
+                break;
+            };
+            let Some(size) = self.evict(at) else {
+                break;
+            };
+            freed += size;
+            count += 1;
+        }
+        self.evicted.budget += count;
+        println!(
+            "[History]: evicted {} jobs ({} bytes) to stay within {} bytes",
+            count, freed, self.budget
+        );
+    }
+
+    /**
+     * gc: evict unpinned jobs finished longer than the max age ago, then
+     * the oldest beyond the max count
+     */
+    pub fn gc(&mut self, now: DateTime<Utc>) {
+        let mut aged = 0;
+        if let Some(max_age) = self.retention.max_age {
+            let mut at = 0;
+            while at < self.jobs.len() {
+                let job = &self.jobs[at];
+                let finished = job.finished_at().unwrap_or(job.created_at());
+                let expired = (now - finished).to_std().is_ok_and(|age| age > max_age);
+                if expired && !job.pinned() && self.evict(at).is_some() {
+                    aged += 1;
+                } else {
+                    at += 1;
+                }
+            }
+        }
+        let mut counted = 0;
+        if self.retention.max_jobs > 0 {
+            while self.jobs.len() > self.retention.max_jobs {
+                let Some(at) = self.jobs.iter().position(|job| !job.pinned()) else {
+                    break;
+                };
+                if self.evict(at).is_none() {
+                    break;
+                }
+                counted += 1;
+            }
+        }
+        self.evicted.age += aged;
+        self.evicted.count += counted;
+        if aged + counted > 0 {
+            println!(
+                "[History]: gc evicted {} jobs past the max age, {} over the max count",
+                aged, counted
+            );
+        }
+    }
+
+    // Remove the job at, archiving it if configured; its size, or None if
+    // there is no such job
+    fn evict(&mut self, at: usize) -> Option<usize> {
+        let (Some(job), Some(size)) = (self.jobs.remove(at), self.sizes.remove(at)) else {
+            return None;
+        };
+        self.bytes -= size;
+        if let Some(out) = &mut self.archive {
+            let written = serde_json::to_writer(&mut *out, &job)
+                .map_err(std::io::Error::from)
+                .and_then(|_| out.write_all(b"\n"))
+                .and_then(|_| out.flush());
+            match written {
+                Ok(()) => self.evicted.archived += 1,
+                Err(e) => println!("[History]: job {}: archive failed: {}", job.id(), e),
+            }
+        }
+        Some(size)
+    }
+
+    /**
+     * gc_interval: how often gc should run; None: retention limits are off
+     */
+    pub fn gc_interval(&self) -> Option<Duration> {
+        self.retention
+            .enabled()
+            .then_some(self.retention.gc_interval)
+    }
+
+    pub fn iter(&self) -> vec_deque::Iter<'_, Job> {
+        self.jobs.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> vec_deque::IterMut<'_, Job> {
+        self.jobs.iter_mut()
+    }
+
+    pub fn usage(&self) -> HistoryUsage {
+        HistoryUsage {
+            jobs: self.jobs.len(),
+            bytes: self.bytes,
+            budget_bytes: self.budget,
+            evicted: self.evicted.budget + self.evicted.count + self.evicted.age,
+            evicted_by: self.evicted.clone(),
+            pinned: self.jobs.iter().filter(|job| job.pinned()).count(),
+        }
+    }
+}
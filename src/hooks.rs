@@ -0,0 +1,190 @@
+/*! Hooks module for async orchestrator
+ * Middleware around executor invocation
+ *
+ * A Hook sees every execution: before it starts (and may rewrite the
+ * submission the executor gets, e.g. to inject secrets), after it finishes,
+ * and when it fails. Hooks are registered in code on the PoolConfig, or by
+ * name with EXECUTOR_HOOKS (e.g. "log"), and wrap whatever executor the pool
+ * runs. before_start hooks run in registration order, the others in reverse,
+ * so the first hook registered is the outermost.
+ */
+use crate::executor::{ExecContext, Executor, ExecutorInfo};
+use crate::failure::FailureInfo;
+use crate::jobs::JobSubmission;
+use crate::resources::Resources;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/**
+ * Hook
+ * Cross-cutting behaviour around each execution; every method defaults to
+ * doing nothing
+ */
+pub trait Hook: Send + Sync + fmt::Debug {
+    /**
+     * before_start: called before the executor runs; an error fails the
+     * execution without running it
+     * Changes to the submission reach the executor only, not the job record
+     */
+    fn before_start(&self, _submission: &mut JobSubmission) -> Result<(), String> {
+        Ok(())
+    }
+
+    /**
+     * after_finish: called after the executor succeeded
+     */
+    fn after_finish(&self, _submission: &JobSubmission, _result: &str, _elapsed: Duration) {}
+
+    /**
+     * on_error: called after the executor, or a before_start hook, failed
+     */
+    fn on_error(&self, _submission: &JobSubmission, _error: &str, _elapsed: Duration) {}
+}
+
+/**
+ * LogHook
+ * Logs each execution's start and outcome, with its duration
+ */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogHook;
+
+impl Hook for LogHook {
+    fn before_start(&self, submission: &mut JobSubmission) -> Result<(), String> {
+        println!("[Hooks]: {} starting", submission.kind.name());
+        Ok(())
+    }
+
+    fn after_finish(&self, submission: &JobSubmission, _result: &str, elapsed: Duration) {
+        println!(
+            "[Hooks]: {} finished in {:?}",
+            submission.kind.name(),
+            elapsed
+        );
+    }
+
+    fn on_error(&self, submission: &JobSubmission, error: &str, elapsed: Duration) {
+        println!(
+            "[Hooks]: {} failed in {:?}: {}",
+            submission.kind.name(),
+            elapsed,
+            error
+        );
+    }
+}
+
+/**
+ * HookList
+ * The hooks the pool wraps its executor in, outermost first
+ */
+#[derive(Debug, Clone, Default)]
+pub struct HookList(pub Vec<Arc<dyn Hook>>);
+
+impl HookList {
+    /**
+     * with: add a hook, inside those already added
+     */
+    pub fn with(mut self, hook: Arc<dyn Hook>) -> Self {
+        self.0.push(hook);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+// "name,...", naming built-in hooks
+impl FromStr for HookList {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut hooks = Self::default();
+        for name in s.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            let hook: Arc<dyn Hook> = match name {
+                "log" => Arc::new(LogHook),
+                _ => return Err(format!("unknown hook '{name}'")),
+            };
+            hooks = hooks.with(hook);
+        }
+        Ok(hooks)
+    }
+}
+
+/**
+ * HookedExecutor
+ * An executor run inside a chain of hooks
+ */
+#[derive(Debug)]
+pub struct HookedExecutor {
+    inner: Arc<dyn Executor>,
+    hooks: HookList,
+}
+
+impl HookedExecutor {
+    /**
+     * wrap: the executor inside the hooks; unchanged when there are none
+     */
+    pub fn wrap(inner: Arc<dyn Executor>, hooks: &HookList) -> Arc<dyn Executor> {
+        if hooks.is_empty() {
+            return inner;
+        }
+        Arc::new(Self {
+            inner,
+            hooks: hooks.clone(),
+        })
+    }
+}
+
+impl Executor for HookedExecutor {
+    fn execute(&self, submission: &JobSubmission) -> Result<String, String> {
+        self.execute_in(submission, &ExecContext::default())
+    }
+
+    // the executor inside; hooks don't change what runs
+    fn info(&self) -> ExecutorInfo {
+        self.inner.info()
+    }
+
+    fn estimate(&self, submission: &JobSubmission) -> Option<Duration> {
+        self.inner.estimate(submission)
+    }
+
+    fn register_resources(&self, resources: &Resources) -> Result<(), String> {
+        self.inner.register_resources(resources)
+    }
+
+    fn execute_in(&self, submission: &JobSubmission, ctx: &ExecContext) -> Result<String, String> {
+        self.execute_classified(submission, ctx)
+            .map_err(|failure| failure.message)
+    }
+
+    fn execute_classified(
+        &self,
+        submission: &JobSubmission,
+        ctx: &ExecContext,
+    ) -> Result<String, FailureInfo> {
+        let started = Instant::now();
+        let mut prepared = submission.clone();
+        let mut outcome = Ok(());
+        for hook in &self.hooks.0 {
+            outcome = hook.before_start(&mut prepared);
+            if outcome.is_err() {
+                break;
+            }
+        }
+        let outcome = match outcome {
+            Ok(()) => self.inner.execute_classified(&prepared, ctx),
+            Err(e) => Err(FailureInfo::executor(format!("before start: {e}"))),
+        };
+        let elapsed = started.elapsed();
+        for hook in self.hooks.0.iter().rev() {
+            match &outcome {
+                Ok(result) => hook.after_finish(&prepared, result, elapsed),
+                Err(failure) => hook.on_error(&prepared, &failure.message, elapsed),
+            }
+        }
+        outcome
+    }
+}
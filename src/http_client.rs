@@ -0,0 +1,368 @@
+/*! HTTP client module for async orchestrator
+ * Minimal HTTP/1.1 client over tokio TcpStream
+ * NOTE: plain http:// only, one request per connection
+ */
+use std::fmt;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+/**
+ * HttpError
+ * Transport level failures (no response, or an unreadable one)
+ */
+#[derive(Debug)]
+pub enum HttpError {
+    InvalidUrl(String),
+    Io(std::io::Error),
+    Timeout,
+    Malformed(String),
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::InvalidUrl(url) => write!(f, "invalid url: {url}"),
+            HttpError::Io(e) => write!(f, "io error: {e}"),
+            HttpError::Timeout => write!(f, "request timed out"),
+            HttpError::Malformed(msg) => write!(f, "malformed response: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+/**
+ * HttpResponse
+ */
+#[derive(Debug)]
+pub struct HttpResponse {
+    pub status: u16,
+    // header names lowercased
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_ascii_lowercase();
+        self.headers
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+/**
+ * Url
+ * The parts of an http:// url needed to make a request
+ */
+#[derive(Debug, Clone)]
+pub struct Url {
+    pub host: String,
+    pub port: u16,
+    // path and query, always starting with '/'
+    pub path: String,
+}
+
+impl Url {
+    pub fn parse(url: &str) -> Result<Self, HttpError> {
+        let invalid = || HttpError::InvalidUrl(url.to_string());
+        let rest = url.strip_prefix("http://").ok_or_else(invalid)?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+}
+
+/**
+ * encode: percent-encode a path segment or query value
+ */
+pub fn encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{b:02X}")),
+        }
+    }
+    out
+}
+
+/**
+ * request: make one HTTP request and read the whole response
+ */
+pub async fn request(
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: Option<&[u8]>,
+    timeout: Duration,
+) -> Result<HttpResponse, HttpError> {
+    let url = Url::parse(url)?;
+    tokio::time::timeout(timeout, send(method, &url, headers, body))
+        .await
+        .map_err(|_| HttpError::Timeout)?
+}
+
+async fn send(
+    method: &str,
+    url: &Url,
+    headers: &[(&str, &str)],
+    body: Option<&[u8]>,
+) -> Result<HttpResponse, HttpError> {
+    let mut stream = connect(method, url, headers, body).await?;
+    let mut raw = Vec::new();
+    stream.read_to_end(&mut raw).await.map_err(HttpError::Io)?;
+    parse_response(&raw)
+}
+
+// Connect and write the request
+async fn connect(
+    method: &str,
+    url: &Url,
+    headers: &[(&str, &str)],
+    body: Option<&[u8]>,
+) -> Result<TcpStream, HttpError> {
+    let mut stream = TcpStream::connect((url.host.as_str(), url.port))
+        .await
+        .map_err(HttpError::Io)?;
+
+    let mut head = format!(
+        "{method} {} HTTP/1.1\r\nHost: {}:{}\r\nConnection: close\r\n",
+        url.path, url.host, url.port
+    );
+    for (name, value) in headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    let body = body.unwrap_or_default();
+    if !body.is_empty() || method == "POST" || method == "PUT" {
+        head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    head.push_str("\r\n");
+
+    stream
+        .write_all(head.as_bytes())
+        .await
+        .map_err(HttpError::Io)?;
+    stream.write_all(body).await.map_err(HttpError::Io)?;
+    Ok(stream)
+}
+
+// Split a complete response into status, headers, and (de-chunked) body
+fn parse_response(raw: &[u8]) -> Result<HttpResponse, HttpError> {
+    let split = raw
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| HttpError::Malformed("no end of headers".to_string()))?;
+    let mut response = parse_head(&raw[..split])?;
+    let body = &raw[split + 4..];
+    response.body = if is_chunked(&response) {
+        dechunk(body)?
+    } else {
+        body.to_vec()
+    };
+    Ok(response)
+}
+
+// Parse the status line and headers, leaving the body empty
+fn parse_head(head: &[u8]) -> Result<HttpResponse, HttpError> {
+    let head = std::str::from_utf8(head)
+        .map_err(|_| HttpError::Malformed("non-utf8 headers".to_string()))?;
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| HttpError::Malformed("bad status line".to_string()))?;
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(n, v)| (n.trim().to_ascii_lowercase(), v.trim().to_string()))
+        .collect();
+    Ok(HttpResponse {
+        status,
+        headers,
+        body: Vec::new(),
+    })
+}
+
+fn is_chunked(response: &HttpResponse) -> bool {
+    response
+        .header("transfer-encoding")
+        .is_some_and(|te| te.eq_ignore_ascii_case("chunked"))
+}
+
+// Decode a chunked transfer-encoded body
+fn dechunk(mut body: &[u8]) -> Result<Vec<u8>, HttpError> {
+    let malformed = || HttpError::Malformed("bad chunk".to_string());
+    let mut out = Vec::new();
+    loop {
+        let eol = body
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or_else(malformed)?;
+        let size = std::str::from_utf8(&body[..eol]).map_err(|_| malformed())?;
+        let size = size.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16).map_err(|_| malformed())?;
+        body = &body[eol + 2..];
+        if size == 0 {
+            return Ok(out);
+        }
+        if body.len() < size + 2 {
+            return Err(malformed());
+        }
+        out.extend_from_slice(&body[..size]);
+        body = &body[size + 2..];
+    }
+}
+
+/**
+ * LineStream
+ * A GET response whose body is read incrementally, line by line
+ * NOTE: for long-lived responses (e.g. server-sent events)
+ */
+pub struct LineStream {
+    pub status: u16,
+    reader: BufReader<TcpStream>,
+    chunked: bool,
+    // bytes left in the current chunk (chunked bodies only)
+    chunk_left: usize,
+    buf: Vec<u8>,
+    done: bool,
+}
+
+/**
+ * stream: make a GET request, returning once the response headers arrive
+ * timeout: applies to connecting and reading the headers only
+ */
+pub async fn stream(
+    url: &str,
+    headers: &[(&str, &str)],
+    timeout: Duration,
+) -> Result<LineStream, HttpError> {
+    let url = Url::parse(url)?;
+    tokio::time::timeout(timeout, open_stream(&url, headers))
+        .await
+        .map_err(|_| HttpError::Timeout)?
+}
+
+async fn open_stream(url: &Url, headers: &[(&str, &str)]) -> Result<LineStream, HttpError> {
+    let stream = connect("GET", url, headers, None).await?;
+    let mut reader = BufReader::new(stream);
+
+    let mut head = Vec::new();
+    loop {
+        let mut line = String::new();
+        let n = reader.read_line(&mut line).await.map_err(HttpError::Io)?;
+        if n == 0 {
+            return Err(HttpError::Malformed("no end of headers".to_string()));
+        }
+        if line == "\r\n" {
+            break;
+        }
+        head.extend_from_slice(line.as_bytes());
+    }
+    let response = parse_head(&head)?;
+
+    Ok(LineStream {
+        status: response.status,
+        chunked: is_chunked(&response),
+        reader,
+        chunk_left: 0,
+        buf: Vec::new(),
+        done: false,
+    })
+}
+
+impl LineStream {
+    /**
+     * next_line: next body line without its line ending; None at end of body
+     */
+    pub async fn next_line(&mut self) -> Result<Option<String>, HttpError> {
+        loop {
+            if let Some(pos) = self.buf.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.buf.drain(..=pos).collect();
+                let line = String::from_utf8_lossy(&line);
+                return Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()));
+            }
+            if self.done {
+                if self.buf.is_empty() {
+                    return Ok(None);
+                }
+                let line = String::from_utf8_lossy(&self.buf).into_owned();
+                self.buf.clear();
+                return Ok(Some(line));
+            }
+            self.fill().await?;
+        }
+    }
+
+    // Read more of the body into buf
+    async fn fill(&mut self) -> Result<(), HttpError> {
+        let malformed = || HttpError::Malformed("bad chunk".to_string());
+        if self.chunked && self.chunk_left == 0 {
+            let mut size = String::new();
+            if self
+                .reader
+                .read_line(&mut size)
+                .await
+                .map_err(HttpError::Io)?
+                == 0
+            {
+                self.done = true;
+                return Ok(());
+            }
+            let size = size.split(';').next().unwrap_or_default().trim();
+            self.chunk_left = usize::from_str_radix(size, 16).map_err(|_| malformed())?;
+            if self.chunk_left == 0 {
+                self.done = true;
+                return Ok(());
+            }
+        }
+
+        let want = if self.chunked { self.chunk_left } else { 4096 };
+        let mut tmp = vec![0; want.min(4096)];
+        let n = self.reader.read(&mut tmp).await.map_err(HttpError::Io)?;
+        if n == 0 {
+            self.done = true;
+            return Ok(());
+        }
+        self.buf.extend_from_slice(&tmp[..n]);
+
+        if self.chunked {
+            self.chunk_left -= n;
+            if self.chunk_left == 0 {
+                // chunk data is followed by CRLF
+                let mut crlf = String::new();
+                self.reader
+                    .read_line(&mut crlf)
+                    .await
+                    .map_err(HttpError::Io)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,304 @@
+/*! Ids module for async orchestrator
+ * Job ids, and the strategy that generates them
+ *
+ * A job id is a short string of ASCII letters, digits, '-' and '_' (at
+ * most MAX_ID_LEN bytes), used as is in urls, events, the event log and
+ * file names. The pool takes new ids from its IdGenerator: ULIDs unless
+ * configured otherwise. Embedders may supply their own generator in
+ * PoolConfig; the server picks a built-in one with ID_FORMAT:
+ *   ulid       "01J9Z3K8C4V6QJ1T2W3X4Y5Z6A" (the default)
+ *   uuidv7     "01926f3a-8c21-7c4e-9a1b-3d5e7f9a1b2c"
+ *   snowflake  "0287416453181452288": milliseconds, SNOWFLAKE_WORKER_ID
+ *              and a sequence, as a 19 digit decimal number
+ * and ID_PREFIX (e.g. "job_") puts a prefix before each.
+ * NOTE: ids of every built-in format sort in creation order, as strings
+ */
+use chrono::Utc;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use ulid::Ulid;
+
+// longest id, in bytes
+pub const MAX_ID_LEN: usize = 64;
+// snowflake ids count milliseconds from 2020-01-01
+const SNOWFLAKE_EPOCH_MS: u64 = 1_577_836_800_000;
+// largest snowflake worker id (10 bits) and sequence number (12 bits)
+pub const MAX_SNOWFLAKE_WORKER: u16 = 0x3ff;
+const MAX_SNOWFLAKE_SEQUENCE: u16 = 0xfff;
+
+/**
+ * JobId
+ * A job's id; held inline, so cheap to copy
+ */
+#[derive(Clone, Copy)]
+pub struct JobId {
+    len: u8,
+    bytes: [u8; MAX_ID_LEN],
+}
+
+impl JobId {
+    /**
+     * new: an id from its text; Err: empty, too long or with a character
+     * ids may not have
+     */
+    pub fn new(id: &str) -> Result<Self, String> {
+        if id.is_empty() || id.len() > MAX_ID_LEN {
+            return Err(format!("job id must be 1 to {MAX_ID_LEN} bytes"));
+        }
+        if let Some(c) = id
+            .chars()
+            .find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_'))
+        {
+            return Err(format!("job id '{id}': unexpected character '{c}'"));
+        }
+        let mut bytes = [0; MAX_ID_LEN];
+        bytes[..id.len()].copy_from_slice(id.as_bytes());
+        Ok(Self {
+            len: id.len() as u8,
+            bytes,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        // only ever built from checked ASCII
+        std::str::from_utf8(&self.bytes[..usize::from(self.len)]).unwrap_or_default()
+    }
+}
+
+impl From<Ulid> for JobId {
+    fn from(ulid: Ulid) -> Self {
+        // 26 characters of Crockford base32
+        Self::new(&ulid.to_string()).unwrap()
+    }
+}
+
+impl PartialEq for JobId {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for JobId {}
+
+impl Hash for JobId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl PartialOrd for JobId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for JobId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl fmt::Display for JobId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for JobId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl FromStr for JobId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl Serialize for JobId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for JobId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = String::deserialize(deserializer)?;
+        Self::new(&id).map_err(serde::de::Error::custom)
+    }
+}
+
+/**
+ * IdGenerator
+ * Makes the ids of new jobs; each must differ from every id made before
+ */
+pub trait IdGenerator: Send + Sync + fmt::Debug {
+    fn next_id(&self) -> JobId;
+}
+
+/**
+ * UlidIds
+ * ULIDs: the default
+ */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UlidIds;
+
+impl IdGenerator for UlidIds {
+    fn next_id(&self) -> JobId {
+        JobId::from(Ulid::new())
+    }
+}
+
+/**
+ * UuidV7Ids
+ * Version 7 UUIDs: a millisecond timestamp, then random bits
+ */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UuidV7Ids;
+
+impl IdGenerator for UuidV7Ids {
+    fn next_id(&self) -> JobId {
+        // a ULID is a 48 bit millisecond timestamp and 80 random bits; a
+        // v7 UUID lays out the same, less 6 random bits for its version
+        // and variant
+        let ulid = Ulid::new();
+        let random = ulid.random();
+        let rand_a = (random >> 68) & 0xfff;
+        let rand_b = random & ((1 << 62) - 1);
+        let uuid = (u128::from(ulid.timestamp_ms()) << 80)
+            | (0x7 << 76)
+            | (rand_a << 64)
+            | (0b10 << 62)
+            | rand_b;
+        let hex = format!("{uuid:032x}");
+        let id = format!(
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        );
+        JobId::new(&id).unwrap()
+    }
+}
+
+/**
+ * SnowflakeIds
+ * 64 bit ids: 41 bits of milliseconds since 2020, a 10 bit worker id and
+ * a 12 bit sequence within the millisecond, in 19 zero-padded decimal
+ * digits (so they sort as strings)
+ * NOTE: unique across instances only if each has its own worker id
+ */
+#[derive(Debug)]
+pub struct SnowflakeIds {
+    worker: u16,
+    // the millisecond last used, and the sequence number within it
+    last: Mutex<(u64, u16)>,
+}
+
+impl SnowflakeIds {
+    pub fn new(worker: u16) -> Self {
+        Self {
+            worker: worker.min(MAX_SNOWFLAKE_WORKER),
+            last: Mutex::new((0, 0)),
+        }
+    }
+}
+
+impl IdGenerator for SnowflakeIds {
+    fn next_id(&self) -> JobId {
+        let now = (Utc::now().timestamp_millis() as u64).saturating_sub(SNOWFLAKE_EPOCH_MS);
+        let mut last = self.last.lock().unwrap();
+        let (ms, sequence) = match *last {
+            // a sequence used up borrows the next millisecond; so does a
+            // clock stepped back
+            (ms, sequence) if now <= ms && sequence < MAX_SNOWFLAKE_SEQUENCE => (ms, sequence + 1),
+            (ms, _) if now <= ms => (ms + 1, 0),
+            _ => (now, 0),
+        };
+        *last = (ms, sequence);
+        let id = (ms << 22) | (u64::from(self.worker) << 12) | u64::from(sequence);
+        JobId::new(&format!("{id:019}")).unwrap()
+    }
+}
+
+/**
+ * PrefixedIds
+ * Another generator's ids, each after a fixed prefix
+ */
+#[derive(Debug)]
+pub struct PrefixedIds {
+    prefix: String,
+    inner: Arc<dyn IdGenerator>,
+}
+
+impl PrefixedIds {
+    /**
+     * new: Err if ids with the prefix could be invalid
+     */
+    pub fn new(prefix: &str, inner: Arc<dyn IdGenerator>) -> Result<Self, String> {
+        // the longest built-in id is a UUID's 36
+        JobId::new(&format!("{prefix}{}", "0".repeat(36)))
+            .map_err(|e| format!("id prefix '{prefix}': {e}"))?;
+        Ok(Self {
+            prefix: prefix.to_string(),
+            inner,
+        })
+    }
+}
+
+impl IdGenerator for PrefixedIds {
+    fn next_id(&self) -> JobId {
+        let id = self.inner.next_id();
+        // an inner id too long to prefix goes without
+        JobId::new(&format!("{}{id}", self.prefix)).unwrap_or(id)
+    }
+}
+
+/**
+ * IdFormat
+ * The built-in id formats, as ID_FORMAT names them
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum IdFormat {
+    #[default]
+    Ulid,
+    UuidV7,
+    Snowflake,
+}
+
+impl FromStr for IdFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "ulid" => Ok(IdFormat::Ulid),
+            "uuidv7" | "uuid" => Ok(IdFormat::UuidV7),
+            "snowflake" => Ok(IdFormat::Snowflake),
+            other => Err(format!(
+                "unknown id format: {other} (expected ulid, uuidv7 or snowflake)"
+            )),
+        }
+    }
+}
+
+impl IdFormat {
+    /**
+     * generator: a generator of the format; worker: a snowflake's worker id
+     */
+    pub fn generator(self, worker: u16) -> Arc<dyn IdGenerator> {
+        match self {
+            IdFormat::Ulid => Arc::new(UlidIds),
+            IdFormat::UuidV7 => Arc::new(UuidV7Ids),
+            IdFormat::Snowflake => Arc::new(SnowflakeIds::new(worker)),
+        }
+    }
+}
@@ -1,83 +1,800 @@
 /*! Jobs module for async orchestrator
  * Defines job structures
  */
+use crate::access::{Admins, Principal};
 use crate::api_error::ApiError;
-use crate::logs::{LogBuffer, LogLevel};
-use chrono::{DateTime, Utc};
+use crate::autoscale::Autoscaler;
+use crate::breaker::{CircuitBreakers, CircuitStatus};
+use crate::cache::{ResultCache, payload_hash};
+use crate::chaining::{self, FollowUp};
+use crate::checkpoints::{Checkpoint, Checkpoints};
+use crate::children::{self, JobTree, SpawnRequest, Spawner};
+use crate::clock::Clock;
+use crate::config::{OverflowPolicy, PoolConfig, StuckAction, StuckConfig};
+use crate::dedup::{Dedup, DedupReport};
+use crate::defaults::JobDefaults;
+use crate::durations::{DurationPercentiles, DurationStats, ShortestFirstConfig};
+use crate::egress::EgressPolicy;
+use crate::estimate::{AdmissionConfig, Estimate, EstimateSource, Refusal};
+use crate::events::{Event, EventBus, EventKind, EventLog};
+use crate::executor::{Budget, CancelToken, ExecContext, Executor, ExecutorInfo, Heartbeat};
+use crate::failure::{FailureClass, FailureInfo};
+use crate::health::{GateStatus, HealthGates};
+use crate::history::{History, HistoryUsage};
+use crate::hooks::HookedExecutor;
+use crate::http_client::Url;
+use crate::ids::{IdGenerator, JobId};
+use crate::labels::{self, LabelCounts, Labels};
+use crate::listing::{JobListing, JobSort, Page};
+use crate::logs::{GroupLogLine, JobLog, LogBuffer, LogLevel, LogOrder};
+use crate::notify::Notifier;
+use crate::overload::LoadShedder;
+use crate::pause::{Pause, PauseState};
+use crate::profiles::EnvProfiles;
+use crate::queues::{DEFAULT_QUEUE, JobQueue, PendingOverflow};
+use crate::ratelimit::{BucketLevel, TokenBuckets};
+use crate::resources::{ResourceStatus, Resources};
+use crate::schedules::Schedules;
+use crate::schemas::Schemas;
+use crate::scratch::{ScratchConfig, ScratchUsage};
+use crate::snapshot::{PoolSnapshot, Snapshots};
+use crate::tenants::TenantPolicies;
+use crate::timing::JobTiming;
+use crate::unique::{DuplicatePolicy, UniqueKeys};
+use crate::usage::{self, CostModel, UsageReport};
+use crate::webhooks::{CallbackStatus, Delivery, DeliveryState, Webhooks};
+use crate::workflows::Workflows;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
-use std::thread;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use tokio::sync::{
     Mutex,
-    mpsc::{self},
+    broadcast::error::RecvError,
+    mpsc::{self, error::SendTimeoutError, error::TrySendError},
+    watch,
 };
+use tokio::task::JoinHandle;
 use ulid::Ulid;
 
+// max number of completions applied under a single lock acquisition
+const COMPLETION_BATCH_SIZE: usize = 64;
+// recent finished jobs of a queue used to estimate its wait
+const WAIT_ESTIMATE_SAMPLE: usize = 20;
+// longest result a JobView carries, in characters
+const RESULT_SUMMARY_LEN: usize = 200;
+// largest metadata object a submission may carry, serialized
+const MAX_METADATA_BYTES: usize = 4096;
+// how often the run loop looks for running jobs past their timeout, or
+// gone quiet
+const WATCHDOG_INTERVAL: Duration = Duration::from_millis(100);
+// most jobs a submission may depend on
+const MAX_DEPENDENCIES: usize = 64;
+// how often the run loop cross-checks slots against their executions
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(5);
+
 /**
  * Job state
  */
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "lowercase")]
+#[allow(clippy::upper_case_acronyms, non_camel_case_types)]
 pub enum State {
     INIT,
+    // held until the jobs it depends on have succeeded
+    WAITING,
+    // held until its run_at time, then queued
+    SCHEDULED,
     QUEUED,
     RUNNING,
+    // running, but its heartbeat has gone quiet for longer than
+    // STUCK_AFTER_MS; back to RUNNING if it beats again
+    STUCK,
     SUCCEEDED,
     FAILED,
+    // cancellation requested while running; the executor is winding down
+    CANCELLING,
+    CANCELLED,
+    // ran past its timeout
+    TIMED_OUT,
+    // its deadline passed before it finished, whether or not it ran
+    DEADLINE_MISSED,
+    // never ran: a job it depends on did not succeed
+    SKIPPED,
+}
+
+impl State {
+    // SUCCEEDED, FAILED, CANCELLED, TIMED_OUT, DEADLINE_MISSED or SKIPPED:
+    // the job will not change state again
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            State::SUCCEEDED
+                | State::FAILED
+                | State::CANCELLED
+                | State::TIMED_OUT
+                | State::DEADLINE_MISSED
+                | State::SKIPPED
+        )
+    }
+
+    /**
+     * can_transition_to: whether a job may move from this state to next
+     * INIT -> [WAITING ->] [SCHEDULED ->] QUEUED -> RUNNING -> SUCCEEDED |
+     * FAILED; a job that never runs may fail or be cancelled from INIT,
+     * WAITING, SCHEDULED or QUEUED, and is skipped from INIT or WAITING
+     * when a job it depends on doesn't succeed; a running job
+     * being cancelled ends CANCELLED however its execution turns out, and
+     * one running past its timeout ends TIMED_OUT; a job reaching its
+     * deadline before it finishes, running or not, ends DEADLINE_MISSED; a
+     * running job whose heartbeat goes quiet is STUCK until it beats
+     * again, and ends from there as it would have from RUNNING; a running
+     * job preempted for a critical one is CANCELLING until its execution
+     * stops, then QUEUED again
+     */
+    pub fn can_transition_to(&self, next: &State) -> bool {
+        matches!(
+            (self, next),
+            (
+                State::INIT,
+                State::WAITING
+                    | State::SCHEDULED
+                    | State::QUEUED
+                    | State::FAILED
+                    | State::CANCELLED
+                    | State::DEADLINE_MISSED
+                    | State::SKIPPED
+            ) | (
+                State::WAITING,
+                State::SCHEDULED
+                    | State::QUEUED
+                    | State::FAILED
+                    | State::CANCELLED
+                    | State::DEADLINE_MISSED
+                    | State::SKIPPED
+            ) | (
+                State::SCHEDULED,
+                State::QUEUED | State::FAILED | State::CANCELLED | State::DEADLINE_MISSED
+            ) | (
+                State::QUEUED,
+                State::RUNNING | State::FAILED | State::CANCELLED | State::DEADLINE_MISSED
+            ) | (
+                State::RUNNING,
+                State::STUCK
+                    | State::SUCCEEDED
+                    | State::FAILED
+                    | State::CANCELLING
+                    | State::TIMED_OUT
+                    | State::DEADLINE_MISSED
+            ) | (
+                State::STUCK,
+                State::RUNNING
+                    | State::SUCCEEDED
+                    | State::FAILED
+                    | State::CANCELLING
+                    | State::TIMED_OUT
+                    | State::DEADLINE_MISSED
+            ) | (State::CANCELLING, State::CANCELLED | State::QUEUED)
+        )
+    }
+}
+
+/**
+ * TransitionError
+ * An illegal job state change, refused
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransitionError {
+    pub job_id: JobId,
+    pub from: State,
+    pub to: State,
+}
+
+impl fmt::Display for TransitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "job {}: illegal transition {} -> {}",
+            self.job_id, self.from, self.to
+        )
+    }
+}
+
+impl std::error::Error for TransitionError {}
+
+/**
+ * Transition
+ * One state change of a job, as recorded on it
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Transition {
+    pub from: State,
+    pub to: State,
+    pub at: DateTime<Utc>,
+    // time spent in from, up to this change
+    pub after_ms: u64,
+    // why it changed
+    pub reason: String,
 }
 
 impl fmt::Display for State {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let s = match self {
             State::INIT => "init",
+            State::WAITING => "waiting",
+            State::SCHEDULED => "scheduled",
             State::QUEUED => "queued",
             State::RUNNING => "running",
+            State::STUCK => "stuck",
             State::SUCCEEDED => "succeeded",
             State::FAILED => "failed",
+            State::CANCELLING => "cancelling",
+            State::CANCELLED => "cancelled",
+            State::TIMED_OUT => "timed_out",
+            State::DEADLINE_MISSED => "deadline_missed",
+            State::SKIPPED => "skipped",
         };
         f.write_str(s)
     }
 }
 
+impl FromStr for State {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "init" => Ok(State::INIT),
+            "waiting" => Ok(State::WAITING),
+            "scheduled" => Ok(State::SCHEDULED),
+            "queued" => Ok(State::QUEUED),
+            "running" => Ok(State::RUNNING),
+            "stuck" => Ok(State::STUCK),
+            "succeeded" => Ok(State::SUCCEEDED),
+            "failed" => Ok(State::FAILED),
+            "cancelling" => Ok(State::CANCELLING),
+            "cancelled" => Ok(State::CANCELLED),
+            "timed_out" => Ok(State::TIMED_OUT),
+            "deadline_missed" => Ok(State::DEADLINE_MISSED),
+            "skipped" => Ok(State::SKIPPED),
+            other => Err(format!("unknown state: {other}")),
+        }
+    }
+}
+
 /**
  * Job payloads
  */
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EchoPayload {
-    message: String,
+    pub message: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SleepPayload {
-    milliseconds: u32,
+    pub milliseconds: u32,
 }
 
 /**
- * Job Submission
- * Submitted by API
+ * Job priority
  */
-#[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(tag = "type", content = "payload")]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(rename_all = "lowercase")]
-pub enum JobSubmission {
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Critical,
+}
+
+impl FromStr for Priority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "low" => Ok(Priority::Low),
+            "normal" => Ok(Priority::Normal),
+            "high" => Ok(Priority::High),
+            "critical" => Ok(Priority::Critical),
+            _ => Err(format!("unknown priority '{s}'")),
+        }
+    }
+}
+
+/**
+ * Job kind
+ * Job type and its payload: {"type": ..., "payload": ...}
+ * A type other than the built-in ones keeps its payload as given; the
+ * pool refuses it unless its executor runs the type (see the handlers
+ * module)
+ */
+#[derive(Debug, Clone)]
+pub enum JobKind {
     Echo(EchoPayload),
     Sleep(SleepPayload),
+    Custom { job_type: String, payload: Value },
+}
+
+// the built-in job types, as they appear in the "type" field
+pub const JOB_TYPES: &[&str] = &["echo", "sleep"];
+
+impl JobKind {
+    // Job type, as it appears in the "type" field
+    pub fn name(&self) -> &str {
+        match self {
+            JobKind::Echo(_) => "echo",
+            JobKind::Sleep(_) => "sleep",
+            JobKind::Custom { job_type, .. } => job_type,
+        }
+    }
+
+    /**
+     * payload: the payload, as JSON
+     */
+    pub fn payload(&self) -> Value {
+        match self {
+            JobKind::Echo(payload) => serde_json::to_value(payload).unwrap_or_default(),
+            JobKind::Sleep(payload) => serde_json::to_value(payload).unwrap_or_default(),
+            JobKind::Custom { payload, .. } => payload.clone(),
+        }
+    }
+}
+
+impl Serialize for JobKind {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("type", self.name())?;
+        match self {
+            JobKind::Echo(payload) => map.serialize_entry("payload", payload)?,
+            JobKind::Sleep(payload) => map.serialize_entry("payload", payload)?,
+            JobKind::Custom { payload, .. } => map.serialize_entry("payload", payload)?,
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for JobKind {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Raw {
+            #[serde(rename = "type")]
+            job_type: String,
+            #[serde(default)]
+            payload: Value,
+        }
+        let raw = Raw::deserialize(deserializer)?;
+        let payload = raw.payload;
+        match raw.job_type.as_str() {
+            "echo" => serde_json::from_value(payload)
+                .map(JobKind::Echo)
+                .map_err(serde::de::Error::custom),
+            "sleep" => serde_json::from_value(payload)
+                .map(JobKind::Sleep)
+                .map_err(serde::de::Error::custom),
+            "" => Err(serde::de::Error::custom("type: empty")),
+            _ => Ok(JobKind::Custom {
+                job_type: raw.job_type,
+                payload,
+            }),
+        }
+    }
+}
+
+/**
+ * Job Submission
+ * Submitted by API
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JobSubmission {
+    #[serde(flatten)]
+    pub kind: JobKind,
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(default = "default_queue")]
+    pub queue: String,
+    // POSTed the job record when the job reaches a terminal state
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>,
+    // jobs sharing a group id are tracked (and cancelled) together
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_id: Option<String>,
+    // position in the input list of the map that expanded into this job
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map_index: Option<usize>,
+    // workflow step this job runs (the run id is the group id)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workflow_step: Option<String>,
+    // caller's own data (correlation ids, ticket numbers, ...), kept as
+    // given and returned with the job and its events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Map<String, Value>>,
+    // key/value tags to filter jobs and break metrics down by
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: Labels,
+    // longest the job may run; None: the pool's default timeout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    // when the job must have finished by, wherever it is then: its result
+    // is worthless after; None: no deadline
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<DateTime<Utc>>,
+    // hold the job until then before queueing it; None: queue it at once
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_at: Option<DateTime<Utc>>,
+    // as run_at, relative to when the pool receives the job
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delay_ms: Option<u64>,
+    // jobs that must all succeed before this one is queued; if one
+    // doesn't, this one is skipped
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<JobId>,
+    // the configured environment profile the job runs with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_profile: Option<String>,
+    // the job this one is a patched copy of (POST /jobs/{id}/clone)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cloned_from: Option<JobId>,
+    // the finished job this one runs again (POST /jobs/{id}/rerun)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rerun_of: Option<JobId>,
+    // at most one unfinished job may hold a unique key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unique_key: Option<String>,
+    // what happens if the unique key is held; None: rejected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_duplicate: Option<DuplicatePolicy>,
+    // the earlier job whose checkpoints this one starts from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resume_from: Option<JobId>,
+    // a critical job finding the pool full may preempt the lowest priority
+    // running job: it is stopped and requeued, and this one takes its slot
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub preempt: bool,
+    // the running job that spawned this one (set by the pool)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<JobId>,
+    // once its execution returns, finish only when its children have: it
+    // succeeds if they all did
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub wait_for_children: bool,
+    // submitted once the job succeeds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_success: Option<Box<JobSubmission>>,
+    // submitted once the job fails, times out or misses its deadline
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_failure: Option<Box<JobSubmission>>,
+    // the finished job whose follow-up this one is (set by the pool)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub follows: Option<JobId>,
+    // the schedule that submitted it (set by the pool)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule_id: Option<String>,
+}
+
+fn default_queue() -> String {
+    DEFAULT_QUEUE.to_string()
+}
+
+// Pauses saved by a previous run
+// NOTE: an unreadable file pauses the pool, so a freeze is never lifted
+// by accident
+fn restore_pauses(config: &PoolConfig) -> PauseState {
+    let Some(path) = &config.pause_state else {
+        return PauseState::default();
+    };
+    match PauseState::load(path) {
+        Ok(mut pauses) => {
+            pauses.queues.retain(|name, _| {
+                let known = config.queues.iter().any(|q| q.name == *name);
+                if !known {
+                    println!("[JobPool]: dropping pause of unknown queue '{}'", name);
+                }
+                known
+            });
+            if pauses != PauseState::default() {
+                println!("[JobPool]: restored pauses: {:?}", pauses);
+            }
+            pauses
+        }
+        Err(e) => {
+            println!("[JobPool]: pause state unreadable, pausing the pool: {}", e);
+            let mut pauses = PauseState::default();
+            pauses.pause(
+                None,
+                &format!("pause state unreadable: {e}"),
+                config.clock.now(),
+            );
+            pauses
+        }
+    }
+}
+
+// How long until a time, by the pool clock; zero once it has passed
+fn time_until(clock: &Arc<dyn Clock>, at: Option<DateTime<Utc>>) -> Duration {
+    at.and_then(|at| (at - clock.now()).to_std().ok())
+        .unwrap_or_default()
+}
+
+// The state a job out of time ends in: DEADLINE_MISSED for a missed
+// deadline, else TIMED_OUT
+fn overdue_state(failure: &FailureInfo) -> State {
+    match failure.class {
+        FailureClass::Deadline => State::DEADLINE_MISSED,
+        _ => State::TIMED_OUT,
+    }
+}
+
+// Why a job is out of time: its deadline has passed, or it has run
+// longer than its timeout; None: it isn't
+fn overdue(job: &Job, timeout: Option<Duration>, now: DateTime<Utc>) -> Option<FailureInfo> {
+    if let Some(deadline) = job.submission.deadline
+        && now >= deadline
+    {
+        let message = format!("missed its deadline of {}", deadline);
+        return Some(FailureInfo::pool(FailureClass::Deadline, &message));
+    }
+    let (Some(timeout), Some(started)) = (timeout, job.started_at) else {
+        return None;
+    };
+    (now - started)
+        .to_std()
+        .is_ok_and(|ran| ran > timeout)
+        .then(|| {
+            let message = format!("timed out after {}ms", timeout.as_millis());
+            FailureInfo::pool(FailureClass::Timeout, &message)
+        })
+}
+
+// Reject metadata over MAX_METADATA_BYTES
+fn check_metadata(job: &JobSubmission) -> Result<(), ApiError> {
+    let Some(metadata) = &job.metadata else {
+        return Ok(());
+    };
+    let size = serde_json::to_vec(metadata).map_or(usize::MAX, |v| v.len());
+    if size > MAX_METADATA_BYTES {
+        return Err(ApiError::BadRequest(format!(
+            "metadata: {size} bytes, over the {MAX_METADATA_BYTES} byte limit"
+        )));
+    }
+    Ok(())
+}
+
+// Apply a JSON merge patch (RFC 7386) to target: objects merge key by key,
+// null removes a key, anything else replaces
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(fields) = target else {
+        return;
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            fields.remove(key);
+        } else {
+            merge_patch(fields.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+// A job's submission as it can be submitted again: without the fields
+// the pool sets for map children, workflow steps (group_id among them) and
+// spawned children, and linked to no earlier job
+fn resubmission(submission: &JobSubmission) -> JobSubmission {
+    let mut copy = submission.clone();
+    if copy.map_index.is_some() || copy.workflow_step.is_some() {
+        copy.group_id = None;
+    }
+    copy.map_index = None;
+    copy.workflow_step = None;
+    copy.parent_id = None;
+    copy.follows = None;
+    copy.schedule_id = None;
+    copy.cloned_from = None;
+    copy.rerun_of = None;
+    copy.resume_from = None;
+    copy
+}
+
+/**
+ * Map Submission
+ * One job template applied to a list of inputs: each input is the
+ * payload of one child job. The children form a group named by the map id.
+ * With a reduce step, one more job of the group (the reducer) runs once
+ * every child has succeeded, given their results in input order; its
+ * result is the map's. A child that doesn't succeed skips the reducer.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MapSubmission {
+    #[serde(rename = "type")]
+    pub job_type: String,
+    pub inputs: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(default = "default_queue")]
+    pub queue: String,
+    // applies to each child
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>,
+    // copied to each child
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Map<String, Value>>,
+    // copied to each child
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: Labels,
+    // applies to each child
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    // applies to each child
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<DateTime<Utc>>,
+    // applies to each child
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_at: Option<DateTime<Utc>>,
+    // applies to each child
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delay_ms: Option<u64>,
+    // applies to each child
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<JobId>,
+    // applies to each child
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_profile: Option<String>,
+    // the job reducing the children's results, e.g. {"type": "echo",
+    // "payload": {...}}; None: the map has no reducer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reduce: Option<JobKind>,
+}
+
+impl MapSubmission {
+    // Expand into one child submission per input
+    fn expand(&self, map_id: &str) -> Result<Vec<JobSubmission>, String> {
+        self.inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| {
+                let kind = serde_json::json!({ "type": self.job_type, "payload": input });
+                let kind: JobKind =
+                    serde_json::from_value(kind).map_err(|e| format!("inputs[{i}]: {e}"))?;
+                Ok(JobSubmission {
+                    kind,
+                    priority: self.priority,
+                    queue: self.queue.clone(),
+                    callback_url: self.callback_url.clone(),
+                    group_id: Some(map_id.to_string()),
+                    map_index: Some(i),
+                    workflow_step: None,
+                    metadata: self.metadata.clone(),
+                    labels: self.labels.clone(),
+                    timeout_ms: self.timeout_ms,
+                    deadline: self.deadline,
+                    run_at: self.run_at,
+                    delay_ms: self.delay_ms,
+                    depends_on: self.depends_on.clone(),
+                    env_profile: self.env_profile.clone(),
+                    cloned_from: None,
+                    rerun_of: None,
+                    unique_key: None,
+                    on_duplicate: None,
+                    resume_from: None,
+                    preempt: false,
+                    parent_id: None,
+                    wait_for_children: false,
+                    on_success: None,
+                    on_failure: None,
+                    follows: None,
+                    schedule_id: None,
+                })
+            })
+            .collect()
+    }
+
+    // The reducer of the map's children, waiting for them all
+    fn reducer(&self, map_id: &str, children: &[JobId]) -> Option<JobSubmission> {
+        Some(JobSubmission {
+            kind: self.reduce.clone()?,
+            priority: self.priority,
+            queue: self.queue.clone(),
+            callback_url: self.callback_url.clone(),
+            group_id: Some(map_id.to_string()),
+            map_index: None,
+            workflow_step: None,
+            metadata: self.metadata.clone(),
+            labels: self.labels.clone(),
+            timeout_ms: self.timeout_ms,
+            deadline: self.deadline,
+            run_at: None,
+            delay_ms: None,
+            depends_on: children.to_vec(),
+            env_profile: self.env_profile.clone(),
+            cloned_from: None,
+            rerun_of: None,
+            unique_key: None,
+            on_duplicate: None,
+            resume_from: None,
+            preempt: false,
+            parent_id: None,
+            wait_for_children: false,
+            on_success: None,
+            on_failure: None,
+            follows: None,
+            schedule_id: None,
+        })
+    }
 }
 
 /**
  * Job
  */
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Job {
-    id: Ulid,
+    id: JobId,
     submission: JobSubmission,
     state: State,
+    // bumped whenever the job record changes; GET /jobs/{id} serves it
+    // as the ETag
+    #[serde(default)]
+    revision: u64,
     created_at: DateTime<Utc>,
+    // when a delayed job is (or was) due to be queued
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scheduled_for: Option<DateTime<Utc>>,
     started_at: Option<DateTime<Utc>>,
     finished_at: Option<DateTime<Utc>>,
+    // when the watchdog last saw its execution's heartbeat move (its start
+    // counts as one)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    heartbeat_at: Option<DateTime<Utc>>,
+    // "name@version" of the executor it ran under, once it started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    executor_version: Option<String>,
     result: String,
+    // why it didn't succeed, once it ended otherwise
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    failure: Option<FailureInfo>,
+    // capacity used, weighted by job type; set once the job completes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cost: Option<f64>,
+    // scratch directory usage, once the execution returned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scratch: Option<ScratchUsage>,
+    // completion callback delivery, if one was requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    callback: Option<CallbackStatus>,
+    // kept in history however full it gets
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pinned: bool,
+    // the critical job it is being stopped for, to be requeued once its
+    // execution returns
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    preempted_by: Option<JobId>,
+    // the jobs its execution spawned, oldest first; set once it returned
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    children: Vec<JobId>,
+    // bytes its execution logged past the log rate limit, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    log_dropped: Option<u64>,
+    // time spent in each phase so far
+    #[serde(default)]
+    timing: JobTiming,
+    // saved by its execution, oldest first; set once the execution returned
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    checkpoints: Vec<Checkpoint>,
+    // every state change, oldest first
+    #[serde(default)]
+    transitions: Vec<Transition>,
     #[serde(skip)]
     log: LogBuffer,
 }
@@ -92,22 +809,288 @@ impl fmt::Display for Job {
     }
 }
 
+// When a job delayed by delay_ms at now is due; None: later than can be
+// represented
+fn delayed_until(now: DateTime<Utc>, delay_ms: u64) -> Option<DateTime<Utc>> {
+    let delay = TimeDelta::try_milliseconds(i64::try_from(delay_ms).ok()?)?;
+    now.checked_add_signed(delay)
+}
+
 impl Job {
-    pub fn new(job_submission: &JobSubmission) -> Self {
-        let now = Utc::now();
+    // NOTE: the id is assigned at submission, so callers learn it up front
+    pub fn new(id: JobId, job_submission: &JobSubmission, now: DateTime<Utc>) -> Self {
         let this = Self {
-            id: Ulid::new(),
+            id,
             submission: job_submission.clone(),
             state: State::INIT,
+            revision: 0,
             created_at: now,
+            // a delay too long to add (refused at submission) holds it for good
+            scheduled_for: job_submission.run_at.or_else(|| {
+                Some(
+                    delayed_until(now, job_submission.delay_ms?)
+                        .unwrap_or(DateTime::<Utc>::MAX_UTC),
+                )
+            }),
             started_at: None,
             finished_at: None,
+            heartbeat_at: None,
+            executor_version: None,
             result: String::new(),
+            failure: None,
+            cost: None,
+            scratch: None,
+            callback: job_submission
+                .callback_url
+                .as_deref()
+                .map(CallbackStatus::new),
+            pinned: false,
+            preempted_by: None,
+            children: Vec::new(),
+            log_dropped: None,
+            timing: JobTiming::default(),
+            checkpoints: Vec::new(),
+            transitions: Vec::new(),
             log: LogBuffer::new(),
         };
         println!("[Job]: new: job {} created at {}", this.id, this.created_at);
         this
     }
+
+    pub fn id(&self) -> JobId {
+        self.id
+    }
+
+    pub fn submission(&self) -> &JobSubmission {
+        &self.submission
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    /**
+     * footprint: estimated bytes the job takes in memory, log included
+     */
+    pub fn footprint(&self) -> usize {
+        let submission = serde_json::to_vec(&self.submission).map_or(0, |s| s.len());
+        let checkpoints = serde_json::to_vec(&self.checkpoints).map_or(0, |s| s.len());
+        std::mem::size_of::<Self>()
+            + self.log.capacity()
+            + self.result.capacity()
+            + submission
+            + checkpoints
+            + self.transitions.capacity() * std::mem::size_of::<Transition>()
+            + self
+                .transitions
+                .iter()
+                .map(|t| t.reason.len())
+                .sum::<usize>()
+    }
+
+    pub fn started_at(&self) -> Option<DateTime<Utc>> {
+        self.started_at
+    }
+
+    pub fn finished_at(&self) -> Option<DateTime<Utc>> {
+        self.finished_at
+    }
+
+    pub fn heartbeat_at(&self) -> Option<DateTime<Utc>> {
+        self.heartbeat_at
+    }
+
+    pub fn executor_version(&self) -> Option<&str> {
+        self.executor_version.as_deref()
+    }
+
+    pub fn result(&self) -> &str {
+        &self.result
+    }
+
+    pub fn failure(&self) -> Option<&FailureInfo> {
+        self.failure.as_ref()
+    }
+
+    pub fn cost(&self) -> Option<f64> {
+        self.cost
+    }
+
+    pub fn scratch(&self) -> Option<&ScratchUsage> {
+        self.scratch.as_ref()
+    }
+
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    pub fn transitions(&self) -> &[Transition] {
+        &self.transitions
+    }
+
+    pub fn log(&self) -> &LogBuffer {
+        &self.log
+    }
+
+    pub fn children(&self) -> &[JobId] {
+        &self.children
+    }
+
+    pub fn callback(&self) -> Option<&CallbackStatus> {
+        self.callback.as_ref()
+    }
+
+    pub fn pinned(&self) -> bool {
+        self.pinned
+    }
+
+    /**
+     * transition: move the job to a new state, if that move is legal
+     * Stamps started_at (and heartbeat_at) on first RUNNING and
+     * finished_at on a terminal state, records the change and why on the
+     * job, and emits it; an illegal move changes nothing
+     */
+    pub fn transition(
+        &mut self,
+        to: State,
+        reason: &str,
+        now: DateTime<Utc>,
+        events: &EventBus,
+    ) -> Result<(), TransitionError> {
+        if !self.state.can_transition_to(&to) {
+            return Err(TransitionError {
+                job_id: self.id,
+                from: self.state.clone(),
+                to,
+            });
+        }
+        if to == State::RUNNING && self.state == State::QUEUED {
+            self.started_at = Some(now);
+            self.heartbeat_at = Some(now);
+        }
+        if to.is_terminal() {
+            self.finished_at = Some(now);
+        }
+        let since = self.transitions.last().map_or(self.created_at, |t| t.at);
+        self.transitions.push(Transition {
+            from: self.state.clone(),
+            to: to.clone(),
+            at: now,
+            after_ms: (now - since).num_milliseconds().max(0) as u64,
+            reason: reason.to_string(),
+        });
+        self.state = to;
+        self.revision += 1;
+        events.emit(EventKind::JobStateChanged {
+            job_id: self.id,
+            state: self.state.clone(),
+            metadata: self.submission.metadata.clone(),
+            labels: self.submission.labels.clone(),
+        });
+        Ok(())
+    }
+}
+
+/**
+ * JobView
+ * A job as listed by the API: what it is, where it stands, and a summary
+ * of its result; the full record is Job
+ */
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JobView {
+    pub id: JobId,
+    #[serde(rename = "type")]
+    pub job_type: String,
+    pub queue: String,
+    pub priority: Priority,
+    pub state: State,
+    pub revision: u64,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheduled_for: Option<DateTime<Utc>>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heartbeat_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub executor_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cloned_from: Option<JobId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rerun_of: Option<JobId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<JobId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub follows: Option<JobId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule_id: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    // the result, cut to RESULT_SUMMARY_LEN characters
+    pub result: String,
+    pub result_truncated: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<FailureInfo>,
+    // bytes of job log
+    pub log_len: usize,
+    // bytes dropped from it by the log rate limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_dropped: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scratch: Option<ScratchUsage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Map<String, Value>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: Labels,
+}
+
+impl From<&Job> for JobView {
+    fn from(job: &Job) -> Self {
+        let mut result: String = job.result.chars().take(RESULT_SUMMARY_LEN).collect();
+        let result_truncated = result.len() < job.result.len();
+        if result_truncated {
+            result.push('…');
+        }
+        Self {
+            id: job.id,
+            job_type: job.submission.kind.name().to_string(),
+            queue: job.submission.queue.clone(),
+            priority: job.submission.priority,
+            state: job.state.clone(),
+            revision: job.revision,
+            created_at: job.created_at,
+            scheduled_for: job.scheduled_for,
+            started_at: job.started_at,
+            finished_at: job.finished_at,
+            heartbeat_at: job.heartbeat_at,
+            executor_version: job.executor_version.clone(),
+            cloned_from: job.submission.cloned_from,
+            rerun_of: job.submission.rerun_of,
+            parent_id: job.submission.parent_id,
+            follows: job.submission.follows,
+            schedule_id: job.submission.schedule_id.clone(),
+            pinned: job.pinned,
+            result,
+            result_truncated,
+            failure: job.failure.clone(),
+            log_len: job.log.len(),
+            log_dropped: job.log_dropped,
+            cost: job.cost,
+            scratch: job.scratch.clone(),
+            metadata: job.submission.metadata.clone(),
+            labels: job.submission.labels.clone(),
+        }
+    }
 }
 
 /**
@@ -121,25 +1104,275 @@ pub enum JobCell {
     Occupied(Arc<std::sync::Mutex<Job>>),
 }
 
+/**
+ * Completion
+ * A job its execution thread is done with, handed back to the pool
+ */
+struct Completion {
+    job_id: JobId,
+    // the job as the thread left it: state, result and log
+    job: Job,
+}
+
+/**
+ * Family
+ * A parent's children that have finished while it was unfinished, and
+ * the parent once its execution returned, if it waits for them
+ */
+#[derive(Default)]
+struct Family {
+    parent: Option<Job>,
+    finished: HashSet<JobId>,
+    // the first child that didn't succeed, and how it ended
+    failed: Option<(JobId, State)>,
+}
+
+/**
+ * MapRun
+ * A map's number of children, and its reducer with the results of the
+ * children that have succeeded
+ */
+struct MapRun {
+    total: usize,
+    reducer: Option<JobId>,
+    // by input position; empty without a reducer
+    results: Vec<String>,
+}
+
 /**
  * JobPoolState
- * Fixed-length (for now) set of max_jobs jobs
+ * Set of up to max_jobs jobs, fed from named queues
  * NOTE: Option None --> job is being executed in a another thread
+ * NOTE: max_jobs may shrink below jobs.len(); slots past max_jobs
+ * are left to drain and never handed out again
  */
 struct JobPoolState {
     jobs: Vec<Option<JobCell>>,
     max_jobs: usize,
-    completed: Vec<Job>,
+    // delayed jobs, by when they are due, then id
+    scheduled: BTreeMap<(DateTime<Utc>, JobId), Job>,
+    // jobs held for their dependencies, by id
+    blocked: BTreeMap<JobId, Blocked>,
+    // dependency id -> jobs blocked on it
+    dependents: HashMap<JobId, Vec<JobId>>,
+    // jobs whose dependencies have all succeeded, for the run loop to
+    // schedule or queue
+    ready: Vec<Job>,
+    completed: History,
+    // counters for GET /metrics, kept across history evictions
+    tally: Tally,
+    // recent run times per job type
+    durations: DurationStats,
+    // None: queues dispatch in priority order, oldest first
+    shortest_first: Option<ShortestFirstConfig>,
+    earliest_deadline_first: bool,
+    // environment variable sets jobs run with
+    env_profiles: EnvProfiles,
+    // handed to every execution
+    egress: EgressPolicy,
+    // downstream health; jobs of unhealthy types are held pending
+    health: HealthGates,
+    // job types failing over and over; their jobs fail fast
+    breakers: CircuitBreakers,
+    // rate limited job types; their jobs are held pending without a token
+    rate_limits: TokenBuckets,
+    // unique keys held by unfinished jobs, released as they finish
+    unique_keys: UniqueKeys,
+    // named queues, in dispatch order
+    queues: Vec<JobQueue>,
+    events: EventBus,
+    webhooks: Webhooks,
+    notifier: Notifier,
+    // submissions turned away for lack of a slot since the last sample
+    rejected_since_sample: usize,
+    // worst dispatch latency (created -> started) seen since the last sample
+    latency_since_sample: Duration,
+    // map id -> its children and reducer
+    maps: HashMap<String, MapRun>,
+    clock: Arc<dyn Clock>,
+    executor: Arc<dyn Executor>,
+    cost_model: CostModel,
+    // operator pauses; paused queues hold their jobs pending
+    pauses: PauseState,
+    result_cache: ResultCache,
+    // idempotency keys and duplicate submissions suppressed
+    dedup: Dedup,
+    // job id -> slot the job occupies
+    slots: HashMap<JobId, usize>,
+    // job id -> token its execution thread polls, for jobs in slots
+    cancel_tokens: HashMap<JobId, CancelToken>,
+    // the wait budget of each running job, and of unfinished jobs waited
+    // on with a request deadline
+    budgets: HashMap<JobId, Budget>,
+    // job id -> its execution's heartbeat and the beats last seen, for jobs
+    // in slots
+    heartbeats: HashMap<JobId, (Heartbeat, u64)>,
+    // job id -> the execution task holding its slot, for jobs in slots
+    leases: HashMap<JobId, Lease>,
+    // where executions send the children they spawn
+    spawn_tx: mpsc::UnboundedSender<SpawnRequest>,
+    // where finished jobs' follow-ups go to be submitted
+    follow_tx: mpsc::UnboundedSender<FollowUp>,
+    // parent id -> its finished children, for unfinished parents
+    families: HashMap<JobId, Family>,
+    // shared by executions
+    resources: Resources,
+    // bytes a second each execution may log; None: unlimited
+    log_rate_limit: Option<u64>,
+    // None: running jobs are never considered stuck
+    stuck: Option<StuckConfig>,
+    // None: jobs get no scratch directory
+    scratch: Option<ScratchConfig>,
+    // timeout of jobs that don't set their own; None: unlimited
+    job_timeout: Option<Duration>,
+    // finished jobs each tenant may pin
+    pin_quota: usize,
+}
+
+/**
+ * Lease
+ * A slot's execution task, and whether the last reconcile pass found it
+ * ended with the slot still taken
+ */
+struct Lease {
+    task: tokio::task::JoinHandle<()>,
+    gone: bool,
+}
+
+/**
+ * Blocked
+ * A job held for its dependencies, and those yet to succeed
+ */
+struct Blocked {
+    job: Job,
+    unmet: BTreeSet<JobId>,
+}
+
+/**
+ * PoolSample
+ * Load signals taken periodically from the pool
+ */
+struct PoolSample {
+    // work waiting for a slot
+    depth: usize,
+    // occupied slots
+    busy: usize,
+    // worst recent dispatch latency
+    latency: Duration,
+}
+
+/**
+ * Tally
+ * Running counts of jobs since startup
+ */
+#[derive(Debug, Default)]
+struct Tally {
+    submitted: u64,
+    succeeded: u64,
+    // failed or timed out
+    failed: u64,
+    // total run time of finished jobs that ran, and how many
+    run_time: Duration,
+    runs: u32,
+    // jobs that didn't succeed, by why
+    failures: BTreeMap<FailureClass, u64>,
+    // slots reclaimed from executions that vanished
+    reclaimed: u64,
+    // finished jobs by label ("key:value")
+    by_label: BTreeMap<String, LabelCounts>,
+    // bytes of execution log dropped by the log rate limit
+    log_dropped: u64,
+}
+
+impl Tally {
+    // Count a finished job
+    fn add(&mut self, job: &Job) {
+        match job.state {
+            State::SUCCEEDED => self.succeeded += 1,
+            State::FAILED | State::TIMED_OUT | State::DEADLINE_MISSED => self.failed += 1,
+            _ => {}
+        }
+        if let Some(failure) = &job.failure {
+            *self.failures.entry(failure.class).or_default() += 1;
+        }
+        self.log_dropped += job.log_dropped.unwrap_or(0);
+        for (key, value) in &job.submission.labels {
+            let counts = self.by_label.entry(format!("{key}:{value}")).or_default();
+            match job.state {
+                State::SUCCEEDED => counts.succeeded += 1,
+                State::CANCELLED | State::SKIPPED => counts.cancelled += 1,
+                _ => counts.failed += 1,
+            }
+        }
+        if let (Some(started), Some(finished)) = (job.started_at, job.finished_at)
+            && let Ok(ran) = (finished - started).to_std()
+        {
+            self.run_time += ran;
+            self.runs += 1;
+        }
+    }
+
+    fn average_run_time(&self) -> Duration {
+        self.run_time.checked_div(self.runs).unwrap_or_default()
+    }
 }
 
 impl JobPoolState {
     // new: create sized job pool
-    pub fn new(max_jobs: usize) -> Self {
-        debug_assert!(max_jobs > 0);
+    pub fn new(
+        config: &PoolConfig,
+        events: EventBus,
+        webhooks: Webhooks,
+        notifier: Notifier,
+        spawn_tx: mpsc::UnboundedSender<SpawnRequest>,
+        follow_tx: mpsc::UnboundedSender<FollowUp>,
+    ) -> Self {
+        debug_assert!(config.max_jobs > 0);
         Self {
-            max_jobs,
+            max_jobs: config.max_jobs,
             jobs: Vec::new(),
-            completed: Vec::new(),
+            scheduled: BTreeMap::new(),
+            blocked: BTreeMap::new(),
+            dependents: HashMap::new(),
+            ready: Vec::new(),
+            completed: History::new(config.history_budget, config.retention.clone()),
+            tally: Tally::default(),
+            durations: DurationStats::default(),
+            shortest_first: config.shortest_first,
+            earliest_deadline_first: config.earliest_deadline_first,
+            env_profiles: config.env_profiles.clone(),
+            egress: config.egress.clone(),
+            health: HealthGates::new(&config.health, config.clock.clone()),
+            breakers: CircuitBreakers::new(config.breaker, config.clock.clone()),
+            rate_limits: TokenBuckets::new(&config.rate_limits, config.clock.now()),
+            unique_keys: UniqueKeys::default(),
+            queues: config.queues.iter().cloned().map(JobQueue::new).collect(),
+            events,
+            webhooks,
+            notifier,
+            rejected_since_sample: 0,
+            latency_since_sample: Duration::ZERO,
+            maps: HashMap::new(),
+            clock: config.clock.clone(),
+            executor: HookedExecutor::wrap(config.executor.clone(), &config.hooks),
+            cost_model: config.cost.clone(),
+            pauses: restore_pauses(config),
+            result_cache: ResultCache::new(config.cache.clone()),
+            dedup: Dedup::new(config.dedup_window),
+            slots: HashMap::new(),
+            cancel_tokens: HashMap::new(),
+            budgets: HashMap::new(),
+            heartbeats: HashMap::new(),
+            leases: HashMap::new(),
+            spawn_tx,
+            follow_tx,
+            families: HashMap::new(),
+            resources: Resources::default(),
+            log_rate_limit: config.log_rate_limit,
+            stuck: config.stuck,
+            scratch: config.scratch.clone(),
+            job_timeout: config.job_timeout,
+            pin_quota: config.pin_quota,
         }
     }
 
@@ -153,7 +1386,7 @@ impl JobPoolState {
         }
         // search for an open slot
         // TODO: optimize to eliminate this O(n) operation
-        for (i, opt) in self.jobs.iter().enumerate() {
+        for (i, opt) in self.jobs.iter().enumerate().take(self.max_jobs) {
             match opt {
                 Some(cell) => match cell {
                     JobCell::Occupied(_) => continue,
@@ -169,228 +1402,4069 @@ impl JobPoolState {
 
     // Fail a job
     // NOTE: takes ownership of job
-    fn fail_and_complete_job(&mut self, mut job: Job, reason: &str) {
-        job.state = State::FAILED;
-        job.result = reason.to_string();
-        self.completed.push(job);
+    fn fail_and_complete_job(&mut self, mut job: Job, failure: FailureInfo) {
+        match job.transition(
+            State::FAILED,
+            &failure.message,
+            self.clock.now(),
+            &self.events,
+        ) {
+            Ok(()) => {
+                job.result = failure.message.clone();
+                job.failure = Some(failure);
+            }
+            Err(e) => println!("[JobPoolState]: fail: {}", e),
+        }
+        self.complete_job(job);
+    }
+
+    // Cancel a job that never ran
+    // NOTE: takes ownership of job
+    fn cancel_and_complete_job(&mut self, mut job: Job, reason: &str) {
+        match job.transition(State::CANCELLED, reason, self.clock.now(), &self.events) {
+            Ok(()) => {
+                job.result = reason.to_string();
+                job.failure = Some(FailureInfo::pool(FailureClass::Cancelled, reason));
+            }
+            Err(e) => println!("[JobPoolState]: cancel: {}", e),
+        }
+        self.complete_job(job);
     }
 
     // Run a job
     // NOTE: takes ownership of job
-    fn run_job(&mut self, mut job: Job, index: usize, completion_tx: &mpsc::Sender<usize>) {
+    fn run_job(&mut self, mut job: Job, index: usize, completion_tx: &mpsc::Sender<Completion>) {
         debug_assert!(index < self.jobs.len());
         debug_assert!(matches!(self.jobs[index], Some(JobCell::Empty)));
-
-        // queue job
-        job.state = State::QUEUED;
-        job.log.logf(
-            LogLevel::INFO,
-            format_args!("queued at {}", chrono::Utc::now()),
-        );
+        job.timing.dispatched();
 
         // package up the job for shared cross thread mutable access
         // the jobs array gets a clone
+        self.slots.insert(job.id, index);
+        let cancel = CancelToken::new();
+        self.cancel_tokens.insert(job.id, cancel.clone());
+        let heartbeat = Heartbeat::new();
+        self.heartbeats.insert(job.id, (heartbeat.clone(), 0));
+        let checkpoints = self.resumed_checkpoints(&mut job);
+        if let Some(bytes_per_sec) = self.log_rate_limit {
+            job.log.limit(bytes_per_sec);
+        }
+        let mut ctx = ExecContext {
+            cancel,
+            heartbeat,
+            resume: checkpoints.last().cloned(),
+            checkpoints: Checkpoints::new(checkpoints, self.clock.clone()),
+            scratch: None,
+            env: job
+                .submission
+                .env_profile
+                .as_deref()
+                .map(|name| self.env_profiles.env(name))
+                .unwrap_or_default(),
+            egress: self.egress.clone(),
+            timeout: self.timeout_for(&job.submission),
+            deadline: job.submission.deadline,
+            budget: self.budgets.entry(job.id).or_default().clone(),
+            children: Spawner::new(job.id, self.spawn_tx.clone()),
+            resources: self.resources.clone(),
+            log: JobLog::default(),
+            map_results: self.map_results(&job),
+        };
         let job_arc = Arc::new(std::sync::Mutex::new(job));
         self.jobs[index] = Some(JobCell::Occupied(job_arc.clone()));
+        let job_for_log = job_arc.clone();
+        ctx.log =
+            JobLog::new(move |level, msg| job_for_log.lock().unwrap().log.log_limited(level, msg));
 
         // execution thread gets clones
         let completion_tx = completion_tx.clone();
         let job_arc_for_thread = job_arc.clone();
-        tokio::task::spawn_blocking(move || {
+        let events = self.events.clone();
+        let clock = self.clock.clone();
+        let executor = self.executor.clone();
+        let scratch = self.scratch.clone();
+        let task = tokio::task::spawn_blocking(move || {
             JobPoolState::run_job_blocking(
                 JobCell::Occupied(job_arc_for_thread),
-                index,
                 completion_tx,
+                events,
+                clock,
+                executor,
+                ctx,
+                scratch,
             );
         });
+        let id = job_arc.lock().unwrap().id;
+        self.leases.insert(id, Lease { task, gone: false });
+    }
+
+    // Start a job from queue q in slot index, taking a rate limit token,
+    // unless its type's circuit is open: then it fails fast, leaving the
+    // slot free
+    // NOTE: takes ownership of job
+    fn start_job(
+        &mut self,
+        job: Job,
+        q: usize,
+        index: usize,
+        completion_tx: &mpsc::Sender<Completion>,
+    ) {
+        if let Err(failure) = self.breakers.admit(job.submission.kind.name(), job.id) {
+            println!("[JobPoolState]: job {}: {}", job.id, failure.message);
+            self.fail_and_complete_job(job, failure);
+            return;
+        }
+        let now = self.clock.now();
+        if !self.rate_limits.take(job.submission.kind.name(), now) {
+            // NOTE: callers check for a token first, so this is a backstop
+            println!("[JobPoolState]: job {}: rate limited, held", job.id);
+            self.queues[q].enqueue(job);
+            return;
+        }
+        self.queues[q].running += 1;
+        self.run_job(job, index, completion_tx);
+    }
+
+    // The checkpoints a job starts with: those of the job it resumes from,
+    // if that is still in history
+    fn resumed_checkpoints(&self, job: &mut Job) -> Vec<Checkpoint> {
+        // preempted before: it carries on from where it was stopped
+        if !job.checkpoints.is_empty() {
+            job.log.logf(
+                LogLevel::INFO,
+                format_args!("resuming: {} checkpoints", job.checkpoints.len()),
+            );
+            return job.checkpoints.clone();
+        }
+        let Some(from) = job.submission.resume_from else {
+            return Vec::new();
+        };
+        match self
+            .completed
+            .iter()
+            .rev()
+            .find(|earlier| earlier.id == from)
+        {
+            Some(earlier) => {
+                job.log.logf(
+                    LogLevel::INFO,
+                    format_args!(
+                        "resuming from job {from}: {} checkpoints",
+                        earlier.checkpoints.len()
+                    ),
+                );
+                earlier.checkpoints.clone()
+            }
+            None => {
+                job.log.logf(
+                    LogLevel::WARNING,
+                    format_args!("job {from} to resume from not in history: starting afresh"),
+                );
+                Vec::new()
+            }
+        }
     }
 
-    fn run_job_blocking(cell: JobCell, index: usize, completion_tx: mpsc::Sender<usize>) {
+    fn run_job_blocking(
+        cell: JobCell,
+        completion_tx: mpsc::Sender<Completion>,
+        events: EventBus,
+        clock: Arc<dyn Clock>,
+        executor: Arc<dyn Executor>,
+        mut ctx: ExecContext,
+        scratch: Option<ScratchConfig>,
+    ) {
         let JobCell::Occupied(job_arc) = cell else {
             panic!("run_job_blocking called with non-occupied cell");
         };
 
+        let job_id: JobId;
         let job_submission: JobSubmission;
 
         {
             let mut job = job_arc.lock().unwrap();
-            job.state = State::RUNNING;
-            job.started_at = Some(Utc::now());
-            job.log.logf(LogLevel::INFO, format_args!("job started"));
+            if let Err(e) =
+                job.transition(State::RUNNING, "execution started", clock.now(), &events)
+            {
+                // not runnable: hand the slot straight back
+                println!("[JobPoolState]: not running: {}", e);
+                drop(job);
+                JobPoolState::report_completion(&completion_tx, &job_arc, &clock, &events);
+                return;
+            }
+            job.timing.started();
+            let stamp = executor.info().stamp();
+            job.log
+                .logf(LogLevel::INFO, format_args!("job started on {stamp}"));
+            job.executor_version = Some(stamp);
+            job_id = job.id;
             job_submission = job.submission.clone();
         }
 
+        // the job's scratch directory, if configured; a job that can't
+        // have one fails without running
+        let scratch_dir = scratch.as_ref().map(|config| config.create(job_id));
+        ctx.scratch = scratch_dir.clone().and_then(Result::ok);
+
         // === ACTUAL WORK HERE ===
         // do heavy computation / I/O / blocking call
         println!("[JobPoolState]: ===========================");
         println!("[JobPoolState]: RUNNING JOB\n{:#?}", job_submission);
         println!("[JobPoolState]: ===========================");
-        thread::sleep(Duration::from_secs(10));
+        let began = Instant::now();
+        let outcome = match scratch_dir {
+            Some(Err(e)) => Err(FailureInfo::pool(
+                FailureClass::Internal,
+                &format!("scratch: {e}"),
+            )),
+            _ => executor.execute_classified(&job_submission, &ctx),
+        };
+        let returned = Instant::now();
 
         {
             let mut job = job_arc.lock().unwrap();
-            job.state = State::SUCCEEDED;
-            job.result = "success".to_string();
-            job.finished_at = Some(Utc::now());
-            job.log.logf(LogLevel::INFO, format_args!("job finished"));
-        }
-
-        completion_tx.blocking_send(index).unwrap();
-    }
-
-    // Handle a job submission
-    fn handle_new_job(
-        &mut self,
-        job_submission: &JobSubmission,
-        completion_tx: &mpsc::Sender<usize>,
-    ) {
+            job.timing.executed(began, returned);
+            job.checkpoints = ctx.checkpoints.list();
+            job.children = ctx.children.spawned();
+            job.log.note_dropped();
+            job.log_dropped = Some(job.log.dropped()).filter(|dropped| *dropped > 0);
+            let now = clock.now();
+            let cancelling = job.state == State::CANCELLING;
+            // the watchdog may have timed the job out, failed it for its
+            // deadline or as stuck, while it ran
+            let ended = matches!(
+                job.state,
+                State::TIMED_OUT | State::DEADLINE_MISSED | State::FAILED
+            );
+            let overdue = match job.state {
+                State::RUNNING | State::STUCK => overdue(&job, ctx.timeout, now),
+                _ => None,
+            };
+            let to = match (outcome, overdue) {
+                _ if ended => {
+                    let state = job.state.clone();
+                    job.log.logf(
+                        LogLevel::INFO,
+                        format_args!("execution returned after the job ended {state}"),
+                    );
+                    None
+                }
+                // finished, but too late
+                (_, Some(failure)) => {
+                    job.log
+                        .logf(LogLevel::ERROR, format_args!("job {}", failure.message));
+                    job.result = failure.message.clone();
+                    let state = overdue_state(&failure);
+                    job.failure = Some(failure);
+                    Some(state)
+                }
+                // stopped for a critical job: requeued, whatever it returned
+                (_, None) if cancelling && job.preempted_by.is_some() => {
+                    job.preempted_by = None;
+                    job.log
+                        .logf(LogLevel::INFO, format_args!("job preempted, requeued"));
+                    Some(State::QUEUED)
+                }
+                // the execution's outcome is kept, but the job ends cancelled
+                (Ok(result), None) if cancelling => {
+                    job.result = result;
+                    job.failure = Some(FailureInfo::pool(
+                        FailureClass::Cancelled,
+                        "cancelled while running",
+                    ));
+                    job.log
+                        .logf(LogLevel::INFO, format_args!("job finished, cancelled"));
+                    Some(State::CANCELLED)
+                }
+                (Err(failure), None) if cancelling => {
+                    job.log.logf(
+                        LogLevel::INFO,
+                        format_args!("job failed, cancelled: {}", failure.message),
+                    );
+                    job.result = failure.message.clone();
+                    job.failure = Some(
+                        failure
+                            .with_class(FailureClass::Cancelled)
+                            .with_retryable(false),
+                    );
+                    Some(State::CANCELLED)
+                }
+                // it ends once its children have
+                (Ok(result), None)
+                    if job.submission.wait_for_children && !job.children.is_empty() =>
+                {
+                    job.result = result;
+                    let children = job.children.len();
+                    job.log.logf(
+                        LogLevel::INFO,
+                        format_args!("job finished, waiting on {children} children"),
+                    );
+                    None
+                }
+                (Ok(result), None) => {
+                    job.result = result;
+                    job.log.logf(LogLevel::INFO, format_args!("job finished"));
+                    Some(State::SUCCEEDED)
+                }
+                (Err(failure), None) => {
+                    job.log
+                        .logf(LogLevel::ERROR, format_args!("job failed: {}", failure));
+                    job.result = failure.message.clone();
+                    job.failure = Some(failure);
+                    Some(State::FAILED)
+                }
+            };
+            if let (Some(config), Some(dir)) = (&scratch, &ctx.scratch) {
+                let failed = ended
+                    || matches!(
+                        to,
+                        Some(State::FAILED | State::TIMED_OUT | State::DEADLINE_MISSED)
+                    );
+                let usage = config.finish(dir, failed);
+                if let Some(kept) = &usage.retained {
+                    job.log.logf(
+                        LogLevel::INFO,
+                        format_args!("scratch kept at {}", kept.display()),
+                    );
+                }
+                job.scratch = Some(usage);
+            }
+            let reason = match (&to, &job.failure) {
+                (Some(State::QUEUED), _) => "preempted: requeued".to_string(),
+                (_, Some(failure)) => failure.message.clone(),
+                (_, None) => "execution finished".to_string(),
+            };
+            if let Some(to) = to
+                && let Err(e) = job.transition(to, &reason, now, &events)
+            {
+                println!("[JobPoolState]: finish: {}", e);
+            }
+        }
+
+        JobPoolState::report_completion(&completion_tx, &job_arc, &clock, &events);
+    }
+
+    // The timeout a submission runs under: its own, else the pool's
+    fn timeout_for(&self, submission: &JobSubmission) -> Option<Duration> {
+        submission
+            .timeout_ms
+            .map(Duration::from_millis)
+            .or(self.job_timeout)
+    }
+
+    // Time out running jobs past their timeout or deadline, asking their
+    // executions to stop, and pending jobs past their deadline
+    // NOTE: a running job's slot stays busy until its execution returns
+    fn expire_overdue(&mut self) {
+        let now = self.clock.now();
+        for cell in self.jobs.iter().flatten() {
+            let JobCell::Occupied(job_arc) = cell else {
+                continue;
+            };
+            let mut job = job_arc.lock().unwrap();
+            if !matches!(job.state, State::RUNNING | State::STUCK) {
+                continue;
+            }
+            let Some(failure) = overdue(&job, self.timeout_for(&job.submission), now) else {
+                continue;
+            };
+            job.log
+                .logf(LogLevel::ERROR, format_args!("job {}", failure.message));
+            job.result = failure.message.clone();
+            let state = overdue_state(&failure);
+            job.failure = Some(failure);
+            let reason = job.result.clone();
+            if let Err(e) = job.transition(state, &reason, now, &self.events) {
+                println!("[JobPoolState]: timeout: {}", e);
+                continue;
+            }
+            println!("[JobPoolState]: job {}: {}", job.id, job.result);
+            if let Some(cancel) = self.cancel_tokens.get(&job.id) {
+                cancel.cancel();
+            }
+        }
+
+        for q in 0..self.queues.len() {
+            let (expired, rest) = self.queues[q]
+                .pending
+                .drain(..)
+                .partition(|job| overdue(job, None, now).is_some());
+            self.queues[q].pending = rest;
+            for job in expired {
+                self.expire_pending_job(job);
+            }
+        }
+        let expired: Vec<(DateTime<Utc>, JobId)> = self
+            .scheduled
+            .iter()
+            .filter(|(_, job)| overdue(job, None, now).is_some())
+            .map(|(key, _)| *key)
+            .collect();
+        for key in expired {
+            if let Some(job) = self.scheduled.remove(&key) {
+                self.expire_pending_job(job);
+            }
+        }
+        let expired: Vec<JobId> = self
+            .blocked
+            .values()
+            .filter(|blocked| overdue(&blocked.job, None, now).is_some())
+            .map(|blocked| blocked.job.id)
+            .collect();
+        for id in expired {
+            if let Some(blocked) = self.blocked.remove(&id) {
+                self.expire_pending_job(blocked.job);
+            }
+        }
+    }
+
+    // Fail pending jobs held for an unhealthy downstream for longer than
+    // the max hold, counted from when they could first have run or when
+    // their downstream went unhealthy, whichever is later
+    fn expire_held(&mut self) {
+        let gated = self.health.unhealthy();
+        if gated.is_empty() {
+            return;
+        }
+        let now = self.clock.now();
+        let max_hold = self.health.max_hold();
+        for q in 0..self.queues.len() {
+            let (expired, rest) = self.queues[q].pending.drain(..).partition(|job| {
+                gated.get(job.submission.kind.name()).is_some_and(|&since| {
+                    let ready = job.scheduled_for.unwrap_or(job.created_at);
+                    (now - since.max(ready))
+                        .to_std()
+                        .is_ok_and(|held| held > max_hold)
+                })
+            });
+            self.queues[q].pending = rest;
+            for job in expired {
+                let job_type = job.submission.kind.name();
+                println!(
+                    "[JobPoolState]: job {}: held too long for unhealthy {}",
+                    job.id, job_type
+                );
+                let message = format!(
+                    "downstream of {job_type} unhealthy: held past the max hold, never ran"
+                );
+                self.fail_and_complete_job(
+                    job,
+                    FailureInfo::pool(FailureClass::Unavailable, &message),
+                );
+            }
+        }
+    }
+
+    // Note running jobs whose heartbeat moved, and flag (or fail) those
+    // quiet for longer than the stuck threshold; a flagged job that beats
+    // again is running again
+    // NOTE: a failed job's slot stays busy until its execution returns
+    fn check_heartbeats(&mut self) {
+        let now = self.clock.now();
+        for cell in self.jobs.iter().flatten() {
+            let JobCell::Occupied(job_arc) = cell else {
+                continue;
+            };
+            let mut job = job_arc.lock().unwrap();
+            if !matches!(job.state, State::RUNNING | State::STUCK) {
+                continue;
+            }
+            let Some((heartbeat, seen)) = self.heartbeats.get_mut(&job.id) else {
+                continue;
+            };
+            let beats = heartbeat.beats();
+            if beats != *seen {
+                *seen = beats;
+                job.heartbeat_at = Some(now);
+                if job.state == State::STUCK {
+                    job.log
+                        .logf(LogLevel::INFO, format_args!("heartbeat resumed"));
+                    if let Err(e) =
+                        job.transition(State::RUNNING, "heartbeat resumed", now, &self.events)
+                    {
+                        println!("[JobPoolState]: unstuck: {}", e);
+                    }
+                }
+                continue;
+            }
+            let Some(stuck) = self.stuck else {
+                continue;
+            };
+            let quiet = job
+                .heartbeat_at
+                .and_then(|at| (now - at).to_std().ok())
+                .unwrap_or_default();
+            if quiet <= stuck.after {
+                continue;
+            }
+            let message = format!("no heartbeat for {}ms", quiet.as_millis());
+            match stuck.action {
+                StuckAction::Flag if job.state == State::RUNNING => {
+                    job.log
+                        .logf(LogLevel::WARNING, format_args!("job stuck: {}", message));
+                    if let Err(e) = job.transition(State::STUCK, &message, now, &self.events) {
+                        println!("[JobPoolState]: stuck: {}", e);
+                        continue;
+                    }
+                    println!("[JobPoolState]: job {}: stuck, {}", job.id, message);
+                }
+                StuckAction::Flag => {}
+                StuckAction::Fail => {
+                    job.log
+                        .logf(LogLevel::ERROR, format_args!("job stuck: {}", message));
+                    job.result = message.clone();
+                    job.failure = Some(FailureInfo::pool(FailureClass::Stuck, &message));
+                    if let Err(e) = job.transition(State::FAILED, &message, now, &self.events) {
+                        println!("[JobPoolState]: stuck: {}", e);
+                        continue;
+                    }
+                    println!(
+                        "[JobPoolState]: job {}: failed as stuck, {}",
+                        job.id, message
+                    );
+                    if let Some(cancel) = self.cancel_tokens.get(&job.id) {
+                        cancel.cancel();
+                    }
+                }
+            }
+        }
+    }
+
+    // Queue the delayed jobs that have come due
+    fn release_due(&mut self, completion_tx: &mpsc::Sender<Completion>) {
+        let now = self.clock.now();
+        while let Some(entry) = self.scheduled.first_entry()
+            && entry.key().0 <= now
+        {
+            let job = entry.remove();
+            println!("[JobPoolState]: job {}: due", job.id);
+            match self.queue_index(&job.submission.queue) {
+                Some(q) => self.queue_job(job, q, completion_tx),
+                None => self.fail_and_complete_job(
+                    job,
+                    FailureInfo::pool(FailureClass::Invalid, "unknown queue: job never queued"),
+                ),
+            }
+        }
+    }
+
+    // When the next delayed job comes due; None: none are waiting
+    fn next_due(&self) -> Option<DateTime<Utc>> {
+        self.scheduled.first_key_value().map(|((due, _), _)| *due)
+    }
+
+    // Jobs not yet in a slot: held for dependencies, delayed, then
+    // pending on their queues
+    fn waiting(&self) -> impl Iterator<Item = &Job> {
+        self.blocked
+            .values()
+            .map(|blocked| &blocked.job)
+            .chain(self.families.values().filter_map(|f| f.parent.as_ref()))
+            .chain(self.ready.iter())
+            .chain(self.scheduled.values())
+            .chain(self.queues.iter().flat_map(|q| q.pending.iter()))
+    }
+
+    // Hold a job until the jobs it depends on have succeeded; one whose
+    // dependency is unknown fails, and one whose dependency has already
+    // ended otherwise is skipped
+    // Returns the job if it has nothing to wait for
+    // NOTE: takes ownership of job
+    fn hold_for_dependencies(&mut self, mut job: Job) -> Option<Job> {
+        let mut unmet = BTreeSet::new();
+        for &dependency in &job.submission.depends_on {
+            match self.find_job(dependency).map(|d| d.state) {
+                None => {
+                    println!(
+                        "[JobPoolState]: job {}: failed (unknown dependency {})",
+                        job.id, dependency
+                    );
+                    let reason = format!("unknown dependency {dependency}: job never queued");
+                    self.fail_and_complete_job(
+                        job,
+                        FailureInfo::pool(FailureClass::Invalid, &reason),
+                    );
+                    return None;
+                }
+                Some(State::SUCCEEDED) => {}
+                Some(state) if state.is_terminal() => {
+                    self.skip_job(job, dependency, &state);
+                    return None;
+                }
+                Some(_) => {
+                    unmet.insert(dependency);
+                }
+            }
+        }
+        if unmet.is_empty() {
+            return Some(job);
+        }
+        let reason = format!("waiting for {} dependencies", unmet.len());
+        if let Err(e) = job.transition(State::WAITING, &reason, self.clock.now(), &self.events) {
+            println!("[JobPoolState]: {}", e);
+            return None;
+        }
+        job.log.logf(
+            LogLevel::INFO,
+            format_args!("waiting for {} dependencies", unmet.len()),
+        );
+        println!("[JobPoolState]: job {}: waiting for {:?}", job.id, unmet);
+        for &dependency in &unmet {
+            self.dependents.entry(dependency).or_default().push(job.id);
+        }
+        self.blocked.insert(job.id, Blocked { job, unmet });
+        None
+    }
+
+    // Pass a finished job's outcome on to the jobs waiting for it: they
+    // are ready once all their dependencies succeeded, and skipped if this
+    // one didn't
+    fn settle_dependents(&mut self, id: JobId, state: &State) {
+        let Some(dependents) = self.dependents.remove(&id) else {
+            return;
+        };
+        for dependent in dependents {
+            // cancelled or timed out while it waited
+            let Some(blocked) = self.blocked.get_mut(&dependent) else {
+                continue;
+            };
+            if *state != State::SUCCEEDED {
+                let blocked = self.blocked.remove(&dependent).unwrap();
+                self.skip_job(blocked.job, id, state);
+                continue;
+            }
+            blocked.unmet.remove(&id);
+            if blocked.unmet.is_empty() {
+                let blocked = self.blocked.remove(&dependent).unwrap();
+                println!("[JobPoolState]: job {}: dependencies met", dependent);
+                self.ready.push(blocked.job);
+            }
+        }
+    }
+
+    // Skip a job that can't run: a job it depends on didn't succeed
+    // NOTE: takes ownership of job
+    fn skip_job(&mut self, mut job: Job, dependency: JobId, state: &State) {
+        let reason = format!("skipped: dependency {dependency} {state}");
+        println!("[JobPoolState]: job {}: {}", job.id, reason);
+        job.log.logf(LogLevel::INFO, format_args!("{}", reason));
+        match job.transition(State::SKIPPED, &reason, self.clock.now(), &self.events) {
+            Ok(()) => {
+                job.failure = Some(FailureInfo::pool(FailureClass::Dependency, &reason));
+                job.result = reason;
+            }
+            Err(e) => println!("[JobPoolState]: skip: {}", e),
+        }
+        self.complete_job(job);
+    }
+
+    // Schedule or queue the jobs whose dependencies have all succeeded
+    fn release_ready(&mut self, completion_tx: &mpsc::Sender<Completion>) {
+        for job in std::mem::take(&mut self.ready) {
+            if overdue(&job, None, self.clock.now()).is_some() {
+                self.expire_pending_job(job);
+                continue;
+            }
+            match self.queue_index(&job.submission.queue) {
+                Some(q) => self.schedule_or_queue(job, q, completion_tx),
+                None => self.fail_and_complete_job(
+                    job,
+                    FailureInfo::pool(FailureClass::Invalid, "unknown queue: job never queued"),
+                ),
+            }
+        }
+    }
+
+    // Fail a job that never ran as its deadline passed while it waited
+    // NOTE: takes ownership of job
+    fn expire_pending_job(&mut self, mut job: Job) {
+        let now = self.clock.now();
+        let mut failure = overdue(&job, None, now)
+            .unwrap_or_else(|| FailureInfo::pool(FailureClass::Deadline, "deadline passed"));
+        job.log
+            .logf(LogLevel::ERROR, format_args!("job {}", failure.message));
+        println!(
+            "[JobPoolState]: job {}: {} (never ran)",
+            job.id, failure.message
+        );
+        let reason = format!("{}: job never ran", failure.message);
+        match job.transition(State::DEADLINE_MISSED, &reason, now, &self.events) {
+            Ok(()) => {
+                failure.message = reason;
+                job.result = failure.message.clone();
+                job.failure = Some(failure);
+            }
+            Err(e) => println!("[JobPoolState]: deadline: {}", e),
+        }
+        self.complete_job(job);
+    }
+
+    // Hand a finished job back to the run loop
+    // If the loop is gone (a send only fails then, for good) the job can't
+    // be reclaimed: it is settled in place (failed, unless already
+    // terminal) and an internal error raised
+    fn report_completion(
+        completion_tx: &mpsc::Sender<Completion>,
+        job_arc: &Arc<std::sync::Mutex<Job>>,
+        clock: &Arc<dyn Clock>,
+        events: &EventBus,
+    ) {
+        let completion = {
+            let job = job_arc.lock().unwrap_or_else(|e| e.into_inner());
+            Completion {
+                job_id: job.id,
+                job: job.clone(),
+            }
+        };
+        if completion_tx.blocking_send(completion).is_ok() {
+            return;
+        }
+
+        let mut job = job_arc.lock().unwrap_or_else(|e| e.into_inner());
+        let message = "completion lost, run loop gone".to_string();
+        JobPoolState::settle_lost(&mut job, &message, clock, events);
+        events.emit(EventKind::InternalError {
+            job_id: Some(job.id),
+            message,
+        });
+    }
+
+    // Settle a job whose execution the pool lost track of: failed, or
+    // cancelled if it was being cancelled; unchanged if it already ended
+    fn settle_lost(job: &mut Job, message: &str, clock: &Arc<dyn Clock>, events: &EventBus) {
+        println!("[JobPoolState]: job {}: {}", job.id, message);
+        job.log.logf(LogLevel::ERROR, format_args!("{}", message));
+        let to = match job.state {
+            State::CANCELLING => Some(State::CANCELLED),
+            ref state if !state.is_terminal() => Some(State::FAILED),
+            _ => None,
+        };
+        if let Some(to) = to {
+            if to == State::FAILED {
+                job.result = message.to_string();
+                job.failure = Some(FailureInfo::pool(FailureClass::Internal, message));
+            } else {
+                job.failure = Some(FailureInfo::pool(FailureClass::Cancelled, message));
+            }
+            if let Err(e) = job.transition(to, message, clock.now(), events) {
+                println!("[JobPoolState]: {}", e);
+            }
+        }
+    }
+
+    // Cross-check taken slots against their executions: a slot whose
+    // execution task has ended without its completion arriving, on two
+    // passes running, or that has no task at all, is reclaimed and its job
+    // settled as lost; slot bookkeeping for jobs in no slot is dropped
+    // NOTE: the pass of grace lets a completion sent just as its task
+    // ended arrive first
+    fn reconcile_slots(&mut self) {
+        let mut orphaned = Vec::new();
+        for (index, cell) in self.jobs.iter().enumerate() {
+            let Some(JobCell::Occupied(job_arc)) = cell else {
+                continue;
+            };
+            let id = job_arc.lock().unwrap_or_else(|e| e.into_inner()).id;
+            let vanished = match self.leases.get_mut(&id) {
+                Some(lease) if !lease.task.is_finished() => false,
+                Some(lease) => std::mem::replace(&mut lease.gone, true),
+                None => true,
+            };
+            if vanished {
+                orphaned.push((index, job_arc.clone()));
+            }
+        }
+        for (index, job_arc) in orphaned {
+            let mut job = job_arc.lock().unwrap_or_else(|e| e.into_inner()).clone();
+            let message = format!(
+                "execution vanished without reporting completion: slot {} reclaimed",
+                index
+            );
+            JobPoolState::settle_lost(&mut job, &message, &self.clock, &self.events);
+            self.tally.reclaimed += 1;
+            self.events.emit(EventKind::SlotReclaimed {
+                job_id: job.id,
+                slot: index,
+                message,
+            });
+            self.slots.insert(job.id, index);
+            self.finish_job(Completion {
+                job_id: job.id,
+                job,
+            });
+        }
+
+        let jobs = &self.jobs;
+        let in_slot = |id: &JobId, index: usize| match jobs.get(index) {
+            Some(Some(JobCell::Occupied(job_arc))) => {
+                job_arc.lock().unwrap_or_else(|e| e.into_inner()).id == *id
+            }
+            _ => false,
+        };
+        let stale: Vec<JobId> = self
+            .slots
+            .iter()
+            .filter(|&(id, &index)| !in_slot(id, index))
+            .map(|(id, _)| *id)
+            .chain(
+                self.leases
+                    .keys()
+                    .filter(|id| !self.slots.contains_key(id))
+                    .copied(),
+            )
+            .collect();
+        for id in stale {
+            println!(
+                "[JobPoolState]: job {}: in no slot, dropping its slot bookkeeping",
+                id
+            );
+            self.slots.remove(&id);
+            self.cancel_tokens.remove(&id);
+            self.budgets.remove(&id);
+            self.heartbeats.remove(&id);
+            self.leases.remove(&id);
+        }
+    }
+
+    // Handle a job submission
+    fn handle_new_job(
+        &mut self,
+        id: JobId,
+        job_submission: &JobSubmission,
+        timing: JobTiming,
+        completion_tx: &mpsc::Sender<Completion>,
+    ) {
         // Create the job
-        // if we have room, queue it; otherwise fail
-        let newjob = Job::new(job_submission);
+        // run it if its queue and the pool have room, else hold it
+        // in its queue's pending list; otherwise fail
+        let mut newjob = Job::new(id, job_submission, self.clock.now());
+        newjob.timing = timing;
+        self.tally.submitted += 1;
         println!("[JobPoolState]: job {}: created", newjob.id);
-        match self.find_slot() {
-            None => {
-                println!("[JobPoolState]: job {}: failed (pool full)", newjob.id);
-                self.fail_and_complete_job(newjob, "pool full: job never queued");
+        let Some(q) = self.queue_index(&newjob.submission.queue) else {
+            // NOTE: JobPool::submit rejects unknown queues up front
+            println!("[JobPoolState]: job {}: failed (unknown queue)", newjob.id);
+            self.fail_and_complete_job(
+                newjob,
+                FailureInfo::pool(FailureClass::Invalid, "unknown queue: job never queued"),
+            );
+            return;
+        };
+        if overdue(&newjob, None, self.clock.now()).is_some() {
+            self.expire_pending_job(newjob);
+            return;
+        }
+        if let Some(newjob) = self.hold_for_dependencies(newjob) {
+            self.schedule_or_queue(newjob, q, completion_tx);
+        }
+    }
+
+    // Hold a delayed job until it is due, else queue it on queue q
+    // NOTE: takes ownership of job
+    fn schedule_or_queue(
+        &mut self,
+        mut newjob: Job,
+        q: usize,
+        completion_tx: &mpsc::Sender<Completion>,
+    ) {
+        if let Some(due) = newjob.scheduled_for
+            && due > self.clock.now()
+        {
+            let reason = format!("delayed until {due}");
+            if let Err(e) =
+                newjob.transition(State::SCHEDULED, &reason, self.clock.now(), &self.events)
+            {
+                println!("[JobPoolState]: {}", e);
+                return;
             }
+            newjob
+                .log
+                .logf(LogLevel::INFO, format_args!("scheduled for {}", due));
+            println!("[JobPoolState]: job {}: scheduled for {}", newjob.id, due);
+            self.scheduled.insert((due, newjob.id), newjob);
+            return;
+        }
+        self.queue_job(newjob, q, completion_tx);
+    }
+
+    // Queue a job on queue q: run it if its queue and the pool have room,
+    // else hold it in its queue's pending list; otherwise fail
+    // NOTE: takes ownership of job
+    fn queue_job(&mut self, mut newjob: Job, q: usize, completion_tx: &mpsc::Sender<Completion>) {
+        let reason = format!("queued on '{}'", newjob.submission.queue);
+        if let Err(e) = newjob.transition(State::QUEUED, &reason, self.clock.now(), &self.events) {
+            println!("[JobPoolState]: {}", e);
+            return;
+        }
+        newjob.log.logf(
+            LogLevel::INFO,
+            format_args!(
+                "queued on '{}' at {}",
+                newjob.submission.queue,
+                self.clock.now()
+            ),
+        );
+
+        // NOTE: a paused queue, an unhealthy downstream or an empty rate
+        // limit bucket holds jobs even past their queue's pending limit
+        let paused = self.pauses.paused(self.queues[q].name()).is_some();
+        let gated = self
+            .held_types()
+            .contains_key(newjob.submission.kind.name());
+        // at its cap, it may steal a slot if nothing of its own waits first
+        let steals =
+            self.queues[q].steals() && self.queues[q].pending.is_empty() && self.steal_budget() > 0;
+        let runnable = (self.queues[q].can_run() || steals) && !paused && !gated;
+        let slot = if runnable { self.find_slot() } else { None };
+        // a preempting job waits at the front of its queue for the slot its
+        // victim frees
+        if slot.is_none() && runnable && newjob.submission.preempt && self.preempt_for(&newjob) {
+            self.queues[q].enqueue(newjob);
+            return;
+        }
+        match slot {
             Some(i) => {
                 println!("[JobPoolState]: queueing job {}: index {}", newjob.id, i);
-                self.run_job(newjob, i, completion_tx);
+                self.start_job(newjob, q, i, completion_tx);
+            }
+            None if paused || gated || self.queues[q].can_pend() => {
+                println!(
+                    "[JobPoolState]: job {}: pending on '{}'",
+                    newjob.id, newjob.submission.queue
+                );
+                self.queues[q].enqueue(newjob);
+            }
+            None if self.queues[q].drops_for(newjob.submission.priority) => {
+                let oldest = self.queues[q].drop_oldest().unwrap();
+                println!(
+                    "[JobPoolState]: job {}: dropped for job {} (pool full)",
+                    oldest.id, newjob.id
+                );
+                self.rejected_since_sample += 1;
+                self.fail_and_complete_job(
+                    oldest,
+                    FailureInfo::pool(FailureClass::PoolFull, "pool full: dropped for a newer job"),
+                );
+                self.queues[q].enqueue(newjob);
+            }
+            None => {
+                println!("[JobPoolState]: job {}: failed (pool full)", newjob.id);
+                self.rejected_since_sample += 1;
+                self.fail_and_complete_job(
+                    newjob,
+                    FailureInfo::pool(FailureClass::PoolFull, "pool full: job never queued"),
+                );
+            }
+        }
+    }
+
+    // Stop the lowest priority running job (of those, the one started last)
+    // of a type that stops early when asked, for a critical job: its
+    // execution is asked to stop, and it is requeued once it returns.
+    // false: no such job runs below critical
+    fn preempt_for(&mut self, job: &Job) -> bool {
+        if job.submission.priority != Priority::Critical {
+            return false;
+        }
+        let info = self.executor.info();
+        let victim = self
+            .jobs
+            .iter()
+            .flatten()
+            .filter_map(|cell| match cell {
+                JobCell::Occupied(job_arc) => Some(job_arc),
+                JobCell::Empty => None,
+            })
+            .filter(|job_arc| {
+                let running = job_arc.lock().unwrap();
+                matches!(running.state, State::RUNNING | State::STUCK)
+                    && running.submission.priority < Priority::Critical
+                    && info.supports(running.submission.kind.name(), "cancel")
+            })
+            .min_by_key(|job_arc| {
+                let running = job_arc.lock().unwrap();
+                (
+                    running.submission.priority,
+                    std::cmp::Reverse(running.started_at),
+                )
+            })
+            .cloned();
+        let Some(victim) = victim else {
+            return false;
+        };
+        let mut victim = victim.lock().unwrap();
+        let reason = format!("preempted for job {}", job.id);
+        if let Err(e) =
+            victim.transition(State::CANCELLING, &reason, self.clock.now(), &self.events)
+        {
+            println!("[JobPoolState]: preempt: {}", e);
+            return false;
+        }
+        victim.preempted_by = Some(job.id);
+        victim.log.logf(LogLevel::INFO, format_args!("{reason}"));
+        if let Some(cancel) = self.cancel_tokens.get(&victim.id) {
+            cancel.cancel();
+        }
+        println!("[JobPoolState]: job {}: {}", victim.id, reason);
+        true
+    }
+
+    fn finish_job(&mut self, completion: Completion) {
+        println!("[JobPoolState]: job {}: finishing", completion.job_id);
+        // reclaim the job's slot; the finished job comes with the completion
+        let Some(index) = self.slots.remove(&completion.job_id) else {
+            println!("[JobPoolState]: job {}: not in a slot", completion.job_id);
+            return;
+        };
+        self.jobs[index] = Some(JobCell::Empty);
+        self.cancel_tokens.remove(&completion.job_id);
+        self.heartbeats.remove(&completion.job_id);
+        self.leases.remove(&completion.job_id);
+        let job = completion.job;
+        if let Some(latency) = job
+            .started_at
+            .and_then(|t| (t - job.created_at).to_std().ok())
+        {
+            self.latency_since_sample = self.latency_since_sample.max(latency);
+        }
+        let q = self.queue_index(&job.submission.queue);
+        if let Some(q) = q {
+            self.queues[q].running -= 1;
+        }
+        // preempted: back on its queue, to run again
+        if let (State::QUEUED, Some(q)) = (&job.state, q) {
+            println!("[JobPoolState]: job {}: requeued", job.id);
+            self.queues[q].requeue(job);
+            return;
+        }
+        // returned, but waiting on its children
+        if job.submission.wait_for_children && matches!(job.state, State::RUNNING | State::STUCK) {
+            println!("[JobPoolState]: job {}: waiting on its children", job.id);
+            let id = job.id;
+            self.families.entry(id).or_default().parent = Some(job);
+            self.settle_parent(id);
+            return;
+        }
+        self.complete_job(job);
+    }
+
+    // Note a child finished; its parent, if unfinished, hears of it
+    fn child_finished(&mut self, parent: JobId, child: &Job) {
+        let waiting = self
+            .families
+            .get(&parent)
+            .is_some_and(|family| family.parent.is_some());
+        if !waiting && !self.slots.contains_key(&parent) {
+            return;
+        }
+        let family = self.families.entry(parent).or_default();
+        family.finished.insert(child.id);
+        if child.state != State::SUCCEEDED && family.failed.is_none() {
+            family.failed = Some((child.id, child.state.clone()));
+        }
+        self.settle_parent(parent);
+    }
+
+    // Finish a parent waiting on its children once they all have: it
+    // succeeds if they all did, else fails
+    fn settle_parent(&mut self, id: JobId) {
+        let Some(family) = self.families.get(&id) else {
+            return;
+        };
+        let Some(parent) = &family.parent else {
+            return;
+        };
+        if !parent
+            .children
+            .iter()
+            .all(|child| family.finished.contains(child))
+        {
+            return;
+        }
+        let family = self.families.remove(&id).unwrap();
+        let mut parent = family.parent.unwrap();
+        let now = self.clock.now();
+        match family.failed {
+            None => {
+                if let Err(e) =
+                    parent.transition(State::SUCCEEDED, "children finished", now, &self.events)
+                {
+                    println!("[JobPoolState]: finish: {}", e);
+                }
+                parent
+                    .log
+                    .logf(LogLevel::INFO, format_args!("children finished"));
+                self.complete_job(parent);
+            }
+            Some((child, state)) => {
+                let failure = FailureInfo::pool(
+                    FailureClass::Dependency,
+                    &format!("child job {child} ended {state}"),
+                );
+                parent
+                    .log
+                    .logf(LogLevel::ERROR, format_args!("job {}", failure.message));
+                self.fail_and_complete_job(parent, failure);
+            }
+        }
+    }
+
+    // Retain a job that reached a terminal state, queueing its callback
+    // and any notifications
+    fn complete_job(&mut self, mut job: Job) {
+        job.timing.completed();
+        self.budgets.remove(&job.id);
+        if let Some(key) = &job.submission.unique_key {
+            self.unique_keys.release(key, job.id);
+        }
+        job.cost = self.cost_model.cost(&job);
+        if job.cost.is_some() {
+            job.revision += 1;
+        }
+        self.result_cache.insert(&job, self.clock.now());
+        self.notifier.notify(&job);
+        if let Some(url) = job.callback.as_ref().map(|c| c.url.clone()) {
+            let delivery = serde_json::to_vec(&job)
+                .map_err(|e| format!("job record: {e}"))
+                .map(|body| Delivery::new(job.id, &url, body))
+                .and_then(|delivery| self.webhooks.enqueue(delivery));
+            if let Err(error) = delivery {
+                println!("[JobPoolState]: job {}: callback failed: {}", job.id, error);
+                let mut status = CallbackStatus::new(&url);
+                status.state = DeliveryState::Failed;
+                status.last_error = Some(error);
+                job.callback = Some(status);
+                job.timing.notified();
+            }
+        }
+        let (id, state) = (job.id, job.state.clone());
+        // a parent that didn't wait forgets its children's outcomes
+        self.families.remove(&id);
+        if let Some(parent) = job.submission.parent_id {
+            self.child_finished(parent, &job);
+        }
+        self.map_job_finished(&job);
+        if let Some(follow_up) = FollowUp::of(&job) {
+            job.log
+                .logf(LogLevel::INFO, format_args!("submitting its follow-up"));
+            // the pool may be shutting down
+            let _ = self.follow_tx.send(follow_up);
+        }
+        self.breakers.record(&job);
+        self.tally.add(&job);
+        if let (Some(started), Some(finished)) = (job.started_at, job.finished_at)
+            && let Ok(ran) = (finished - started).to_std()
+        {
+            self.durations.record(job.submission.kind.name(), ran);
+        }
+        self.completed.push(job);
+        self.settle_dependents(id, &state);
+    }
+
+    // Keep a map child's result for the map's reducer; once the reducer
+    // has finished, they are dropped
+    fn map_job_finished(&mut self, job: &Job) {
+        let Some(run) = job
+            .submission
+            .group_id
+            .as_ref()
+            .and_then(|map_id| self.maps.get_mut(map_id))
+        else {
+            return;
+        };
+        match job.submission.map_index {
+            Some(index) if job.state == State::SUCCEEDED => {
+                if let Some(result) = run.results.get_mut(index) {
+                    *result = job.result.clone();
+                }
+            }
+            None if run.reducer == Some(job.id) => run.results = Vec::new(),
+            _ => {}
+        }
+    }
+
+    // The results a map's reducer reduces; empty for any other job
+    fn map_results(&self, job: &Job) -> Vec<String> {
+        job.submission
+            .group_id
+            .as_ref()
+            .and_then(|map_id| self.maps.get(map_id))
+            .filter(|run| run.reducer == Some(job.id))
+            .map(|run| run.results.clone())
+            .unwrap_or_default()
+    }
+
+    // Pin or unpin a finished job; pinned jobs are never evicted from
+    // history, and each tenant may pin up to pin_quota of them
+    fn pin_job(&mut self, id: JobId, pin: bool) -> Result<Job, ApiError> {
+        let Some(job) = self.completed.iter().rev().find(|job| job.id == id) else {
+            return Err(match self.find_job(id) {
+                Some(job) => ApiError::Conflict(format!(
+                    "job {id} is {}: only finished jobs can be pinned",
+                    job.state
+                )),
+                None => ApiError::NotFound(format!("job {id}")),
+            });
+        };
+        if pin && !job.pinned {
+            let tenant = usage::tenant(job);
+            let pins = self
+                .completed
+                .iter()
+                .filter(|other| other.pinned && usage::tenant(other) == tenant)
+                .count();
+            if pins >= self.pin_quota {
+                let owner = tenant.map_or("jobs without a tenant".to_string(), |t| {
+                    format!("tenant '{t}'")
+                });
+                return Err(ApiError::Conflict(format!(
+                    "pin quota reached: {owner} already has {pins} pinned"
+                )));
+            }
+        }
+        // NOTE: recent jobs are at the end
+        let job = self
+            .completed
+            .iter_mut()
+            .rev()
+            .find(|job| job.id == id)
+            .unwrap();
+        if job.pinned != pin {
+            job.pinned = pin;
+            job.revision += 1;
+            println!(
+                "[JobPoolState]: job {}: {}",
+                id,
+                if pin { "pinned" } else { "unpinned" }
+            );
+        }
+        Ok(job.clone())
+    }
+
+    // Record a callback delivery status reported by the webhook worker
+    fn update_callback(&mut self, job_id: JobId, status: CallbackStatus) {
+        // NOTE: recent jobs are at the end
+        match self.completed.iter_mut().rev().find(|job| job.id == job_id) {
+            Some(job) => {
+                if matches!(
+                    status.state,
+                    DeliveryState::Delivered | DeliveryState::Failed
+                ) {
+                    job.timing.notified();
+                }
+                job.callback = Some(status);
+                job.revision += 1;
+            }
+            None => println!(
+                "[JobPoolState]: job {}: callback status for unknown job",
+                job_id
+            ),
+        }
+    }
+
+    // Job types whose jobs are held pending, each with since when: those
+    // whose downstream is unhealthy, and those rate limited with no token
+    // left (since now)
+    fn held_types(&mut self) -> BTreeMap<String, DateTime<Utc>> {
+        let mut held = self.health.unhealthy();
+        let now = self.clock.now();
+        for job_type in self.rate_limits.empty(now) {
+            held.entry(job_type).or_insert(now);
+        }
+        held
+    }
+
+    // Move pending jobs into free slots
+    // Queues are served in configured order, each up to its concurrency cap,
+    // then those stealing idle queues' capacity beyond theirs
+    // Jobs of types whose downstream is unhealthy, or out of rate limit
+    // tokens, stay pending
+    fn dispatch_pending(&mut self, completion_tx: &mpsc::Sender<Completion>) {
+        loop {
+            let held = self.held_types();
+            let next = self.next_queue(&held).or_else(|| self.next_steal(&held));
+            let Some((q, at)) = next else {
+                return;
+            };
+            let Some(i) = self.find_slot() else {
+                // pool full
+                return;
+            };
+            let job = self.queues[q].pending.remove(at).unwrap();
+            // the watchdog may not have got to it yet
+            if overdue(&job, None, self.clock.now()).is_some() {
+                self.expire_pending_job(job);
+                continue;
+            }
+            println!("[JobPoolState]: dispatching job {}: index {}", job.id, i);
+            self.start_job(job, q, i, completion_tx);
+        }
+    }
+
+    // Where in queue q the job to start next is, skipping jobs of gated
+    // types: the front job, or with earliest-deadline-first dispatch, of
+    // its highest priority startable jobs the one due soonest, if any has a
+    // deadline; or with shortest-first dispatch and the queue deep enough,
+    // of those the one whose type is expected to run shortest, oldest
+    // first among equals; a type that hasn't run yet is expected to be
+    // shortest, so it gets measured.
+    // None: nothing on q can start
+    fn pending_to_start(&self, q: usize, gated: &BTreeMap<String, DateTime<Utc>>) -> Option<usize> {
+        let pending = &self.queues[q].pending;
+        let mut startable = pending
+            .iter()
+            .enumerate()
+            .filter(|(_, job)| !gated.contains_key(job.submission.kind.name()));
+        if self.earliest_deadline_first {
+            let mut top = startable.clone().peekable();
+            let priority = top.peek()?.1.submission.priority;
+            let due = top
+                .take_while(|(_, job)| job.submission.priority == priority)
+                .filter_map(|(at, job)| Some((job.submission.deadline?, at)))
+                .min();
+            if let Some((_, at)) = due {
+                return Some(at);
+            }
+        }
+        let config = match self.shortest_first {
+            Some(config) if pending.len() >= config.depth => config,
+            _ => return startable.next().map(|(at, _)| at),
+        };
+        let mut startable = startable.peekable();
+        let priority = startable.peek()?.1.submission.priority;
+        let mut expected: HashMap<&str, Duration> = HashMap::new();
+        startable
+            .take_while(|(_, job)| job.submission.priority == priority)
+            .min_by_key(|(_, job)| {
+                let job_type = job.submission.kind.name();
+                *expected.entry(job_type).or_insert_with(|| {
+                    self.durations
+                        .percentile(job_type, config.percentile)
+                        .unwrap_or_default()
+                })
+            })
+            .map(|(at, _)| at)
+    }
+
+    // The queue whose job should take the next free slot, and where in it
+    // that job is: of the queues that may run one, the one with the highest
+    // priority startable job waiting, earlier configured queues winning ties
+    // (with earliest-deadline-first dispatch, the job due soonest wins
+    // first; jobs without a deadline come after those with one)
+    fn next_queue(&self, gated: &BTreeMap<String, DateTime<Utc>>) -> Option<(usize, usize)> {
+        (0..self.queues.len())
+            .filter(|&q| self.queues[q].can_run())
+            .filter(|&q| self.pauses.paused(self.queues[q].name()).is_none())
+            .filter_map(|q| {
+                let at = self.pending_to_start(q, gated)?;
+                let submission = &self.queues[q].pending[at].submission;
+                let due = submission
+                    .deadline
+                    .filter(|_| self.earliest_deadline_first)
+                    .unwrap_or(DateTime::<Utc>::MAX_UTC);
+                Some((submission.priority, std::cmp::Reverse(due), q, at))
+            })
+            .max_by_key(|&(priority, due, q, _)| (priority, due, std::cmp::Reverse(q)))
+            .map(|(_, _, q, at)| (q, at))
+    }
+
+    // Slots queues may still steal: idle capacity of capped queues with
+    // nothing waiting, less what is already stolen
+    fn steal_budget(&self) -> usize {
+        let idle: usize = self.queues.iter().map(JobQueue::idle).sum();
+        let stolen: usize = self.queues.iter().map(JobQueue::stolen).sum();
+        idle.saturating_sub(stolen)
+    }
+
+    // The stealing queue whose job should take the next free slot, and
+    // where in it that job is: of the queues at their cap with a steal
+    // weight and a startable job, the one holding the fewest stolen slots
+    // for its weight (the next slot included), earlier configured queues
+    // winning ties. None: none steals, or there is no capacity to steal
+    fn next_steal(&self, gated: &BTreeMap<String, DateTime<Utc>>) -> Option<(usize, usize)> {
+        if self.steal_budget() == 0 {
+            return None;
+        }
+        // (stolen + 1) / weight, compared without dividing
+        let share = |q: usize| {
+            let queue = &self.queues[q];
+            (
+                queue.stolen() as u64 + 1,
+                u64::from(queue.config.steal_weight),
+            )
+        };
+        (0..self.queues.len())
+            .filter(|&q| self.queues[q].steals())
+            .filter(|&q| self.pauses.paused(self.queues[q].name()).is_none())
+            .filter_map(|q| Some((q, self.pending_to_start(q, gated)?)))
+            .min_by(|&(a, _), &(b, _)| {
+                let ((stolen_a, weight_a), (stolen_b, weight_b)) = (share(a), share(b));
+                (stolen_a * weight_b)
+                    .cmp(&(stolen_b * weight_a))
+                    .then(a.cmp(&b))
+            })
+    }
+
+    // Note that a client waits on an unfinished job until by, extending
+    // its budget; false: it is expected to finish after by, or so late the
+    // time can't be represented
+    fn waited_until(&mut self, job: &Job, by: DateTime<Utc>) -> bool {
+        self.budgets.entry(job.id).or_default().extend_to(by);
+        let expected = job.started_at.zip(self.estimate(&job.submission, None));
+        match expected {
+            Some((started, estimate)) => i64::try_from(estimate.duration_ms)
+                .ok()
+                .and_then(TimeDelta::try_milliseconds)
+                .and_then(|duration| started.checked_add_signed(duration))
+                .is_some_and(|ends| ends <= by),
+            // not started, or no estimate: it may yet make it
+            None => true,
+        }
+    }
+
+    // Call f with every job: running, then pending, then completed
+    fn for_each_job(&self, mut f: impl FnMut(&Job)) -> Result<(), ApiError> {
+        for cell in self.jobs.iter().flatten() {
+            if let JobCell::Occupied(job_arc) = cell {
+                let job = job_arc
+                    .lock()
+                    .map_err(|_| ApiError::InternalError("failed to lock job".to_string()))?;
+                f(&job);
+            }
+        }
+        self.waiting().chain(self.completed.iter()).for_each(f);
+        Ok(())
+    }
+
+    // Find a job anywhere in the pool: running, pending, or completed
+    fn find_job(&self, id: JobId) -> Option<Job> {
+        for cell in self.jobs.iter().flatten() {
+            if let JobCell::Occupied(job_arc) = cell {
+                let job = job_arc.lock().unwrap();
+                if job.id == id {
+                    return Some(job.clone());
+                }
+            }
+        }
+        self.waiting()
+            .chain(self.completed.iter().rev())
+            .find(|job| job.id == id)
+            .cloned()
+    }
+
+    // Count a group's jobs by state
+    // NOTE: scans every job; groups have no index of their own
+    fn group_status(&self, group_id: &str) -> Option<GroupStatus> {
+        let in_group = |job: &Job| job.submission.group_id.as_deref() == Some(group_id);
+        let mut status = GroupStatus::new(group_id);
+        let mut count = |job: &Job| {
+            if in_group(job) {
+                status.count(&job.state);
+            }
+        };
+        for cell in self.jobs.iter().flatten() {
+            if let JobCell::Occupied(job_arc) = cell {
+                count(&job_arc.lock().unwrap());
             }
         }
+        self.waiting().chain(self.completed.iter()).for_each(count);
+        (status.total > 0).then(|| status.finish())
+    }
+
+    // A group's log lines, job by job in creation order; None: no such group
+    // NOTE: scans every job, as group_status does
+    fn group_logs(&self, group_id: &str) -> Result<Option<Vec<GroupLogLine>>, ApiError> {
+        let mut logs = Vec::new();
+        self.for_each_job(|job| {
+            if job.submission.group_id.as_deref() == Some(group_id) {
+                let lines: Vec<_> = job
+                    .log
+                    .lines()
+                    .into_iter()
+                    .map(|(at, line)| GroupLogLine {
+                        job_id: job.id,
+                        at,
+                        line: line.to_string(),
+                    })
+                    .collect();
+                logs.push((job.created_at, job.id, lines));
+            }
+        })?;
+        if logs.is_empty() {
+            return Ok(None);
+        }
+        logs.sort_by_key(|(created_at, id, _)| (*created_at, *id));
+        Ok(Some(
+            logs.into_iter().flat_map(|(_, _, lines)| lines).collect(),
+        ))
+    }
+
+    // A map's progress and the results of its finished children
+    // NOTE: children not yet seen by the pool count as queued
+    fn map_status(&self, map_id: &str) -> Option<MapStatus> {
+        let run = self.maps.get(map_id)?;
+        let total = run.total + usize::from(run.reducer.is_some());
+        let mut progress = self
+            .group_status(map_id)
+            .unwrap_or_else(|| GroupStatus::new(map_id));
+        progress.queued += total.saturating_sub(progress.total);
+        progress.total = total;
+
+        let mut items = Vec::new();
+        let mut result = None;
+        let mut collect = |job: &Job| {
+            if job.submission.group_id.as_deref() != Some(map_id) {
+                return;
+            }
+            if let Some(index) = job.submission.map_index {
+                items.push(MapItem {
+                    index,
+                    job_id: job.id,
+                    state: job.state.clone(),
+                    result: job.state.is_terminal().then(|| job.result.clone()),
+                });
+            } else if run.reducer == Some(job.id) && job.state.is_terminal() {
+                result = Some(job.result.clone());
+            }
+        };
+        for cell in self.jobs.iter().flatten() {
+            if let JobCell::Occupied(job_arc) = cell {
+                collect(&job_arc.lock().unwrap());
+            }
+        }
+        self.waiting()
+            .chain(self.completed.iter())
+            .for_each(collect);
+        items.sort_by_key(|item| item.index);
+
+        Some(MapStatus {
+            progress: progress.finish(),
+            items,
+            reducer: run.reducer,
+            result,
+        })
+    }
+
+    // Cancel a group's jobs; returns how many were cancelled outright, and
+    // how many are cancelling
+    // NOTE: running jobs can't be interrupted: they are marked CANCELLING
+    // and end CANCELLED when their execution returns
+    fn cancel_group(&mut self, group_id: &str) -> (usize, usize) {
+        let (cancelled, cancelling) = self.cancel_matching(
+            |job| job.submission.group_id.as_deref() == Some(group_id),
+            "group cancelled",
+        );
+        (cancelled.len(), cancelling.len())
+    }
+
+    // Cancel every queued or running job that matches: jobs not yet
+    // started end cancelled, running ones are marked cancelling
+    // Returns the ids of each
+    fn cancel_matching(
+        &mut self,
+        matches: impl Fn(&Job) -> bool,
+        why: &str,
+    ) -> (Vec<JobId>, Vec<JobId>) {
+        let now = self.clock.now();
+        let reason = format!("cancelled: {why} before job ran");
+        let (mut cancelled, mut cancelling) = (Vec::new(), Vec::new());
+        for cell in self.jobs.iter().flatten() {
+            let JobCell::Occupied(job_arc) = cell else {
+                continue;
+            };
+            let mut job = job_arc.lock().unwrap();
+            if !matches(&job) {
+                continue;
+            }
+            // being preempted: it ends cancelled, rather than requeued
+            if job.preempted_by.take().is_some() {
+                job.log
+                    .logf(LogLevel::INFO, format_args!("cancelling: {}", why));
+                cancelling.push(job.id);
+                continue;
+            }
+            let to = match job.state {
+                // in a slot but not started: the execution thread hands
+                // the slot back when it finds the job cancelled
+                State::QUEUED => State::CANCELLED,
+                State::RUNNING | State::STUCK => State::CANCELLING,
+                _ => continue,
+            };
+            if job.transition(to, why, now, &self.events).is_err() {
+                continue;
+            }
+            if job.state == State::CANCELLED {
+                job.result = reason.clone();
+                job.failure = Some(FailureInfo::pool(FailureClass::Cancelled, &reason));
+                cancelled.push(job.id);
+            } else {
+                job.log
+                    .logf(LogLevel::INFO, format_args!("cancelling: {}", why));
+                // ask the execution to stop early, if it can
+                if let Some(cancel) = self.cancel_tokens.get(&job.id) {
+                    cancel.cancel();
+                }
+                cancelling.push(job.id);
+            }
+        }
+
+        let mut pending = Vec::new();
+        for q in &mut self.queues {
+            let (matched, rest): (VecDeque<Job>, VecDeque<Job>) =
+                q.pending.drain(..).partition(&matches);
+            q.pending = rest;
+            pending.extend(matched);
+        }
+        let (matched, rest): (BTreeMap<_, _>, BTreeMap<_, _>) = std::mem::take(&mut self.scheduled)
+            .into_iter()
+            .partition(|(_, job)| matches(job));
+        self.scheduled = rest;
+        pending.extend(matched.into_values());
+        let (matched, rest): (BTreeMap<_, _>, BTreeMap<_, _>) = std::mem::take(&mut self.blocked)
+            .into_iter()
+            .partition(|(_, blocked)| matches(&blocked.job));
+        self.blocked = rest;
+        pending.extend(matched.into_values().map(|blocked| blocked.job));
+        let (matched, rest) = std::mem::take(&mut self.ready)
+            .into_iter()
+            .partition(&matches);
+        self.ready = rest;
+        pending.extend(matched);
+        for job in pending {
+            println!("[JobPoolState]: job {}: cancelled, {}", job.id, why);
+            cancelled.push(job.id);
+            self.cancel_and_complete_job(job, &reason);
+        }
+
+        // parents waiting on their children
+        let parents: Vec<JobId> = self
+            .families
+            .iter()
+            .filter(|(_, family)| family.parent.as_ref().is_some_and(&matches))
+            .map(|(&id, _)| id)
+            .collect();
+        let reason = format!("cancelled: {why} while waiting on its children");
+        for id in parents {
+            let Some(mut parent) = self.families.remove(&id).and_then(|f| f.parent) else {
+                continue;
+            };
+            if let Err(e) = parent.transition(State::CANCELLING, why, now, &self.events) {
+                println!("[JobPoolState]: cancel: {}", e);
+            }
+            println!("[JobPoolState]: job {}: cancelled, {}", id, why);
+            cancelled.push(id);
+            self.cancel_and_complete_job(parent, &reason);
+        }
+        (cancelled, cancelling)
+    }
+
+    fn queue_index(&self, name: &str) -> Option<usize> {
+        self.queues.iter().position(|q| q.name() == name)
+    }
+
+    // Jobs waiting for a slot, across all queues
+    fn pending_jobs(&self) -> usize {
+        self.queues.iter().map(|q| q.pending.len()).sum()
+    }
+
+    // Running jobs flagged stuck
+    fn stuck_jobs(&self) -> usize {
+        self.jobs
+            .iter()
+            .flatten()
+            .filter(|cell| match cell {
+                JobCell::Occupied(job_arc) => job_arc.lock().unwrap().state == State::STUCK,
+                JobCell::Empty => false,
+            })
+            .count()
+    }
+
+    // What a submission to queue q would meet if it arrived now
+    // NOTE: the wait estimate assumes the jobs ahead run as long as the
+    // queue's recent jobs did, as many at a time as the queue allows
+    fn dry_run(&self, q: usize, priority: Priority) -> DryRunResult {
+        let queue = &self.queues[q];
+        let position = queue.position_for(priority);
+        let mut result = DryRunResult {
+            queue: queue.name().to_string(),
+            placement: Placement::Run,
+            reason: None,
+            position,
+            estimated_wait_ms: Some(0),
+            estimate: None,
+        };
+        let paused = self.pauses.paused(queue.name());
+        // a queue's pending jobs only wait when it has no room to run (or
+        // to steal)
+        let steals = queue.steals() && queue.pending.is_empty() && self.steal_budget() > 0;
+        if paused.is_none()
+            && (queue.can_run() || steals)
+            && position == 0
+            && self.busy_slots() < self.max_jobs
+        {
+            return result;
+        }
+        if let Some(pause) = paused {
+            result.placement = Placement::Pending;
+            result.reason = Some(format!("paused: {}", pause.reason));
+            result.estimated_wait_ms = None;
+            return result;
+        }
+        if !queue.can_pend() {
+            if !queue.drops_for(priority) {
+                result.placement = Placement::Rejected;
+                result.reason = Some("pool full: no room to run or pend".to_string());
+                result.estimated_wait_ms = None;
+                return result;
+            }
+            result.reason =
+                Some("pool full: the oldest lowest priority job would be dropped".to_string());
+            result.position = position.min(queue.pending.len() - 1);
+        }
+
+        result.placement = Placement::Pending;
+        result.estimated_wait_ms = self.average_run_time(queue.name()).map(|average| {
+            let parallel = queue
+                .config
+                .max_concurrency
+                .map_or(self.max_jobs, |max| max.min(self.max_jobs))
+                .max(1);
+            let rounds = result.position / parallel + 1;
+            (average * rounds as u32).as_millis() as u64
+        });
+        result
+    }
+
+    // How long a submission would run and what it would cost, completing
+    // after wait_ms; None: its executor can't say and its type hasn't run
+    fn estimate(&self, job: &JobSubmission, wait_ms: Option<u64>) -> Option<Estimate> {
+        let job_type = job.kind.name();
+        let (duration, source) = match self.executor.estimate(job) {
+            Some(duration) => (duration, EstimateSource::Executor),
+            None => (
+                self.durations.percentile(job_type, 50)?,
+                EstimateSource::History,
+            ),
+        };
+        let now = self.clock.now();
+        // None: too far off to represent
+        let completes_at = wait_ms.and_then(|wait| {
+            let wait = TimeDelta::try_milliseconds(i64::try_from(wait).ok()?)?;
+            let duration = TimeDelta::from_std(duration).ok()?;
+            now.checked_add_signed(wait)?.checked_add_signed(duration)
+        });
+        Some(Estimate {
+            duration_ms: u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+            cost: duration.as_secs_f64() * self.cost_model.weight(job_type),
+            source,
+            completes_at,
+        })
+    }
+
+    // What a tenant's jobs finished since since cost
+    fn tenant_cost(&self, tenant: &str, since: DateTime<Utc>) -> f64 {
+        let mut report = UsageReport::new(Some(since), None);
+        for job in self.completed.iter() {
+            report.add(job);
+        }
+        report.by_tenant.get(tenant).map_or(0.0, |usage| usage.cost)
+    }
+
+    // How long the queue's recent jobs ran, on average; None: no history
+    fn average_run_time(&self, queue: &str) -> Option<Duration> {
+        let run_times: Vec<Duration> = self
+            .completed
+            .iter()
+            .rev()
+            .filter(|job| job.submission.queue == queue)
+            .filter_map(|job| (job.finished_at? - job.started_at?).to_std().ok())
+            .take(WAIT_ESTIMATE_SAMPLE)
+            .collect();
+        (!run_times.is_empty()).then(|| run_times.iter().sum::<Duration>() / run_times.len() as u32)
+    }
+
+    // Pending jobs in the order dispatch will start them, each with an
+    // estimated start
+    // NOTE: plays dispatch forward, taking each job (running or to run) to
+    // last as long as its queue's recent jobs did; a start that depends on
+    // a queue with no history has no estimate. Paused queues' jobs come
+    // last, unordered between queues and without an estimate. Shortest-first
+    // dispatch isn't played: each queue's jobs are listed in priority order
+    fn dispatch_order(&self) -> Vec<QueuedJob> {
+        let now = self.clock.now();
+        let average: Vec<Option<Duration>> = self
+            .queues
+            .iter()
+            .map(|q| self.average_run_time(q.name()))
+            .collect();
+        let finish = |q: usize, start: DateTime<Utc>, known: bool| {
+            let run = average[q].unwrap_or_default();
+            let end = start + chrono::Duration::from_std(run).unwrap_or_default();
+            (end, known && average[q].is_some(), Some(q))
+        };
+
+        // slot events: (free at, estimate known, queue of the job leaving)
+        let mut events: Vec<(DateTime<Utc>, bool, Option<usize>)> = Vec::new();
+        let mut busy = 0;
+        for cell in self.jobs.iter().flatten() {
+            if let JobCell::Occupied(job_arc) = cell {
+                let job = job_arc.lock().unwrap();
+                if let Some(q) = self.queue_index(&job.submission.queue) {
+                    let (end, known, q) = finish(q, job.started_at.unwrap_or(now), true);
+                    events.push((end.max(now), known, q));
+                }
+                busy += 1;
+            }
+        }
+        events.extend((busy..self.max_jobs).map(|_| (now, true, None)));
+
+        let mut running: Vec<usize> = self.queues.iter().map(|q| q.running).collect();
+        let mut next: Vec<usize> = vec![0; self.queues.len()];
+        let paused: Vec<Option<&Pause>> = self
+            .queues
+            .iter()
+            .map(|q| self.pauses.paused(q.name()))
+            .collect();
+        let can_take = |q: usize, running: &[usize], next: &[usize]| {
+            paused[q].is_none()
+                && next[q] < self.queues[q].pending.len()
+                && self.queues[q]
+                    .config
+                    .max_concurrency
+                    .is_none_or(|max| running[q] < max)
+        };
+
+        let mut order = Vec::new();
+        // slots freed that no queue could use yet
+        let mut idle: Vec<(DateTime<Utc>, bool)> = Vec::new();
+        while !events.is_empty() {
+            // the earliest event next
+            let i = (0..events.len()).min_by_key(|&i| events[i].0).unwrap();
+            let (at, known, leaving) = events.swap_remove(i);
+            if let Some(q) = leaving {
+                running[q] = running[q].saturating_sub(1);
+                busy -= 1;
+            }
+            // a drained slot past max_jobs is never handed out again
+            if busy < self.max_jobs {
+                idle.push((at, known));
+            }
+            // as dispatch_pending: highest priority first, then queue order
+            while let Some(q) = (0..self.queues.len())
+                .filter(|&q| can_take(q, &running, &next))
+                .max_by_key(|&q| {
+                    let priority = self.queues[q].pending[next[q]].submission.priority;
+                    (priority, std::cmp::Reverse(q))
+                })
+            {
+                let Some((_, slot_known)) = idle.pop() else {
+                    break;
+                };
+                let job = &self.queues[q].pending[next[q]];
+                next[q] += 1;
+                running[q] += 1;
+                busy += 1;
+                order.push(QueuedJob::new(job, Some(at).filter(|_| slot_known)));
+                events.push(finish(q, at, slot_known));
+            }
+        }
+
+        for (q, queue) in self.queues.iter().enumerate() {
+            for job in queue.pending.iter().skip(next[q]) {
+                let mut queued = QueuedJob::new(job, None);
+                queued.paused = paused[q].map(|pause| pause.reason.clone());
+                order.push(queued);
+            }
+        }
+        for (position, queued) in order.iter_mut().enumerate() {
+            queued.position = position;
+        }
+        order
+    }
+
+    // Number of slots currently holding a job
+    fn busy_slots(&self) -> usize {
+        self.jobs
+            .iter()
+            .filter(|opt| !matches!(opt, Some(JobCell::Empty)))
+            .count()
+    }
+
+    // Jobs not yet finished, in slots or waiting, highest priority first
+    fn outstanding(&self) -> Vec<(JobId, Priority)> {
+        let mut out: Vec<(JobId, Priority)> = Vec::new();
+        for cell in self.jobs.iter().flatten() {
+            if let JobCell::Occupied(job_arc) = cell {
+                let job = job_arc.lock().unwrap();
+                if !job.state.is_terminal() {
+                    out.push((job.id, job.submission.priority));
+                }
+            }
+        }
+        out.extend(self.waiting().map(|job| (job.id, job.submission.priority)));
+        out.sort_by_key(|&(_, priority)| std::cmp::Reverse(priority));
+        out
+    }
+
+    // No job running, waiting on a queue, held for its dependencies or
+    // waiting on its children
+    fn idle(&self) -> bool {
+        self.busy_slots() == 0
+            && self.pending_jobs() == 0
+            && self.families.values().all(|f| f.parent.is_none())
+            && self.blocked.is_empty()
+            && self.ready.is_empty()
+    }
+
+    // Take a load sample, resetting the since-last-sample counters
+    // backlog: submissions waiting in the channel
+    fn sample(&mut self, backlog: usize) -> PoolSample {
+        let sample = PoolSample {
+            depth: backlog + self.pending_jobs() + self.rejected_since_sample,
+            busy: self.busy_slots(),
+            latency: self.latency_since_sample,
+        };
+        self.rejected_since_sample = 0;
+        self.latency_since_sample = Duration::ZERO;
+        sample
+    }
+}
+
+/**
+ * Controllers
+ * Optional controllers fed from the run loop's periodic pool sample
+ */
+struct Controllers {
+    interval: Duration,
+    autoscaler: Option<Autoscaler>,
+    shedder: Option<LoadShedder>,
+    // shed mode flag, shared with JobPool::submit
+    shedding: Arc<AtomicBool>,
+    events: EventBus,
+}
+
+impl Controllers {
+    fn enabled(&self) -> bool {
+        self.autoscaler.is_some() || self.shedder.is_some()
+    }
+
+    // Feed one sample to each controller and apply the results
+    fn apply(&mut self, p: &mut JobPoolState, sample: &PoolSample) {
+        if let Some(autoscaler) = self.autoscaler.as_mut()
+            && let Some(max_jobs) = autoscaler.observe(sample.depth, sample.busy, p.max_jobs)
+        {
+            println!(
+                "[JobPool]: autoscale: max_jobs {} -> {} (depth {})",
+                p.max_jobs, max_jobs, sample.depth
+            );
+            p.max_jobs = max_jobs;
+        }
+        if let Some(shedder) = self.shedder.as_mut() {
+            match shedder.observe(sample.depth, sample.latency) {
+                Some(true) => {
+                    self.shedding.store(true, Ordering::Relaxed);
+                    self.events.emit(EventKind::ShedModeEntered {
+                        depth: sample.depth,
+                        latency_ms: sample.latency.as_millis() as u64,
+                    });
+                }
+                Some(false) => {
+                    self.shedding.store(false, Ordering::Relaxed);
+                    self.events.emit(EventKind::ShedModeExited);
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+// Where a listing reads jobs from
+enum JobSource<'a> {
+    Live(tokio::sync::MutexGuard<'a, JobPoolState>),
+    Snapshot(Arc<PoolSnapshot>),
+}
+
+impl JobSource<'_> {
+    fn for_each_job(&self, f: impl FnMut(&Job)) -> Result<(), ApiError> {
+        match self {
+            JobSource::Live(p) => p.for_each_job(f),
+            JobSource::Snapshot(snapshot) => {
+                snapshot.jobs.iter().for_each(f);
+                Ok(())
+            }
+        }
+    }
+
+    // When the snapshot read was taken; None: the live pool
+    fn as_of(&self) -> Option<DateTime<Utc>> {
+        match self {
+            JobSource::Live(_) => None,
+            JobSource::Snapshot(snapshot) => Some(snapshot.taken_at),
+        }
+    }
+}
+
+/**
+ * WaitStatus
+ * A waited-for job as it stood when the wait ended
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WaitStatus {
+    pub id: JobId,
+    // None: not (yet) known to the pool
+    pub job: Option<Job>,
+}
+
+/**
+ * WaitResult
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WaitResult {
+    // the deadline passed before every job finished
+    pub timed_out: bool,
+    // in the order asked for
+    pub jobs: Vec<WaitStatus>,
+    // jobs expected to finish after the request deadline, ending the
+    // wait early
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub late: Vec<JobId>,
+}
+
+/**
+ * GroupState
+ * Overall state of a job group
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupState {
+    // some jobs haven't finished
+    Running,
+    // every job succeeded
+    Succeeded,
+    // every job finished, and at least one failed
+    Failed,
+    // every job finished, none failed, and at least one was cancelled
+    Cancelled,
+}
+
+/**
+ * GroupStatus
+ * Aggregate view of the jobs sharing a group id
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GroupStatus {
+    pub group_id: String,
+    pub state: GroupState,
+    pub total: usize,
+    // not yet running
+    pub queued: usize,
+    pub running: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub cancelled: usize,
+}
+
+impl GroupStatus {
+    fn new(group_id: &str) -> Self {
+        Self {
+            group_id: group_id.to_string(),
+            state: GroupState::Running,
+            total: 0,
+            queued: 0,
+            running: 0,
+            succeeded: 0,
+            failed: 0,
+            cancelled: 0,
+        }
+    }
+
+    fn count(&mut self, state: &State) {
+        self.total += 1;
+        match state {
+            State::INIT | State::WAITING | State::SCHEDULED | State::QUEUED => self.queued += 1,
+            // cancelling jobs are still executing
+            State::RUNNING | State::STUCK | State::CANCELLING => self.running += 1,
+            State::SUCCEEDED => self.succeeded += 1,
+            State::FAILED | State::TIMED_OUT | State::DEADLINE_MISSED => self.failed += 1,
+            // skipped jobs never ran, like cancelled ones
+            State::CANCELLED | State::SKIPPED => self.cancelled += 1,
+        }
+    }
+
+    // Derive the overall state from the counts
+    fn finish(mut self) -> Self {
+        self.state = if self.succeeded + self.failed + self.cancelled < self.total {
+            GroupState::Running
+        } else if self.failed > 0 {
+            GroupState::Failed
+        } else if self.cancelled > 0 {
+            GroupState::Cancelled
+        } else {
+            GroupState::Succeeded
+        };
+        self
+    }
+}
+
+/**
+ * MapItem
+ * One child of a map
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MapItem {
+    // position in the map's inputs
+    pub index: usize,
+    pub job_id: JobId,
+    pub state: State,
+    // None until the child finishes
+    pub result: Option<String>,
+}
+
+/**
+ * MapStatus
+ * A map's aggregate progress and its children, in input order
+ * NOTE: children not yet seen by the pool are counted but not listed
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MapStatus {
+    #[serde(flatten)]
+    pub progress: GroupStatus,
+    pub items: Vec<MapItem>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reducer: Option<JobId>,
+    // the reducer's result, once it finished
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+}
+
+/**
+ * JobAccepted
+ * A submission the pool took; the job is at /jobs/{id}
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JobAccepted {
+    pub id: JobId,
+}
+
+/**
+ * MapAccepted
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MapAccepted {
+    // also the children's group id
+    pub map_id: String,
+    // children, not counting the reducer
+    pub total: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reducer: Option<JobId>,
+}
+
+/**
+ * GroupCancelResult
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GroupCancelResult {
+    // jobs cancelled before they started
+    pub cancelled: usize,
+    // running jobs marked cancelling; they end cancelled when they return
+    pub cancelling: usize,
+    pub group: GroupStatus,
+}
+
+/**
+ * CancelFilter
+ * Which unfinished jobs a bulk cancel applies to; every criterion
+ * given must match, and at least one must be given
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CancelFilter {
+    // WAITING, SCHEDULED, QUEUED (including pending), RUNNING or STUCK
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<State>,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub job_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_before: Option<DateTime<Utc>>,
+    // labels the job must carry, each with the value given
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: Labels,
+}
+
+impl CancelFilter {
+    fn is_empty(&self) -> bool {
+        self.state.is_none()
+            && self.job_type.is_none()
+            && self.created_before.is_none()
+            && self.labels.is_empty()
+    }
+
+    fn matches(&self, job: &Job) -> bool {
+        self.state.as_ref().is_none_or(|state| job.state == *state)
+            && self
+                .job_type
+                .as_deref()
+                .is_none_or(|t| job.submission.kind.name() == t)
+            && self
+                .created_before
+                .is_none_or(|before| job.created_at < before)
+            && self
+                .labels
+                .iter()
+                .all(|(key, value)| job.submission.labels.get(key) == Some(value))
+    }
+}
+
+/**
+ * BulkCancelResult
+ * The jobs a bulk cancel affected
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BulkCancelResult {
+    // jobs cancelled before they started
+    pub cancelled: Vec<JobId>,
+    // running jobs marked cancelling; they end cancelled when they return
+    pub cancelling: Vec<JobId>,
+}
+
+/**
+ * QueueStatus
+ * Point-in-time view of one named queue
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueueStatus {
+    pub name: String,
+    pub running: usize,
+    pub pending: usize,
+    pub max_concurrency: Option<usize>,
+    pub pending_limit: usize,
+    pub overflow: PendingOverflow,
+    // holding its jobs pending: paused itself, or the pool is
+    pub paused: bool,
+    // share of idle queues' capacity it may steal; 0: none
+    #[serde(default)]
+    pub steal_weight: u32,
+    // its jobs running on stolen capacity, beyond max_concurrency
+    #[serde(default)]
+    pub stolen: usize,
+}
+
+/**
+ * DrainOptions
+ * How a shutdown drains the pool
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrainOptions {
+    // how long jobs get to finish before the rest are cancelled
+    pub deadline: Duration,
+    // jobs below this priority are cancelled when the drain starts
+    // None: every job gets its chance
+    pub preempt_below: Option<Priority>,
+}
+
+impl DrainOptions {
+    pub fn new(deadline: Duration) -> Self {
+        Self {
+            deadline,
+            preempt_below: None,
+        }
+    }
+}
+
+/**
+ * DrainOutcome
+ * What the drain did with a job
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DrainOutcome {
+    // left to end on its own (delayed jobs are cancelled by the pool)
+    Finished,
+    // cancelled as the drain started, for its low priority
+    Preempted,
+    // cancelled at the deadline
+    Cancelled,
+}
+
+/**
+ * DrainedJob
+ * One job that was outstanding when the drain started
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrainedJob {
+    pub id: JobId,
+    pub priority: Priority,
+    // where it ended up; None: no longer in history
+    pub state: Option<State>,
+    pub outcome: DrainOutcome,
+}
+
+/**
+ * ShutdownReport
+ * How a pool shutdown went
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShutdownReport {
+    // every queued and running job finished before the deadline
+    pub drained: bool,
+    // jobs cancelled at the deadline (queued or running then)
+    pub cancelled: Vec<JobId>,
+    // jobs outstanding when the drain started, highest priority first
+    pub jobs: Vec<DrainedJob>,
+}
+
+/**
+ * PoolStatus
+ * Point-in-time view of pool occupancy
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PoolStatus {
+    pub max_jobs: usize,
+    // occupied slots
+    pub busy: usize,
+    pub shedding: bool,
+    // paused by an operator: submissions are held pending, running jobs
+    // finish
+    pub paused: bool,
+    pub queues: Vec<QueueStatus>,
+}
+
+/**
+ * PoolMetrics
+ * Job counts and run times since startup, and history's memory use
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PoolMetrics {
+    pub total_submitted: u64,
+    pub running: usize,
+    // of those running, how many have a quiet heartbeat
+    pub stuck: usize,
+    // waiting on their queues
+    pub queued: usize,
+    pub succeeded: u64,
+    // failed or timed out
+    pub failed: u64,
+    pub avg_duration_ms: u64,
+    // jobs that didn't succeed (cancelled and skipped ones too), by
+    // failure class
+    pub failures: BTreeMap<FailureClass, u64>,
+    // slots reclaimed from executions that vanished without reporting
+    // their completion
+    #[serde(default)]
+    pub reclaimed_slots: u64,
+    // finished jobs by label ("key:value"), by how they ended
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, LabelCounts>,
+    // token bucket of each rate limited job type
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rate_limits: BTreeMap<String, BucketLevel>,
+    // bytes of execution log dropped by the log rate limit
+    #[serde(default)]
+    pub log_bytes_dropped: u64,
+    // run time percentiles of recent runs, by job type
+    pub durations: BTreeMap<String, DurationPercentiles>,
+    pub history: HistoryUsage,
+}
+
+/**
+ * Placement
+ * Where a submission would go
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Placement {
+    // straight into a slot
+    Run,
+    // onto its queue's pending list
+    Pending,
+    Rejected,
+}
+
+/**
+ * DryRunResult
+ * What submitting a job would do right now, without submitting it
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DryRunResult {
+    pub queue: String,
+    pub placement: Placement,
+    // why it would be rejected, or held while its queue is paused
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    // jobs ahead of it on its queue
+    pub position: usize,
+    // None: rejected, paused, or no recent jobs on the queue to estimate
+    // from
+    pub estimated_wait_ms: Option<u64>,
+    // how long it would run and what it would cost; None: no estimate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate: Option<Estimate>,
+}
+
+/**
+ * QueuedJob
+ * A pending job's place in line
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueuedJob {
+    // jobs that will start before it
+    pub position: usize,
+    pub id: JobId,
+    #[serde(rename = "type")]
+    pub job_type: String,
+    pub queue: String,
+    // the priority dispatch orders it by: highest first, then queue
+    // order, then arrival
+    // NOTE: this is the submitted priority; it doesn't age
+    pub effective_priority: Priority,
+    pub created_at: DateTime<Utc>,
+    // None: paused, or started after jobs with no run time history
+    pub estimated_start: Option<DateTime<Utc>>,
+    // why it is held, while its queue (or the pool) is paused
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paused: Option<String>,
+}
+
+impl QueuedJob {
+    fn new(job: &Job, estimated_start: Option<DateTime<Utc>>) -> Self {
+        Self {
+            position: 0,
+            id: job.id,
+            job_type: job.submission.kind.name().to_string(),
+            queue: job.submission.queue.clone(),
+            effective_priority: job.submission.priority,
+            created_at: job.created_at,
+            estimated_start,
+            paused: None,
+        }
+    }
+}
+
+/**
+ * JobPool
+ */
+pub struct JobPool {
+    pool: Arc<Mutex<JobPoolState>>,
+    // used by API to submit jobs to the pool
+    // carries the id assigned at submission
+    submission_tx: mpsc::Sender<(JobId, JobSubmission, JobTiming)>,
+    // what submit does when the submission channel is full
+    overflow_policy: OverflowPolicy,
+    // shed mode: set by the load shedder, checked on submission
+    shedding: Arc<AtomicBool>,
+    // while shedding, submissions below this priority are rejected
+    shed_below: Priority,
+    events: EventBus,
+    // names of the configured queues
+    queue_names: Vec<String>,
+    // the job types the executor runs; checked on submission
+    job_types: Vec<String>,
+    workflows: Workflows,
+    // payload schemas, checked on submission
+    schemas: Schemas,
+    schedules: Schedules,
+    // who may change any schedule or workflow
+    admins: Admins,
+    // checked on submission
+    env_profiles: EnvProfiles,
+    health: HealthGates,
+    breakers: CircuitBreakers,
+    // closed once the pool has shut down
+    resources: Resources,
+    // claimed on submission
+    unique_keys: UniqueKeys,
+    // lets operations outside the run loop (resume) dispatch held jobs
+    completion_tx: mpsc::Sender<Completion>,
+    // where pauses are saved; None: not persisted
+    pause_state: Option<PathBuf>,
+    // per job type defaults for omitted submission fields
+    defaults: JobDefaults,
+    tenants: TenantPolicies,
+    admission: AdmissionConfig,
+    // makes new jobs' ids
+    ids: Arc<dyn IdGenerator>,
+    // dates submissions as they are checked
+    clock: Arc<dyn Clock>,
+    // read snapshots for list and stats endpoints
+    snapshots: Snapshots,
+    // tells the run loop to stop taking submissions and wind down
+    shutdown_tx: watch::Sender<bool>,
+    // None: not started yet, or already shut down
+    run_loop: std::sync::Mutex<Option<JoinHandle<()>>>,
+}
+
+impl JobPool {
+    pub fn start(config: &PoolConfig) -> Arc<Self> {
+        println!("[JobPool]: start");
+
+        // message-passing channels
+        println!("[JobPool]: creating job messaging channels");
+        // channel for job submissions
+        let (submission_tx, mut submission_rx) = mpsc::channel(config.submission_queue_size);
+        // channel for job completions
+        let (completion_tx, mut completion_rx) = mpsc::channel::<Completion>(32);
+        // channel for callback delivery status from the webhook worker
+        let (callback_tx, mut callback_rx) = mpsc::channel::<(JobId, CallbackStatus)>(32);
+        // shutdown signal
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let webhooks = Webhooks::start(config.webhooks.clone(), config.clock.clone(), callback_tx);
+
+        // construct underlying pool state
+        println!("[JobPool]: create new pool");
+        let events = EventBus::new(config.clock.clone());
+        if let Some(path) = &config.event_log
+            && let Err(e) = EventLog::start(path, &events)
+        {
+            println!("[JobPool]: event log disabled: {}", e);
+        }
+        let notifier = Notifier::new(&config.notify, &webhooks);
+        // channel for the children executions spawn
+        let (spawn_tx, spawn_requests) = children::channel();
+        // channel for finished jobs' follow-ups
+        let (follow_tx, follow_ups) = chaining::channel();
+        let state = JobPoolState::new(
+            config,
+            events.clone(),
+            webhooks,
+            notifier,
+            spawn_tx,
+            follow_tx,
+        );
+        if let Err(e) = state.executor.register_resources(&state.resources) {
+            println!("[JobPool]: registering resources: {}", e);
+        }
+        let job_types = state.executor.info().job_types;
+        let resources = state.resources.clone();
+        let health = state.health.clone();
+        let breakers = state.breakers.clone();
+        let unique_keys = state.unique_keys.clone();
+        let pool = Arc::new(Mutex::new(state));
+        let shedding = Arc::new(AtomicBool::new(false));
+        // NOTE: private constructor pattern
+        let this = Arc::new(Self {
+            pool: pool.clone(),
+            submission_tx,
+            overflow_policy: config.overflow_policy,
+            shedding: shedding.clone(),
+            shed_below: config
+                .load_shed
+                .as_ref()
+                .map_or(Priority::Low, |c| c.shed_below),
+            events: events.clone(),
+            queue_names: config.queues.iter().map(|q| q.name.clone()).collect(),
+            workflows: Workflows::new(config.clock.clone(), job_types.clone()),
+            schemas: Schemas::new(job_types.clone()),
+            schedules: Schedules::new(config.clock.clone(), config.schedule_purge_after),
+            admins: config.admins.clone(),
+            env_profiles: config.env_profiles.clone(),
+            health,
+            breakers,
+            resources,
+            unique_keys,
+            completion_tx: completion_tx.clone(),
+            pause_state: config.pause_state.clone(),
+            defaults: config.defaults.clone(),
+            tenants: config.tenants.clone(),
+            admission: config.admission,
+            ids: config.ids.clone(),
+            clock: config.clock.clone(),
+            job_types,
+            snapshots: Snapshots::new(config.snapshot_interval),
+            shutdown_tx,
+            run_loop: std::sync::Mutex::new(None),
+        });
+
+        // Spawn the workflow driver
+        let workflows = this.workflows.clone();
+        let weak = Arc::downgrade(&this);
+        tokio::spawn(async move {
+            workflows.drive(weak).await;
+        });
+
+        // Spawn the child job submitter
+        let weak = Arc::downgrade(&this);
+        tokio::spawn(async move {
+            spawn_requests.drive(weak).await;
+        });
+
+        // Spawn the follow-up submitter
+        let weak = Arc::downgrade(&this);
+        tokio::spawn(async move {
+            follow_ups.drive(weak).await;
+        });
+
+        // Spawn the schedule driver
+        let schedules = this.schedules.clone();
+        let weak = Arc::downgrade(&this);
+        tokio::spawn(async move {
+            schedules.drive(weak).await;
+        });
+
+        // Spawn the health checker, if any job type is gated
+        if !this.health.is_empty() {
+            let health = this.health.clone();
+            let weak = Arc::downgrade(&this);
+            tokio::spawn(async move {
+                health.drive(weak).await;
+            });
+        }
+
+        // Spawn the snapshot taker, if snapshots are on
+        if this.snapshots.is_enabled() {
+            let snapshots = this.snapshots.clone();
+            let weak = Arc::downgrade(&this);
+            tokio::spawn(async move {
+                snapshots.drive(weak).await;
+            });
+        }
+
+        // Spawn the async loop that handles job submissions and completions
+        println!("[JobPool]: spawning job handling loop");
+        let pool_clone = pool.clone();
+        let controllers = Controllers {
+            interval: config.sample_interval,
+            autoscaler: config.autoscale.clone().map(Autoscaler::new),
+            shedder: config.load_shed.clone().map(LoadShedder::new),
+            shedding,
+            events,
+        };
+        let run_loop = tokio::spawn(async move {
+            JobPool::run_loop(
+                pool_clone,
+                // optional periodic controllers
+                controllers,
+                // receives submissions
+                &mut submission_rx,
+                // receives completions
+                &mut completion_rx,
+                // provides completion channel to execution threads
+                completion_tx,
+                // receives callback delivery status
+                &mut callback_rx,
+                // receives the shutdown signal
+                shutdown_rx,
+            )
+            .await;
+        });
+        *this.run_loop.lock().unwrap() = Some(run_loop);
+
+        // private constructor pattern:
+        // return "this" so calling function has the pool
+        this
+    }
+
+    async fn run_loop(
+        pool: Arc<Mutex<JobPoolState>>,
+        mut controllers: Controllers,
+        submission_rx: &mut mpsc::Receiver<(JobId, JobSubmission, JobTiming)>,
+        completion_rx: &mut mpsc::Receiver<Completion>,
+        completion_tx: mpsc::Sender<Completion>,
+        callback_rx: &mut mpsc::Receiver<(JobId, CallbackStatus)>,
+        mut shutdown_rx: watch::Receiver<bool>,
+    ) {
+        println!("[JobPool]: [run_loop]: starting");
+        let mut completed: Vec<Completion> = Vec::with_capacity(COMPLETION_BATCH_SIZE);
+        // NOTE: the sample tick only fires when a controller is configured
+        let mut sample_tick = tokio::time::interval(controllers.interval);
+        let mut watchdog_tick = tokio::time::interval(WATCHDOG_INTERVAL);
+        let mut reconcile_tick = tokio::time::interval(RECONCILE_INTERVAL);
+        // NOTE: the GC tick only fires when retention limits are set
+        let gc_interval = pool.lock().await.completed.gc_interval();
+        let mut gc_tick = tokio::time::interval(gc_interval.unwrap_or(WATCHDOG_INTERVAL));
+        // set once shutdown starts: submissions are closed, and the loop
+        // exits when what was submitted has drained
+        let mut closing = false;
+        // when the next delayed job comes due; None: none are waiting
+        let mut next_due: Option<DateTime<Utc>> = None;
+        let clock = pool.lock().await.clock.clone();
+        loop {
+            if closing && submission_rx.is_empty() && pool.lock().await.idle() {
+                println!("[JobPool]: [run_loop]: drained, stopping");
+                break;
+            }
+            tokio::select! {
+
+                // ----------------------------------------
+                // Shutdown
+                // ----------------------------------------
+                // NOTE: dropping the JobPool winds the loop down too
+                _ = shutdown_rx.changed(), if !closing => {
+                    println!("[JobPool]: [run_loop]: shutting down");
+                    closing = true;
+                    // submitting now fails; what was sent still runs, but
+                    // delayed jobs won't come due in time
+                    submission_rx.close();
+                    let mut p = pool.lock().await;
+                    p.cancel_matching(|job| job.state == State::SCHEDULED, "pool shut down");
+                    drop(p);
+                }
+
+                // ----------------------------------------
+                // New job submitted
+                // ----------------------------------------
+                Some((id, job_submission, timing)) = submission_rx.recv() => {
+                    println!("[JobPool]: [run_loop]: job submission received: {:?}", job_submission);
+                    // acquire lock
+                    let mut p = pool.lock().await;
+                    let completion_tx_channel = completion_tx.clone();
+                    p.handle_new_job(id, &job_submission, timing, &completion_tx_channel);
+                    next_due = p.next_due();
+                    println!("[JobPool]: [run_loop]: job submission complete: {:?}", job_submission);
+                    // release lock
+                    drop(p);
+                }
+
+                // ----------------------------------------
+                // Job completed
+                // drain completions in batches so bursts are
+                // applied under a single lock acquisition
+                // ----------------------------------------
+                // NOTE: run_loop holds a completion sender, so the channel
+                // never closes and recv_many never returns 0 here
+                n = completion_rx.recv_many(&mut completed, COMPLETION_BATCH_SIZE) => {
+                    let ids: Vec<JobId> = completed.iter().map(|c| c.job_id).collect();
+                    println!("[JobPool]: [run_loop]: job completions received: {:?}", ids);
+                    // acquire lock
+                    let mut p = pool.lock().await;
+                    for completion in completed.drain(..) {
+                        p.finish_job(completion);
+                    }
+                    p.release_ready(&completion_tx);
+                    next_due = p.next_due();
+                    p.dispatch_pending(&completion_tx);
+                    // release lock
+                    drop(p);
+                    println!("[JobPool]: [run_loop]: job completions processed: {}", n);
+                }
+
+                // ----------------------------------------
+                // Callback delivery status
+                // ----------------------------------------
+                Some((job_id, status)) = callback_rx.recv() => {
+                    let mut p = pool.lock().await;
+                    p.update_callback(job_id, status);
+                    drop(p);
+                }
+
+                // ----------------------------------------
+                // Delayed jobs coming due
+                // ----------------------------------------
+                _ = tokio::time::sleep(time_until(&clock, next_due)), if next_due.is_some() => {
+                    let mut p = pool.lock().await;
+                    p.release_due(&completion_tx);
+                    next_due = p.next_due();
+                    drop(p);
+                }
+
+                // ----------------------------------------
+                // Timeouts and deadlines
+                // ----------------------------------------
+                _ = watchdog_tick.tick() => {
+                    let mut p = pool.lock().await;
+                    p.expire_overdue();
+                    p.check_heartbeats();
+                    p.expire_held();
+                    // rate limit buckets may have refilled
+                    if !p.rate_limits.is_empty() {
+                        p.dispatch_pending(&completion_tx);
+                    }
+                    drop(p);
+                }
+
+                // ----------------------------------------
+                // Slots whose executions vanished
+                // ----------------------------------------
+                _ = reconcile_tick.tick() => {
+                    let mut p = pool.lock().await;
+                    p.reconcile_slots();
+                    p.release_ready(&completion_tx);
+                    next_due = p.next_due();
+                    p.dispatch_pending(&completion_tx);
+                    drop(p);
+                }
+
+                // ----------------------------------------
+                // Finished job retention
+                // ----------------------------------------
+                _ = gc_tick.tick(), if gc_interval.is_some() => {
+                    let mut p = pool.lock().await;
+                    let now = p.clock.now();
+                    p.completed.gc(now);
+                    drop(p);
+                }
+
+                // ----------------------------------------
+                // Pool sample for the controllers
+                // ----------------------------------------
+                _ = sample_tick.tick(), if controllers.enabled() => {
+                    let mut p = pool.lock().await;
+                    let sample = p.sample(submission_rx.len());
+                    controllers.apply(&mut p, &sample);
+                    // max_jobs may have grown
+                    p.dispatch_pending(&completion_tx);
+                    drop(p);
+                }
+            }
+        }
+    }
+
+    /**
+     * submit: submit a job to the pool, returning its id
+     * Applies admission control, and the overflow policy if the
+     * submission channel is full
+     */
+    pub async fn submit(&self, job: JobSubmission) -> Result<JobId, ApiError> {
+        self.submit_as(self.ids.next_id(), job).await
+    }
+
+    // Submit a job as id, already assigned
+    async fn submit_as(&self, id: JobId, job: JobSubmission) -> Result<JobId, ApiError> {
+        let timing = self.validate(&job)?;
+        self.admit(&job, 1).await?;
+        match self.overflow_policy {
+            OverflowPolicy::Reject => self.send_now(id, job, timing),
+            OverflowPolicy::BlockWithDeadline(deadline) => {
+                if let Some(holder) = self.claim_unique(id, &job)? {
+                    return Ok(holder);
+                }
+                let unique_key = job.unique_key.clone();
+                let sent = self
+                    .submission_tx
+                    .send_timeout((id, job, timing), deadline)
+                    .await;
+                if let Err(e) = sent {
+                    if let Some(key) = &unique_key {
+                        self.unique_keys.release(key, id);
+                    }
+                    return Err(match e {
+                        SendTimeoutError::Timeout(_) => ApiError::QueueFull,
+                        SendTimeoutError::Closed(_) => ApiError::JobQueueClosed,
+                    });
+                }
+                Ok(id)
+            }
+        }
+    }
+
+    // Claim a submission's unique key for job id, if it has one; a key
+    // held by another job is a conflict, or with on_duplicate "coalesce"
+    // that job (Some) to answer with instead
+    fn claim_unique(&self, id: JobId, job: &JobSubmission) -> Result<Option<JobId>, ApiError> {
+        let Some(key) = &job.unique_key else {
+            return Ok(None);
+        };
+        match self.unique_keys.claim(key, id) {
+            Ok(()) => Ok(None),
+            Err(holder) if job.on_duplicate == Some(DuplicatePolicy::Coalesce) => {
+                println!(
+                    "[JobPool]: unique key '{}': coalesced into job {}",
+                    key, holder
+                );
+                Ok(Some(holder))
+            }
+            Err(holder) => Err(ApiError::Conflict(format!(
+                "unique key '{key}' is held by unfinished job {holder}"
+            ))),
+        }
+    }
+
+    /**
+     * unique_holder: the unfinished job holding a unique key, if any
+     */
+    pub fn unique_holder(&self, key: &str) -> Option<JobId> {
+        self.unique_keys.holder(key)
+    }
+
+    /**
+     * unique_keys: every held unique key and the job holding it
+     */
+    pub fn unique_keys(&self) -> BTreeMap<String, JobId> {
+        self.unique_keys.list()
+    }
+
+    /**
+     * clone_job: submit a copy of a job's submission with a JSON merge patch
+     * applied, linked back to the job by cloned_from
+     * The copy leaves out the fields the pool sets for map children and
+     * workflow steps (group_id among them), so it runs on its own
+     */
+    pub async fn clone_job(&self, id: JobId, patch: &Value) -> Result<JobId, ApiError> {
+        let job = self
+            .job(id)
+            .await
+            .ok_or_else(|| ApiError::NotFound(format!("job {id}")))?;
+        let submission = resubmission(&job.submission);
+        let mut raw = serde_json::to_value(&submission)
+            .map_err(|e| ApiError::InternalError(e.to_string()))?;
+        merge_patch(&mut raw, patch);
+        let mut copy: JobSubmission =
+            serde_json::from_value(raw).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+        copy.cloned_from = Some(id);
+        let clone_id = self.submit(copy).await?;
+        println!("[JobPool]: job {}: cloned as job {}", id, clone_id);
+        Ok(clone_id)
+    }
+
+    /**
+     * rerun_job: submit a finished job's submission again as is, linked
+     * back to the job by rerun_of
+     * As with clone_job, the rerun runs on its own; a deadline already
+     * passed is dropped, so it doesn't expire at once. The rerun of a job
+     * that didn't succeed resumes from its checkpoints. A job not yet
+     * finished is a conflict
+     */
+    pub async fn rerun_job(&self, id: JobId) -> Result<JobId, ApiError> {
+        let job = self
+            .job(id)
+            .await
+            .ok_or_else(|| ApiError::NotFound(format!("job {id}")))?;
+        if !job.state.is_terminal() {
+            return Err(ApiError::Conflict(format!("job {id} is {}", job.state)));
+        }
+        let mut rerun = resubmission(&job.submission);
+        let now = self.pool.lock().await.clock.now();
+        if rerun.deadline.is_some_and(|deadline| deadline <= now) {
+            rerun.deadline = None;
+        }
+        rerun.rerun_of = Some(id);
+        if job.state != State::SUCCEEDED {
+            rerun.resume_from = Some(id);
+        }
+        let rerun_id = self.submit(rerun).await?;
+        println!("[JobPool]: job {}: rerun as job {}", id, rerun_id);
+        Ok(rerun_id)
+    }
+
+    /**
+     * dry_run: validate a job and report what submitting it would do,
+     * without creating it
+     * Invalid jobs fail as submit would; a valid job that would be turned
+     * away now (shedding, full) is reported as Rejected
+     */
+    pub async fn dry_run(&self, job: &JobSubmission) -> Result<DryRunResult, ApiError> {
+        self.check_submission(job)?;
+        let p = self.pool.lock().await;
+        let Some(q) = p.queue_index(&job.queue) else {
+            return Err(ApiError::BadRequest(format!(
+                "unknown queue '{}'",
+                job.queue
+            )));
+        };
+        let mut result = p.dry_run(q, job.priority);
+        result.estimate = p.estimate(job, result.estimated_wait_ms);
+        let refusal = match self.check_shedding(job) {
+            Err(_) => Some("overloaded: shedding low-priority submissions".to_string()),
+            Ok(()) if self.submission_tx.capacity() == 0 => {
+                Some("job submission queue full".to_string())
+            }
+            Ok(()) => result
+                .estimate
+                .as_ref()
+                .and_then(|estimate| self.refusal(&p, job, estimate, 1))
+                .map(|refusal| refusal.message().to_string()),
+        };
+        if let Some(reason) = refusal {
+            result.placement = Placement::Rejected;
+            result.reason = Some(reason);
+            result.estimated_wait_ms = None;
+        }
+        Ok(result)
+    }
+
+    // Refuse count jobs like job if admission control turns them away
+    async fn admit(&self, job: &JobSubmission, count: usize) -> Result<(), ApiError> {
+        let quota = usage::submission_tenant(job).and_then(|t| self.tenants.quota(t));
+        let deadline = job.deadline.filter(|_| self.admission.deadlines);
+        if quota.is_none() && deadline.is_none() {
+            return Ok(());
+        }
+        let p = self.pool.lock().await;
+        let Some(q) = p.queue_index(&job.queue) else {
+            return Ok(());
+        };
+        let wait = p.dry_run(q, job.priority).estimated_wait_ms;
+        let Some(estimate) = p.estimate(job, wait) else {
+            return Ok(());
+        };
+        match self.refusal(&p, job, &estimate, count) {
+            Some(refusal) => {
+                println!("[JobPool]: not admitted: {}", refusal.message());
+                Err(refusal.into())
+            }
+            None => Ok(()),
+        }
+    }
+
+    // Why admission control would turn count jobs like job away, given
+    // its estimate; None: it wouldn't
+    fn refusal(
+        &self,
+        p: &JobPoolState,
+        job: &JobSubmission,
+        estimate: &Estimate,
+        count: usize,
+    ) -> Option<Refusal> {
+        if self.admission.deadlines
+            && let (Some(deadline), Some(completes_at)) = (job.deadline, estimate.completes_at)
+            && completes_at > deadline
+        {
+            return Some(Refusal::Deadline(format!(
+                "estimated to complete at {completes_at}, after its deadline of {deadline}"
+            )));
+        }
+        let tenant = usage::submission_tenant(job)?;
+        let quota = self.tenants.quota(tenant)?;
+        let since = p.clock.now() - self.admission.quota_window;
+        let remaining = (quota - p.tenant_cost(tenant, since)).max(0.0);
+        let cost = estimate.cost * count as f64;
+        (cost > remaining).then(|| {
+            Refusal::Quota(format!(
+                "tenant '{tenant}' has {remaining:.2} of its {quota} cost quota left, \
+                 short of the estimated {cost:.2}"
+            ))
+        })
+    }
+
+    /**
+     * shutdown: stop taking submissions and let queued and running jobs
+     * finish; any still unfinished at the deadline are cancelled
+     * Resolves once the run loop has exited
+     */
+    pub async fn shutdown(&self, deadline: Duration) -> ShutdownReport {
+        self.shutdown_with(&DrainOptions::new(deadline)).await
+    }
+
+    /**
+     * shutdown_with: shutdown, draining as the options say; jobs below
+     * preempt_below are cancelled as the drain starts, leaving the time
+     * to the more important ones
+     * NOTE: executions that ignore cancellation are still waited for
+     */
+    pub async fn shutdown_with(&self, options: &DrainOptions) -> ShutdownReport {
+        let Some(mut run_loop) = self.run_loop.lock().unwrap().take() else {
+            println!("[JobPool]: shutdown: already shut down");
+            return ShutdownReport {
+                drained: true,
+                ..ShutdownReport::default()
+            };
+        };
+        println!(
+            "[JobPool]: shutdown: draining for up to {:?}",
+            options.deadline
+        );
+        self.shutdown_tx.send_replace(true);
+
+        let mut p = self.pool.lock().await;
+        let outstanding = p.outstanding();
+        let mut preempted = Vec::new();
+        if let Some(below) = options.preempt_below {
+            let (cancelled, cancelling) = p.cancel_matching(
+                |job| job.submission.priority < below,
+                "preempted by shutdown",
+            );
+            preempted.extend(cancelled.into_iter().chain(cancelling));
+            println!(
+                "[JobPool]: shutdown: {} jobs below {:?} preempted",
+                preempted.len(),
+                below
+            );
+        }
+        drop(p);
+
+        let drained = tokio::time::timeout(options.deadline, &mut run_loop)
+            .await
+            .is_ok();
+        let mut cancelled = Vec::new();
+        if drained {
+            println!("[JobPool]: shutdown: drained");
+        } else {
+            let (stopped, stopping) = self
+                .pool
+                .lock()
+                .await
+                .cancel_matching(|_| true, "pool shut down");
+            cancelled.extend(stopped.into_iter().chain(stopping));
+            println!(
+                "[JobPool]: shutdown: deadline passed, {} jobs cancelled",
+                cancelled.len()
+            );
+            if let Err(e) = run_loop.await {
+                println!("[JobPool]: shutdown: run loop: {}", e);
+            }
+        }
+
+        // nothing runs any more
+        self.resources.shutdown();
+
+        let p = self.pool.lock().await;
+        let jobs = outstanding
+            .into_iter()
+            .map(|(id, priority)| DrainedJob {
+                id,
+                priority,
+                state: p.find_job(id).map(|job| job.state),
+                outcome: if preempted.contains(&id) {
+                    DrainOutcome::Preempted
+                } else if cancelled.contains(&id) {
+                    DrainOutcome::Cancelled
+                } else {
+                    DrainOutcome::Finished
+                },
+            })
+            .collect();
+        ShutdownReport {
+            drained,
+            cancelled,
+            jobs,
+        }
+    }
+
+    /**
+     * submit_map: expand a map into child jobs and submit them all
+     * NOTE: waits for room in the submission channel rather than applying
+     * the overflow policy, so a map is never partially accepted
+     */
+    pub async fn submit_map(&self, map: MapSubmission) -> Result<MapAccepted, ApiError> {
+        let map_id = Ulid::new().to_string();
+        let children = map.expand(&map_id).map_err(ApiError::BadRequest)?;
+        let Some(first) = children.first() else {
+            return Err(ApiError::BadRequest("no inputs to map over".to_string()));
+        };
+        // children differ only in payload: checking one checks them all
+        let timing = self.validate(first)?;
+        let total = children.len();
+        let ids: Vec<JobId> = children.iter().map(|_| self.ids.next_id()).collect();
+        let reducer = map
+            .reducer(&map_id, &ids)
+            .map(|reducer| {
+                Ok::<_, ApiError>((self.ids.next_id(), self.validate(&reducer)?, reducer))
+            })
+            .transpose()?;
+        self.admit(first, total + usize::from(reducer.is_some()))
+            .await?;
+
+        self.pool.lock().await.maps.insert(
+            map_id.clone(),
+            MapRun {
+                total,
+                reducer: reducer.as_ref().map(|(id, _, _)| *id),
+                results: match reducer {
+                    Some(_) => vec![String::new(); total],
+                    None => Vec::new(),
+                },
+            },
+        );
+        for (id, child) in ids.into_iter().zip(children) {
+            self.submission_tx
+                .send((id, child, timing.clone()))
+                .await
+                .map_err(|_| ApiError::JobQueueClosed)?;
+        }
+        // after its children, so it finds them
+        let reducer = match reducer {
+            Some((id, timing, submission)) => {
+                self.submission_tx
+                    .send((id, submission, timing))
+                    .await
+                    .map_err(|_| ApiError::JobQueueClosed)?;
+                Some(id)
+            }
+            None => None,
+        };
+        println!("[JobPool]: map {}: submitted {} jobs", map_id, total);
+        Ok(MapAccepted {
+            map_id,
+            total,
+            reducer,
+        })
+    }
+
+    /**
+     * map_status: a map's progress and per-item results
+     */
+    pub async fn map_status(&self, map_id: &str) -> Result<MapStatus, ApiError> {
+        let p = self.pool.lock().await;
+        p.map_status(map_id)
+            .ok_or_else(|| ApiError::NotFound(format!("map '{map_id}'")))
+    }
+
+    /**
+     * try_submit: submit a job to the pool without waiting, returning its id
+     * Fails fast if the submission channel is full
+     * NOTE: skips admission control, which waits for the pool
+     */
+    pub fn try_submit(&self, job: JobSubmission) -> Result<JobId, ApiError> {
+        let timing = self.validate(&job)?;
+        self.send_now(self.ids.next_id(), job, timing)
+    }
+
+    // Send a checked job to the run loop as id, or fail if the channel is
+    // full
+    fn send_now(
+        &self,
+        id: JobId,
+        job: JobSubmission,
+        timing: JobTiming,
+    ) -> Result<JobId, ApiError> {
+        if let Some(holder) = self.claim_unique(id, &job)? {
+            return Ok(holder);
+        }
+        let unique_key = job.unique_key.clone();
+        if let Err(e) = self.submission_tx.try_send((id, job, timing)) {
+            if let Some(key) = &unique_key {
+                self.unique_keys.release(key, id);
+            }
+            return Err(match e {
+                TrySendError::Full(_) => ApiError::QueueFull,
+                TrySendError::Closed(_) => ApiError::JobQueueClosed,
+            });
+        }
+        Ok(id)
+    }
+
+    /**
+     * queue: pending jobs in the order they will start
+     */
+    pub async fn queue(&self) -> Vec<QueuedJob> {
+        self.pool.lock().await.dispatch_order()
+    }
+
+    /**
+     * status: current pool occupancy
+     */
+    pub async fn status(&self) -> PoolStatus {
+        self.status_of(&*self.pool.lock().await)
+    }
+
+    fn status_of(&self, p: &JobPoolState) -> PoolStatus {
+        PoolStatus {
+            max_jobs: p.max_jobs,
+            busy: p.busy_slots(),
+            shedding: self.shedding.load(Ordering::Relaxed),
+            paused: p.pauses.pool.is_some(),
+            queues: p
+                .queues
+                .iter()
+                .map(|q| QueueStatus {
+                    name: q.name().to_string(),
+                    running: q.running,
+                    pending: q.pending.len(),
+                    max_concurrency: q.config.max_concurrency,
+                    pending_limit: q.config.pending_limit,
+                    overflow: q.config.overflow,
+                    paused: p.pauses.paused(q.name()).is_some(),
+                    steal_weight: q.config.steal_weight,
+                    stolen: q.stolen(),
+                })
+                .collect(),
+        }
+    }
+
+    /**
+     * metrics: job counts and run times since startup, with history usage
+     */
+    pub async fn metrics(&self) -> PoolMetrics {
+        Self::metrics_of(&mut *self.pool.lock().await)
+    }
+
+    fn metrics_of(p: &mut JobPoolState) -> PoolMetrics {
+        let now = p.clock.now();
+        PoolMetrics {
+            total_submitted: p.tally.submitted,
+            running: p.queues.iter().map(|q| q.running).sum(),
+            stuck: p.stuck_jobs(),
+            queued: p.pending_jobs(),
+            succeeded: p.tally.succeeded,
+            failed: p.tally.failed,
+            avg_duration_ms: p.tally.average_run_time().as_millis() as u64,
+            failures: p.tally.failures.clone(),
+            reclaimed_slots: p.tally.reclaimed,
+            labels: p.tally.by_label.clone(),
+            rate_limits: p.rate_limits.levels(now),
+            log_bytes_dropped: p.tally.log_dropped,
+            durations: p.durations.report(),
+            history: p.completed.usage(),
+        }
+    }
+
+    /**
+     * snapshot: the latest read snapshot; None: snapshots are off, or the
+     * first is yet to be taken
+     */
+    pub fn snapshot(&self) -> Option<Arc<PoolSnapshot>> {
+        self.snapshots.latest()
+    }
+
+    /**
+     * take_snapshot: copy the pool's jobs, status and metrics
+     */
+    pub async fn take_snapshot(&self) -> PoolSnapshot {
+        let mut p = self.pool.lock().await;
+        let mut jobs = Vec::new();
+        if let Err(e) = p.for_each_job(|job| jobs.push(job.clone())) {
+            println!("[JobPool]: snapshot: {:?}", e);
+        }
+        PoolSnapshot {
+            taken_at: p.clock.now(),
+            jobs,
+            status: self.status_of(&p),
+            metrics: Self::metrics_of(&mut p),
+        }
+    }
+
+    /**
+     * subscribe: receive pool events
+     */
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<crate::events::Event> {
+        self.events.subscribe()
+    }
+
+    /**
+     * job_types: the job types the pool runs, as its executor reports them
+     */
+    pub fn job_types(&self) -> &[String] {
+        &self.job_types
+    }
+
+    /**
+     * workflows: stored workflow definitions and their runs
+     */
+    pub fn workflows(&self) -> &Workflows {
+        &self.workflows
+    }
+
+    /**
+     * schemas: payload schemas registered per job type
+     */
+    pub fn schemas(&self) -> &Schemas {
+        &self.schemas
+    }
+
+    /**
+     * schedules: cron schedules and their run history
+     */
+    pub fn schedules(&self) -> &Schedules {
+        &self.schedules
     }
 
-    fn finish_job(&mut self, job_index: usize) {
-        println!("[JobPoolState]: job {}: finishing", job_index);
+    /**
+     * env_profiles: the configured environment profiles
+     */
+    pub fn env_profiles(&self) -> &EnvProfiles {
+        &self.env_profiles
     }
-}
 
-/**
- * JobPool
- */
-pub struct JobPool {
-    pool: Arc<Mutex<JobPoolState>>,
-    // used by API to submit jobs to the pool
-    submission_tx: mpsc::Sender<JobSubmission>,
-}
+    /**
+     * health_gates: each gated job type's downstream health
+     */
+    pub fn health_gates(&self) -> Vec<GateStatus> {
+        self.health.list()
+    }
 
-impl JobPool {
-    pub fn start() -> Arc<Self> {
-        println!("[JobPool]: start");
+    /**
+     * circuit_breakers: the circuit of each job type that has finished a
+     * job
+     */
+    pub fn circuit_breakers(&self) -> Vec<CircuitStatus> {
+        self.breakers.list()
+    }
 
-        // message-passing channels
-        println!("[JobPool]: creating job messaging channels");
-        // channel for job submissions
-        let (submission_tx, mut submission_rx) = mpsc::channel(32);
-        // channel for job completions
-        let (completion_tx, mut completion_rx) = mpsc::channel::<usize>(32);
+    /**
+     * resources: the shared resources the executor registered
+     */
+    pub fn resources(&self) -> Vec<ResourceStatus> {
+        self.resources.list()
+    }
 
-        // construct underlying pool state
-        println!("[JobPool]: create new pool");
-        let state = JobPoolState::new(4);
-        let pool = Arc::new(Mutex::new(state));
-        // NOTE: private constructor pattern
-        let this = Arc::new(Self {
-            pool: pool.clone(),
-            submission_tx,
-        });
+    /**
+     * dispatch_held: dispatch pending jobs a recovered downstream held
+     */
+    pub async fn dispatch_held(&self) {
+        self.pool.lock().await.dispatch_pending(&self.completion_tx);
+    }
 
-        // Spawn the async loop that handles job submissions and completions
-        println!("[JobPool]: spawning job handling loop");
-        let pool_clone = pool.clone();
-        tokio::spawn(async move {
-            JobPool::run_loop(
-                pool_clone,
-                // receives submissions
-                &mut submission_rx,
-                // receives completions
-                &mut completion_rx,
-                // provides completion channel to execution threads
-                completion_tx,
-            )
-            .await;
-        });
+    /**
+     * principal: who a request naming name (if any) acts as
+     */
+    pub fn principal(&self, name: Option<&str>) -> Principal {
+        self.admins.principal(name)
+    }
 
-        // private constructor pattern:
-        // return "this" so calling function has the pool
-        this
+    /**
+     * job: a job anywhere in the pool, if it has reached it
+     */
+    pub async fn job(&self, id: JobId) -> Option<Job> {
+        self.pool.lock().await.find_job(id)
     }
 
-    async fn run_loop(
-        pool: Arc<Mutex<JobPoolState>>,
-        submission_rx: &mut mpsc::Receiver<JobSubmission>,
-        completion_rx: &mut mpsc::Receiver<usize>,
-        completion_tx: mpsc::Sender<usize>,
-    ) {
-        println!("[JobPool]: [run_loop]: starting");
-        loop {
-            tokio::select! {
+    /**
+     * cached: a recent successful job with the same type and payload, if
+     * the type is cacheable and one is within its TTL
+     */
+    pub async fn cached(&self, job: &JobSubmission) -> Option<Job> {
+        let mut p = self.pool.lock().await;
+        let now = p.clock.now();
+        let cached = p.result_cache.get(job, now).cloned()?;
+        p.dedup
+            .cache_hit(payload_hash(job), cached.id(), job.kind.name(), now);
+        Some(cached)
+    }
 
-                // ----------------------------------------
-                // New job submitted
-                // ----------------------------------------
-                Some(job_submission) = submission_rx.recv() => {
-                    println!("[JobPool]: [run_loop]: job submission received: {:?}", job_submission);
-                    // acquire lock
-                    let mut p = pool.lock().await;
-                    let completion_tx_channel = completion_tx.clone();
-                    p.handle_new_job(&job_submission, &completion_tx_channel);
-                    println!("[JobPool]: [run_loop]: job submission complete: {:?}", job_submission);
-                    // release lock
-                    drop(p);
-                }
+    /**
+     * executors: the executors jobs run on, and what each can do
+     */
+    pub async fn executors(&self) -> Vec<ExecutorInfo> {
+        vec![self.pool.lock().await.executor.info()]
+    }
 
-                // ----------------------------------------
-                // Job completed
-                // ----------------------------------------
-                Some(completed_job_index) = completion_rx.recv() => {
-                    println!("[JobPool]: [run_loop]: job completion received: {}", completed_job_index);
-                    // acquire lock
-                    let mut p = pool.lock().await;
-                    p.finish_job(completed_job_index);
-                    // release lock
-                    println!("[JobPool]: [run_loop]: job completion processed: {}", completed_job_index);
-                    drop(p);
-                }
+    /**
+     * claim_key: claim an idempotency key for a job about to be submitted,
+     * returning the id to submit it as (with submit_claimed), or to let
+     * the key go with (release_key) if it isn't submitted after all
+     * Err: the job already submitted under the key within the dedup window
+     */
+    pub async fn claim_key(&self, key: &str, job: &JobSubmission) -> Result<JobId, JobId> {
+        let id = self.ids.next_id();
+        let mut p = self.pool.lock().await;
+        let now = p.clock.now();
+        match p.dedup.claim(key, id, job.kind.name(), now) {
+            Some(earlier) => Err(earlier),
+            None => Ok(id),
+        }
+    }
+
+    /**
+     * release_key: let go of an idempotency key claimed for job id
+     */
+    pub async fn release_key(&self, key: &str, id: JobId) {
+        self.pool.lock().await.dedup.release(key, id);
+    }
+
+    /**
+     * submit_claimed: submit a job as the id claim_key gave it, letting the
+     * key go if it is refused; a job coalesced into another (by unique
+     * key) leaves the key with that one
+     */
+    pub async fn submit_claimed(
+        &self,
+        key: &str,
+        id: JobId,
+        job: JobSubmission,
+    ) -> Result<JobId, ApiError> {
+        let job_type = job.kind.name().to_string();
+        let submitted = self.submit_as(id, job).await;
+        let mut p = self.pool.lock().await;
+        match submitted {
+            Ok(holder) if holder != id => {
+                p.dedup.release(key, id);
+                let now = p.clock.now();
+                p.dedup.claim(key, holder, &job_type, now);
             }
+            Ok(_) => {}
+            Err(_) => p.dedup.release(key, id),
         }
+        submitted
     }
 
     /**
-     * submit: submit a job to the pool
+     * dedup_report: duplicate submissions suppressed, counted and recent
      */
-    pub async fn submit(&self, job: JobSubmission) -> Result<(), ApiError> {
-        self.submission_tx
-            .send(job)
-            .await
-            .map_err(|_| ApiError::JobQueueClosed)
+    pub async fn dedup_report(&self) -> DedupReport {
+        self.pool.lock().await.dedup.report()
+    }
+
+    /**
+     * pauses: what is paused, why, and since when
+     */
+    pub async fn pauses(&self) -> PauseState {
+        self.pool.lock().await.pauses.clone()
+    }
+
+    /**
+     * pause: hold new and pending jobs of the pool (queue None) or of one
+     * queue; running jobs finish
+     */
+    pub async fn pause(&self, queue: Option<&str>, reason: &str) -> Result<PauseState, ApiError> {
+        self.check_pause_target(queue)?;
+        let mut p = self.pool.lock().await;
+        let mut pauses = p.pauses.clone();
+        pauses.pause(queue, reason, p.clock.now());
+        self.save_pauses(&pauses)?;
+        println!("[JobPool]: paused {}: {}", queue.unwrap_or("pool"), reason);
+        p.pauses = pauses;
+        Ok(p.pauses.clone())
+    }
+
+    /**
+     * resume: lift a pause, dispatching the jobs it held
+     */
+    pub async fn resume(&self, queue: Option<&str>) -> Result<PauseState, ApiError> {
+        self.check_pause_target(queue)?;
+        let mut p = self.pool.lock().await;
+        let mut pauses = p.pauses.clone();
+        pauses.resume(queue);
+        self.save_pauses(&pauses)?;
+        println!("[JobPool]: resumed {}", queue.unwrap_or("pool"));
+        p.pauses = pauses;
+        p.dispatch_pending(&self.completion_tx);
+        Ok(p.pauses.clone())
+    }
+
+    fn check_pause_target(&self, queue: Option<&str>) -> Result<(), ApiError> {
+        match queue {
+            Some(name) if !self.queue_names.iter().any(|q| q == name) => {
+                Err(ApiError::NotFound(format!("queue '{name}'")))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Persist pauses before they take effect, so what is saved is never
+    // behind what is in force
+    fn save_pauses(&self, pauses: &PauseState) -> Result<(), ApiError> {
+        let Some(path) = &self.pause_state else {
+            return Ok(());
+        };
+        pauses
+            .save(path)
+            .map_err(|e| ApiError::InternalError(format!("saving pause state: {e}")))
+    }
+
+    /**
+     * usage: cost of the jobs that finished in [from, to)
+     */
+    pub async fn usage(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> UsageReport {
+        let mut report = UsageReport::new(from, to);
+        for job in self.pool.lock().await.completed.iter() {
+            report.add(job);
+        }
+        report
+    }
+
+    /**
+     * with_defaults: parse a raw submission (job or map), filling the
+     * fields it omits from its tenant's and then its job type's defaults
+     */
+    pub fn with_defaults<T: DeserializeOwned>(&self, mut raw: Value) -> Result<T, ApiError> {
+        self.tenants.fill_defaults(&mut raw);
+        self.defaults.apply(raw)
+    }
+
+    /**
+     * check_submission: the checks a submission must pass whenever it is
+     * made (known queue, valid callback, metadata within limits), without
+     * submitting it
+     */
+    pub fn check_submission(&self, job: &JobSubmission) -> Result<(), ApiError> {
+        self.check_job_type(job)?;
+        self.check_queue(job)?;
+        self.check_env_profile(job)?;
+        self.tenants.check(job)?;
+        if job
+            .unique_key
+            .as_deref()
+            .is_some_and(|key| key.trim().is_empty())
+        {
+            return Err(ApiError::BadRequest("unique_key: empty".to_string()));
+        }
+        self.check_callback(job)?;
+        check_metadata(job)?;
+        labels::check(&job.labels)?;
+        for follow_up in job.on_success.iter().chain(&job.on_failure) {
+            self.check_submission(follow_up)?;
+        }
+        if job.run_at.is_some() && job.delay_ms.is_some() {
+            return Err(ApiError::BadRequest(
+                "give run_at or delay_ms, not both".to_string(),
+            ));
+        }
+        if let Some(delay_ms) = job.delay_ms
+            && delayed_until(self.clock.now(), delay_ms).is_none()
+        {
+            return Err(ApiError::BadRequest(format!(
+                "delay_ms: {delay_ms} is too long"
+            )));
+        }
+        if job.depends_on.len() > MAX_DEPENDENCIES {
+            return Err(ApiError::BadRequest(format!(
+                "depends_on: {} jobs, over the limit of {MAX_DEPENDENCIES}",
+                job.depends_on.len()
+            )));
+        }
+        self.schemas.check(job)
     }
 
     /**
-     * get_jobs: get active jobs
+     * group_jobs: every job in a group, in no particular order
      */
-    pub async fn get_jobs(&self) -> Result<Vec<Job>, ApiError> {
+    pub async fn group_jobs(&self, group_id: &str) -> Vec<Job> {
         let p = self.pool.lock().await;
+        let in_group = |job: &Job| job.submission.group_id.as_deref() == Some(group_id);
         let mut out = Vec::new();
-        for opt in &p.jobs {
-            match opt {
-                Some(JobCell::Occupied(job_arc)) => {
-                    let job = job_arc
-                        .lock()
-                        .map_err(|_| ApiError::InternalError("failed to lock job".to_string()))?;
-                    println!("[JobPool]: [get_jobs]: job {}", job.id);
+        for cell in p.jobs.iter().flatten() {
+            if let JobCell::Occupied(job_arc) = cell {
+                let job = job_arc.lock().unwrap();
+                if in_group(&job) {
                     out.push(job.clone());
                 }
-                Some(JobCell::Empty) => {
-                    println!("[JobPool]: [get_jobs]: job EMPTY");
+            }
+        }
+        out.extend(
+            p.waiting()
+                .chain(p.completed.iter())
+                .filter(|job| in_group(job))
+                .cloned(),
+        );
+        out
+    }
+
+    /**
+     * submit_child: submit a job a running job spawned, as its child
+     */
+    pub async fn submit_child(
+        &self,
+        parent: JobId,
+        mut job: JobSubmission,
+    ) -> Result<JobId, ApiError> {
+        job.parent_id = Some(parent);
+        self.submit(job).await
+    }
+
+    /**
+     * submit_follow_up: submit a finished job's follow-up
+     */
+    pub async fn submit_follow_up(
+        &self,
+        after: JobId,
+        mut job: JobSubmission,
+    ) -> Result<JobId, ApiError> {
+        job.follows = Some(after);
+        self.submit(job).await
+    }
+
+    /**
+     * job_children: the tree of the children a job spawned, and theirs
+     */
+    pub async fn job_children(&self, id: JobId) -> Result<Vec<JobTree>, ApiError> {
+        let p = self.pool.lock().await;
+        if p.find_job(id).is_none() {
+            return Err(ApiError::NotFound(format!("job {id}")));
+        }
+        let mut by_parent: HashMap<JobId, Vec<JobView>> = HashMap::new();
+        p.for_each_job(|job| {
+            if let Some(parent) = job.submission.parent_id {
+                by_parent
+                    .entry(parent)
+                    .or_default()
+                    .push(JobView::from(job));
+            }
+        })?;
+        drop(p);
+        Ok(children::tree(id, &mut by_parent))
+    }
+
+    /**
+     * group_status: aggregate status of a job group
+     */
+    pub async fn group_status(&self, group_id: &str) -> Result<GroupStatus, ApiError> {
+        let p = self.pool.lock().await;
+        p.group_status(group_id)
+            .ok_or_else(|| ApiError::NotFound(format!("group '{group_id}'")))
+    }
+
+    /**
+     * group_logs: the log lines of a group's jobs, in the order asked for
+     */
+    pub async fn group_logs(
+        &self,
+        group_id: &str,
+        order: LogOrder,
+    ) -> Result<Vec<GroupLogLine>, ApiError> {
+        let mut lines = self
+            .pool
+            .lock()
+            .await
+            .group_logs(group_id)?
+            .ok_or_else(|| ApiError::NotFound(format!("group '{group_id}'")))?;
+        if order == LogOrder::Interleaved {
+            // stable: lines logged at the same time keep their job order
+            lines.sort_by_key(|line| line.at);
+        }
+        Ok(lines)
+    }
+
+    /**
+     * cancel_group: cancel a group's jobs; running jobs are marked
+     * cancelling and end cancelled once they return
+     * NOTE: submissions still in the submission channel aren't seen
+     */
+    pub async fn cancel_group(&self, group_id: &str) -> Result<GroupCancelResult, ApiError> {
+        let mut p = self.pool.lock().await;
+        if p.group_status(group_id).is_none() {
+            return Err(ApiError::NotFound(format!("group '{group_id}'")));
+        }
+        let (cancelled, cancelling) = p.cancel_group(group_id);
+        let group = p.group_status(group_id).unwrap();
+        Ok(GroupCancelResult {
+            cancelled,
+            cancelling,
+            group,
+        })
+    }
+
+    /**
+     * cancel_job: cancel one job; a running job is marked cancelling and
+     * its execution asked to stop, ending cancelled once it returns
+     * Returns the job as it is after the cancel
+     */
+    pub async fn cancel_job(&self, id: JobId) -> Result<Job, ApiError> {
+        let mut p = self.pool.lock().await;
+        let Some(job) = p.find_job(id) else {
+            return Err(ApiError::NotFound(format!("job {id}")));
+        };
+        if !matches!(
+            job.state,
+            State::WAITING | State::SCHEDULED | State::QUEUED | State::RUNNING | State::STUCK
+        ) && job.preempted_by.is_none()
+        {
+            return Err(ApiError::Conflict(format!("job {id} is {}", job.state)));
+        }
+        p.cancel_matching(|job| job.id == id, "job cancelled");
+        println!("[JobPool]: job {}: cancel requested", id);
+        Ok(p.find_job(id).unwrap_or(job))
+    }
+
+    /**
+     * pin_job: pin (or unpin) a finished job, keeping it in history however
+     * full history gets; each tenant may pin up to PIN_QUOTA jobs
+     * Returns the job as it is after
+     */
+    pub async fn pin_job(&self, id: JobId, pin: bool) -> Result<Job, ApiError> {
+        self.pool.lock().await.pin_job(id, pin)
+    }
+
+    /**
+     * cancel_jobs: cancel every queued or running job matching a filter;
+     * running jobs are marked cancelling and end cancelled once they return
+     * NOTE: submissions still in the submission channel aren't seen
+     */
+    pub async fn cancel_jobs(&self, filter: &CancelFilter) -> Result<BulkCancelResult, ApiError> {
+        if filter.is_empty() {
+            return Err(ApiError::BadRequest(
+                "filter: give at least one of state, type, created_before, labels".to_string(),
+            ));
+        }
+        if let Some(state) = &filter.state
+            && !matches!(
+                state,
+                State::WAITING | State::SCHEDULED | State::QUEUED | State::RUNNING | State::STUCK
+            )
+        {
+            return Err(ApiError::BadRequest(format!(
+                "state: only waiting, scheduled, queued, running or stuck jobs can be cancelled, not {state}"
+            )));
+        }
+        if let Some(job_type) = &filter.job_type
+            && !self.job_types.contains(job_type)
+        {
+            return Err(ApiError::BadRequest(format!(
+                "type: unknown job type '{job_type}'"
+            )));
+        }
+        let mut p = self.pool.lock().await;
+        let (cancelled, cancelling) = p.cancel_matching(|job| filter.matches(job), "bulk cancel");
+        println!(
+            "[JobPool]: bulk cancel {:?}: {} cancelled, {} cancelling",
+            filter,
+            cancelled.len(),
+            cancelling.len()
+        );
+        Ok(BulkCancelResult {
+            cancelled,
+            cancelling,
+        })
+    }
+
+    /**
+     * wait_for: wait until every job reaches a terminal state, or timeout
+     * respond_by: a request deadline the wait ends by, given to the jobs'
+     * executions as their budget; the wait ends at once if a job is
+     * expected to finish after it
+     * NOTE: an unknown id is waited on too, since a just-accepted
+     * submission may not have reached the pool yet
+     */
+    pub async fn wait_for(
+        &self,
+        ids: &[JobId],
+        timeout: Duration,
+        respond_by: Option<DateTime<Utc>>,
+    ) -> WaitResult {
+        let now = self.pool.lock().await.clock.now();
+        let timeout = respond_by.map_or(timeout, |by| {
+            timeout.min((by - now).to_std().unwrap_or_default())
+        });
+        let deadline = tokio::time::Instant::now() + timeout;
+        // subscribe before looking, so no transition slips in between
+        let mut events = self.subscribe();
+        let mut timed_out = false;
+        loop {
+            let mut p = self.pool.lock().await;
+            let jobs: Vec<WaitStatus> = ids
+                .iter()
+                .map(|&id| WaitStatus {
+                    id,
+                    job: p.find_job(id),
+                })
+                .collect();
+            let mut late = Vec::new();
+            if let Some(by) = respond_by {
+                let unfinished = jobs
+                    .iter()
+                    .filter_map(|s| s.job.as_ref())
+                    .filter(|job| !job.state.is_terminal());
+                for job in unfinished {
+                    if !p.waited_until(job, by) {
+                        late.push(job.id);
+                    }
                 }
-                None => {
-                    println!("[JobPool]: [get_jobs]: job NONE");
+            }
+            drop(p);
+            let done = jobs
+                .iter()
+                .all(|s| s.job.as_ref().is_some_and(|job| job.state.is_terminal()));
+            if done || timed_out || !late.is_empty() {
+                return WaitResult {
+                    timed_out: !done,
+                    jobs,
+                    late,
+                };
+            }
+
+            // sleep until one of the jobs changes state
+            let changed = tokio::time::timeout_at(deadline, async {
+                loop {
+                    match events.recv().await {
+                        Ok(Event {
+                            kind: EventKind::JobStateChanged { job_id, .. },
+                            ..
+                        }) if ids.contains(&job_id) => return,
+                        Ok(_) => continue,
+                        // missed events: look again
+                        Err(RecvError::Lagged(_)) => return,
+                        Err(RecvError::Closed) => std::future::pending().await,
+                    }
                 }
-                _ => {}
+            })
+            .await;
+            timed_out = changed.is_err();
+        }
+    }
+
+    // Reject callback urls that can't be delivered to
+    fn check_callback(&self, job: &JobSubmission) -> Result<(), ApiError> {
+        if let Some(url) = &job.callback_url {
+            Url::parse(url).map_err(|e| ApiError::BadRequest(format!("callback_url: {e}")))?;
+        }
+        Ok(())
+    }
+
+    // Reject submissions of job types the executor doesn't run
+    fn check_job_type(&self, job: &JobSubmission) -> Result<(), ApiError> {
+        let job_type = job.kind.name();
+        if !self.job_types.iter().any(|t| t == job_type) {
+            return Err(ApiError::BadRequest(format!(
+                "unknown job type '{job_type}'"
+            )));
+        }
+        Ok(())
+    }
+
+    // Reject submissions to queues that don't exist
+    fn check_queue(&self, job: &JobSubmission) -> Result<(), ApiError> {
+        if !self.queue_names.contains(&job.queue) {
+            return Err(ApiError::BadRequest(format!(
+                "unknown queue '{}'",
+                job.queue
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_env_profile(&self, job: &JobSubmission) -> Result<(), ApiError> {
+        if let Some(name) = &job.env_profile
+            && !self.env_profiles.contains(name)
+        {
+            return Err(ApiError::BadRequest(format!(
+                "env_profile: unknown profile '{name}'"
+            )));
+        }
+        Ok(())
+    }
+
+    // Check a submission as submit does, timing the checks
+    fn validate(&self, job: &JobSubmission) -> Result<JobTiming, ApiError> {
+        let validating = Instant::now();
+        self.check_submission(job)?;
+        self.check_shedding(job)?;
+        Ok(JobTiming::accepted(validating.elapsed()))
+    }
+
+    // In shed mode, reject submissions below the shed priority
+    fn check_shedding(&self, job: &JobSubmission) -> Result<(), ApiError> {
+        if job.priority < self.shed_below && self.shedding.load(Ordering::Relaxed) {
+            return Err(ApiError::Overloaded);
+        }
+        Ok(())
+    }
+
+    /**
+     * get_jobs: a snapshot of every job the pool holds: running, then
+     * pending, then completed (oldest first)
+     * queue: only jobs submitted to this queue
+     */
+    pub async fn get_jobs(&self, queue: Option<&str>) -> Result<Vec<JobView>, ApiError> {
+        let in_queue = |job: &Job| queue.is_none_or(|q| job.submission.queue == q);
+        let listed = self
+            .get_jobs_as(in_queue, None, |job| JobView::from(job))
+            .await?;
+        Ok(listed.jobs)
+    }
+
+    // Where listings read jobs: the latest snapshot, if there is one
+    async fn job_source(&self) -> JobSource<'_> {
+        match self.snapshots.latest() {
+            Some(snapshot) => JobSource::Snapshot(snapshot),
+            None => JobSource::Live(self.pool.lock().await),
+        }
+    }
+
+    /**
+     * get_jobs_as: get_jobs, only the jobs filter keeps, in sort's order
+     * if given, each as view makes it
+     * Read from the latest snapshot, when snapshots are on
+     */
+    pub async fn get_jobs_as<T>(
+        &self,
+        filter: impl Fn(&Job) -> bool,
+        sort: Option<&JobSort>,
+        view: impl Fn(&Job) -> T,
+    ) -> Result<JobListing<T>, ApiError> {
+        let source = self.job_source().await;
+        let mut out = Vec::new();
+        source.for_each_job(|job| {
+            if filter(job) {
+                out.push((sort.and_then(|s| s.value(job)), view(job)));
             }
+        })?;
+        let as_of = source.as_of();
+        drop(source);
+        if let Some(sort) = sort {
+            sort.sort(&mut out);
         }
+        Ok(JobListing {
+            jobs: out.into_iter().map(|(_, job)| job).collect(),
+            next: None,
+            as_of,
+        })
+    }
+
+    /**
+     * get_jobs_page: a page of the jobs filter keeps, in id order, each as
+     * view makes it, with the cursor of the next page (None: this is the
+     * last)
+     * Read from the latest snapshot, when snapshots are on
+     */
+    pub async fn get_jobs_page<T>(
+        &self,
+        filter: impl Fn(&Job) -> bool,
+        page: &Page,
+        view: impl Fn(&Job) -> T,
+    ) -> Result<JobListing<T>, ApiError> {
+        let p = self.job_source().await;
+        // ids first, so only the page's jobs are viewed
+        let mut ids = Vec::new();
+        p.for_each_job(|job| {
+            if page.holds(job.id) && filter(job) {
+                ids.push(job.id);
+            }
+        })?;
+        ids.sort_unstable();
+        let next = (ids.len() > page.limit).then(|| ids[page.limit - 1]);
+        ids.truncate(page.limit);
+        let on_page: HashSet<JobId> = ids.into_iter().collect();
+        let mut out = Vec::with_capacity(on_page.len());
+        p.for_each_job(|job| {
+            if on_page.contains(&job.id) {
+                out.push((job.id, view(job)));
+            }
+        })?;
+        let as_of = p.as_of();
+        drop(p);
+        out.sort_unstable_by_key(|(id, _)| *id);
+        Ok(JobListing {
+            jobs: out.into_iter().map(|(_, job)| job).collect(),
+            next,
+            as_of,
+        })
+    }
+
+    /**
+     * get_jobs_sorted_page: get_jobs_page, with the pages following sort
+     * (ties in id order); the cursor is the last job of the page before
+     * NOTE: BadRequest if that job no longer matches filter
+     */
+    pub async fn get_jobs_sorted_page<T>(
+        &self,
+        filter: impl Fn(&Job) -> bool,
+        sort: &JobSort,
+        page: &Page,
+        view: impl Fn(&Job) -> T,
+    ) -> Result<JobListing<T>, ApiError> {
+        let p = self.job_source().await;
+        let mut keys = Vec::new();
+        p.for_each_job(|job| {
+            if filter(job) {
+                keys.push((sort.value(job), job.id));
+            }
+        })?;
+        keys.sort_unstable_by_key(|(_, id)| *id);
+        sort.sort(&mut keys);
+        let start = match page.after {
+            Some(after) => {
+                keys.iter()
+                    .position(|(_, id)| *id == after)
+                    .ok_or_else(|| {
+                        ApiError::BadRequest(format!(
+                            "cursor {after}: no longer in the results; search again from the start"
+                        ))
+                    })?
+                    + 1
+            }
+            None => 0,
+        };
+        let ids: Vec<JobId> = keys[start..]
+            .iter()
+            .take(page.limit)
+            .map(|(_, id)| *id)
+            .collect();
+        let next = (keys.len() > start + page.limit).then(|| ids[page.limit - 1]);
+        let position: HashMap<JobId, usize> =
+            ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let mut out = Vec::with_capacity(ids.len());
+        p.for_each_job(|job| {
+            if let Some(&at) = position.get(&job.id) {
+                out.push((at, view(job)));
+            }
+        })?;
+        let as_of = p.as_of();
         drop(p);
-        Ok(out)
+        out.sort_unstable_by_key(|(at, _)| *at);
+        Ok(JobListing {
+            jobs: out.into_iter().map(|(_, job)| job).collect(),
+            next,
+            as_of,
+        })
     }
 }
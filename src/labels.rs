@@ -0,0 +1,107 @@
+/*! Labels module for async orchestrator
+ * Job labels: key/value tags a submission carries, to filter jobs by and
+ * break metrics down by
+ *
+ * A submission's labels ({"team": "payments"}) are kept on the job, listed
+ * with it and carried on its state change events, and so the event log.
+ * GET /jobs?label=team:payments lists only the jobs labelled so ("team"
+ * alone: with any value; several, comma separated: all must match), a
+ * bulk cancel's filter may require labels, and GET /metrics counts
+ * finished jobs by label.
+ * NOTE: a label key may not contain ':'
+ */
+use crate::api_error::ApiError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+// most labels a submission may carry
+const MAX_LABELS: usize = 16;
+// longest label key and value, in bytes
+const MAX_KEY_LEN: usize = 63;
+const MAX_VALUE_LEN: usize = 255;
+
+/**
+ * Labels
+ * Label key -> value
+ */
+pub type Labels = BTreeMap<String, String>;
+
+/**
+ * check: BadRequest for too many labels, or a key or value out of bounds
+ */
+pub fn check(labels: &Labels) -> Result<(), ApiError> {
+    if labels.len() > MAX_LABELS {
+        return Err(ApiError::BadRequest(format!(
+            "labels: {} labels, over the limit of {MAX_LABELS}",
+            labels.len()
+        )));
+    }
+    for (key, value) in labels {
+        if key.is_empty() || key.len() > MAX_KEY_LEN || key.contains(':') {
+            return Err(ApiError::BadRequest(format!(
+                "labels: key '{key}' must be 1 to {MAX_KEY_LEN} bytes, without ':'"
+            )));
+        }
+        if value.len() > MAX_VALUE_LEN {
+            return Err(ApiError::BadRequest(format!(
+                "labels: value of '{key}' over {MAX_VALUE_LEN} bytes"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/**
+ * LabelSelector
+ * Labels a job must carry: each key, with the value given (if any)
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LabelSelector(pub Vec<(String, Option<String>)>);
+
+impl LabelSelector {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn matches(&self, labels: &Labels) -> bool {
+        self.0
+            .iter()
+            .all(|(key, value)| match (labels.get(key), value) {
+                (Some(have), Some(want)) => have == want,
+                (Some(_), None) => true,
+                (None, _) => false,
+            })
+    }
+}
+
+// "key:value,key,..."
+impl FromStr for LabelSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let wanted = s
+            .split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+            .map(|entry| match entry.split_once(':') {
+                Some((key, value)) => (key.to_string(), Some(value.to_string())),
+                None => (entry.to_string(), None),
+            })
+            .collect();
+        Ok(LabelSelector(wanted))
+    }
+}
+
+/**
+ * LabelCounts
+ * Finished jobs carrying a label, by how they ended
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct LabelCounts {
+    pub succeeded: u64,
+    // failed, timed out or past their deadline
+    pub failed: u64,
+    // cancelled or skipped
+    pub cancelled: u64,
+}
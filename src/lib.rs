@@ -0,0 +1,59 @@
+/*! Async job orchestrator
+ * Library root: job pool, HTTP API, and supporting modules
+ */
+pub mod access;
+pub mod api;
+pub mod api_error;
+pub mod autoscale;
+pub mod breaker;
+pub mod cache;
+pub mod chaining;
+pub mod chat;
+pub mod checkpoints;
+pub mod children;
+pub mod client;
+pub mod clock;
+pub mod config;
+pub mod cron;
+pub mod dedup;
+pub mod defaults;
+pub mod durations;
+pub mod egress;
+pub mod email;
+pub mod estimate;
+pub mod events;
+pub mod executor;
+pub mod failure;
+pub mod fields;
+pub mod handlers;
+pub mod health;
+pub mod history;
+pub mod hooks;
+pub mod http_client;
+pub mod ids;
+pub mod jobs;
+pub mod labels;
+pub mod listing;
+pub mod logs;
+pub mod notify;
+pub mod overload;
+pub mod pause;
+pub mod profiles;
+pub mod queues;
+pub mod ratelimit;
+pub mod replay;
+pub mod resources;
+pub mod schedules;
+pub mod schemas;
+pub mod scratch;
+#[cfg(feature = "sim")]
+pub mod sim;
+pub mod snapshot;
+pub mod tenants;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod timing;
+pub mod unique;
+pub mod usage;
+pub mod webhooks;
+pub mod workflows;
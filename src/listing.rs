@@ -0,0 +1,370 @@
+/*! Listing module for async orchestrator
+ * Filtering and sorting of job listings
+ *
+ * GET /jobs takes, besides ?queue= and ?label=:
+ *   state           comma separated states, e.g. "running,queued"
+ *   type            comma separated job types
+ *   created_after   jobs created at or after this time (RFC 3339)
+ *   created_before  jobs created before this time
+ *   sort            created_at, started_at, finished_at, priority or
+ *                   type; descending with a leading '-' ("-created_at")
+ *   limit, cursor   a page: see below
+ * Every filter given must match. Unsorted, jobs are listed running, then
+ * pending, then completed (oldest first).
+ * A listing given a limit is paged: it lists up to limit jobs in id order
+ * (creation order, for the built-in id formats), and when more follow,
+ * answers with an X-Next-Cursor header; passing that back as ?cursor=
+ * lists the next page. Pages don't take a sort.
+ * POST /jobs/search takes the same criteria, and more, as a JSON query
+ * (see JobSearch): started and finished time ranges, and text the result
+ * must contain. A search may be both sorted and paged: its pages follow
+ * the sort, ties in id order.
+ * NOTE: jobs without the sort key (one not yet started, say) are listed
+ * last whichever the direction. A cursor is opaque, but only as stable as
+ * the jobs the pool still holds: a job created behind it isn't listed,
+ * and a sorted search's cursor fails once its job no longer matches.
+ * Searches scan the jobs the pool holds: there is no index to narrow them
+ */
+use crate::api_error::ApiError;
+use crate::ids::JobId;
+use crate::jobs::{Job, Priority, State};
+use crate::labels::LabelSelector;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+// jobs on a page when a cursor is given without a limit
+const DEFAULT_PAGE_LIMIT: usize = 100;
+// most jobs a page may ask for
+pub const MAX_PAGE_LIMIT: usize = 1000;
+// response header carrying the cursor of the next page
+pub const NEXT_CURSOR_HEADER: &str = "X-Next-Cursor";
+
+/**
+ * ListFilter
+ * Which jobs a listing keeps; empty criteria keep every job
+ */
+#[derive(Debug, Clone, Default)]
+pub struct ListFilter {
+    pub queue: Option<String>,
+    pub states: Vec<State>,
+    pub job_types: Vec<String>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub started: TimeRange,
+    pub finished: TimeRange,
+    pub labels: LabelSelector,
+    // text the result must contain, ignoring case; kept lowercased
+    pub result_contains: Option<String>,
+}
+
+impl ListFilter {
+    /**
+     * with_states: keep jobs in any of the comma separated states
+     */
+    pub fn with_states(mut self, list: &str) -> Result<Self, ApiError> {
+        self.states = names(list)
+            .map(str::parse)
+            .collect::<Result<_, String>>()
+            .map_err(ApiError::BadRequest)?;
+        Ok(self)
+    }
+
+    /**
+     * with_types: keep jobs of any of the comma separated job types, each
+     * one of known
+     */
+    pub fn with_types(mut self, list: &str, known: &[String]) -> Result<Self, ApiError> {
+        self.job_types = job_types(names(list), known)?;
+        Ok(self)
+    }
+
+    pub fn matches(&self, job: &Job) -> bool {
+        let submission = job.submission();
+        let created = job.created_at();
+        self.queue.as_deref().is_none_or(|q| submission.queue == q)
+            && (self.states.is_empty() || self.states.contains(job.state()))
+            && (self.job_types.is_empty()
+                || self.job_types.iter().any(|t| submission.kind.name() == t))
+            && self.created_after.is_none_or(|after| created >= after)
+            && self.created_before.is_none_or(|before| created < before)
+            && self.started.holds(job.started_at())
+            && self.finished.holds(job.finished_at())
+            && self.labels.matches(&submission.labels)
+            && self
+                .result_contains
+                .as_deref()
+                .is_none_or(|text| job.result().to_lowercase().contains(text))
+    }
+}
+
+// Known job types, or BadRequest naming the first unknown
+fn job_types<'a>(
+    names: impl Iterator<Item = &'a str>,
+    known: &[String],
+) -> Result<Vec<String>, ApiError> {
+    names
+        .map(|name| {
+            if known.iter().any(|t| t == name) {
+                Ok(name.to_string())
+            } else {
+                Err(ApiError::BadRequest(format!("unknown job type: {name}")))
+            }
+        })
+        .collect()
+}
+
+// The non-empty names of a comma separated list
+fn names(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+}
+
+/**
+ * TimeRange
+ * Times at or after after, and before before; an end left out is open
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TimeRange {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<DateTime<Utc>>,
+}
+
+impl TimeRange {
+    /**
+     * holds: whether a time falls in the range; with an end given, a job
+     * without the time (not yet started, say) doesn't
+     */
+    pub fn holds(&self, at: Option<DateTime<Utc>>) -> bool {
+        if self.after.is_none() && self.before.is_none() {
+            return true;
+        }
+        at.is_some_and(|at| {
+            self.after.is_none_or(|after| at >= after)
+                && self.before.is_none_or(|before| at < before)
+        })
+    }
+}
+
+/**
+ * JobSearch
+ * A POST /jobs/search query; every criterion given must match
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct JobSearch {
+    #[serde(default)]
+    pub queue: Option<String>,
+    // any of these states
+    #[serde(default)]
+    pub states: Vec<State>,
+    // any of these job types
+    #[serde(default)]
+    pub types: Vec<String>,
+    // labels the jobs must all carry: "key:value", or "key" for any value
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub created: TimeRange,
+    #[serde(default)]
+    pub started: TimeRange,
+    #[serde(default)]
+    pub finished: TimeRange,
+    // text the result must contain, ignoring case
+    #[serde(default)]
+    pub result_contains: Option<String>,
+    // a sort key, descending with a leading '-'
+    #[serde(default)]
+    pub sort: Option<String>,
+    // page size, and where the page starts (a previous page's next cursor)
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub cursor: Option<String>,
+    // only these fields of each job, and heavy fields to add, as GET
+    // /jobs takes them
+    #[serde(default)]
+    pub fields: Vec<String>,
+    #[serde(default)]
+    pub include: Vec<String>,
+}
+
+impl JobSearch {
+    /**
+     * filter: the jobs the search keeps; its types must be among known
+     */
+    pub fn filter(&self, known: &[String]) -> Result<ListFilter, ApiError> {
+        let labels: LabelSelector = self
+            .labels
+            .join(",")
+            .parse()
+            .map_err(ApiError::BadRequest)?;
+        Ok(ListFilter {
+            queue: self.queue.clone(),
+            states: self.states.clone(),
+            job_types: job_types(self.types.iter().map(String::as_str), known)?,
+            created_after: self.created.after,
+            created_before: self.created.before,
+            started: self.started,
+            finished: self.finished,
+            labels,
+            result_contains: self
+                .result_contains
+                .as_deref()
+                .filter(|text| !text.is_empty())
+                .map(str::to_lowercase),
+        })
+    }
+}
+
+/**
+ * SortKey
+ * What a listing may be sorted by
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    CreatedAt,
+    StartedAt,
+    FinishedAt,
+    Priority,
+    Type,
+}
+
+/**
+ * SortValue
+ * A job's sort key value
+ */
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SortValue {
+    Time(DateTime<Utc>),
+    Priority(Priority),
+    Text(String),
+}
+
+/**
+ * JobSort
+ * The order a listing is given in
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JobSort {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+impl JobSort {
+    /**
+     * value: a job's value of the sort key; None: it has none yet
+     */
+    pub fn value(&self, job: &Job) -> Option<SortValue> {
+        match self.key {
+            SortKey::CreatedAt => Some(SortValue::Time(job.created_at())),
+            SortKey::StartedAt => job.started_at().map(SortValue::Time),
+            SortKey::FinishedAt => job.finished_at().map(SortValue::Time),
+            SortKey::Priority => Some(SortValue::Priority(job.submission().priority)),
+            SortKey::Type => Some(SortValue::Text(job.submission().kind.name().to_string())),
+        }
+    }
+
+    /**
+     * sort: order listed jobs, each with its sort key value
+     * Jobs with equal values keep their order
+     */
+    pub fn sort<T>(&self, jobs: &mut [(Option<SortValue>, T)]) {
+        jobs.sort_by(|(a, _), (b, _)| match (a, b) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(a), Some(b)) if self.descending => b.cmp(a),
+            (Some(a), Some(b)) => a.cmp(b),
+        });
+    }
+}
+
+// "key" or "-key"
+impl FromStr for JobSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, descending) = match s.strip_prefix('-') {
+            Some(name) => (name, true),
+            None => (s, false),
+        };
+        let key = match name {
+            "created_at" => SortKey::CreatedAt,
+            "started_at" => SortKey::StartedAt,
+            "finished_at" => SortKey::FinishedAt,
+            "priority" => SortKey::Priority,
+            "type" => SortKey::Type,
+            other => {
+                return Err(format!(
+                    "unknown sort key: {other} (expected created_at, started_at, \
+                     finished_at, priority or type)"
+                ));
+            }
+        };
+        Ok(Self { key, descending })
+    }
+}
+
+/**
+ * Page
+ * A page of a listing in id order: up to limit jobs after a cursor
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Page {
+    // the last job of the page before; None: the first page
+    pub after: Option<JobId>,
+    pub limit: usize,
+}
+
+impl Page {
+    /**
+     * parse: a page from the limit and cursor query parameters; None:
+     * neither was given, and the listing isn't paged
+     */
+    pub fn parse(limit: Option<usize>, cursor: Option<&str>) -> Result<Option<Self>, ApiError> {
+        if limit.is_none() && cursor.is_none() {
+            return Ok(None);
+        }
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        if !(1..=MAX_PAGE_LIMIT).contains(&limit) {
+            return Err(ApiError::BadRequest(format!(
+                "limit must be 1 to {MAX_PAGE_LIMIT}"
+            )));
+        }
+        let after = cursor
+            .map(|cursor| {
+                cursor
+                    .parse()
+                    .map_err(|_| ApiError::BadRequest(format!("invalid cursor: {cursor}")))
+            })
+            .transpose()?;
+        Ok(Some(Self { after, limit }))
+    }
+
+    /**
+     * holds: whether a job with this id falls after the cursor
+     */
+    pub fn holds(&self, id: JobId) -> bool {
+        self.after.is_none_or(|after| id > after)
+    }
+}
+
+/**
+ * JobListing
+ * Jobs listed, with the next page's cursor if paged and more follow, and
+ * when the snapshot they were read from was taken
+ */
+#[derive(Debug, Clone)]
+pub struct JobListing<T> {
+    pub jobs: Vec<T>,
+    pub next: Option<JobId>,
+    // None: read from the live pool
+    pub as_of: Option<DateTime<Utc>>,
+}
@@ -1,8 +1,30 @@
 /*! Logss module for async orchestrator
  * Defines log structures
+ *
+ * GET /groups/{id}/logs downloads the logs of all of a group's jobs (a
+ * map's children, a workflow run's steps) as one NDJSON stream, a line
+ * {"job_id", "at", "line"} per log line:
+ *   ?order=interleaved  every job's lines by the time they were logged
+ *                       (the default)
+ *   ?order=segmented    job by job, in creation order
+ * NOTE: the logs are copied when asked for, so lines logged later aren't
+ * in the download
+ *
+ * An execution writes its job's log through ExecContext::log. With
+ * LOG_RATE_LIMIT_BYTES set, each execution may write that many bytes a
+ * second (up to a second's worth at once); lines past the limit are
+ * dropped, counted in the job's log_dropped and GET /metrics, and noted in
+ * the log once lines get through again. The pool's own lines (queued,
+ * started, finished, ...) are never limited.
  */
+use crate::ids::JobId;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::io::Write;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
 
 // Log: an append-only fixed size buffer
 
@@ -11,6 +33,7 @@ const TRUNCATION_MSG: &str = "...[ TRUNCATED ]...\n";
 const AVAILABLE_SIZE: usize = BLOCK_SIZE - TRUNCATION_MSG.len();
 
 #[derive(Copy, Clone)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum LogLevel {
     DEBUG,
     INFO,
@@ -34,6 +57,38 @@ pub struct LogBuffer {
     data: Box<[u8; BLOCK_SIZE]>,
     len: usize,
     full: bool,
+    // where each logged line starts, and when it was logged
+    stamps: Vec<(usize, DateTime<Utc>)>,
+    // None: executions may log as fast as they like
+    limiter: Option<LogLimiter>,
+    // bytes executions logged past the rate limit
+    dropped: u64,
+}
+
+// A token bucket of log bytes, refilled every second
+#[derive(Clone, Debug)]
+struct LogLimiter {
+    bytes_per_sec: u64,
+    tokens: f64,
+    refilled: Instant,
+    // dropped since the log last said so
+    unnoted: u64,
+}
+
+impl LogLimiter {
+    // Take bytes from the bucket; false: not enough in it
+    fn take(&mut self, bytes: usize) -> bool {
+        let now = Instant::now();
+        let rate = self.bytes_per_sec as f64;
+        let elapsed = now.duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(rate);
+        self.refilled = now;
+        if self.tokens < bytes as f64 {
+            return false;
+        }
+        self.tokens -= bytes as f64;
+        true
+    }
 }
 
 impl fmt::Display for LogBuffer {
@@ -51,10 +106,7 @@ impl fmt::Debug for LogBuffer {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let used = &self.data[..self.len];
 
-        let text = match std::str::from_utf8(used) {
-            Ok(s) => s,
-            Err(_) => "<non-utf8 log data>",
-        };
+        let text = std::str::from_utf8(used).unwrap_or("<non-utf8 log data>");
 
         f.debug_struct("LogBuffer")
             .field("len", &self.len)
@@ -89,23 +141,128 @@ impl std::io::Write for LogBuffer {
     }
 }
 
+impl Default for LogBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl LogBuffer {
     pub fn new() -> Self {
         Self {
             len: 0,
             data: Box::new([0; BLOCK_SIZE]),
             full: false,
+            stamps: Vec::new(),
+            limiter: None,
+            dropped: 0,
         }
     }
 
+    // bytes of log written so far
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // bytes the buffer holds on to, however little is written
+    pub fn capacity(&self) -> usize {
+        BLOCK_SIZE + self.stamps.capacity() * std::mem::size_of::<(usize, DateTime<Utc>)>()
+    }
+
     pub fn log(&mut self, level: LogLevel, msg: &str) {
-        let _ = write!(self, "[{}] {}\n", level, msg);
+        self.stamp();
+        let _ = writeln!(self, "[{}] {}", level, msg);
     }
 
     pub fn logf(&mut self, level: LogLevel, args: fmt::Arguments<'_>) {
+        self.stamp();
         let _ = self.write_fmt(format_args!("[{}] {}\n", level, args));
     }
 
+    /**
+     * lines: each line of the log, with when it was logged (None: written
+     * other than through log or logf, before any line that was)
+     */
+    pub fn lines(&self) -> Vec<(Option<DateTime<Utc>>, &str)> {
+        let text = std::str::from_utf8(&self.data[..self.len]).unwrap_or("<non-utf8 log data>");
+        let mut lines = Vec::new();
+        let mut start = 0;
+        let mut stamps = self.stamps.iter().peekable();
+        let mut at = None;
+        for line in text.lines() {
+            // the latest line logged that starts at or before this one
+            while let Some((_, time)) = stamps.next_if(|(offset, _)| *offset <= start) {
+                at = Some(*time);
+            }
+            lines.push((at, line));
+            start += line.len() + 1;
+        }
+        lines
+    }
+
+    /**
+     * limit: let executions log up to bytes_per_sec, with a second's worth
+     * free to start
+     */
+    pub fn limit(&mut self, bytes_per_sec: u64) {
+        self.limiter = Some(LogLimiter {
+            bytes_per_sec,
+            tokens: bytes_per_sec as f64,
+            refilled: Instant::now(),
+            unnoted: 0,
+        });
+    }
+
+    /**
+     * log_limited: log an execution's line, unless it is over the rate
+     * limit: then it is dropped and counted
+     */
+    pub fn log_limited(&mut self, level: LogLevel, msg: &str) {
+        let line = format!("[{}] {}\n", level, msg);
+        if let Some(limiter) = &mut self.limiter {
+            if !limiter.take(line.len()) {
+                limiter.unnoted += line.len() as u64;
+                self.dropped += line.len() as u64;
+                return;
+            }
+            self.note_dropped();
+        }
+        self.stamp();
+        let _ = self.write_all(line.as_bytes());
+    }
+
+    /**
+     * note_dropped: say in the log how much was dropped since it last said
+     */
+    pub fn note_dropped(&mut self) {
+        let Some(limiter) = &mut self.limiter else {
+            return;
+        };
+        let unnoted = std::mem::take(&mut limiter.unnoted);
+        if unnoted > 0 {
+            self.logf(
+                LogLevel::WARNING,
+                format_args!("log rate limited: {unnoted} bytes dropped"),
+            );
+        }
+    }
+
+    // bytes executions logged past the rate limit
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    // Mark the next line as logged now
+    fn stamp(&mut self) {
+        if !self.full {
+            self.stamps.push((self.len, Utc::now()));
+        }
+    }
+
     fn write_bytes(&mut self, bytes: &[u8]) {
         debug_assert!(self.len + bytes.len() <= BLOCK_SIZE);
         let end = self.len + bytes.len();
@@ -118,3 +275,69 @@ impl LogBuffer {
         self.len += amount;
     }
 }
+
+type LogWriter = dyn Fn(LogLevel, &str) + Send + Sync;
+
+/**
+ * JobLog
+ * Where an execution writes its job's log; the default writes nowhere
+ */
+#[derive(Clone, Default)]
+pub struct JobLog(Option<Arc<LogWriter>>);
+
+impl fmt::Debug for JobLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("JobLog").field(&self.0.is_some()).finish()
+    }
+}
+
+impl JobLog {
+    pub fn new(write: impl Fn(LogLevel, &str) + Send + Sync + 'static) -> Self {
+        Self(Some(Arc::new(write)))
+    }
+
+    pub fn log(&self, level: LogLevel, msg: &str) {
+        if let Some(write) = &self.0 {
+            write(level, msg);
+        }
+    }
+}
+
+/**
+ * LogOrder
+ * How a group's log lines are ordered
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LogOrder {
+    // every job's lines by when they were logged
+    #[default]
+    Interleaved,
+    // job by job, in creation order
+    Segmented,
+}
+
+impl FromStr for LogOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "interleaved" => Ok(LogOrder::Interleaved),
+            "segmented" => Ok(LogOrder::Segmented),
+            other => Err(format!(
+                "unknown log order: {other} (expected interleaved or segmented)"
+            )),
+        }
+    }
+}
+
+/**
+ * GroupLogLine
+ * A line of a group job's log
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GroupLogLine {
+    pub job_id: JobId,
+    // None: written without a time
+    pub at: Option<DateTime<Utc>>,
+    pub line: String,
+}
@@ -1,23 +1,22 @@
-mod api;
-mod api_error;
-mod jobs;
-mod logs;
-
-use jobs::JobPool;
+use async_job_orchestrator::api;
+use async_job_orchestrator::config::Config;
+use async_job_orchestrator::jobs::JobPool;
 
 #[tokio::main]
 async fn main() {
     println!("[main] Starting application");
+    let config = Config::from_env();
+    println!("[main] Config: {:?}", config);
 
     println!("[main] Starting jobpool");
-    let job_pool = JobPool::start();
+    let job_pool = JobPool::start(&config.pool);
 
     // Create the router that the API will use
     // Embed the job pool as app specific data
     println!("[main] Creating router");
     let app = api::create_router(job_pool.clone());
 
-    let addr = "0.0.0.0:3000";
+    let addr = format!("0.0.0.0:{}", config.port);
     println!("[main] Serving on {}", addr);
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
 
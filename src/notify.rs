@@ -0,0 +1,345 @@
+/*! Notify module for async orchestrator
+ * Routes job notifications to pluggable channels by declarative rules
+ *
+ * Channels are named and configured once (NOTIFY_CHANNELS); rules say
+ * which events go where (NOTIFY_RULES). A new kind of channel only needs
+ * a NotificationChannel impl and a ChannelKind variant.
+ */
+use crate::chat::{ChatChannel, ChatService};
+use crate::email::{self, EmailChannel, EmailConfig, Mailer};
+use crate::http_client::Url;
+use crate::jobs::{Job, State};
+use crate::webhooks::{WebhookChannel, Webhooks};
+use std::fmt;
+use std::str::FromStr;
+
+/**
+ * NotifyEvent
+ * Job events that can be routed to channels
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NotifyEvent {
+    Failed,
+    Succeeded,
+}
+
+impl NotifyEvent {
+    // The event a job in this state represents, if any
+    pub fn for_state(state: &State) -> Option<Self> {
+        match state {
+            State::FAILED | State::TIMED_OUT | State::DEADLINE_MISSED => Some(NotifyEvent::Failed),
+            State::SUCCEEDED => Some(NotifyEvent::Succeeded),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for NotifyEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            NotifyEvent::Failed => "failed",
+            NotifyEvent::Succeeded => "succeeded",
+        })
+    }
+}
+
+impl FromStr for NotifyEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "failed" => Ok(NotifyEvent::Failed),
+            "succeeded" => Ok(NotifyEvent::Succeeded),
+            _ => Err(format!("unknown notify event '{s}'")),
+        }
+    }
+}
+
+/**
+ * Notification
+ * What a channel is asked to send
+ */
+pub struct Notification<'a> {
+    pub event: NotifyEvent,
+    pub job: &'a Job,
+}
+
+/**
+ * NotificationChannel
+ * A destination for notifications (email, chat, webhook, ...)
+ * NOTE: called with the pool locked: queue the work and return, never
+ * block on the network
+ */
+pub trait NotificationChannel: Send + Sync {
+    fn send(&self, notification: &Notification) -> Result<(), String>;
+}
+
+/**
+ * ChannelKind
+ * Where a configured channel delivers
+ */
+#[derive(Debug, Clone)]
+pub enum ChannelKind {
+    // recipients, sent through the SMTP relay
+    Email(Vec<String>),
+    Slack(String),
+    Discord(String),
+    // POSTs the job record
+    Webhook(String),
+}
+
+/**
+ * ChannelConfig
+ * A named channel
+ */
+#[derive(Debug, Clone)]
+pub struct ChannelConfig {
+    pub name: String,
+    pub kind: ChannelKind,
+}
+
+impl FromStr for ChannelConfig {
+    type Err = String;
+
+    // "name=kind:target"; target is a url, or '|'-separated addresses for email
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("channel '{s}' is not name=kind:target");
+        let (name, spec) = s.split_once('=').ok_or_else(invalid)?;
+        let (kind, target) = spec.split_once(':').ok_or_else(invalid)?;
+        let url = || {
+            Url::parse(target)
+                .map(|_| target.to_string())
+                .map_err(|e| format!("channel '{name}': {e}"))
+        };
+        let kind = match kind {
+            "email" => match email::parse_addresses(target, '|')? {
+                to if to.is_empty() => return Err(format!("channel '{name}': no recipients")),
+                to => ChannelKind::Email(to),
+            },
+            "slack" => ChannelKind::Slack(url()?),
+            "discord" => ChannelKind::Discord(url()?),
+            "webhook" => ChannelKind::Webhook(url()?),
+            _ => return Err(format!("channel '{name}': unknown kind '{kind}'")),
+        };
+        if name.trim().is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            name: name.trim().to_string(),
+            kind,
+        })
+    }
+}
+
+/**
+ * ChannelList
+ * Comma-separated channels
+ */
+#[derive(Debug, Clone, Default)]
+pub struct ChannelList(pub Vec<ChannelConfig>);
+
+impl FromStr for ChannelList {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .filter(|c| !c.trim().is_empty())
+            .map(|c| c.trim().parse())
+            .collect::<Result<_, _>>()
+            .map(ChannelList)
+    }
+}
+
+/**
+ * Filter
+ * A job field a rule can match on
+ */
+#[derive(Debug, Clone)]
+pub enum Filter {
+    Queue(String),
+    Type(String),
+    Priority(String),
+}
+
+impl Filter {
+    fn matches(&self, job: &Job) -> bool {
+        let submission = job.submission();
+        match self {
+            Filter::Queue(queue) => submission.queue == *queue,
+            Filter::Type(job_type) => submission.kind.name() == job_type,
+            Filter::Priority(priority) => {
+                format!("{:?}", submission.priority).eq_ignore_ascii_case(priority)
+            }
+        }
+    }
+}
+
+/**
+ * Rule
+ * Route an event, optionally filtered, to a channel
+ */
+#[derive(Debug, Clone)]
+pub struct Rule {
+    // None: any event
+    pub event: Option<NotifyEvent>,
+    // all must match
+    pub filters: Vec<Filter>,
+    pub channel: String,
+}
+
+impl Rule {
+    fn matches(&self, event: NotifyEvent, job: &Job) -> bool {
+        self.event.is_none_or(|e| e == event) && self.filters.iter().all(|f| f.matches(job))
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    // "event[,field=value...]->channel", event "*" for any
+    // e.g. "failed,queue=critical->oncall"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("rule '{s}' is not event[,field=value...]->channel");
+        let (matcher, channel) = s.split_once("->").ok_or_else(invalid)?;
+        let mut parts = matcher.split(',').map(str::trim);
+        let event = match parts.next() {
+            Some("*") => None,
+            Some(event) => Some(event.parse()?),
+            None => return Err(invalid()),
+        };
+        let filters = parts
+            .map(|filter| match filter.split_once('=') {
+                Some(("queue", v)) => Ok(Filter::Queue(v.to_string())),
+                Some(("type", v)) => Ok(Filter::Type(v.to_string())),
+                Some(("priority", v)) => Ok(Filter::Priority(v.to_string())),
+                _ => Err(format!("rule '{s}': unknown filter '{filter}'")),
+            })
+            .collect::<Result<_, _>>()?;
+        let channel = channel.trim();
+        if channel.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            event,
+            filters,
+            channel: channel.to_string(),
+        })
+    }
+}
+
+/**
+ * RuleList
+ * Semicolon-separated rules
+ */
+#[derive(Debug, Clone, Default)]
+pub struct RuleList(pub Vec<Rule>);
+
+impl FromStr for RuleList {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(';')
+            .filter(|r| !r.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map(RuleList)
+    }
+}
+
+/**
+ * NotifyConfig
+ */
+#[derive(Debug, Clone, Default)]
+pub struct NotifyConfig {
+    pub channels: Vec<ChannelConfig>,
+    pub rules: Vec<Rule>,
+    // SMTP relay for email channels; None: email channels are disabled
+    pub email: Option<EmailConfig>,
+}
+
+/**
+ * Notifier
+ * Named channels and the rules routing to them
+ */
+pub struct Notifier {
+    channels: Vec<(String, Box<dyn NotificationChannel>)>,
+    rules: Vec<Rule>,
+}
+
+impl Notifier {
+    // Build the configured channels
+    // NOTE: misconfigured channels and rules are reported and skipped
+    pub fn new(config: &NotifyConfig, webhooks: &Webhooks) -> Self {
+        let mailer = config.email.clone().map(Mailer::start);
+        let mut channels: Vec<(String, Box<dyn NotificationChannel>)> = Vec::new();
+        for c in &config.channels {
+            let channel: Box<dyn NotificationChannel> = match &c.kind {
+                ChannelKind::Email(to) => match &mailer {
+                    Some(mailer) => Box::new(EmailChannel::new(mailer.clone(), to.clone())),
+                    None => {
+                        println!(
+                            "[Notifier]: channel '{}': email needs SMTP_SERVER, skipping",
+                            c.name
+                        );
+                        continue;
+                    }
+                },
+                ChannelKind::Slack(url) => {
+                    Box::new(ChatChannel::new(ChatService::Slack, url, webhooks.clone()))
+                }
+                ChannelKind::Discord(url) => Box::new(ChatChannel::new(
+                    ChatService::Discord,
+                    url,
+                    webhooks.clone(),
+                )),
+                ChannelKind::Webhook(url) => Box::new(WebhookChannel::new(url, webhooks.clone())),
+            };
+            channels.push((c.name.clone(), channel));
+        }
+
+        let rules = config
+            .rules
+            .iter()
+            .filter(|rule| {
+                let known = channels.iter().any(|(name, _)| *name == rule.channel);
+                if !known {
+                    println!(
+                        "[Notifier]: ignoring rule for unknown channel '{}'",
+                        rule.channel
+                    );
+                }
+                known
+            })
+            .cloned()
+            .collect();
+        Self { channels, rules }
+    }
+
+    /**
+     * notify: send a finished job to every channel a rule routes it to
+     * NOTE: each channel gets the job at most once, however many rules match
+     */
+    pub fn notify(&self, job: &Job) {
+        let Some(event) = NotifyEvent::for_state(job.state()) else {
+            return;
+        };
+        let notification = Notification { event, job };
+        let mut sent: Vec<&str> = Vec::new();
+        for rule in self.rules.iter().filter(|r| r.matches(event, job)) {
+            if sent.contains(&rule.channel.as_str()) {
+                continue;
+            }
+            sent.push(&rule.channel);
+            if let Some((name, channel)) = self.channels.iter().find(|(n, _)| *n == rule.channel)
+                && let Err(error) = channel.send(&notification)
+            {
+                println!(
+                    "[Notifier]: job {}: channel '{}': {}",
+                    job.id(),
+                    name,
+                    error
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,72 @@
+/*! Overload module for async orchestrator
+ * Detects sustained saturation and drives load-shedding mode
+ */
+use crate::jobs::Priority;
+use std::time::Duration;
+
+/**
+ * LoadShedConfig
+ * Saturation thresholds and hysteresis for shed mode
+ * NOTE: the pool counts as saturated when either threshold is crossed
+ */
+#[derive(Debug, Clone)]
+pub struct LoadShedConfig {
+    // queue depth at or above which the pool counts as saturated
+    pub depth: Option<usize>,
+    // dispatch latency at or above which the pool counts as saturated
+    pub latency: Option<Duration>,
+    // consecutive saturated samples before entering shed mode
+    pub enter_after: u32,
+    // consecutive clear samples before leaving shed mode
+    pub exit_after: u32,
+    // while shedding, submissions below this priority are rejected
+    pub shed_below: Priority,
+}
+
+/**
+ * LoadShedder
+ * Tracks saturation streaks and decides when to enter/leave shed mode
+ */
+pub struct LoadShedder {
+    config: LoadShedConfig,
+    saturated_streak: u32,
+    clear_streak: u32,
+    shedding: bool,
+}
+
+impl LoadShedder {
+    pub fn new(config: LoadShedConfig) -> Self {
+        Self {
+            config,
+            saturated_streak: 0,
+            clear_streak: 0,
+            shedding: false,
+        }
+    }
+
+    // Take one sample of the pool
+    // depth: work waiting for a slot; latency: worst recent dispatch latency
+    // Returns the new shed mode if it should change
+    pub fn observe(&mut self, depth: usize, latency: Duration) -> Option<bool> {
+        let saturated = self.config.depth.is_some_and(|d| depth >= d)
+            || self.config.latency.is_some_and(|l| latency >= l);
+
+        self.saturated_streak = if saturated {
+            self.saturated_streak + 1
+        } else {
+            0
+        };
+        self.clear_streak = if saturated { 0 } else { self.clear_streak + 1 };
+
+        let shedding = if self.shedding {
+            self.clear_streak < self.config.exit_after
+        } else {
+            self.saturated_streak >= self.config.enter_after
+        };
+        if shedding == self.shedding {
+            return None;
+        }
+        self.shedding = shedding;
+        Some(shedding)
+    }
+}
@@ -0,0 +1,98 @@
+/*! Pause module for async orchestrator
+ * Operator pauses of the whole pool or of single queues, with reasons
+ *
+ * A paused queue (or any queue, while the pool is paused) still accepts
+ * submissions, but holds them pending instead of dispatching them; running
+ * jobs finish. With PAUSE_STATE set, pauses are saved to that file on every
+ * change and restored at startup, so a restart during a maintenance freeze
+ * doesn't resume dispatching.
+ */
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::path::Path;
+
+/**
+ * Pause
+ * Why and since when something is paused
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Pause {
+    pub reason: String,
+    pub since: DateTime<Utc>,
+}
+
+/**
+ * PauseState
+ * The pool pause, if any, and each paused queue
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PauseState {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool: Option<Pause>,
+    #[serde(default)]
+    pub queues: BTreeMap<String, Pause>,
+}
+
+impl PauseState {
+    /**
+     * load: read saved pauses; a missing file means nothing is paused
+     */
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read(path) {
+            Ok(raw) => serde_json::from_slice(&raw).map_err(|e| format!("{}: {e}", path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("{}: {e}", path.display())),
+        }
+    }
+
+    /**
+     * save: write the pauses, replacing the file whole
+     */
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let raw = serde_json::to_vec_pretty(self).map_err(|e| e.to_string())?;
+        // write beside the file and rename over it, so a crash mid-write
+        // leaves the old state rather than a torn one
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, raw)
+            .and_then(|_| std::fs::rename(&tmp, path))
+            .map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    /**
+     * paused: why jobs of a queue are held, if they are
+     * The pool pause takes precedence
+     */
+    pub fn paused(&self, queue: &str) -> Option<&Pause> {
+        self.pool.as_ref().or_else(|| self.queues.get(queue))
+    }
+
+    /**
+     * pause: pause the pool (queue None) or one queue
+     * Pausing again only updates the reason
+     */
+    pub fn pause(&mut self, queue: Option<&str>, reason: &str, now: DateTime<Utc>) {
+        let new = || Pause {
+            reason: String::new(),
+            since: now,
+        };
+        let pause = match queue {
+            None => self.pool.get_or_insert_with(new),
+            Some(name) => self.queues.entry(name.to_string()).or_insert_with(new),
+        };
+        pause.reason = reason.to_string();
+    }
+
+    /**
+     * resume: lift the pool pause (queue None) or one queue's
+     */
+    pub fn resume(&mut self, queue: Option<&str>) {
+        match queue {
+            None => self.pool = None,
+            Some(name) => {
+                self.queues.remove(name);
+            }
+        }
+    }
+}
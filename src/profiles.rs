@@ -0,0 +1,124 @@
+/*! Profiles module for async orchestrator
+ * Named sets of environment variables that jobs run with, managed centrally
+ *
+ * Set by ENV_PROFILES as "profile.VAR=value" entries, e.g.
+ * "prod.DB_HOST=db.internal,prod.DB_TOKEN=$PROD_DB_TOKEN". A value starting
+ * with $ is a secret reference: it is read from the orchestrator's own
+ * environment at startup, and never shown (GET /admin/env-profiles lists
+ * where it comes from instead). A submission names a profile in
+ * env_profile; its executor finds the variables in ExecContext::env.
+ * NOTE: values can't contain commas; a profile referencing a variable that
+ * isn't set makes ENV_PROFILES invalid as a whole
+ */
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::str::FromStr;
+
+/**
+ * EnvVar
+ * One variable of a profile
+ */
+#[derive(Debug, Clone, PartialEq)]
+struct EnvVar {
+    value: String,
+    // the orchestrator variable a secret was read from; None: a literal
+    secret: Option<String>,
+}
+
+/**
+ * ProfileView
+ * A profile as listed: literal values, and where secrets come from
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProfileView {
+    pub name: String,
+    pub values: BTreeMap<String, String>,
+    // variable -> the orchestrator variable it is read from
+    pub secrets: BTreeMap<String, String>,
+}
+
+/**
+ * EnvProfiles
+ * Every configured profile, secrets resolved
+ */
+#[derive(Debug, Clone, Default)]
+pub struct EnvProfiles {
+    profiles: BTreeMap<String, BTreeMap<String, EnvVar>>,
+}
+
+impl EnvProfiles {
+    pub fn contains(&self, name: &str) -> bool {
+        self.profiles.contains_key(name)
+    }
+
+    /**
+     * env: the variables of a profile; empty if there is no such profile
+     */
+    pub fn env(&self, name: &str) -> BTreeMap<String, String> {
+        self.profiles
+            .get(name)
+            .into_iter()
+            .flatten()
+            .map(|(var, v)| (var.clone(), v.value.clone()))
+            .collect()
+    }
+
+    /**
+     * list: every profile, secret values left out
+     */
+    pub fn list(&self) -> Vec<ProfileView> {
+        self.profiles
+            .iter()
+            .map(|(name, vars)| {
+                let mut view = ProfileView {
+                    name: name.clone(),
+                    values: BTreeMap::new(),
+                    secrets: BTreeMap::new(),
+                };
+                for (var, v) in vars {
+                    match &v.secret {
+                        Some(source) => view.secrets.insert(var.clone(), source.clone()),
+                        None => view.values.insert(var.clone(), v.value.clone()),
+                    };
+                }
+                view
+            })
+            .collect()
+    }
+}
+
+// "profile.VAR=value,..."; "$NAME" values read from the environment
+impl FromStr for EnvProfiles {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut profiles: BTreeMap<String, BTreeMap<String, EnvVar>> = BTreeMap::new();
+        for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (key, value) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("'{entry}': expected profile.VAR=value"))?;
+            let (profile, var) = key
+                .trim()
+                .split_once('.')
+                .filter(|(profile, var)| !profile.is_empty() && !var.is_empty())
+                .ok_or_else(|| format!("'{entry}': expected profile.VAR=value"))?;
+            let value = value.trim();
+            let var_value = match value.strip_prefix('$') {
+                Some(source) => EnvVar {
+                    value: env::var(source).map_err(|_| format!("'{key}': {source} is not set"))?,
+                    secret: Some(source.to_string()),
+                },
+                None => EnvVar {
+                    value: value.to_string(),
+                    secret: None,
+                },
+            };
+            profiles
+                .entry(profile.to_string())
+                .or_default()
+                .insert(var.to_string(), var_value);
+        }
+        Ok(Self { profiles })
+    }
+}
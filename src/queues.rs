@@ -0,0 +1,240 @@
+/*! Queues module for async orchestrator
+ * Named queues, each with its own concurrency cap and pending limit
+ *
+ * A queue's cap is capacity set aside for it. So that capacity doesn't
+ * sit idle while other queues back up, a capped queue with a steal weight
+ * may run jobs beyond its cap on the unused capacity of capped queues with
+ * nothing waiting: free pool slots go to queues within their cap first,
+ * then to queues stealing, each holding stolen slots in proportion to its
+ * weight. A queue with weight 0 (the default) never steals.
+ * NOTE: stealing doesn't preempt: a queue whose capacity was lent waits
+ * for the stolen slots as their jobs finish
+ */
+use crate::jobs::{Job, Priority};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+pub const DEFAULT_QUEUE: &str = "default";
+
+/**
+ * PendingOverflow
+ * What a submission does when its queue can neither run nor pend it
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PendingOverflow {
+    // the new job fails
+    #[default]
+    Reject,
+    // the oldest pending job of the lowest priority fails, making room for
+    // the new one (unless the new one is lower still)
+    DropOldest,
+}
+
+impl FromStr for PendingOverflow {
+    type Err = String;
+
+    // "reject" or "drop-oldest"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(PendingOverflow::Reject),
+            "drop-oldest" => Ok(PendingOverflow::DropOldest),
+            _ => Err(format!("unknown pending overflow policy '{s}'")),
+        }
+    }
+}
+
+/**
+ * QueueConfig
+ * Capacity of one named queue
+ */
+#[derive(Debug, Clone)]
+pub struct QueueConfig {
+    pub name: String,
+    // max jobs of this queue running at once; None: only the pool limit applies
+    pub max_concurrency: Option<usize>,
+    // max jobs of this queue waiting for a slot
+    pub pending_limit: usize,
+    // when the pending limit is reached
+    pub overflow: PendingOverflow,
+    // share of capacity stolen from idle queues it takes, beyond its cap
+    // 0: it never steals
+    pub steal_weight: u32,
+}
+
+impl QueueConfig {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            max_concurrency: None,
+            pending_limit: 0,
+            overflow: PendingOverflow::Reject,
+            steal_weight: 0,
+        }
+    }
+}
+
+impl FromStr for QueueConfig {
+    type Err = String;
+
+    // "name[:max_concurrency[:pending_limit[:overflow[:steal_weight]]]]",
+    // max_concurrency "*" for uncapped
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let name = parts.next().unwrap_or_default().trim();
+        if name.is_empty() {
+            return Err(format!("missing queue name in '{s}'"));
+        }
+        let mut queue = QueueConfig::new(name);
+        match parts.next() {
+            None | Some("*") => {}
+            Some(max) => {
+                let max = max
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid max concurrency in '{s}'"))?;
+                queue.max_concurrency = Some(max);
+            }
+        }
+        if let Some(pending) = parts.next() {
+            queue.pending_limit = pending
+                .parse()
+                .map_err(|_| format!("invalid pending limit in '{s}'"))?;
+        }
+        if let Some(overflow) = parts.next() {
+            queue.overflow = overflow.parse()?;
+        }
+        if let Some(weight) = parts.next() {
+            queue.steal_weight = weight
+                .parse()
+                .map_err(|_| format!("invalid steal weight in '{s}'"))?;
+        }
+        if parts.next().is_some() {
+            return Err(format!("too many fields in '{s}'"));
+        }
+        Ok(queue)
+    }
+}
+
+/**
+ * QueueList
+ * Comma separated queue configs, as read from config
+ */
+#[derive(Debug, Clone)]
+pub struct QueueList(pub Vec<QueueConfig>);
+
+impl FromStr for QueueList {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::parse)
+            .collect::<Result<Vec<_>, _>>()
+            .map(QueueList)
+    }
+}
+
+/**
+ * JobQueue
+ * Runtime state of one named queue
+ */
+pub struct JobQueue {
+    pub config: QueueConfig,
+    // jobs of this queue currently holding a slot
+    pub running: usize,
+    // jobs waiting for a slot, highest priority first, then oldest first
+    pub pending: VecDeque<Job>,
+}
+
+impl JobQueue {
+    pub fn new(config: QueueConfig) -> Self {
+        Self {
+            config,
+            running: 0,
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    // Below this queue's concurrency cap
+    pub fn can_run(&self) -> bool {
+        self.config
+            .max_concurrency
+            .is_none_or(|max| self.running < max)
+    }
+
+    // Jobs running beyond its cap, on capacity stolen from other queues
+    pub fn stolen(&self) -> usize {
+        self.config
+            .max_concurrency
+            .map_or(0, |max| self.running.saturating_sub(max))
+    }
+
+    // Capacity of its cap it has no use for: none while jobs wait on it
+    pub fn idle(&self) -> usize {
+        if !self.pending.is_empty() {
+            return 0;
+        }
+        self.config
+            .max_concurrency
+            .map_or(0, |max| max.saturating_sub(self.running))
+    }
+
+    // At its cap, but may steal capacity beyond it
+    pub fn steals(&self) -> bool {
+        self.config.steal_weight > 0 && !self.can_run()
+    }
+
+    // Room for another pending job
+    pub fn can_pend(&self) -> bool {
+        self.pending.len() < self.config.pending_limit
+    }
+
+    // Where a pending job of this priority goes: behind those of its
+    // priority or higher
+    pub fn position_for(&self, priority: Priority) -> usize {
+        self.pending
+            .partition_point(|job| job.submission().priority >= priority)
+    }
+
+    // Add a pending job in priority order
+    pub fn enqueue(&mut self, job: Job) {
+        let at = self.position_for(job.submission().priority);
+        self.pending.insert(at, job);
+    }
+
+    // Put a job back that was preempted: ahead of those of its priority
+    pub fn requeue(&mut self, job: Job) {
+        let priority = job.submission().priority;
+        let at = self
+            .pending
+            .partition_point(|job| job.submission().priority > priority);
+        self.pending.insert(at, job);
+    }
+
+    // Full, but would drop a pending job to pend one of this priority
+    pub fn drops_for(&self, priority: Priority) -> bool {
+        self.config.overflow == PendingOverflow::DropOldest
+            && self
+                .pending
+                .back()
+                .is_some_and(|lowest| lowest.submission().priority <= priority)
+    }
+
+    // Remove the oldest pending job of the lowest priority
+    pub fn drop_oldest(&mut self) -> Option<Job> {
+        let lowest = self.pending.back()?.submission().priority;
+        let at = self
+            .pending
+            .partition_point(|job| job.submission().priority > lowest);
+        self.pending.remove(at)
+    }
+
+    // Priority of the job this queue would dispatch next
+    pub fn next_priority(&self) -> Option<Priority> {
+        self.pending.front().map(|job| job.submission().priority)
+    }
+}
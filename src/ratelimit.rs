@@ -0,0 +1,189 @@
+/*! Rate limit module for async orchestrator
+ * Token bucket rate limits per job type, beyond concurrency caps
+ *
+ * RATE_LIMITS gives job types a rate ("type=count/period,...", e.g.
+ * "echo=10/min,sleep=2/s"; periods s, min or h). Each limited type has a
+ * bucket holding up to count tokens, refilled evenly over the period; a
+ * job takes a token as it starts, and while its type's bucket is empty
+ * its jobs are held pending (even past their queue's pending limit) until
+ * a token comes back. GET /metrics shows each bucket's level.
+ * NOTE: buckets start full, so a burst of up to count jobs runs at once
+ */
+use crate::jobs::JOB_TYPES;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+/**
+ * RateLimit
+ * At most count jobs per period
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub count: u32,
+    pub per: Duration,
+}
+
+// "count/period"
+impl FromStr for RateLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (count, period) = s
+            .split_once('/')
+            .ok_or_else(|| format!("'{s}': expected count/period"))?;
+        let count: u32 = count
+            .trim()
+            .parse()
+            .map_err(|e| format!("'{s}': count: {e}"))?;
+        if count == 0 {
+            return Err(format!("'{s}': count must be at least 1"));
+        }
+        let per = match period.trim() {
+            "s" | "sec" => Duration::from_secs(1),
+            "min" => Duration::from_secs(60),
+            "h" | "hour" => Duration::from_secs(3600),
+            other => return Err(format!("'{s}': unknown period '{other}' (s, min or h)")),
+        };
+        Ok(Self { count, per })
+    }
+}
+
+/**
+ * RateLimitList
+ * Job type -> its rate limit, as read from config
+ */
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitList(pub BTreeMap<String, RateLimit>);
+
+// "type=count/period,..."
+impl FromStr for RateLimitList {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut limits = BTreeMap::new();
+        for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (job_type, limit) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("'{entry}': expected type=count/period"))?;
+            let job_type = job_type.trim();
+            if !JOB_TYPES.contains(&job_type) {
+                return Err(format!("'{entry}': unknown job type '{job_type}'"));
+            }
+            limits.insert(job_type.to_string(), limit.parse()?);
+        }
+        Ok(RateLimitList(limits))
+    }
+}
+
+/**
+ * BucketLevel
+ * A job type's token bucket, as shown on GET /metrics
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BucketLevel {
+    pub tokens: f64,
+    pub capacity: u32,
+    pub period_ms: u64,
+}
+
+// One job type's bucket, as of at
+#[derive(Debug, Clone)]
+struct Bucket {
+    limit: RateLimit,
+    tokens: f64,
+    at: DateTime<Utc>,
+}
+
+impl Bucket {
+    // Top the bucket up for the time since it was last
+    fn refill(&mut self, now: DateTime<Utc>) {
+        let Ok(elapsed) = (now - self.at).to_std() else {
+            return;
+        };
+        let capacity = f64::from(self.limit.count);
+        let added = capacity * elapsed.as_secs_f64() / self.limit.per.as_secs_f64();
+        self.tokens = (self.tokens + added).min(capacity);
+        self.at = now;
+    }
+}
+
+/**
+ * TokenBuckets
+ * Every rate limited job type's bucket
+ */
+#[derive(Debug, Clone, Default)]
+pub struct TokenBuckets {
+    buckets: BTreeMap<String, Bucket>,
+}
+
+impl TokenBuckets {
+    /**
+     * new: a full bucket per limited type
+     */
+    pub fn new(limits: &RateLimitList, now: DateTime<Utc>) -> Self {
+        let buckets = limits
+            .0
+            .iter()
+            .map(|(job_type, &limit)| {
+                let bucket = Bucket {
+                    limit,
+                    tokens: f64::from(limit.count),
+                    at: now,
+                };
+                (job_type.clone(), bucket)
+            })
+            .collect();
+        Self { buckets }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+
+    /**
+     * empty: the limited types with no token to start a job now
+     */
+    pub fn empty(&mut self, now: DateTime<Utc>) -> Vec<String> {
+        self.buckets
+            .iter_mut()
+            .filter_map(|(job_type, bucket)| {
+                bucket.refill(now);
+                (bucket.tokens < 1.0).then(|| job_type.clone())
+            })
+            .collect()
+    }
+
+    /**
+     * take: a token for a job of job_type starting now; false: none left
+     * Types without a limit always have one
+     */
+    pub fn take(&mut self, job_type: &str, now: DateTime<Utc>) -> bool {
+        let Some(bucket) = self.buckets.get_mut(job_type) else {
+            return true;
+        };
+        bucket.refill(now);
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+
+    pub fn levels(&mut self, now: DateTime<Utc>) -> BTreeMap<String, BucketLevel> {
+        self.buckets
+            .iter_mut()
+            .map(|(job_type, bucket)| {
+                bucket.refill(now);
+                let level = BucketLevel {
+                    tokens: (bucket.tokens * 100.0).floor() / 100.0,
+                    capacity: bucket.limit.count,
+                    period_ms: bucket.limit.per.as_millis() as u64,
+                };
+                (job_type.clone(), level)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,203 @@
+/*! Replay module for async orchestrator
+ * Rebuilds pool state from a persisted event log (see events::EventLog)
+ *
+ * A Projection folds events into per-job state and pool counters; Replay
+ * feeds it a log one event at a time, optionally stopping at a timestamp.
+ * The same log always replays to the same state, so a projection change
+ * that alters how history reads shows up as a different replay.
+ */
+use crate::events::{Event, EventKind};
+use crate::ids::JobId;
+use crate::jobs::State;
+use crate::labels::Labels;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::io::BufRead;
+
+/**
+ * JobProjection
+ * A job as the event log describes it
+ */
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JobProjection {
+    pub state: State,
+    // first event seen for the job
+    pub first_seen: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Map<String, Value>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: Labels,
+}
+
+/**
+ * Shedding
+ * An overload episode in progress
+ */
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Shedding {
+    pub since: DateTime<Utc>,
+    pub depth: usize,
+    pub latency_ms: u64,
+}
+
+/**
+ * Projection
+ * Pool state built from events, in log order
+ */
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Projection {
+    pub jobs: BTreeMap<JobId, JobProjection>,
+    pub shedding: Option<Shedding>,
+    // time of the last event applied
+    pub at: Option<DateTime<Utc>>,
+    pub events_applied: usize,
+}
+
+impl Projection {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+     * apply: fold one event into the projection
+     */
+    pub fn apply(&mut self, event: &Event) {
+        match &event.kind {
+            EventKind::JobStateChanged {
+                job_id,
+                state,
+                metadata,
+                labels,
+            } => {
+                let job = self.jobs.entry(*job_id).or_insert_with(|| JobProjection {
+                    state: state.clone(),
+                    first_seen: event.at,
+                    started_at: None,
+                    finished_at: None,
+                    metadata: metadata.clone(),
+                    labels: labels.clone(),
+                });
+                job.state = state.clone();
+                if *state == State::RUNNING {
+                    job.started_at = Some(event.at);
+                }
+                if state.is_terminal() {
+                    job.finished_at = Some(event.at);
+                }
+            }
+            EventKind::ShedModeEntered { depth, latency_ms } => {
+                self.shedding = Some(Shedding {
+                    since: event.at,
+                    depth: *depth,
+                    latency_ms: *latency_ms,
+                });
+            }
+            EventKind::ShedModeExited => self.shedding = None,
+            // no state of its own; the job's settling is its own event
+            EventKind::SlotReclaimed { .. } | EventKind::InternalError { .. } => {}
+        }
+        self.at = Some(event.at);
+        self.events_applied += 1;
+    }
+
+    /**
+     * counts: number of jobs in each state
+     */
+    pub fn counts(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for job in self.jobs.values() {
+            *counts.entry(job.state.to_string()).or_default() += 1;
+        }
+        counts
+    }
+
+    /**
+     * unfinished: jobs the log leaves short of a terminal state
+     */
+    pub fn unfinished(&self) -> impl Iterator<Item = (&JobId, &JobProjection)> {
+        self.jobs.iter().filter(|(_, job)| !job.state.is_terminal())
+    }
+}
+
+/**
+ * read_log: parse an event log, one JSON event per line
+ * Blank lines are skipped; errors name the offending line
+ */
+pub fn read_log(reader: impl BufRead) -> Result<Vec<Event>, String> {
+    let mut events = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| format!("line {}: {e}", i + 1))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event = serde_json::from_str(&line).map_err(|e| format!("line {}: {e}", i + 1))?;
+        events.push(event);
+    }
+    Ok(events)
+}
+
+/**
+ * Replay
+ * Steps a projection through a recorded event log
+ */
+pub struct Replay {
+    events: Vec<Event>,
+    next: usize,
+    until: Option<DateTime<Utc>>,
+    projection: Projection,
+}
+
+impl Replay {
+    pub fn new(events: Vec<Event>) -> Self {
+        Self {
+            events,
+            next: 0,
+            until: None,
+            projection: Projection::new(),
+        }
+    }
+
+    /**
+     * until: stop before the first event after this time
+     */
+    pub fn until(mut self, until: DateTime<Utc>) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    /**
+     * step: apply the next event, returning it; None when done
+     */
+    pub fn step(&mut self) -> Option<&Event> {
+        let event = self.events.get(self.next)?;
+        if self.until.is_some_and(|until| event.at > until) {
+            return None;
+        }
+        self.projection.apply(event);
+        self.next += 1;
+        Some(event)
+    }
+
+    /**
+     * run: apply every remaining event
+     */
+    pub fn run(&mut self) -> &Projection {
+        while self.step().is_some() {}
+        &self.projection
+    }
+
+    pub fn projection(&self) -> &Projection {
+        &self.projection
+    }
+
+    /**
+     * remaining: events not (yet) applied, including any past `until`
+     */
+    pub fn remaining(&self) -> usize {
+        self.events.len() - self.next
+    }
+}
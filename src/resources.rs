@@ -0,0 +1,214 @@
+/*! Resources module for async orchestrator
+ * Shared resources executions use: HTTP clients, connection pools, ...
+ *
+ * When the pool starts, its executor registers the resources its job
+ * types share (Executor::register_resources), each by name with how to
+ * make it and, optionally, how to close it. Nothing is made then: a
+ * resource is made the first time an execution asks for it
+ * (ExecContext::resources), and every execution after shares that one,
+ * so a burst of jobs opens one client rather than one each. Executions
+ * asking while it is being made wait for it; one that fails to be made
+ * is tried again by the next ask. Once the pool has shut down (its jobs
+ * drained or cancelled), each resource made is closed, the last
+ * registered first, and asks fail from then on.
+ * GET /admin/resources lists them, and whether each has been made.
+ * NOTE: a close hook runs on the task shutting the pool down, so it
+ * should be quick
+ */
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+type Shared = Arc<dyn Any + Send + Sync>;
+type Init = Box<dyn Fn() -> Result<Shared, String> + Send + Sync>;
+type Close = Box<dyn Fn(&Shared) + Send + Sync>;
+
+/**
+ * ResourceStatus
+ * A registered resource, as GET /admin/resources lists it
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResourceStatus {
+    pub name: String,
+    // the type executions ask for it as
+    pub type_name: String,
+    // None: not made yet (or made and closed)
+    pub initialized_at: Option<DateTime<Utc>>,
+    // times executions asked for it
+    pub uses: u64,
+}
+
+// A resource, and the one made of it, once made
+struct Resource {
+    name: String,
+    type_name: &'static str,
+    init: Init,
+    close: Option<Close>,
+    // held while making it, so it is made once
+    made: Mutex<Option<(Shared, DateTime<Utc>)>>,
+    uses: AtomicU64,
+}
+
+/**
+ * Resources
+ * The resources an executor registered; cheap to clone, clones share them
+ */
+#[derive(Clone, Default)]
+pub struct Resources {
+    // in registration order
+    registry: Arc<Mutex<Vec<Arc<Resource>>>>,
+    closed: Arc<AtomicBool>,
+}
+
+impl fmt::Debug for Resources {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<String> = self
+            .registry
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| r.name.clone())
+            .collect();
+        f.debug_struct("Resources")
+            .field("names", &names)
+            .field("closed", &self.closed.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+impl Resources {
+    /**
+     * register: a resource made by init when first asked for
+     * Err: the name is taken
+     */
+    pub fn register<T, I>(&self, name: &str, init: I) -> Result<(), String>
+    where
+        T: Send + Sync + 'static,
+        I: Fn() -> Result<T, String> + Send + Sync + 'static,
+    {
+        self.add::<T>(
+            name,
+            Box::new(move || Ok(Arc::new(init()?) as Shared)),
+            None,
+        )
+    }
+
+    /**
+     * register_with_close: register, with close run on the resource (if
+     * made) when the pool shuts down
+     */
+    pub fn register_with_close<T, I, C>(&self, name: &str, init: I, close: C) -> Result<(), String>
+    where
+        T: Send + Sync + 'static,
+        I: Fn() -> Result<T, String> + Send + Sync + 'static,
+        C: Fn(&T) + Send + Sync + 'static,
+    {
+        let close = move |shared: &Shared| {
+            if let Some(resource) = shared.downcast_ref::<T>() {
+                close(resource);
+            }
+        };
+        self.add::<T>(
+            name,
+            Box::new(move || Ok(Arc::new(init()?) as Shared)),
+            Some(Box::new(close)),
+        )
+    }
+
+    // Add a resource under name, typed T
+    fn add<T>(&self, name: &str, init: Init, close: Option<Close>) -> Result<(), String> {
+        let mut registry = self.registry.lock().unwrap();
+        if registry.iter().any(|r| r.name == name) {
+            return Err(format!("resource '{name}' is already registered"));
+        }
+        println!("[Resources]: registered '{}'", name);
+        registry.push(Arc::new(Resource {
+            name: name.to_string(),
+            type_name: std::any::type_name::<T>(),
+            init,
+            close,
+            made: Mutex::new(None),
+            uses: AtomicU64::new(0),
+        }));
+        Ok(())
+    }
+
+    /**
+     * get: the resource registered under name, made if it isn't yet
+     * Err: no such resource, not a T, failed to be made, or the pool has
+     * shut down
+     */
+    pub fn get<T: Send + Sync + 'static>(&self, name: &str) -> Result<Arc<T>, String> {
+        if self.closed.load(Ordering::Relaxed) {
+            return Err(format!("resource '{name}': closed"));
+        }
+        let resource = self
+            .registry
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|r| r.name == name)
+            .cloned()
+            .ok_or_else(|| format!("no resource '{name}'"))?;
+        let mut made = resource.made.lock().unwrap();
+        let shared = match &*made {
+            Some((shared, _)) => shared.clone(),
+            None => {
+                let shared = (resource.init)().map_err(|e| format!("resource '{name}': {e}"))?;
+                println!("[Resources]: made '{}'", name);
+                *made = Some((shared.clone(), Utc::now()));
+                shared
+            }
+        };
+        drop(made);
+        let shared = shared.downcast::<T>().map_err(|_| {
+            format!(
+                "resource '{name}' is a {}, not a {}",
+                resource.type_name,
+                std::any::type_name::<T>()
+            )
+        })?;
+        resource.uses.fetch_add(1, Ordering::Relaxed);
+        Ok(shared)
+    }
+
+    /**
+     * shutdown: close every resource made, the last registered first; asks
+     * fail from then on
+     */
+    pub fn shutdown(&self) {
+        if self.closed.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let registry = self.registry.lock().unwrap().clone();
+        for resource in registry.iter().rev() {
+            let Some((shared, _)) = resource.made.lock().unwrap().take() else {
+                continue;
+            };
+            if let Some(close) = &resource.close {
+                close(&shared);
+            }
+            println!("[Resources]: closed '{}'", resource.name);
+        }
+    }
+
+    /**
+     * list: the registered resources, in registration order
+     */
+    pub fn list(&self) -> Vec<ResourceStatus> {
+        self.registry
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|resource| ResourceStatus {
+                name: resource.name.clone(),
+                type_name: resource.type_name.to_string(),
+                initialized_at: resource.made.lock().unwrap().as_ref().map(|(_, at)| *at),
+                uses: resource.uses.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
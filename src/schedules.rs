@@ -0,0 +1,659 @@
+/*! Schedules module for async orchestrator
+ * Recurring job submissions on cron schedules, with a bounded history of
+ * the runs each schedule fired
+ *
+ * The driver sleeps until the earliest enabled schedule is due, submits its
+ * job template, and records the spawned job. A run's outcome is read from
+ * the pool when the history is asked for. Unless a schedule allows overlap,
+ * a fire while its previous run's job is still queued or running is skipped.
+ * A schedule records who created it (its owner) and who last changed it;
+ * only the owner or an admin may replace or delete it (see the access
+ * module), and each replace keeps the definition it replaced.
+ * Deleting a schedule only marks it deleted: it stops firing and is left
+ * out of listings, but GET /schedules/{id} (and its runs and history)
+ * still answers, so the jobs it submitted (which carry its id as
+ * schedule_id) keep a reference that resolves. Until the purge window
+ * (SCHEDULE_PURGE_AFTER_SECS, a week by default) has passed, an admin may
+ * list deleted schedules and restore one; after it, the schedule is gone.
+ * NOTE: schedules live in memory; timezones are fixed UTC offsets
+ */
+use crate::access::Principal;
+use crate::api_error::ApiError;
+use crate::clock::Clock;
+use crate::cron::{self, Cron};
+use crate::ids::JobId;
+use crate::jobs::{JobPool, JobSubmission, State};
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::sync::Notify;
+use ulid::Ulid;
+
+// runs kept per schedule, oldest dropped first
+const HISTORY_LEN: usize = 50;
+// replaced definitions kept per schedule, oldest dropped first
+const CHANGES_LEN: usize = 50;
+// how long the driver sleeps when nothing is scheduled
+const IDLE_WAIT: Duration = Duration::from_secs(60);
+// most fire times a preview lists
+pub const MAX_PREVIEW: usize = 100;
+
+fn default_timezone() -> String {
+    "UTC".to_string()
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/**
+ * ScheduleDefinition
+ * What a client creates or replaces
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduleDefinition {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    // five fields: minute hour day-of-month month day-of-week
+    pub cron: String,
+    // "UTC" or an offset like "+02:00"
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    // submitted on every fire
+    pub job: JobSubmission,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // fire even while the previous run is still queued or running
+    #[serde(default)]
+    pub allow_overlap: bool,
+}
+
+/**
+ * Schedule
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Schedule {
+    pub id: String,
+    #[serde(flatten)]
+    pub definition: ScheduleDefinition,
+    pub created_at: DateTime<Utc>,
+    // the owner
+    pub created_by: String,
+    pub modified_at: DateTime<Utc>,
+    pub modified_by: String,
+    pub last_run_at: Option<DateTime<Utc>>,
+    // None while disabled or deleted
+    pub next_run_at: Option<DateTime<Utc>>,
+    // None unless deleted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
+}
+
+/**
+ * RunOutcome
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RunOutcome {
+    // the pool turned the submission away; no job was created
+    Rejected,
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+    // not submitted: the previous run was still active
+    Skipped,
+    // the pool no longer knows the job
+    Unknown,
+}
+
+impl RunOutcome {
+    fn for_state(state: &State) -> Self {
+        match state {
+            State::INIT | State::WAITING | State::SCHEDULED | State::QUEUED => RunOutcome::Queued,
+            State::RUNNING | State::STUCK | State::CANCELLING => RunOutcome::Running,
+            State::SUCCEEDED => RunOutcome::Succeeded,
+            State::FAILED | State::TIMED_OUT | State::DEADLINE_MISSED => RunOutcome::Failed,
+            State::CANCELLED => RunOutcome::Cancelled,
+            State::SKIPPED => RunOutcome::Skipped,
+        }
+    }
+
+    fn is_final(&self) -> bool {
+        matches!(
+            self,
+            RunOutcome::Rejected
+                | RunOutcome::Skipped
+                | RunOutcome::Succeeded
+                | RunOutcome::Failed
+                | RunOutcome::Cancelled
+        )
+    }
+}
+
+/**
+ * ScheduleRun
+ * One firing of a schedule
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduleRun {
+    pub fired_at: DateTime<Utc>,
+    // None if the submission was rejected
+    pub job_id: Option<JobId>,
+    pub outcome: RunOutcome,
+    // why the submission was rejected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/**
+ * ScheduleChange
+ * One replace of a schedule, with the definition it replaced
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduleChange {
+    pub changed_at: DateTime<Utc>,
+    pub changed_by: String,
+    pub previous: ScheduleDefinition,
+}
+
+/**
+ * FireTime
+ * When a schedule will fire, in UTC and in the schedule's timezone
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FireTime {
+    pub at: DateTime<Utc>,
+    pub local: DateTime<FixedOffset>,
+}
+
+/**
+ * SchedulePreview
+ * A schedule's next fire times, whether or not it is enabled
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SchedulePreview {
+    pub id: String,
+    pub cron: String,
+    pub timezone: String,
+    pub enabled: bool,
+    // empty if the expression never fires
+    pub next: Vec<FireTime>,
+}
+
+struct Entry {
+    schedule: Schedule,
+    cron: Cron,
+    timezone: FixedOffset,
+    // newest last
+    history: VecDeque<ScheduleRun>,
+    // newest last
+    changes: VecDeque<ScheduleChange>,
+}
+
+impl Entry {
+    // Parse a definition; fails on a bad cron expression or timezone, or
+    // one that never fires
+    fn new(schedule: Schedule, now: DateTime<Utc>) -> Result<Self, ApiError> {
+        let cron: Cron = schedule
+            .definition
+            .cron
+            .parse()
+            .map_err(ApiError::BadRequest)?;
+        let timezone =
+            cron::parse_timezone(&schedule.definition.timezone).map_err(ApiError::BadRequest)?;
+        let mut entry = Self {
+            schedule,
+            cron,
+            timezone,
+            history: VecDeque::new(),
+            changes: VecDeque::new(),
+        };
+        entry.plan_next(now);
+        if entry.schedule.definition.enabled && entry.schedule.next_run_at.is_none() {
+            return Err(ApiError::BadRequest(format!(
+                "cron expression '{}' never fires",
+                entry.cron
+            )));
+        }
+        Ok(entry)
+    }
+
+    fn plan_next(&mut self, after: DateTime<Utc>) {
+        self.schedule.next_run_at =
+            if self.schedule.definition.enabled && self.schedule.deleted_at.is_none() {
+                self.cron.next_after(after, self.timezone)
+            } else {
+                None
+            };
+    }
+
+    // The job of the latest run that submitted one, unless it is known to
+    // have finished
+    fn unsettled_job(&self) -> Option<JobId> {
+        let run = self.history.iter().rev().find(|r| r.job_id.is_some())?;
+        if run.outcome.is_final() {
+            return None;
+        }
+        run.job_id
+    }
+
+    fn record(&mut self, run: ScheduleRun) {
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(run);
+    }
+}
+
+/**
+ * Schedules
+ * Schedule table; cheap to clone
+ */
+#[derive(Clone)]
+pub struct Schedules {
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+    // wakes the driver when the table changes
+    changed: Arc<Notify>,
+    clock: Arc<dyn Clock>,
+    // how long deleted schedules are kept; None: until restart
+    purge_after: Option<Duration>,
+}
+
+impl Schedules {
+    pub fn new(clock: Arc<dyn Clock>, purge_after: Option<Duration>) -> Self {
+        Self {
+            entries: Arc::default(),
+            changed: Arc::default(),
+            clock,
+            purge_after,
+        }
+    }
+
+    // When a deleted schedule is purged; None: it isn't deleted, or
+    // deleted schedules are kept
+    fn purge_at(&self, schedule: &Schedule) -> Option<DateTime<Utc>> {
+        let purge_after = chrono::Duration::from_std(self.purge_after?).ok()?;
+        schedule
+            .deleted_at
+            .and_then(|at| at.checked_add_signed(purge_after))
+    }
+
+    /**
+     * create: add a schedule
+     * NOTE: the job template is checked against the pool by the caller
+     */
+    pub fn create(
+        &self,
+        definition: ScheduleDefinition,
+        by: &Principal,
+    ) -> Result<Schedule, ApiError> {
+        let now = self.clock.now();
+        let entry = Entry::new(
+            Schedule {
+                id: Ulid::new().to_string(),
+                definition,
+                created_at: now,
+                created_by: by.name.clone(),
+                modified_at: now,
+                modified_by: by.name.clone(),
+                last_run_at: None,
+                next_run_at: None,
+                deleted_at: None,
+                deleted_by: None,
+            },
+            now,
+        )?;
+        let schedule = entry.schedule.clone();
+        println!(
+            "[Schedules]: {} created by {}: '{}', next run {:?}",
+            schedule.id, schedule.created_by, schedule.definition.cron, schedule.next_run_at
+        );
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(schedule.id.clone(), entry);
+        self.changed.notify_one();
+        Ok(schedule)
+    }
+
+    /**
+     * replace: update a schedule's definition, keeping its run history and
+     * the definition replaced
+     * NOTE: by must own the schedule or be an admin
+     */
+    pub fn replace(
+        &self,
+        id: &str,
+        definition: ScheduleDefinition,
+        by: &Principal,
+    ) -> Result<Schedule, ApiError> {
+        let mut entries = self.entries.lock().unwrap();
+        let old = entries
+            .get_mut(id)
+            .filter(|e| e.schedule.deleted_at.is_none())
+            .ok_or_else(|| ApiError::NotFound(format!("schedule '{id}'")))?;
+        by.check_owner(&format!("schedule '{id}'"), &old.schedule.created_by)?;
+        let now = self.clock.now();
+        let mut entry = Entry::new(
+            Schedule {
+                definition,
+                modified_at: now,
+                modified_by: by.name.clone(),
+                ..old.schedule.clone()
+            },
+            now,
+        )?;
+        entry.history = std::mem::take(&mut old.history);
+        entry.changes = std::mem::take(&mut old.changes);
+        if entry.changes.len() == CHANGES_LEN {
+            entry.changes.pop_front();
+        }
+        entry.changes.push_back(ScheduleChange {
+            changed_at: now,
+            changed_by: by.name.clone(),
+            previous: old.schedule.definition.clone(),
+        });
+        *old = entry;
+        println!("[Schedules]: {} replaced by {}", id, by.name);
+        self.changed.notify_one();
+        Ok(old.schedule.clone())
+    }
+
+    /**
+     * delete: mark a schedule deleted: it stops firing and is left out of
+     * listings, but can be restored until it is purged
+     * NOTE: by must own the schedule or be an admin
+     */
+    pub fn delete(&self, id: &str, by: &Principal) -> Result<(), ApiError> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries
+            .get_mut(id)
+            .filter(|e| e.schedule.deleted_at.is_none())
+            .ok_or_else(|| ApiError::NotFound(format!("schedule '{id}'")))?;
+        by.check_owner(&format!("schedule '{id}'"), &entry.schedule.created_by)?;
+        entry.schedule.deleted_at = Some(self.clock.now());
+        entry.schedule.deleted_by = Some(by.name.clone());
+        entry.schedule.next_run_at = None;
+        println!("[Schedules]: {} deleted by {}", id, by.name);
+        self.changed.notify_one();
+        Ok(())
+    }
+
+    /**
+     * restore: bring back a deleted schedule, firing again as defined
+     * NOTE: by must be an admin
+     */
+    pub fn restore(&self, id: &str, by: &Principal) -> Result<Schedule, ApiError> {
+        by.check_admin(&format!("restoring schedule '{id}'"))?;
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries
+            .get_mut(id)
+            .ok_or_else(|| ApiError::NotFound(format!("schedule '{id}'")))?;
+        if entry.schedule.deleted_at.is_none() {
+            return Err(ApiError::Conflict(format!(
+                "schedule '{id}' is not deleted"
+            )));
+        }
+        let now = self.clock.now();
+        entry.schedule.deleted_at = None;
+        entry.schedule.deleted_by = None;
+        entry.schedule.modified_at = now;
+        entry.schedule.modified_by = by.name.clone();
+        entry.plan_next(now);
+        println!("[Schedules]: {} restored by {}", id, by.name);
+        self.changed.notify_one();
+        Ok(entry.schedule.clone())
+    }
+
+    /**
+     * deleted: the schedules deleted and not yet purged, most recently
+     * deleted first
+     */
+    pub fn deleted(&self) -> Vec<Schedule> {
+        let mut schedules: Vec<Schedule> = self
+            .entries
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|e| e.schedule.deleted_at.is_some())
+            .map(|e| e.schedule.clone())
+            .collect();
+        schedules.sort_by_key(|s| std::cmp::Reverse(s.deleted_at));
+        schedules
+    }
+
+    // Drop the deleted schedules whose purge window has passed
+    fn purge(&self, now: DateTime<Utc>) {
+        self.entries.lock().unwrap().retain(|id, e| {
+            let purge = self.purge_at(&e.schedule).is_some_and(|at| at <= now);
+            if purge {
+                println!("[Schedules]: {} purged", id);
+            }
+            !purge
+        });
+    }
+
+    /**
+     * get: a schedule, deleted or not
+     */
+    pub fn get(&self, id: &str) -> Result<Schedule, ApiError> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(id)
+            .map(|e| e.schedule.clone())
+            .ok_or_else(|| ApiError::NotFound(format!("schedule '{id}'")))
+    }
+
+    /**
+     * changes: a schedule's replaced definitions, newest first
+     */
+    pub fn changes(&self, id: &str) -> Result<Vec<ScheduleChange>, ApiError> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(id)
+            .map(|e| e.changes.iter().rev().cloned().collect())
+            .ok_or_else(|| ApiError::NotFound(format!("schedule '{id}'")))
+    }
+
+    /**
+     * list: every schedule not deleted, oldest first
+     */
+    pub fn list(&self) -> Vec<Schedule> {
+        let mut schedules: Vec<Schedule> = self
+            .entries
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|e| e.schedule.deleted_at.is_none())
+            .map(|e| e.schedule.clone())
+            .collect();
+        schedules.sort_by(|a, b| a.id.cmp(&b.id));
+        schedules
+    }
+
+    /**
+     * preview: the next count fire times of a schedule, from now
+     * NOTE: computed for disabled schedules too, so an expression can be
+     * checked before the schedule is enabled
+     */
+    pub fn preview(&self, id: &str, count: usize) -> Result<SchedulePreview, ApiError> {
+        if count > MAX_PREVIEW {
+            return Err(ApiError::BadRequest(format!(
+                "count: at most {MAX_PREVIEW}"
+            )));
+        }
+        let entries = self.entries.lock().unwrap();
+        let entry = entries
+            .get(id)
+            .filter(|e| e.schedule.deleted_at.is_none())
+            .ok_or_else(|| ApiError::NotFound(format!("schedule '{id}'")))?;
+        let mut next = Vec::with_capacity(count);
+        let mut after = self.clock.now();
+        while next.len() < count {
+            let Some(at) = entry.cron.next_after(after, entry.timezone) else {
+                break;
+            };
+            next.push(FireTime {
+                at,
+                local: at.with_timezone(&entry.timezone),
+            });
+            after = at;
+        }
+        Ok(SchedulePreview {
+            id: id.to_string(),
+            cron: entry.schedule.definition.cron.clone(),
+            timezone: entry.schedule.definition.timezone.clone(),
+            enabled: entry.schedule.definition.enabled,
+            next,
+        })
+    }
+
+    /**
+     * runs: a schedule's recent runs, newest first, with their outcomes
+     * brought up to date from the pool
+     */
+    pub async fn runs(&self, id: &str, pool: &JobPool) -> Result<Vec<ScheduleRun>, ApiError> {
+        let mut runs: Vec<ScheduleRun> = {
+            let entries = self.entries.lock().unwrap();
+            let entry = entries
+                .get(id)
+                .ok_or_else(|| ApiError::NotFound(format!("schedule '{id}'")))?;
+            entry.history.iter().rev().cloned().collect()
+        };
+        let mut settled = Vec::new();
+        for run in runs.iter_mut().filter(|r| !r.outcome.is_final()) {
+            let Some(job_id) = run.job_id else {
+                continue;
+            };
+            run.outcome = match pool.job(job_id).await {
+                Some(job) => RunOutcome::for_state(job.state()),
+                // accepted but not yet in the pool
+                None if run.outcome == RunOutcome::Queued => RunOutcome::Queued,
+                None => RunOutcome::Unknown,
+            };
+            if run.outcome.is_final() {
+                settled.push((job_id, run.outcome.clone()));
+            }
+        }
+
+        // keep final outcomes, so they survive the job leaving the pool
+        if !settled.is_empty()
+            && let Some(entry) = self.entries.lock().unwrap().get_mut(id)
+        {
+            for run in entry.history.iter_mut() {
+                if let Some((_, outcome)) = settled.iter().find(|(j, _)| Some(*j) == run.job_id) {
+                    run.outcome = outcome.clone();
+                }
+            }
+        }
+        Ok(runs)
+    }
+
+    /**
+     * drive: fire schedules as they come due, and purge deleted ones
+     * NOTE: holds the pool weakly; returns once the pool is gone. A fire
+     * time missed while the driver was busy runs once, late
+     */
+    pub async fn drive(&self, pool: Weak<JobPool>) {
+        loop {
+            self.purge(self.clock.now());
+            let wait = {
+                let entries = self.entries.lock().unwrap();
+                entries
+                    .values()
+                    .filter_map(|e| e.schedule.next_run_at.or(self.purge_at(&e.schedule)))
+                    .min()
+                    .map_or(IDLE_WAIT, |next| {
+                        (next - self.clock.now()).to_std().unwrap_or(Duration::ZERO)
+                    })
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = self.changed.notified() => continue,
+            }
+            let Some(pool) = pool.upgrade() else {
+                return;
+            };
+            self.fire_due(&pool).await;
+        }
+    }
+
+    // Submit the job of every schedule that is due
+    async fn fire_due(&self, pool: &JobPool) {
+        let now = self.clock.now();
+        // decide under the lock, submit outside it
+        let due: Vec<(String, JobSubmission, Option<JobId>)> = {
+            let mut entries = self.entries.lock().unwrap();
+            entries
+                .values_mut()
+                .filter(|e| e.schedule.next_run_at.is_some_and(|t| t <= now))
+                .map(|e| {
+                    e.schedule.last_run_at = Some(now);
+                    e.plan_next(now);
+                    let previous = (!e.schedule.definition.allow_overlap)
+                        .then(|| e.unsettled_job())
+                        .flatten();
+                    let mut job = e.schedule.definition.job.clone();
+                    job.schedule_id = Some(e.schedule.id.clone());
+                    (e.schedule.id.clone(), job, previous)
+                })
+                .collect()
+        };
+        for (id, job, previous) in due {
+            if let Some(previous) = previous
+                && still_active(pool, previous).await
+            {
+                println!("[Schedules]: {} skipped: job {} still active", id, previous);
+                if let Some(entry) = self.entries.lock().unwrap().get_mut(&id) {
+                    entry.record(ScheduleRun {
+                        fired_at: now,
+                        job_id: None,
+                        outcome: RunOutcome::Skipped,
+                        error: Some(format!("previous run's job {previous} still active")),
+                    });
+                }
+                continue;
+            }
+            let run = match pool.submit(job).await {
+                Ok(job_id) => {
+                    println!("[Schedules]: {} fired: job {}", id, job_id);
+                    ScheduleRun {
+                        fired_at: now,
+                        job_id: Some(job_id),
+                        outcome: RunOutcome::Queued,
+                        error: None,
+                    }
+                }
+                Err(e) => {
+                    println!("[Schedules]: {} fired: rejected: {:?}", id, e);
+                    ScheduleRun {
+                        fired_at: now,
+                        job_id: None,
+                        outcome: RunOutcome::Rejected,
+                        error: Some(format!("{e:?}")),
+                    }
+                }
+            };
+            // the schedule may have gone while submitting
+            if let Some(entry) = self.entries.lock().unwrap().get_mut(&id) {
+                entry.record(run);
+            }
+        }
+    }
+}
+
+// Whether a schedule's job is still queued or running
+// NOTE: a job the pool no longer knows has long finished
+async fn still_active(pool: &JobPool, job_id: JobId) -> bool {
+    pool.job(job_id)
+        .await
+        .is_some_and(|job| !job.state().is_terminal())
+}
@@ -0,0 +1,391 @@
+/*! Schemas module for async orchestrator
+ * Admin-registered JSON Schemas for job payloads, checked at submission
+ *
+ * One schema per job type; a submission whose payload doesn't match its
+ * type's schema is rejected with an error per offending location, as a
+ * JSON pointer into the submission (e.g. "/payload/milliseconds").
+ * NOTE: a subset of JSON Schema is supported: type, enum, const,
+ * properties, required, additionalProperties, items, minItems, maxItems,
+ * minimum, maximum, exclusiveMinimum, exclusiveMaximum, minLength,
+ * maxLength, allOf and anyOf. Schemas using anything else are refused at
+ * registration rather than half-enforced. Schemas are kept in memory only.
+ */
+use crate::api_error::ApiError;
+use crate::jobs::JobSubmission;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+// keywords that describe rather than constrain; accepted and ignored
+const ANNOTATIONS: &[&str] = &[
+    "$schema",
+    "$id",
+    "$comment",
+    "title",
+    "description",
+    "default",
+    "examples",
+];
+
+// keywords validate understands
+const KEYWORDS: &[&str] = &[
+    "type",
+    "enum",
+    "const",
+    "properties",
+    "required",
+    "additionalProperties",
+    "items",
+    "minItems",
+    "maxItems",
+    "minimum",
+    "maximum",
+    "exclusiveMinimum",
+    "exclusiveMaximum",
+    "minLength",
+    "maxLength",
+    "allOf",
+    "anyOf",
+];
+
+/**
+ * SchemaError
+ * One way an instance fails a schema, and where
+ */
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SchemaError {
+    // JSON pointer to the offending value
+    pub pointer: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pointer = if self.pointer.is_empty() {
+            "/"
+        } else {
+            &self.pointer
+        };
+        write!(f, "{}: {}", pointer, self.message)
+    }
+}
+
+/**
+ * Schemas
+ * Payload schema per job type; cheap to clone, clones share the registry
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Schemas {
+    registry: Arc<Mutex<BTreeMap<String, Value>>>,
+    // the job types schemas may be registered for
+    job_types: Vec<String>,
+}
+
+impl Schemas {
+    pub fn new(job_types: Vec<String>) -> Self {
+        Self {
+            registry: Arc::default(),
+            job_types,
+        }
+    }
+
+    /**
+     * put: register (or replace) a job type's payload schema
+     */
+    pub fn put(&self, job_type: &str, schema: Value) -> Result<(), ApiError> {
+        if !self.job_types.iter().any(|t| t == job_type) {
+            return Err(ApiError::NotFound(format!("job type '{job_type}'")));
+        }
+        check_schema(&schema, "").map_err(ApiError::BadRequest)?;
+        self.registry
+            .lock()
+            .unwrap()
+            .insert(job_type.to_string(), schema);
+        println!("[Schemas]: {} schema registered", job_type);
+        Ok(())
+    }
+
+    pub fn get(&self, job_type: &str) -> Option<Value> {
+        self.registry.lock().unwrap().get(job_type).cloned()
+    }
+
+    /**
+     * list: every registered schema, by job type
+     */
+    pub fn list(&self) -> BTreeMap<String, Value> {
+        self.registry.lock().unwrap().clone()
+    }
+
+    /**
+     * remove: drop a job type's schema; its payloads are no longer checked
+     */
+    pub fn remove(&self, job_type: &str) -> Result<(), ApiError> {
+        match self.registry.lock().unwrap().remove(job_type) {
+            Some(_) => {
+                println!("[Schemas]: {} schema removed", job_type);
+                Ok(())
+            }
+            None => Err(ApiError::NotFound(format!("schema for '{job_type}'"))),
+        }
+    }
+
+    /**
+     * check: reject a submission whose payload fails its type's schema
+     */
+    pub fn check(&self, job: &JobSubmission) -> Result<(), ApiError> {
+        let Some(schema) = self.get(job.kind.name()) else {
+            return Ok(());
+        };
+        let kind =
+            serde_json::to_value(&job.kind).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+        let payload = kind.get("payload").unwrap_or(&Value::Null);
+        let errors = validate(&schema, payload, "/payload");
+        if errors.is_empty() {
+            return Ok(());
+        }
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        Err(ApiError::BadRequest(format!(
+            "payload doesn't match the {} schema: {}",
+            job.kind.name(),
+            errors.join("; ")
+        )))
+    }
+}
+
+/**
+ * validate: every way an instance fails a schema; empty if it matches
+ * Pointers are relative to `at`, the instance's own location
+ */
+pub fn validate(schema: &Value, instance: &Value, at: &str) -> Vec<SchemaError> {
+    let mut errors = Vec::new();
+    let Some(schema) = schema.as_object() else {
+        // true accepts everything, false nothing
+        if schema == &Value::Bool(false) {
+            errors.push(error(at, "no value is allowed here"));
+        }
+        return errors;
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let names: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !names.iter().any(|name| has_type(instance, name)) {
+            errors.push(error(
+                at,
+                &format!(
+                    "expected {}, got {}",
+                    names.join(" or "),
+                    type_name(instance)
+                ),
+            ));
+            // the remaining keywords would only restate the mismatch
+            return errors;
+        }
+    }
+    if let Some(Value::Array(allowed)) = schema.get("enum")
+        && !allowed.contains(instance)
+    {
+        errors.push(error(
+            at,
+            &format!("must be one of {}", Value::Array(allowed.clone())),
+        ));
+    }
+    if let Some(constant) = schema.get("const")
+        && constant != instance
+    {
+        errors.push(error(at, &format!("must be {constant}")));
+    }
+
+    match instance {
+        Value::Object(fields) => check_object(schema, fields, at, &mut errors),
+        Value::Array(items) => check_array(schema, items, at, &mut errors),
+        Value::String(s) => check_string(schema, s, at, &mut errors),
+        Value::Number(n) => check_number(schema, n.as_f64().unwrap_or_default(), at, &mut errors),
+        _ => {}
+    }
+
+    if let Some(Value::Array(all)) = schema.get("allOf") {
+        for sub in all {
+            errors.extend(validate(sub, instance, at));
+        }
+    }
+    if let Some(Value::Array(any)) = schema.get("anyOf")
+        && !any.iter().any(|sub| validate(sub, instance, at).is_empty())
+    {
+        errors.push(error(at, "matches none of the anyOf schemas"));
+    }
+    errors
+}
+
+fn check_object(
+    schema: &Map<String, Value>,
+    fields: &Map<String, Value>,
+    at: &str,
+    errors: &mut Vec<SchemaError>,
+) {
+    if let Some(Value::Array(required)) = schema.get("required") {
+        for name in required.iter().filter_map(Value::as_str) {
+            if !fields.contains_key(name) {
+                errors.push(error(&pointer(at, name), "required"));
+            }
+        }
+    }
+    let properties = schema.get("properties").and_then(Value::as_object);
+    for (name, value) in fields {
+        let at = pointer(at, name);
+        match properties.and_then(|p| p.get(name)) {
+            Some(sub) => errors.extend(validate(sub, value, &at)),
+            None => match schema.get("additionalProperties") {
+                Some(Value::Bool(false)) => errors.push(error(&at, "not an allowed property")),
+                Some(sub @ Value::Object(_)) => errors.extend(validate(sub, value, &at)),
+                _ => {}
+            },
+        }
+    }
+}
+
+fn check_array(
+    schema: &Map<String, Value>,
+    items: &[Value],
+    at: &str,
+    errors: &mut Vec<SchemaError>,
+) {
+    if let Some(min) = schema.get("minItems").and_then(Value::as_u64)
+        && (items.len() as u64) < min
+    {
+        errors.push(error(at, &format!("fewer than {min} items")));
+    }
+    if let Some(max) = schema.get("maxItems").and_then(Value::as_u64)
+        && (items.len() as u64) > max
+    {
+        errors.push(error(at, &format!("more than {max} items")));
+    }
+    if let Some(sub) = schema.get("items") {
+        for (i, item) in items.iter().enumerate() {
+            errors.extend(validate(sub, item, &pointer(at, &i.to_string())));
+        }
+    }
+}
+
+fn check_string(schema: &Map<String, Value>, s: &str, at: &str, errors: &mut Vec<SchemaError>) {
+    let len = s.chars().count() as u64;
+    if let Some(min) = schema.get("minLength").and_then(Value::as_u64)
+        && len < min
+    {
+        errors.push(error(at, &format!("shorter than {min} characters")));
+    }
+    if let Some(max) = schema.get("maxLength").and_then(Value::as_u64)
+        && len > max
+    {
+        errors.push(error(at, &format!("longer than {max} characters")));
+    }
+}
+
+fn check_number(schema: &Map<String, Value>, n: f64, at: &str, errors: &mut Vec<SchemaError>) {
+    let bound = |key: &str| schema.get(key).and_then(Value::as_f64);
+    if let Some(min) = bound("minimum")
+        && n < min
+    {
+        errors.push(error(at, &format!("less than {min}")));
+    }
+    if let Some(max) = bound("maximum")
+        && n > max
+    {
+        errors.push(error(at, &format!("greater than {max}")));
+    }
+    if let Some(min) = bound("exclusiveMinimum")
+        && n <= min
+    {
+        errors.push(error(at, &format!("not greater than {min}")));
+    }
+    if let Some(max) = bound("exclusiveMaximum")
+        && n >= max
+    {
+        errors.push(error(at, &format!("not less than {max}")));
+    }
+}
+
+// Refuse schemas using keywords validate doesn't enforce
+fn check_schema(schema: &Value, at: &str) -> Result<(), String> {
+    let schema = match schema {
+        Value::Bool(_) => return Ok(()),
+        Value::Object(schema) => schema,
+        _ => {
+            return Err(format!(
+                "{}: a schema is an object or a boolean",
+                at_or_root(at)
+            ));
+        }
+    };
+    for (keyword, value) in schema {
+        let here = pointer(at, keyword);
+        if ANNOTATIONS.contains(&keyword.as_str()) {
+            continue;
+        }
+        if !KEYWORDS.contains(&keyword.as_str()) {
+            return Err(format!(
+                "{}: unsupported keyword '{keyword}'",
+                at_or_root(at)
+            ));
+        }
+        match (keyword.as_str(), value) {
+            ("properties", Value::Object(props)) => {
+                for (name, sub) in props {
+                    check_schema(sub, &pointer(&here, name))?;
+                }
+            }
+            ("additionalProperties" | "items", sub) => check_schema(sub, &here)?,
+            ("allOf" | "anyOf", Value::Array(subs)) => {
+                for (i, sub) in subs.iter().enumerate() {
+                    check_schema(sub, &pointer(&here, &i.to_string()))?;
+                }
+            }
+            ("properties" | "allOf" | "anyOf", _) => {
+                return Err(format!("{here}: malformed '{keyword}'"));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// Whether a value has a JSON Schema type
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "integer" => value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        "number" => value.is_number(),
+        other => type_name(value) == other,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+// Extend a JSON pointer by one token, escaped per RFC 6901
+fn pointer(at: &str, token: &str) -> String {
+    format!("{}/{}", at, token.replace('~', "~0").replace('/', "~1"))
+}
+
+fn at_or_root(at: &str) -> &str {
+    if at.is_empty() { "/" } else { at }
+}
+
+fn error(at: &str, message: &str) -> SchemaError {
+    SchemaError {
+        pointer: at.to_string(),
+        message: message.to_string(),
+    }
+}
@@ -0,0 +1,93 @@
+/*! Scratch module for async orchestrator
+ * Per-job temporary directories, metered and cleaned up
+ *
+ * With SCRATCH_DIR set, each job gets an empty directory under it, named by
+ * its id, for the length of its execution (see executor::ExecContext). When
+ * the execution returns, the directory's disk usage is recorded on the job
+ * and it is removed; with SCRATCH_RETAIN_FAILED=true the directories of
+ * failed jobs are kept for debugging instead, and their path recorded.
+ * NOTE: retained directories are never removed by the orchestrator
+ */
+use crate::ids::JobId;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/**
+ * ScratchConfig
+ */
+#[derive(Debug, Clone)]
+pub struct ScratchConfig {
+    // job directories are created under here
+    pub root: PathBuf,
+    // keep the directories of failed jobs
+    pub retain_failed: bool,
+}
+
+/**
+ * ScratchUsage
+ * What a job left in its scratch directory
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScratchUsage {
+    // disk used when the execution returned
+    pub bytes: u64,
+    // where the directory was kept; None: it was removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retained: Option<PathBuf>,
+}
+
+impl ScratchConfig {
+    /**
+     * create: a fresh, empty directory for a job
+     */
+    pub fn create(&self, job_id: JobId) -> Result<PathBuf, String> {
+        let dir = self.root.join(job_id.to_string());
+        // clear any leftover for the same id, so the job starts empty
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+        Ok(dir)
+    }
+
+    /**
+     * finish: meter a job's directory, then remove it, unless the job
+     * failed and failed jobs' directories are retained
+     */
+    pub fn finish(&self, dir: &Path, failed: bool) -> ScratchUsage {
+        let bytes = disk_usage(dir).unwrap_or_else(|e| {
+            println!("[Scratch]: {}: usage unknown: {}", dir.display(), e);
+            0
+        });
+        if failed && self.retain_failed {
+            println!("[Scratch]: {}: retained", dir.display());
+            return ScratchUsage {
+                bytes,
+                retained: Some(dir.to_path_buf()),
+            };
+        }
+        if let Err(e) = fs::remove_dir_all(dir) {
+            println!("[Scratch]: {}: not removed: {}", dir.display(), e);
+        }
+        ScratchUsage {
+            bytes,
+            retained: None,
+        }
+    }
+}
+
+// Total size of the files under a directory
+// NOTE: symlinks are counted as links, not followed
+fn disk_usage(dir: &Path) -> io::Result<u64> {
+    let mut total = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let meta = entry.path().symlink_metadata()?;
+        total += if meta.is_dir() {
+            disk_usage(&entry.path())?
+        } else {
+            meta.len()
+        };
+    }
+    Ok(total)
+}
@@ -0,0 +1,296 @@
+/*! Sim module for async orchestrator
+ * Deterministic simulation: a pool on virtual time with scripted executors
+ *
+ * Time is tokio's paused clock, and nothing moves it but
+ * Simulation::advance, so schedules, retries, backoffs and timeouts play
+ * out the same way on every run, and an hour of them takes about a second.
+ * Time moves in steps of the simulation's resolution, and the pool settles
+ * after each, so a timer fires up to one step after it was due.
+ * NOTE: needs a current-thread runtime, e.g. #[tokio::test]; built with
+ * the "sim" feature
+ */
+use crate::clock::Clock;
+use crate::config::PoolConfig;
+use crate::executor::{Executor, ExecutorInfo};
+use crate::jobs::{JobPool, JobSubmission};
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+// largest single jump of virtual time, so timers due during an advance
+// fire close to when they were due
+const DEFAULT_RESOLUTION: Duration = Duration::from_millis(100);
+// how long settle waits on execution threads before giving up
+const SETTLE_LIMIT: Duration = Duration::from_secs(5);
+
+/**
+ * VirtualClock
+ * Wall-clock time that follows tokio's (paused) clock from an epoch
+ */
+#[derive(Debug)]
+pub struct VirtualClock {
+    epoch: DateTime<Utc>,
+    origin: Instant,
+}
+
+impl VirtualClock {
+    pub fn new(epoch: DateTime<Utc>) -> Self {
+        Self {
+            epoch,
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.epoch + (Instant::now() - self.origin)
+    }
+}
+
+/**
+ * Scripted
+ * One scripted execution: how long it takes and how it ends
+ */
+#[derive(Debug, Clone)]
+pub struct Scripted {
+    pub duration: Duration,
+    pub outcome: Result<String, String>,
+}
+
+impl Scripted {
+    pub fn succeed(duration: Duration, result: &str) -> Self {
+        Self {
+            duration,
+            outcome: Ok(result.to_string()),
+        }
+    }
+
+    pub fn fail(duration: Duration, error: &str) -> Self {
+        Self {
+            duration,
+            outcome: Err(error.to_string()),
+        }
+    }
+}
+
+/**
+ * Execution
+ * A job the scripted executor ran
+ */
+#[derive(Debug, Clone)]
+pub struct Execution {
+    pub job_type: String,
+    pub started_at: DateTime<Utc>,
+    pub outcome: Result<String, String>,
+}
+
+/**
+ * ScriptedExecutor
+ * Fake executor: plays back scripted executions per job type, taking
+ * their durations in virtual time, and records what it ran
+ * Unscripted jobs succeed at once with "ok"
+ */
+#[derive(Debug)]
+pub struct ScriptedExecutor {
+    clock: Arc<VirtualClock>,
+    // job type -> executions still to play, in order
+    script: Mutex<HashMap<String, VecDeque<Scripted>>>,
+    executions: Mutex<Vec<Execution>>,
+    // when each execution in progress is due to end
+    deadlines: Mutex<Vec<Instant>>,
+    // signalled by the simulation whenever time may have moved
+    tick: Condvar,
+}
+
+impl ScriptedExecutor {
+    pub fn new(clock: Arc<VirtualClock>) -> Self {
+        Self {
+            clock,
+            script: Mutex::default(),
+            executions: Mutex::default(),
+            deadlines: Mutex::default(),
+            tick: Condvar::new(),
+        }
+    }
+
+    /**
+     * script: queue executions for the next jobs of a type
+     */
+    pub fn script(&self, job_type: &str, executions: impl IntoIterator<Item = Scripted>) {
+        self.script
+            .lock()
+            .unwrap()
+            .entry(job_type.to_string())
+            .or_default()
+            .extend(executions);
+    }
+
+    /**
+     * executions: everything run so far, in start order
+     */
+    pub fn executions(&self) -> Vec<Execution> {
+        self.executions.lock().unwrap().clone()
+    }
+
+    // Let executions whose deadline has passed return
+    fn wake(&self) {
+        let _deadlines = self.deadlines.lock().unwrap();
+        self.tick.notify_all();
+    }
+
+    // Executions still waiting for their deadline; one whose deadline has
+    // passed is about to return, so doesn't count
+    fn waiting(&self) -> usize {
+        let now = Instant::now();
+        self.deadlines
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|d| **d > now)
+            .count()
+    }
+}
+
+impl Executor for ScriptedExecutor {
+    fn info(&self) -> ExecutorInfo {
+        ExecutorInfo::new("scripted", env!("CARGO_PKG_VERSION"))
+    }
+
+    fn execute(&self, submission: &JobSubmission) -> Result<String, String> {
+        let job_type = submission.kind.name();
+        let scripted = self
+            .script
+            .lock()
+            .unwrap()
+            .get_mut(job_type)
+            .and_then(VecDeque::pop_front)
+            .unwrap_or_else(|| Scripted::succeed(Duration::ZERO, "ok"));
+        self.executions.lock().unwrap().push(Execution {
+            job_type: job_type.to_string(),
+            started_at: self.clock.now(),
+            outcome: scripted.outcome.clone(),
+        });
+
+        if !scripted.duration.is_zero() {
+            // settle holds time still until the deadline is registered, so
+            // the execution takes exactly its duration
+            let deadline = Instant::now() + scripted.duration;
+            let mut deadlines = self.deadlines.lock().unwrap();
+            deadlines.push(deadline);
+            while Instant::now() < deadline {
+                deadlines = self.tick.wait(deadlines).unwrap();
+            }
+            if let Some(i) = deadlines.iter().position(|d| *d == deadline) {
+                deadlines.swap_remove(i);
+            }
+        }
+        scripted.outcome
+    }
+}
+
+/**
+ * Simulation
+ * A job pool on a virtual clock, with a scripted executor
+ */
+pub struct Simulation {
+    pool: Arc<JobPool>,
+    clock: Arc<VirtualClock>,
+    executor: Arc<ScriptedExecutor>,
+    resolution: Duration,
+    // dropping it ends the task that holds time still
+    _anchor: std::sync::mpsc::Sender<()>,
+}
+
+impl Simulation {
+    /**
+     * start: pause time and start a pool whose clock reads `epoch` now
+     * The config's clock and executor are replaced
+     */
+    pub fn start(mut config: PoolConfig, epoch: DateTime<Utc>) -> Self {
+        tokio::time::pause();
+        // a running blocking task stops the runtime skipping ahead to its
+        // next timer whenever it idles; keep one for the whole simulation
+        let (anchor, held) = std::sync::mpsc::channel::<()>();
+        tokio::task::spawn_blocking(move || {
+            let _ = held.recv();
+        });
+        let clock = Arc::new(VirtualClock::new(epoch));
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        config.clock = clock.clone();
+        config.executor = executor.clone();
+        Self {
+            pool: JobPool::start(&config),
+            clock,
+            executor,
+            resolution: DEFAULT_RESOLUTION,
+            _anchor: anchor,
+        }
+    }
+
+    /**
+     * with_resolution: the largest jump of virtual time advance makes
+     */
+    pub fn with_resolution(mut self, resolution: Duration) -> Self {
+        self.resolution = resolution.max(Duration::from_millis(1));
+        self
+    }
+
+    pub fn pool(&self) -> &Arc<JobPool> {
+        &self.pool
+    }
+
+    pub fn executor(&self) -> &ScriptedExecutor {
+        &self.executor
+    }
+
+    /**
+     * now: the virtual wall-clock time
+     */
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    /**
+     * advance: move virtual time forward, letting the pool react at each
+     * step
+     */
+    pub async fn advance(&self, by: Duration) {
+        let target = Instant::now() + by;
+        self.settle().await;
+        loop {
+            let now = Instant::now();
+            if now >= target {
+                return;
+            }
+            tokio::time::advance((target - now).min(self.resolution)).await;
+            self.settle().await;
+        }
+    }
+
+    /**
+     * settle: let the pool do everything it can without time passing,
+     * until every running job is waiting on virtual time
+     * NOTE: jobs execute on real threads, so this briefly waits for them
+     */
+    pub async fn settle(&self) {
+        let started = std::time::Instant::now();
+        loop {
+            self.executor.wake();
+            for _ in 0..16 {
+                tokio::task::yield_now().await;
+            }
+            if self.pool.status().await.busy == self.executor.waiting() {
+                return;
+            }
+            if started.elapsed() > SETTLE_LIMIT {
+                println!("[Simulation]: settle: jobs still executing, giving up");
+                return;
+            }
+            // give the execution threads a moment of real time
+            std::thread::sleep(Duration::from_micros(200));
+        }
+    }
+}
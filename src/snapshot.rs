@@ -0,0 +1,87 @@
+/*! Snapshot module for async orchestrator
+ * Periodically refreshed read snapshots of the pool, for list and stats
+ * endpoints
+ *
+ * With SNAPSHOT_INTERVAL_MS set, the pool copies its jobs, status and
+ * metrics into a snapshot that often. GET /jobs, GET /pool and GET
+ * /metrics answer from the latest snapshot instead of taking the pool's
+ * lock, saying when it was taken in an X-Snapshot-At header, so dashboard
+ * traffic costs dispatch one copy per interval however heavy it is. A new
+ * snapshot replaces the last whole (copy on write): readers share it
+ * without blocking each other or the refresh.
+ * NOTE: answers may be up to an interval stale. Until the first snapshot
+ * is taken, and for single jobs, waits and every change, the live pool is
+ * used
+ */
+use crate::jobs::{Job, JobPool, PoolMetrics, PoolStatus};
+use chrono::{DateTime, Utc};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::Duration;
+
+// response header carrying when the snapshot answered from was taken
+pub const SNAPSHOT_AT_HEADER: &str = "X-Snapshot-At";
+
+/**
+ * PoolSnapshot
+ * A copy of the pool, as it stood when taken
+ */
+#[derive(Debug, Clone)]
+pub struct PoolSnapshot {
+    pub taken_at: DateTime<Utc>,
+    // running, then pending, then completed (oldest first)
+    pub jobs: Vec<Job>,
+    pub status: PoolStatus,
+    pub metrics: PoolMetrics,
+}
+
+/**
+ * Snapshots
+ * The latest snapshot, and how often it is retaken; cheap to clone
+ */
+#[derive(Debug, Clone)]
+pub struct Snapshots {
+    // None: snapshots are off; reads use the live pool
+    interval: Option<Duration>,
+    latest: Arc<RwLock<Option<Arc<PoolSnapshot>>>>,
+}
+
+impl Snapshots {
+    pub fn new(interval: Option<Duration>) -> Self {
+        Self {
+            interval,
+            latest: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.interval.is_some()
+    }
+
+    /**
+     * latest: the snapshot to read from; None: use the live pool
+     */
+    pub fn latest(&self) -> Option<Arc<PoolSnapshot>> {
+        self.latest.read().unwrap().clone()
+    }
+
+    pub fn replace(&self, snapshot: PoolSnapshot) {
+        *self.latest.write().unwrap() = Some(Arc::new(snapshot));
+    }
+
+    /**
+     * drive: retake the snapshot every interval, until the pool is gone
+     */
+    pub async fn drive(&self, pool: Weak<JobPool>) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+        loop {
+            let Some(pool) = pool.upgrade() else {
+                return;
+            };
+            self.replace(pool.take_snapshot().await);
+            drop(pool);
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
@@ -0,0 +1,126 @@
+/*! Tenants module for async orchestrator
+ * Per tenant default queue and priority ceiling, managed centrally
+ *
+ * Set by TENANT_POLICIES as "tenant.field=value" entries, e.g.
+ * "acme.queue=batch,acme.max_priority=normal". A submission's tenant is
+ * its metadata "tenant" (as usage is billed). A tenant's queue fills the
+ * queue of submissions that omit one, ahead of the job type's defaults;
+ * a submission above its tenant's max_priority is refused (403), so
+ * capacity classes can be allocated per team. A tenant's quota caps what
+ * its jobs may cost (see the estimate module).
+ * NOTE: the tenant is taken as the submission states it
+ */
+use crate::api_error::ApiError;
+use crate::jobs::{JobSubmission, Priority};
+use crate::usage::TENANT_KEY;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/**
+ * TenantPolicy
+ * One tenant's policy; None leaves the field unrestricted
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TenantPolicy {
+    pub queue: Option<String>,
+    pub max_priority: Option<Priority>,
+    // cost its jobs may run up over the quota window
+    pub quota: Option<f64>,
+}
+
+/**
+ * TenantPolicies
+ * The policy of each tenant that has one
+ */
+#[derive(Debug, Clone, Default)]
+pub struct TenantPolicies {
+    pub by_tenant: HashMap<String, TenantPolicy>,
+}
+
+impl TenantPolicies {
+    /**
+     * fill_defaults: give a raw submission (job or map) that omits its
+     * queue its tenant's default queue
+     */
+    pub fn fill_defaults(&self, raw: &mut Value) {
+        let queue = raw
+            .get("metadata")
+            .and_then(|metadata| metadata.get(TENANT_KEY))
+            .and_then(Value::as_str)
+            .and_then(|tenant| self.by_tenant.get(tenant))
+            .and_then(|policy| policy.queue.clone());
+        if let (Some(queue), Some(fields)) = (queue, raw.as_object_mut()) {
+            fields.entry("queue").or_insert(Value::String(queue));
+        }
+    }
+
+    /**
+     * check: Forbidden if a submission is above its tenant's max priority
+     */
+    pub fn check(&self, job: &JobSubmission) -> Result<(), ApiError> {
+        let Some(tenant) = job
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.get(TENANT_KEY))
+            .and_then(Value::as_str)
+        else {
+            return Ok(());
+        };
+        match self.by_tenant.get(tenant).and_then(|p| p.max_priority) {
+            Some(max) if job.priority > max => {
+                let (priority, max) = (json_name(job.priority), json_name(max));
+                Err(ApiError::Forbidden(format!(
+                    "tenant '{tenant}' may submit up to {max} priority, not {priority}"
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn quota(&self, tenant: &str) -> Option<f64> {
+        self.by_tenant.get(tenant)?.quota
+    }
+}
+
+// A priority as submissions spell it
+fn json_name(priority: Priority) -> String {
+    serde_json::to_value(priority)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+// "tenant.field=value,..."
+impl FromStr for TenantPolicies {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut by_tenant: HashMap<String, TenantPolicy> = HashMap::new();
+        for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (key, value) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("'{entry}': expected tenant.field=value"))?;
+            let (tenant, field) = key
+                .trim()
+                .rsplit_once('.')
+                .filter(|(tenant, _)| !tenant.is_empty())
+                .ok_or_else(|| format!("'{entry}': expected tenant.field=value"))?;
+            let value = value.trim();
+            let policy = by_tenant.entry(tenant.to_string()).or_default();
+            match field {
+                "queue" => policy.queue = Some(value.to_string()),
+                "max_priority" => policy.max_priority = Some(value.parse()?),
+                "quota" => {
+                    let quota: f64 = value.parse().map_err(|e| format!("'{entry}': {e}"))?;
+                    if !quota.is_finite() || quota < 0.0 {
+                        return Err(format!("'{entry}': quota must be a non-negative number"));
+                    }
+                    policy.quota = Some(quota);
+                }
+                _ => return Err(format!("'{entry}': unknown field '{field}'")),
+            }
+        }
+        Ok(Self { by_tenant })
+    }
+}
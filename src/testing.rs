@@ -0,0 +1,260 @@
+/*! Testing module for async orchestrator
+ * An in-memory orchestrator for downstream integration tests
+ *
+ * TestOrchestrator serves the real router over a simulated pool (see the
+ * sim module): drive the API with tower's oneshot, move virtual time with
+ * advance, and assert on job states, with no server, sockets or real
+ * executors. Job types run as scripted on the executor, or succeed at once.
+ * NOTE: built with the "testing" feature; needs a current-thread runtime,
+ * e.g. #[tokio::test]
+ */
+use crate::access::Admins;
+use crate::api;
+use crate::cache::CacheConfig;
+use crate::clock::SystemClock;
+use crate::config::{OverflowPolicy, PoolConfig};
+use crate::defaults::JobDefaults;
+use crate::egress::EgressPolicy;
+use crate::estimate::AdmissionConfig;
+use crate::handlers::Handlers;
+use crate::health::HealthConfig;
+use crate::history::RetentionConfig;
+use crate::hooks::HookList;
+use crate::ids::{JobId, UlidIds};
+use crate::jobs::{Job, JobPool, JobSubmission, State};
+use crate::notify::NotifyConfig;
+use crate::profiles::EnvProfiles;
+use crate::queues::{DEFAULT_QUEUE, QueueConfig};
+use crate::ratelimit::RateLimitList;
+use crate::sim::{ScriptedExecutor, Simulation};
+use crate::tenants::TenantPolicies;
+use crate::usage::CostModel;
+use crate::webhooks::WebhookConfig;
+use axum::Router;
+use axum::body::{self, Body};
+use axum::http::{Request, StatusCode, header};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+use std::time::Duration;
+use tower::ServiceExt;
+
+// largest response body request will read
+const MAX_BODY: usize = 16 * 1024 * 1024;
+
+/**
+ * test_config: a small pool with just the default queue, no notification
+ * channels, and a single callback attempt
+ */
+pub fn test_config() -> PoolConfig {
+    PoolConfig {
+        max_jobs: 4,
+        submission_queue_size: 64,
+        overflow_policy: OverflowPolicy::Reject,
+        sample_interval: Duration::from_secs(1),
+        autoscale: None,
+        load_shed: None,
+        queues: vec![QueueConfig {
+            pending_limit: 64,
+            ..QueueConfig::new(DEFAULT_QUEUE)
+        }],
+        webhooks: WebhookConfig {
+            max_attempts: 1,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+            timeout: Duration::from_secs(1),
+            queue_size: 64,
+            concurrency: 1,
+        },
+        notify: NotifyConfig::default(),
+        // replaced by the simulation
+        clock: Arc::new(SystemClock),
+        executor: Arc::new(Handlers::builtin()),
+        ids: Arc::new(UlidIds),
+        hooks: HookList::default(),
+        event_log: None,
+        cost: CostModel::default(),
+        pause_state: None,
+        cache: CacheConfig::default(),
+        defaults: JobDefaults::default(),
+        tenants: TenantPolicies::default(),
+        admission: AdmissionConfig {
+            deadlines: false,
+            quota_window: Duration::from_secs(86_400),
+        },
+        scratch: None,
+        job_timeout: None,
+        history_budget: 0,
+        retention: RetentionConfig::default(),
+        dedup_window: Duration::from_secs(3600),
+        pin_quota: 100,
+        admins: Admins::default(),
+        stuck: None,
+        breaker: None,
+        shortest_first: None,
+        earliest_deadline_first: false,
+        env_profiles: EnvProfiles::default(),
+        egress: EgressPolicy::default(),
+        health: HealthConfig::default(),
+        rate_limits: RateLimitList::default(),
+        snapshot_interval: None,
+        log_rate_limit: None,
+        schedule_purge_after: None,
+    }
+}
+
+/**
+ * TestResponse
+ * A response read in full
+ */
+#[derive(Debug, Clone)]
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub body: Vec<u8>,
+}
+
+impl TestResponse {
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /**
+     * json: decode the body; panics (failing the test) if it doesn't
+     */
+    pub fn json<T: DeserializeOwned>(&self) -> T {
+        serde_json::from_slice(&self.body).unwrap_or_else(|e| {
+            panic!(
+                "response ({}) is not the expected JSON: {e}: {}",
+                self.status,
+                self.text()
+            )
+        })
+    }
+}
+
+/**
+ * TestOrchestrator
+ * The API over a simulated pool
+ */
+pub struct TestOrchestrator {
+    sim: Simulation,
+    router: Router,
+}
+
+impl TestOrchestrator {
+    /**
+     * start: test_config, with the virtual clock starting now
+     */
+    pub fn start() -> Self {
+        Self::with_config(test_config(), Utc::now())
+    }
+
+    /**
+     * with_config: a pool from config, with the virtual clock at epoch
+     * The config's clock and executor are replaced
+     */
+    pub fn with_config(config: PoolConfig, epoch: DateTime<Utc>) -> Self {
+        let sim = Simulation::start(config, epoch);
+        let router = api::create_router(sim.pool().clone());
+        Self { sim, router }
+    }
+
+    /**
+     * router: the API, to drive with tower::ServiceExt::oneshot
+     */
+    pub fn router(&self) -> Router {
+        self.router.clone()
+    }
+
+    pub fn pool(&self) -> &Arc<JobPool> {
+        self.sim.pool()
+    }
+
+    /**
+     * executor: script job outcomes here
+     */
+    pub fn executor(&self) -> &ScriptedExecutor {
+        self.sim.executor()
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.sim.now()
+    }
+
+    /**
+     * request: send one request through the router, JSON body if given
+     */
+    pub async fn request(
+        &self,
+        method: &str,
+        uri: &str,
+        body: Option<serde_json::Value>,
+    ) -> TestResponse {
+        let request = Request::builder().method(method).uri(uri);
+        let request = match body {
+            Some(json) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .expect("invalid test request");
+        let response = self
+            .router()
+            .oneshot(request)
+            .await
+            .expect("router is infallible");
+        let status = response.status();
+        let body = body::to_bytes(response.into_body(), MAX_BODY)
+            .await
+            .expect("failed to read response body");
+        // let the pool act on what the request did
+        self.sim.settle().await;
+        TestResponse {
+            status,
+            body: body.to_vec(),
+        }
+    }
+
+    /**
+     * submit: submit a job straight to the pool, returning its id
+     * Panics if the pool rejects it
+     */
+    pub async fn submit(&self, job: JobSubmission) -> JobId {
+        let id = self
+            .pool()
+            .submit(job)
+            .await
+            .unwrap_or_else(|e| panic!("submission rejected: {e:?}"));
+        self.sim.settle().await;
+        id
+    }
+
+    /**
+     * advance: move virtual time forward, letting the pool react
+     */
+    pub async fn advance(&self, by: Duration) {
+        self.sim.advance(by).await;
+    }
+
+    /**
+     * job: a job anywhere in the pool
+     */
+    pub async fn job(&self, id: JobId) -> Option<Job> {
+        self.pool().job(id).await
+    }
+
+    /**
+     * assert_state: panic (failing the test) unless the job is in state
+     */
+    pub async fn assert_state(&self, id: JobId, state: State) {
+        match self.job(id).await {
+            Some(job) if *job.state() == state => {}
+            Some(job) => panic!(
+                "job {id}: expected {state}, found {} ({})",
+                job.state(),
+                job.result()
+            ),
+            None => panic!("job {id}: expected {state}, but the pool doesn't know it"),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+/*! Timing module for async orchestrator
+ * Where a job's time went, phase by phase
+ *
+ * Each job records how long it spent in each phase it got to, in
+ * milliseconds, shown as timing on GET /jobs/{id}:
+ *   validation    checking the submission, before it was accepted
+ *   queued        from being accepted until given a slot (waiting on
+ *                 dependencies, a delay or a free slot included)
+ *   dispatch      from being given a slot until its execution thread ran it
+ *   setup         preparing the execution (scratch directory, ...)
+ *   execution     the executor's own run
+ *   teardown      from the executor returning until the slot was freed
+ *   notification  from finishing until its completion callback was
+ *                 delivered or given up on; only with a callback_url
+ * NOTE: phases are measured on the monotonic clock, not the pool's, so
+ * they are real time even in simulations
+ */
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/**
+ * JobTiming
+ * Time a job spent in each phase; None: it hasn't been through it
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct JobTiming {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validation_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queued_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dispatch_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub setup_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execution_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub teardown_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notification_ms: Option<f64>,
+    // when the phase underway began
+    #[serde(skip)]
+    mark: Option<Instant>,
+}
+
+impl JobTiming {
+    /**
+     * accepted: a submission that took validation to check, accepted now
+     */
+    pub fn accepted(validation: Duration) -> Self {
+        Self {
+            validation_ms: Some(millis(validation)),
+            mark: Some(Instant::now()),
+            ..Self::default()
+        }
+    }
+
+    /**
+     * dispatched: the job was given a slot
+     */
+    pub fn dispatched(&mut self) {
+        self.queued_ms = self.lap(Instant::now());
+    }
+
+    /**
+     * started: its execution thread picked the job up
+     */
+    pub fn started(&mut self) {
+        self.dispatch_ms = self.lap(Instant::now());
+    }
+
+    /**
+     * executed: the executor ran from began to ended
+     */
+    pub fn executed(&mut self, began: Instant, ended: Instant) {
+        self.setup_ms = self.lap(began);
+        self.execution_ms = self.lap(ended);
+    }
+
+    /**
+     * completed: the job finished and its slot is free; notification
+     * timing starts
+     */
+    pub fn completed(&mut self) {
+        let now = Instant::now();
+        if self.execution_ms.is_some() {
+            self.teardown_ms = self.lap(now);
+        }
+        self.mark = Some(now);
+    }
+
+    /**
+     * notified: its completion callback was delivered or given up on
+     */
+    pub fn notified(&mut self) {
+        self.notification_ms = self.lap(Instant::now());
+    }
+
+    // Milliseconds since the phase underway began, starting the next one
+    // at; None (and nothing started) if no phase was underway
+    fn lap(&mut self, at: Instant) -> Option<f64> {
+        let began = self.mark?;
+        self.mark = Some(at);
+        Some(millis(at.saturating_duration_since(began)))
+    }
+}
+
+// Milliseconds, to the microsecond
+fn millis(d: Duration) -> f64 {
+    d.as_micros() as f64 / 1000.0
+}
@@ -0,0 +1,80 @@
+/*! Unique module for async orchestrator
+ * Unique job keys: at most one unfinished job per key
+ *
+ * A submission with a unique_key holds that key from when it is accepted
+ * until it finishes, so two "reindex" jobs never run side by side. A
+ * submission whose key is held is rejected (409) by default; with
+ * on_duplicate "coalesce" it is answered with the job holding the key
+ * instead (200), as a reused idempotency key is.
+ * NOTE: unlike idempotency keys a unique key is free again as soon as its
+ * job finishes, however soon
+ */
+use crate::ids::JobId;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+/**
+ * DuplicatePolicy
+ * What happens to a submission whose unique key is held
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicatePolicy {
+    // 409
+    #[default]
+    Reject,
+    // answered with the job holding the key
+    Coalesce,
+}
+
+/**
+ * UniqueKeys
+ * Unique key -> the unfinished job holding it; cheap to clone
+ */
+#[derive(Debug, Clone, Default)]
+pub struct UniqueKeys {
+    held: Arc<Mutex<HashMap<String, JobId>>>,
+}
+
+impl UniqueKeys {
+    /**
+     * claim: hold key for job_id; Err: the job already holding it
+     */
+    pub fn claim(&self, key: &str, job_id: JobId) -> Result<(), JobId> {
+        let mut held = self.held.lock().unwrap();
+        match held.get(key) {
+            Some(&holder) if holder != job_id => Err(holder),
+            _ => {
+                held.insert(key.to_string(), job_id);
+                Ok(())
+            }
+        }
+    }
+
+    /**
+     * holder: the job holding key, if any
+     */
+    pub fn holder(&self, key: &str) -> Option<JobId> {
+        self.held.lock().unwrap().get(key).copied()
+    }
+
+    /**
+     * release: free key, if job_id holds it
+     */
+    pub fn release(&self, key: &str, job_id: JobId) {
+        let mut held = self.held.lock().unwrap();
+        if held.get(key) == Some(&job_id) {
+            held.remove(key);
+        }
+    }
+
+    pub fn list(&self) -> BTreeMap<String, JobId> {
+        self.held
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, &id)| (key.clone(), id))
+            .collect()
+    }
+}
@@ -0,0 +1,167 @@
+/*! Usage module for async orchestrator
+ * Job cost accounting, for chargeback of shared capacity
+ *
+ * A job's cost is its wall time (started to finished) in seconds, times the
+ * weight of its type (COST_WEIGHTS, e.g. "sleep=0.1,echo=2"; default 1).
+ * Jobs that never ran cost nothing. Usage is attributed to the tenant named
+ * by a job's "tenant" metadata string, when it has one.
+ * NOTE: executors don't report resource usage, so cost is wall time only
+ */
+use crate::jobs::{Job, JobSubmission};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+// weight of job types COST_WEIGHTS doesn't name
+pub const DEFAULT_WEIGHT: f64 = 1.0;
+// metadata key naming the tenant a job is billed to
+pub const TENANT_KEY: &str = "tenant";
+
+/**
+ * CostModel
+ * Cost weight per job type
+ */
+#[derive(Debug, Clone, Default)]
+pub struct CostModel {
+    weights: HashMap<String, f64>,
+}
+
+impl CostModel {
+    pub fn weight(&self, job_type: &str) -> f64 {
+        self.weights
+            .get(job_type)
+            .copied()
+            .unwrap_or(DEFAULT_WEIGHT)
+    }
+
+    /**
+     * cost: what a finished job cost; None if it never ran
+     */
+    pub fn cost(&self, job: &Job) -> Option<f64> {
+        let wall = wall_time(job)?;
+        Some(wall.as_secs_f64() * self.weight(job.submission().kind.name()))
+    }
+}
+
+// "type=weight,..."
+impl FromStr for CostModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = HashMap::new();
+        for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (job_type, weight) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("'{entry}': expected type=weight"))?;
+            let weight: f64 = weight
+                .trim()
+                .parse()
+                .map_err(|e| format!("'{entry}': {e}"))?;
+            if !weight.is_finite() || weight < 0.0 {
+                return Err(format!("'{entry}': weight must be a non-negative number"));
+            }
+            weights.insert(job_type.trim().to_string(), weight);
+        }
+        Ok(Self { weights })
+    }
+}
+
+/**
+ * UsageTotals
+ * Jobs run, their wall time, and their cost
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct UsageTotals {
+    pub jobs: usize,
+    pub wall_time_ms: u64,
+    pub cost: f64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, wall_time_ms: u64, cost: f64) {
+        self.jobs += 1;
+        self.wall_time_ms += wall_time_ms;
+        self.cost += cost;
+    }
+}
+
+/**
+ * UsageReport
+ * Usage of jobs that finished in [from, to), in total and broken down
+ * NOTE: jobs without a tenant appear in the totals only
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UsageReport {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub total: UsageTotals,
+    pub by_type: BTreeMap<String, UsageTotals>,
+    pub by_queue: BTreeMap<String, UsageTotals>,
+    pub by_tenant: BTreeMap<String, UsageTotals>,
+}
+
+impl UsageReport {
+    pub fn new(from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Self {
+        Self {
+            from,
+            to,
+            total: UsageTotals::default(),
+            by_type: BTreeMap::new(),
+            by_queue: BTreeMap::new(),
+            by_tenant: BTreeMap::new(),
+        }
+    }
+
+    /**
+     * add: count a job, if it ran and finished within the report's window
+     */
+    pub fn add(&mut self, job: &Job) {
+        let (Some(finished), Some(cost), Some(wall)) =
+            (job.finished_at(), job.cost(), wall_time(job))
+        else {
+            return;
+        };
+        let after_from = self.from.is_none_or(|from| finished >= from);
+        let before_to = self.to.is_none_or(|to| finished < to);
+        if !(after_from && before_to) {
+            return;
+        }
+        let wall_time_ms = wall.as_millis() as u64;
+        let submission = job.submission();
+        self.total.add(wall_time_ms, cost);
+        self.by_type
+            .entry(submission.kind.name().to_string())
+            .or_default()
+            .add(wall_time_ms, cost);
+        self.by_queue
+            .entry(submission.queue.clone())
+            .or_default()
+            .add(wall_time_ms, cost);
+        if let Some(tenant) = tenant(job) {
+            self.by_tenant
+                .entry(tenant.to_string())
+                .or_default()
+                .add(wall_time_ms, cost);
+        }
+    }
+}
+
+/**
+ * tenant: the tenant a job is billed to, from its metadata
+ */
+pub fn tenant(job: &Job) -> Option<&str> {
+    submission_tenant(job.submission())
+}
+
+/**
+ * submission_tenant: the tenant a submission would be billed to
+ */
+pub fn submission_tenant(submission: &JobSubmission) -> Option<&str> {
+    submission.metadata.as_ref()?.get(TENANT_KEY)?.as_str()
+}
+
+// Time from start to finish; None unless the job ran and finished
+fn wall_time(job: &Job) -> Option<std::time::Duration> {
+    (job.finished_at()? - job.started_at()?).to_std().ok()
+}
@@ -0,0 +1,253 @@
+/*! Webhooks module for async orchestrator
+ * Queued completion callbacks, retried with backoff
+ */
+use crate::clock::Clock;
+use crate::http_client;
+use crate::ids::JobId;
+use crate::notify::{Notification, NotificationChannel};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Semaphore, mpsc};
+
+/**
+ * WebhookConfig
+ * Delivery queue, retry, and concurrency limits
+ */
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    // total attempts per delivery, including the first
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    // per-attempt request timeout
+    pub timeout: Duration,
+    // deliveries waiting to be attempted
+    pub queue_size: usize,
+    // attempts in flight at once
+    pub concurrency: usize,
+}
+
+/**
+ * DeliveryState
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryState {
+    // job not finished yet, or delivery waiting for its first attempt
+    Pending,
+    // an attempt failed; another is scheduled
+    Retrying,
+    Delivered,
+    // gave up
+    Failed,
+}
+
+/**
+ * CallbackStatus
+ * Delivery status of a job's completion callback, kept on the job
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CallbackStatus {
+    pub url: String,
+    pub state: DeliveryState,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+impl CallbackStatus {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            state: DeliveryState::Pending,
+            attempts: 0,
+            last_error: None,
+            delivered_at: None,
+        }
+    }
+}
+
+/**
+ * Delivery
+ * One callback to deliver: POST body to url
+ */
+#[derive(Debug, Clone)]
+pub struct Delivery {
+    pub job_id: JobId,
+    pub url: String,
+    pub body: Vec<u8>,
+    attempts: u32,
+    // report status changes as the job's callback status
+    tracked: bool,
+}
+
+impl Delivery {
+    // A job's own completion callback
+    pub fn new(job_id: JobId, url: &str, body: Vec<u8>) -> Self {
+        Self {
+            job_id,
+            url: url.to_string(),
+            body,
+            attempts: 0,
+            tracked: true,
+        }
+    }
+
+    // Any other POST about a job (e.g. a notification); status is only logged
+    pub fn untracked(job_id: JobId, url: &str, body: Vec<u8>) -> Self {
+        Self {
+            tracked: false,
+            ..Self::new(job_id, url, body)
+        }
+    }
+}
+
+/**
+ * Webhooks
+ * Handle to the delivery worker; cheap to clone
+ * NOTE: every status change of a tracked delivery is reported on
+ * status_tx as (job id, status)
+ */
+#[derive(Clone)]
+pub struct Webhooks {
+    tx: mpsc::Sender<Delivery>,
+}
+
+impl Webhooks {
+    pub fn start(
+        config: WebhookConfig,
+        clock: Arc<dyn Clock>,
+        status_tx: mpsc::Sender<(JobId, CallbackStatus)>,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel(config.queue_size);
+        let requeue_tx = tx.clone();
+        tokio::spawn(async move {
+            Webhooks::run(config, clock, rx, requeue_tx, status_tx).await;
+        });
+        Self { tx }
+    }
+
+    /**
+     * enqueue: queue a delivery without waiting
+     * Fails if the delivery queue is full
+     */
+    pub fn enqueue(&self, delivery: Delivery) -> Result<(), String> {
+        self.tx
+            .try_send(delivery)
+            .map_err(|_| "webhook delivery queue full".to_string())
+    }
+
+    async fn run(
+        config: WebhookConfig,
+        clock: Arc<dyn Clock>,
+        mut rx: mpsc::Receiver<Delivery>,
+        requeue_tx: mpsc::Sender<Delivery>,
+        status_tx: mpsc::Sender<(JobId, CallbackStatus)>,
+    ) {
+        println!("[Webhooks]: [run]: starting");
+        let permits = Arc::new(Semaphore::new(config.concurrency));
+        while let Some(delivery) = rx.recv().await {
+            let Ok(permit) = permits.clone().acquire_owned().await else {
+                return;
+            };
+            let config = config.clone();
+            let requeue_tx = requeue_tx.clone();
+            let status_tx = status_tx.clone();
+            let clock = clock.clone();
+            tokio::spawn(async move {
+                let retry = Webhooks::attempt(delivery, &config, &clock, &status_tx).await;
+                // don't hold a delivery slot while backing off
+                drop(permit);
+                if let Some((delivery, backoff)) = retry {
+                    tokio::time::sleep(backoff).await;
+                    let _ = requeue_tx.send(delivery).await;
+                }
+            });
+        }
+    }
+
+    // Attempt one delivery and report its status
+    // Returns the delivery and its backoff if it should be retried
+    async fn attempt(
+        mut delivery: Delivery,
+        config: &WebhookConfig,
+        clock: &Arc<dyn Clock>,
+        status_tx: &mpsc::Sender<(JobId, CallbackStatus)>,
+    ) -> Option<(Delivery, Duration)> {
+        delivery.attempts += 1;
+        let result = http_client::request(
+            "POST",
+            &delivery.url,
+            &[("Content-Type", "application/json")],
+            Some(&delivery.body),
+            config.timeout,
+        )
+        .await;
+
+        let mut status = CallbackStatus::new(&delivery.url);
+        status.attempts = delivery.attempts;
+        let error = match result {
+            Ok(response) if response.is_success() => None,
+            Ok(response) => Some(format!("http {}", response.status)),
+            Err(e) => Some(e.to_string()),
+        };
+        let retry = match error {
+            None => {
+                status.state = DeliveryState::Delivered;
+                status.delivered_at = Some(clock.now());
+                None
+            }
+            Some(error) => {
+                println!(
+                    "[Webhooks]: job {}: POST {}: attempt {} failed: {}",
+                    delivery.job_id, delivery.url, delivery.attempts, error
+                );
+                status.last_error = Some(error);
+                if delivery.attempts < config.max_attempts {
+                    status.state = DeliveryState::Retrying;
+                    let backoff = config
+                        .initial_backoff
+                        .saturating_mul(1 << (delivery.attempts - 1).min(16))
+                        .min(config.max_backoff);
+                    Some(backoff)
+                } else {
+                    status.state = DeliveryState::Failed;
+                    None
+                }
+            }
+        };
+        if delivery.tracked {
+            let _ = status_tx.send((delivery.job_id, status)).await;
+        }
+        retry.map(|backoff| (delivery, backoff))
+    }
+}
+
+/**
+ * WebhookChannel
+ * Notification channel POSTing the job record to a fixed url
+ */
+pub struct WebhookChannel {
+    url: String,
+    webhooks: Webhooks,
+}
+
+impl WebhookChannel {
+    pub fn new(url: &str, webhooks: Webhooks) -> Self {
+        Self {
+            url: url.to_string(),
+            webhooks,
+        }
+    }
+}
+
+impl NotificationChannel for WebhookChannel {
+    fn send(&self, notification: &Notification) -> Result<(), String> {
+        let job = notification.job;
+        let body = serde_json::to_vec(job).map_err(|e| format!("job record: {e}"))?;
+        self.webhooks
+            .enqueue(Delivery::untracked(job.id(), &self.url, body))
+    }
+}
@@ -0,0 +1,754 @@
+/*! Workflows module for async orchestrator
+ * Stored, versioned workflow definitions (named steps with dependencies,
+ * retry policies, and parameters) and the driver that runs them
+ *
+ * A run's steps are ordinary jobs, grouped under the run id. The driver
+ * reconciles each active run against its jobs: it submits steps whose
+ * dependencies have succeeded, resubmits failed steps that have attempts
+ * left, and skips steps whose dependencies failed.
+ * A DAG of nodes and edges can also be submitted in one go (POST
+ * /workflows): it is stored as the next version of its name and run at once.
+ * Whoever uploads a workflow's first version owns it: later versions may
+ * only be uploaded by the owner or an admin (see the access module). Every
+ * version is kept, with who uploaded it.
+ * NOTE: definitions are JSON; there is no YAML support
+ */
+use crate::access::Principal;
+use crate::api_error::ApiError;
+use crate::clock::Clock;
+use crate::events::EventKind;
+use crate::ids::JobId;
+use crate::jobs::{Job, JobKind, JobPool, JobSubmission, Priority, State};
+use crate::labels::Labels;
+use crate::queues::DEFAULT_QUEUE;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use ulid::Ulid;
+
+// how often active runs are reconciled, absent job events
+const DRIVE_INTERVAL: Duration = Duration::from_millis(500);
+
+/**
+ * StepRetry
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StepRetry {
+    // total attempts, including the first
+    pub max_attempts: u32,
+}
+
+impl Default for StepRetry {
+    fn default() -> Self {
+        Self { max_attempts: 1 }
+    }
+}
+
+/**
+ * StepDefinition
+ * One job in a workflow
+ * Strings in the payload may refer to parameters as ${name}; a string
+ * that is exactly "${name}" takes the parameter's value as is
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StepDefinition {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub job_type: String,
+    #[serde(default)]
+    pub payload: Value,
+    // steps that must succeed before this one runs
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub retry: StepRetry,
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue: Option<String>,
+}
+
+/**
+ * WorkflowDefinition
+ * As uploaded; the name comes from the url
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WorkflowDefinition {
+    #[serde(default)]
+    pub description: String,
+    // parameter -> default; null: required at run time
+    #[serde(default)]
+    pub params: BTreeMap<String, Value>,
+    pub steps: Vec<StepDefinition>,
+}
+
+impl WorkflowDefinition {
+    // Check the step graph: unique names, types among job_types, known
+    // dependencies, and no cycles
+    fn validate(&self, job_types: &[String]) -> Result<(), String> {
+        if self.steps.is_empty() {
+            return Err("workflow has no steps".to_string());
+        }
+        for (i, step) in self.steps.iter().enumerate() {
+            if self.steps[..i].iter().any(|s| s.name == step.name) {
+                return Err(format!("duplicate step '{}'", step.name));
+            }
+            if !job_types.contains(&step.job_type) {
+                return Err(format!(
+                    "step '{}': unknown job type '{}'",
+                    step.name, step.job_type
+                ));
+            }
+            if step.retry.max_attempts == 0 {
+                return Err(format!("step '{}': max_attempts must be > 0", step.name));
+            }
+            for dep in &step.depends_on {
+                if !self.steps.iter().any(|s| s.name == *dep) {
+                    return Err(format!(
+                        "step '{}': unknown dependency '{}'",
+                        step.name, dep
+                    ));
+                }
+            }
+        }
+
+        // Kahn's algorithm: every step must become ready eventually
+        let mut done: Vec<&str> = Vec::new();
+        while done.len() < self.steps.len() {
+            let ready: Vec<&str> = self
+                .steps
+                .iter()
+                .filter(|s| !done.contains(&s.name.as_str()))
+                .filter(|s| s.depends_on.iter().all(|d| done.contains(&d.as_str())))
+                .map(|s| s.name.as_str())
+                .collect();
+            if ready.is_empty() {
+                return Err("dependency cycle between steps".to_string());
+            }
+            done.extend(ready);
+        }
+        Ok(())
+    }
+
+    // Fill in parameters: defaults overridden by the given values
+    fn resolve_params(&self, given: &Map<String, Value>) -> Result<Map<String, Value>, String> {
+        if let Some(unknown) = given.keys().find(|k| !self.params.contains_key(*k)) {
+            return Err(format!("unknown parameter '{unknown}'"));
+        }
+        let mut params = Map::new();
+        for (name, default) in &self.params {
+            match given.get(name).unwrap_or(default) {
+                Value::Null => return Err(format!("missing required parameter '{name}'")),
+                value => params.insert(name.clone(), value.clone()),
+            };
+        }
+        Ok(params)
+    }
+}
+
+/**
+ * DagEdge
+ * from must succeed before to runs
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DagEdge {
+    pub from: String,
+    pub to: String,
+}
+
+/**
+ * DagSubmission
+ * A named graph of job nodes, to store and run at once
+ * Edges add to the nodes' own depends_on; payloads take no parameters
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DagSubmission {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub nodes: Vec<StepDefinition>,
+    #[serde(default)]
+    pub edges: Vec<DagEdge>,
+}
+
+impl DagSubmission {
+    // As a workflow definition: each edge becomes a dependency of its
+    // target node
+    fn into_definition(self) -> Result<WorkflowDefinition, String> {
+        let mut steps = self.nodes;
+        for edge in self.edges {
+            if !steps.iter().any(|s| s.name == edge.from) {
+                return Err(format!("edge from unknown node '{}'", edge.from));
+            }
+            let Some(to) = steps.iter_mut().find(|s| s.name == edge.to) else {
+                return Err(format!("edge to unknown node '{}'", edge.to));
+            };
+            if !to.depends_on.contains(&edge.from) {
+                to.depends_on.push(edge.from);
+            }
+        }
+        Ok(WorkflowDefinition {
+            description: self.description,
+            params: BTreeMap::new(),
+            steps,
+        })
+    }
+}
+
+/**
+ * WorkflowVersion
+ * A stored definition
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WorkflowVersion {
+    pub name: String,
+    // starts at 1; each upload adds a version
+    pub version: u32,
+    pub uploaded_at: DateTime<Utc>,
+    pub uploaded_by: String,
+    // the owner: who uploaded version 1
+    pub created_by: String,
+    pub definition: WorkflowDefinition,
+}
+
+/**
+ * RunState
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RunState {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/**
+ * StepState
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StepState {
+    // dependencies not yet succeeded
+    Waiting,
+    // current attempt submitted
+    Running,
+    Succeeded,
+    // out of attempts
+    Failed,
+    // a dependency failed
+    Skipped,
+}
+
+impl StepState {
+    fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            StepState::Succeeded | StepState::Failed | StepState::Skipped
+        )
+    }
+}
+
+/**
+ * StepRun
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StepRun {
+    pub name: String,
+    pub state: StepState,
+    // attempts submitted so far
+    pub attempts: u32,
+    // job of the latest attempt, once the pool has it
+    pub job_id: Option<JobId>,
+    pub result: Option<String>,
+}
+
+/**
+ * WorkflowRun
+ * One instantiation of a workflow version
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WorkflowRun {
+    // also the group id of the run's jobs
+    pub id: String,
+    pub workflow: String,
+    pub version: u32,
+    pub params: Map<String, Value>,
+    pub state: RunState,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub steps: Vec<StepRun>,
+}
+
+/**
+ * RunRequest
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RunRequest {
+    #[serde(default)]
+    pub params: Map<String, Value>,
+    // None: latest
+    #[serde(default)]
+    pub version: Option<u32>,
+}
+
+#[derive(Default)]
+struct Registry {
+    // name -> versions, oldest first
+    definitions: HashMap<String, Vec<WorkflowVersion>>,
+    runs: HashMap<String, WorkflowRun>,
+}
+
+/**
+ * Workflows
+ * Definition store and run table; cheap to clone
+ */
+#[derive(Clone)]
+pub struct Workflows {
+    registry: Arc<Mutex<Registry>>,
+    // stamps uploads and runs
+    clock: Arc<dyn Clock>,
+    // the job types steps may have
+    job_types: Vec<String>,
+}
+
+impl Workflows {
+    pub fn new(clock: Arc<dyn Clock>, job_types: Vec<String>) -> Self {
+        Self {
+            registry: Arc::default(),
+            clock,
+            job_types,
+        }
+    }
+
+    /**
+     * upload: validate and store a new version of a workflow
+     * NOTE: by must own the workflow (if it exists) or be an admin
+     */
+    pub fn upload(
+        &self,
+        name: &str,
+        definition: WorkflowDefinition,
+        by: &Principal,
+    ) -> Result<WorkflowVersion, ApiError> {
+        definition
+            .validate(&self.job_types)
+            .map_err(ApiError::BadRequest)?;
+        let mut registry = self.registry.lock().unwrap();
+        let versions = registry.definitions.entry(name.to_string()).or_default();
+        let created_by = match versions.first() {
+            Some(first) => {
+                by.check_owner(&format!("workflow '{name}'"), &first.created_by)?;
+                first.created_by.clone()
+            }
+            None => by.name.clone(),
+        };
+        let stored = WorkflowVersion {
+            name: name.to_string(),
+            version: versions.len() as u32 + 1,
+            uploaded_at: self.clock.now(),
+            uploaded_by: by.name.clone(),
+            created_by,
+            definition,
+        };
+        versions.push(stored.clone());
+        println!(
+            "[Workflows]: {} v{} stored by {}",
+            name, stored.version, stored.uploaded_by
+        );
+        Ok(stored)
+    }
+
+    /**
+     * get: a stored version of a workflow, the latest if None
+     */
+    pub fn get(&self, name: &str, version: Option<u32>) -> Result<WorkflowVersion, ApiError> {
+        let registry = self.registry.lock().unwrap();
+        let versions = registry
+            .definitions
+            .get(name)
+            .ok_or_else(|| ApiError::NotFound(format!("workflow '{name}'")))?;
+        match version {
+            None => versions.last(),
+            Some(v) => versions.iter().find(|w| w.version == v),
+        }
+        .cloned()
+        .ok_or_else(|| ApiError::NotFound(format!("workflow '{name}' version {version:?}")))
+    }
+
+    /**
+     * versions: every stored version of a workflow, newest first
+     */
+    pub fn versions(&self, name: &str) -> Result<Vec<WorkflowVersion>, ApiError> {
+        self.registry
+            .lock()
+            .unwrap()
+            .definitions
+            .get(name)
+            .map(|versions| versions.iter().rev().cloned().collect())
+            .ok_or_else(|| ApiError::NotFound(format!("workflow '{name}'")))
+    }
+
+    /**
+     * list: the latest version of every workflow
+     */
+    pub fn list(&self) -> Vec<WorkflowVersion> {
+        let registry = self.registry.lock().unwrap();
+        let mut latest: Vec<WorkflowVersion> = registry
+            .definitions
+            .values()
+            .filter_map(|versions| versions.last().cloned())
+            .collect();
+        latest.sort_by(|a, b| a.name.cmp(&b.name));
+        latest
+    }
+
+    /**
+     * start_run: instantiate a workflow; the driver submits its steps
+     */
+    pub fn start_run(&self, name: &str, request: RunRequest) -> Result<WorkflowRun, ApiError> {
+        let stored = self.get(name, request.version)?;
+        let params = stored
+            .definition
+            .resolve_params(&request.params)
+            .map_err(ApiError::BadRequest)?;
+        // render every step now, so a bad parameter fails the request
+        // rather than the run
+        for step in &stored.definition.steps {
+            render_step(step, &params, "").map_err(ApiError::BadRequest)?;
+        }
+
+        let run = WorkflowRun {
+            id: Ulid::new().to_string(),
+            workflow: stored.name.clone(),
+            version: stored.version,
+            params,
+            state: RunState::Running,
+            created_at: self.clock.now(),
+            finished_at: None,
+            steps: stored
+                .definition
+                .steps
+                .iter()
+                .map(|s| StepRun {
+                    name: s.name.clone(),
+                    state: StepState::Waiting,
+                    attempts: 0,
+                    job_id: None,
+                    result: None,
+                })
+                .collect(),
+        };
+        println!(
+            "[Workflows]: run {}: {} v{} started",
+            run.id, run.workflow, run.version
+        );
+        self.registry
+            .lock()
+            .unwrap()
+            .runs
+            .insert(run.id.clone(), run.clone());
+        Ok(run)
+    }
+
+    /**
+     * submit_dag: store a DAG as the next version of its workflow and start
+     * a run of it; independent nodes run in parallel
+     */
+    pub fn submit_dag(&self, dag: DagSubmission, by: &Principal) -> Result<WorkflowRun, ApiError> {
+        if dag.name.trim().is_empty() {
+            return Err(ApiError::BadRequest("name: must not be empty".to_string()));
+        }
+        let name = dag.name.clone();
+        let definition = dag.into_definition().map_err(ApiError::BadRequest)?;
+        let stored = self.upload(&name, definition, by)?;
+        self.start_run(
+            &name,
+            RunRequest {
+                params: Map::new(),
+                version: Some(stored.version),
+            },
+        )
+    }
+
+    /**
+     * get_run: a run's current state
+     */
+    pub fn get_run(&self, id: &str) -> Result<WorkflowRun, ApiError> {
+        self.registry
+            .lock()
+            .unwrap()
+            .runs
+            .get(id)
+            .cloned()
+            .ok_or_else(|| ApiError::NotFound(format!("workflow run '{id}'")))
+    }
+
+    /**
+     * drive: reconcile active runs whenever a job changes state, and
+     * periodically (submissions that were turned away are retried)
+     * NOTE: holds the pool weakly; returns once the pool is gone
+     */
+    pub async fn drive(&self, pool: Weak<JobPool>) {
+        let Some(mut events) = pool.upgrade().map(|p| p.subscribe()) else {
+            return;
+        };
+        let mut tick = tokio::time::interval(DRIVE_INTERVAL);
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(event) if !matches!(event.kind, EventKind::JobStateChanged { .. }) => continue,
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return,
+                },
+                _ = tick.tick() => {}
+            }
+            let Some(pool) = pool.upgrade() else {
+                return;
+            };
+            self.reconcile(&pool).await;
+        }
+    }
+
+    // Advance every active run one step
+    async fn reconcile(&self, pool: &JobPool) {
+        let active: Vec<String> = {
+            let registry = self.registry.lock().unwrap();
+            registry
+                .runs
+                .values()
+                .filter(|r| r.state == RunState::Running)
+                .map(|r| r.id.clone())
+                .collect()
+        };
+        for run_id in active {
+            let jobs = pool.group_jobs(&run_id).await;
+            // decide under the lock, submit outside it
+            let to_submit = self.plan(&run_id, &jobs);
+            for (step, submission) in to_submit {
+                let result = pool.submit(submission).await;
+                self.submitted(&run_id, &step, result);
+            }
+        }
+    }
+
+    // Update a run from its jobs; returns the steps to submit now
+    fn plan(&self, run_id: &str, jobs: &[Job]) -> Vec<(String, JobSubmission)> {
+        let mut registry = self.registry.lock().unwrap();
+        let Registry { definitions, runs } = &mut *registry;
+        let Some(run) = runs.get_mut(run_id) else {
+            return Vec::new();
+        };
+        let Some(definition) = definitions
+            .get(&run.workflow)
+            .and_then(|v| v.iter().find(|w| w.version == run.version))
+            .map(|w| &w.definition)
+        else {
+            return Vec::new();
+        };
+
+        // finished attempts
+        for (i, step) in run.steps.iter_mut().enumerate() {
+            if step.state != StepState::Running {
+                continue;
+            }
+            let attempts: Vec<&Job> = jobs
+                .iter()
+                .filter(|j| j.submission().workflow_step.as_deref() == Some(step.name.as_str()))
+                .collect();
+            // the latest attempt may not have reached the pool yet
+            if (attempts.len() as u32) < step.attempts {
+                continue;
+            }
+            let Some(latest) = attempts.iter().max_by_key(|j| (j.created_at(), j.id())) else {
+                continue;
+            };
+            step.job_id = Some(latest.id());
+            match latest.state() {
+                State::SUCCEEDED => {
+                    step.state = StepState::Succeeded;
+                    step.result = Some(latest.result().to_string());
+                }
+                State::FAILED | State::TIMED_OUT | State::DEADLINE_MISSED => {
+                    step.result = Some(latest.result().to_string());
+                    // a retry can't help e.g. a missed deadline
+                    let retryable = latest.failure().is_none_or(|f| f.retryable);
+                    if retryable && step.attempts < definition.steps[i].retry.max_attempts {
+                        println!(
+                            "[Workflows]: run {}: step '{}' failed, retrying",
+                            run.id, step.name
+                        );
+                        // resubmitted below
+                        step.state = StepState::Waiting;
+                    } else {
+                        step.state = StepState::Failed;
+                    }
+                }
+                // cancelled by hand: not retried
+                State::CANCELLED => {
+                    step.result = Some(latest.result().to_string());
+                    step.state = StepState::Failed;
+                }
+                _ => {}
+            }
+        }
+
+        // newly ready (or skipped) steps
+        let mut to_submit = Vec::new();
+        for i in 0..run.steps.len() {
+            if run.steps[i].state != StepState::Waiting {
+                continue;
+            }
+            let deps = &definition.steps[i].depends_on;
+            let dep_state = |name: &String| {
+                run.steps
+                    .iter()
+                    .find(|s| s.name == *name)
+                    .map(|s| s.state.clone())
+            };
+            if deps
+                .iter()
+                .any(|d| matches!(dep_state(d), Some(StepState::Failed | StepState::Skipped)))
+            {
+                run.steps[i].state = StepState::Skipped;
+                continue;
+            }
+            if deps
+                .iter()
+                .all(|d| dep_state(d) == Some(StepState::Succeeded))
+            {
+                match render_step(&definition.steps[i], &run.params, &run.id) {
+                    Ok(mut submission) => {
+                        // a retry resumes from the failed attempt's checkpoints
+                        submission.resume_from = run.steps[i].job_id;
+                        run.steps[i].state = StepState::Running;
+                        run.steps[i].attempts += 1;
+                        to_submit.push((run.steps[i].name.clone(), submission));
+                    }
+                    Err(e) => {
+                        run.steps[i].state = StepState::Failed;
+                        run.steps[i].result = Some(e);
+                    }
+                }
+            }
+        }
+
+        // skips cascade, so settle the run only once nothing is waiting
+        if to_submit.is_empty() && run.steps.iter().all(|s| s.state.is_terminal()) {
+            run.state = if run.steps.iter().all(|s| s.state == StepState::Succeeded) {
+                RunState::Succeeded
+            } else {
+                RunState::Failed
+            };
+            run.finished_at = Some(self.clock.now());
+            println!("[Workflows]: run {}: {:?}", run.id, run.state);
+        }
+        to_submit
+    }
+
+    // Record the outcome of submitting a step's attempt
+    fn submitted(&self, run_id: &str, step_name: &str, result: Result<JobId, ApiError>) {
+        let mut registry = self.registry.lock().unwrap();
+        let Some(step) = registry
+            .runs
+            .get_mut(run_id)
+            .and_then(|r| r.steps.iter_mut().find(|s| s.name == step_name))
+        else {
+            return;
+        };
+        match result {
+            Ok(id) => step.job_id = Some(id),
+            // turned away for now: try again next time round
+            Err(ApiError::QueueFull | ApiError::Overloaded) => {
+                step.state = StepState::Waiting;
+                step.attempts -= 1;
+            }
+            Err(e) => {
+                println!(
+                    "[Workflows]: run {}: step '{}' not submitted: {:?}",
+                    run_id, step_name, e
+                );
+                step.state = StepState::Failed;
+                step.result = Some(format!("not submitted: {e:?}"));
+            }
+        }
+    }
+}
+
+// Build a step's job submission with parameters substituted
+fn render_step(
+    step: &StepDefinition,
+    params: &Map<String, Value>,
+    run_id: &str,
+) -> Result<JobSubmission, String> {
+    let payload = substitute(&step.payload, params);
+    let kind = serde_json::json!({ "type": step.job_type, "payload": payload });
+    let kind: JobKind =
+        serde_json::from_value(kind).map_err(|e| format!("step '{}': {e}", step.name))?;
+    Ok(JobSubmission {
+        kind,
+        priority: step.priority,
+        queue: step
+            .queue
+            .clone()
+            .unwrap_or_else(|| DEFAULT_QUEUE.to_string()),
+        callback_url: None,
+        group_id: Some(run_id.to_string()),
+        map_index: None,
+        workflow_step: Some(step.name.clone()),
+        metadata: None,
+        labels: Labels::new(),
+        timeout_ms: None,
+        deadline: None,
+        run_at: None,
+        delay_ms: None,
+        depends_on: Vec::new(),
+        env_profile: None,
+        cloned_from: None,
+        rerun_of: None,
+        unique_key: None,
+        on_duplicate: None,
+        resume_from: None,
+        preempt: false,
+        parent_id: None,
+        wait_for_children: false,
+        on_success: None,
+        on_failure: None,
+        follows: None,
+        schedule_id: None,
+    })
+}
+
+// Replace ${name} references in every string of a JSON value
+fn substitute(value: &Value, params: &Map<String, Value>) -> Value {
+    match value {
+        Value::String(s) => {
+            // whole-string reference: keep the parameter's type
+            if let Some(name) = s.strip_prefix("${").and_then(|r| r.strip_suffix('}'))
+                && let Some(v) = params.get(name)
+            {
+                return v.clone();
+            }
+            let mut out = s.clone();
+            for (name, v) in params {
+                let text = match v {
+                    Value::String(t) => t.clone(),
+                    other => other.to_string(),
+                };
+                out = out.replace(&format!("${{{name}}}"), &text);
+            }
+            Value::String(out)
+        }
+        Value::Array(items) => Value::Array(items.iter().map(|v| substitute(v, params)).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(k, v)| (k.clone(), substitute(v, params)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}